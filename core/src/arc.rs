@@ -0,0 +1,12 @@
+//! Reading files straight out of the game's data.arc, so nus3audio files can be
+//! opened without extracting them with a separate tool first.
+
+use std::path::Path;
+use smash_arc::{ ArcFile, ArcLookup, Hash40, Region };
+
+/// Read the file at `path` inside the data.arc at `data_arc`.
+pub fn read_file(data_arc: &Path, path: &str) -> Result<Vec<u8>, String> {
+	let arc = ArcFile::open(data_arc).map_err(|error| format!("Error opening {:?}:\n{:?}", data_arc, error))?;
+	arc.get_file_contents(Hash40::from(path.trim_start_matches('/')), Region::UsEnglish)
+		.map_err(|error| format!("Error reading {} from data.arc:\n{:?}", path, error))
+}
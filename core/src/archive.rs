@@ -0,0 +1,902 @@
+use std::{
+	collections::HashMap,
+	fs,
+	path::{ Component, Path, PathBuf },
+	sync::{ mpsc, Mutex },
+	thread,
+	time::Duration
+};
+use nus3audio::Nus3audioFile;
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use crate::{
+	bank::tone_names,
+	cancel::Cancel,
+	codec::EncodingType,
+	config::Config,
+	item::{ extension_of_encoded, AudioExtension, ListItem, SaveSource },
+	project::{ Entry, Grouping, Project, Sort, SortKey },
+	structure::{ self, ExtraSection },
+	util::{ human_readable_size, long_path }
+};
+
+/// The error given when a file can't be parsed as a nus3audio file, in which
+/// case [Archive::recover] may still salvage its audio.
+pub const PARSE_ERROR: &str = "Error parsing file";
+
+/// How many tone IDs each group spans when grouping by [Grouping::IdRange].
+pub const ID_GROUP_SIZE: u32 = 100;
+
+/// Approximate size of a nus3audio file's headers, for projecting sizes.
+const NUS3AUDIO_OVERHEAD: u64 = 0x60;
+/// Approximate size of each item's entries in a nus3audio file's headers,
+/// excluding its name and padding.
+const NUS3AUDIO_ITEM_OVERHEAD: u64 = 0x30;
+/// The most items decoded at once when opening a nus3audio file. Each runs an
+/// external tool, so more than this mostly fights over the disk.
+const MAX_DECODE_WORKERS: usize = 4;
+/// How often progress is reported while waiting on items to decode, so the
+/// progress window stays responsive during a long decode.
+const DECODE_PROGRESS_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What a thread decoding items while opening reports back.
+enum DecodeEvent {
+	/// The item with this name started decoding.
+	Started(String),
+	/// The item with this number finished decoding, with the error it gave, if any.
+	Finished(usize, Option<String>)
+}
+
+/// How big an archive is projected to be when written. See [Archive::projected_size].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProjectedSize {
+	/// The projected size, in bytes.
+	pub bytes: u64,
+	/// How many items still have to be encoded, so their size is estimated.
+	pub estimated: usize,
+	/// How many items' size isn't known at all, so they are left out.
+	pub unknown: usize
+}
+
+/// What saving an archive did with each item, by name. See [Archive::save_report].
+#[derive(Clone, Debug, Default)]
+pub struct SaveReport {
+	/// Items that were encoded, while saving or earlier in the session.
+	pub encoded: Vec<String>,
+	/// How many of the encoded items were encoded while saving.
+	pub fresh_encodes: usize,
+	/// Encoded items whose audio was resampled, with the sample rate before and after.
+	pub resampled: Vec<(String, u32, u32)>,
+	/// Items written with the bytes they were opened with, or as binary data.
+	pub untouched: Vec<String>,
+	/// Items written empty.
+	pub empty: Vec<String>
+}
+
+/// A nus3audio file being worked on.
+pub struct Archive {
+	/// The name of this nus3audio file.
+	/// 
+	/// A nus3audio has no name of its own inside it, so this is only the name of
+	/// the file it was opened from or last saved to.
+	pub name: String,
+	/// The path of this archive's original nus3audio file.
+	pub path: Option<PathBuf>,
+	/// The path inside data.arc this archive was opened from, if it was.
+	pub arc_path: Option<String>,
+	/// Items in this nus3audio file.
+	pub items: Vec<ListItem>,
+	/// Whether or not this archive has been modified. This is used to track unsaved changes.
+	pub modified: bool,
+	/// What is kept about this archive in its sidecar file.
+	pub project: Project,
+	/// Items removed this session, oldest first, see [Archive::restore].
+	pub trash: Vec<Trashed>,
+	/// Sections of the opened file that the nus3audio crate doesn't write,
+	/// put back as they were when saving.
+	pub extra_sections: Vec<ExtraSection>,
+	/// Whether or not nothing has changed since this archive was opened from
+	/// its file, so saving can write that file back as it is.
+	unchanged_since_open: bool
+}
+
+/// An item removed from an [Archive], kept so it can be put back.
+pub struct Trashed {
+	/// Where the item was in the archive when it was removed.
+	pub index: usize,
+	/// The item itself.
+	pub item: ListItem,
+	/// What the sidecar held about the item.
+	pub entry: Entry
+}
+
+impl Default for Archive {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Archive {
+	/// Return a new, empty archive.
+	pub fn new() -> Self {
+		Self {
+			name: String::new(),
+			path: None,
+			arc_path: None,
+			items: Vec::new(),
+			modified: false,
+			project: Project::default(),
+			trash: Vec::new(),
+			extra_sections: Vec::new(),
+			unchanged_since_open: false
+		}
+	}
+
+	/// Parse the nus3audio file at `path`.
+	/// 
+	/// The file is memory-mapped instead of read into a buffer first, which saves
+	/// a copy of the whole file. It doesn't lower the peak memory use much more
+	/// than that: [Nus3audioFile::try_from_bytes] still copies the data of every
+	/// item out of the map, so all of it is in memory once the file is parsed.
+	/// Also returns the sections that won't be written when saving, see [structure::extra_sections].
+	fn read_nus3audio(path: &Path) -> Result<(Nus3audioFile, Vec<ExtraSection>), String> {
+		let file = match fs::File::open(long_path(path)) {
+			Ok(file) => file,
+			Err(error) => return Err(format!("Error reading file:\n{}", error))
+		};
+
+		// Safety: the map only lives until the items are copied out of it. Another program
+		// truncating the file in that short window could crash us, which is accepted.
+		let map = match unsafe { memmap2::Mmap::map(&file) } {
+			Ok(map) => map,
+			Err(error) => return Err(format!("Error reading file:\n{}", error))
+		};
+
+		match Nus3audioFile::try_from_bytes(&map) {
+			Some(nus3audio) => Ok((nus3audio, structure::extra_sections(&map))),
+			None => Err(PARSE_ERROR.to_owned())
+		}
+	}
+
+	/// Open the nus3audio file at `path` and decode its items.
+	/// 
+	/// Items that fail to decode are still added to the archive. Their names are
+	/// returned along with the error they gave. `progress` is called before each
+	/// item is decoded and `cancel` is checked, see [Archive::from_nus3audio].
+	pub fn open(path: &Path, config: &dyn Config, progress: &mut dyn FnMut(usize, usize, &str), cancel: &Cancel) -> Result<(Self, Vec<(String, String)>), String> {
+		let (nus3audio, extra_sections) = Self::read_nus3audio(path)?;
+
+		let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+		let (mut archive, errors) = Self::from_nus3audio(name, Some(path.to_owned()), nus3audio, config, progress, cancel)?;
+		archive.extra_sections = extra_sections;
+		archive.unchanged_since_open = true;
+		archive.project = Project::load(path).unwrap_or_else(|error| {
+			warn!("Could not read the sidecar of {:?}: {}", path, error);
+			Project::default()
+		});
+		Ok((archive, errors))
+	}
+
+	/// Salvage what audio can be found in the damaged nus3audio file at `path`,
+	/// see [structure::recover], and decode it like [Archive::open].
+	/// 
+	/// The items are marked as recovered, and the archive as modified, since
+	/// saving it writes a whole file in place of the damaged one.
+	pub fn recover(path: &Path, config: &dyn Config, progress: &mut dyn FnMut(usize, usize, &str), cancel: &Cancel) -> Result<(Self, Vec<(String, String)>), String> {
+		let bytes = fs::read(long_path(path)).map_err(|error| format!("Error reading file:\n{}", error))?;
+		let nus3audio = structure::recover(&bytes);
+		if nus3audio.files.is_empty() {
+			return Err("No audio could be found in the file.".to_owned())
+		}
+		info!("Recovered {} items from {:?}", nus3audio.files.len(), path);
+
+		let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+		let (mut archive, errors) = Self::from_nus3audio(name, Some(path.to_owned()), nus3audio, config, progress, cancel)?;
+		for item in &mut archive.items {
+			item.recovered = true
+		}
+		archive.modified = true;
+		archive.project = Project::load(path).unwrap_or_else(|error| {
+			warn!("Could not read the sidecar of {:?}: {}", path, error);
+			Project::default()
+		});
+		Ok((archive, errors))
+	}
+
+	/// Open the nus3audio file at `arc_path` inside the data.arc at `data_arc`,
+	/// and decode its items.
+	/// 
+	/// The archive has no path of its own, see [Archive::mod_path] for where to save it.
+	/// `progress` is called before each item is decoded and `cancel` is checked,
+	/// see [Archive::from_nus3audio].
+	#[cfg(feature = "smash-arc")]
+	pub fn open_from_arc(data_arc: &Path, arc_path: &str, config: &dyn Config, progress: &mut dyn FnMut(usize, usize, &str), cancel: &Cancel) -> Result<(Self, Vec<(String, String)>), String> {
+		let raw = crate::arc::read_file(data_arc, arc_path)?;
+
+		let nus3audio = match Nus3audioFile::try_from_bytes(&raw) {
+			Some(f) => f,
+			None => return Err(PARSE_ERROR.to_owned())
+		};
+
+		let name = arc_path.rsplit('/').next().unwrap_or_default().to_owned();
+		let (mut archive, errors) = Self::from_nus3audio(name, None, nus3audio, config, progress, cancel)?;
+		archive.arc_path = Some(arc_path.to_owned());
+		archive.extra_sections = structure::extra_sections(&raw);
+		Ok((archive, errors))
+	}
+
+	/// Return where this archive goes in the mod folder `mod_dir`.
+	/// 
+	/// Returns an error if it didn't come from data.arc, or if its path there
+	/// would lead outside of `mod_dir`.
+	pub fn mod_path(&self, mod_dir: &Path) -> Result<PathBuf, String> {
+		let arc_path = match &self.arc_path {
+			Some(arc_path) => arc_path,
+			None => return Err("Only files opened from data.arc know where they go in a mod folder.".to_owned())
+		};
+		// A leading separator would make the path absolute, replacing the mod folder
+		let relative = Path::new(arc_path.trim_start_matches(['/', '\\']));
+		let inside = relative.file_name().is_some()
+			&& relative.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+		let target = mod_dir.join(relative);
+		if !inside || !target.starts_with(mod_dir) {
+			return Err(format!("{} would be saved outside of the mod folder.", arc_path))
+		}
+		Ok(target)
+	}
+
+	/// Create an empty archive with the same item names and formats as the
+	/// nus3audio file at `path`, to fill in as a template.
+	/// 
+	/// Nothing is decoded, so this is quick even for large files.
+	pub fn template(path: &Path) -> Result<Self, String> {
+		let (nus3audio, _) = Self::read_nus3audio(path)?;
+
+		let mut archive = Self::new();
+		archive.name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+		for file in nus3audio.files.into_iter() {
+			let mut item = ListItem::new(file.name);
+			item.id = Some(file.id);
+			item.extension = extension_of_encoded(&file.data).unwrap_or(AudioExtension::Bin);
+			archive.items.push(item)
+		}
+
+		Ok(archive)
+	}
+
+	/// Create an archive named `name` from an already parsed nus3audio file, decoding its items.
+	/// 
+	/// Items that fail to decode are still added to the archive. Their names are
+	/// returned along with the error they gave.
+	/// 
+	/// Items are decoded a few at a time, since decoding is mostly waiting on the
+	/// external tools. `progress` is called with the number of items decoded so far,
+	/// the number of items and the name of the item starting to decode, and again
+	/// every so often with the name of the last one while waiting on them.
+	/// 
+	/// Once `cancel` is set, no more items start decoding and this returns
+	/// [crate::cancel::CANCELLED] when the ones already started are done.
+	pub fn from_nus3audio(name: String, path: Option<PathBuf>, nus3audio: Nus3audioFile, config: &dyn Config, progress: &mut dyn FnMut(usize, usize, &str), cancel: &Cancel) -> Result<(Self, Vec<(String, String)>), String> {
+		let mut archive = Self { name, path, ..Self::new() };
+		let total = nus3audio.files.len();
+
+		let mut items: Vec<ListItem> = nus3audio.files.iter()
+			.map(|file| {
+				let mut item = ListItem::new(file.name.clone());
+				item.id = Some(file.id);
+				// Set the item extension
+				if let Ok(extension) = extension_of_encoded(&file.data) {
+					item.extension = extension
+				}
+				item
+			})
+			.collect();
+
+		let workers = thread::available_parallelism().map(|count| count.get()).unwrap_or(1).clamp(1, MAX_DECODE_WORKERS);
+		debug!("Decoding {} items with {} workers", total, workers);
+
+		let mut failed: Vec<(usize, String)> = Vec::new();
+		let nus3audio_name = &archive.name;
+		thread::scope(|scope| {
+			let queue = Mutex::new(items.iter_mut().zip(nus3audio.files).enumerate());
+			let queue = &queue;
+			let (sender, receiver) = mpsc::channel();
+			for _ in 0..workers {
+				let sender = sender.clone();
+				scope.spawn(move || loop {
+					if cancel.is_cancelled() { break }
+					let next = queue.lock().expect("decode queue lock").next();
+					let (number, (item, file)) = match next {
+						Some(next) => next,
+						None => break
+					};
+					let _ = sender.send(DecodeEvent::Started(file.name.clone()));
+					let result = item.from_encoded(nus3audio_name, file.data, config);
+					let _ = sender.send(DecodeEvent::Finished(number, result.err()));
+				});
+			}
+			// Only the workers hold senders now, so this ends when they're all done
+			drop(sender);
+
+			let mut done = 0;
+			let mut current = String::new();
+			loop {
+				match receiver.recv_timeout(DECODE_PROGRESS_INTERVAL) {
+					Ok(DecodeEvent::Started(name)) => {
+						progress(done, total, &name);
+						current = name
+					},
+					Ok(DecodeEvent::Finished(number, error)) => {
+						done += 1;
+						if let Some(error) = error {
+							error!("{}", error);
+							failed.push((number, error))
+						}
+					},
+					// Keep the tool output and the progress window going while items decode
+					Err(mpsc::RecvTimeoutError::Timeout) => {
+						crate::tool::poll();
+						if !current.is_empty() {
+							progress(done, total, &current)
+						}
+					},
+					Err(mpsc::RecvTimeoutError::Disconnected) => break
+				}
+			}
+		});
+		cancel.check()?;
+
+		failed.sort_by_key(|(number, _)| *number);
+		let errors = failed.into_iter()
+			.map(|(number, error)| (format!("{}.{}", items[number].name, items[number].extension), error))
+			.collect();
+		archive.items = items;
+
+		Ok((archive, errors))
+	}
+
+	/// Add an item to the end of this archive.
+	/// 
+	/// An item without a tone ID is given the next free one.
+	/// Marks this archive as being modified.
+	pub fn add(&mut self, mut item: ListItem) {
+		if item.id.is_none() {
+			item.id = Some(self.next_free_id())
+		}
+		self.items.push(item);
+		self.modified = true
+	}
+
+	/// Remove an item from this archive by index, moving it to the trash.
+	/// 
+	/// Marks this archive as being modified.
+	pub fn remove(&mut self, index: usize) {
+		self.modified = true;
+		let item = self.items.remove(index);
+		let entry = self.project.forget(&item.name);
+		self.trash.push(Trashed { index, item, entry })
+	}
+
+	/// Remove every item from this archive, moving them to the trash so each
+	/// can be restored where it was.
+	/// 
+	/// Marks this archive as being modified if there were any items.
+	pub fn remove_all(&mut self) {
+		// Last first, so each keeps its own index in the trash
+		for index in (0..self.items.len()).rev() {
+			self.remove(index)
+		}
+	}
+
+	/// Put the item at `trash_index` in the trash back where it was removed from,
+	/// or at the end if there are fewer items now. Returns its index.
+	/// 
+	/// The item gets the next free tone ID if another item has taken its own, and
+	/// a number after its name if another item has taken that, so what the sidecar
+	/// holds about the other item is kept. Marks this archive as being modified.
+	pub fn restore(&mut self, trash_index: usize) -> Result<usize, String> {
+		if trash_index >= self.trash.len() {
+			return Err(format!("There is no item {} in the trash", trash_index))
+		}
+		let Trashed { index, mut item, entry } = self.trash.remove(trash_index);
+		if item.id.is_some() && self.items.iter().any(|other| other.id == item.id) {
+			item.id = Some(self.next_free_id())
+		}
+		if self.items.iter().any(|other| other.name == item.name) {
+			let name = (2..)
+				.map(|number| format!("{}_{}", item.name, number))
+				.find(|name| self.items.iter().all(|other| &other.name != name))
+				.expect("some number is free");
+			info!("Restoring {} as {}, since another item has its name", item.name, name);
+			item.name = name
+		}
+		let index = index.min(self.items.len());
+		self.project.remember(&item.name, entry);
+		self.items.insert(index, item);
+		self.modified = true;
+		Ok(index)
+	}
+
+	/// Clear the items in this archive.
+	/// 
+	/// Marks this archive as being unmodified.
+	pub fn clear(&mut self) {
+		self.items.clear();
+		self.arc_path = None;
+		self.project = Project::default();
+		self.trash.clear();
+		self.unchanged_since_open = false;
+		self.modified = false
+	}
+
+	/// Replace the audio of the item at `index` with the file at `path`.
+	/// 
+	/// If it doesn't fail, marks this archive as being modified.
+	pub fn replace(&mut self, index: usize, path: &Path, config: &dyn Config) -> Result<(), String> {
+		let item = match self.items.get_mut(index) {
+			Some(item) => item,
+			None => return Err(format!("There is no item at index {}", index))
+		};
+
+		item.replace_from_file(&self.name, path, config)?;
+		self.project.set_source(&item.name, path);
+		self.modified = true;
+		Ok(())
+	}
+
+	/// Replace the audio of the item at `index` with that of `candidate`, which
+	/// was made from the file at `path`, without decoding the file again. See
+	/// [ListItem::take_audio].
+	/// 
+	/// If it doesn't fail, marks this archive as being modified.
+	pub fn replace_with_item(&mut self, index: usize, candidate: ListItem, path: &Path, config: &dyn Config) -> Result<(), String> {
+		let item = match self.items.get_mut(index) {
+			Some(item) => item,
+			None => {
+				candidate.discard(&self.name, config);
+				return Err(format!("There is no item at index {}", index))
+			}
+		};
+
+		item.take_audio(&self.name, candidate, config);
+		self.project.set_source(&item.name, path);
+		self.modified = true;
+		Ok(())
+	}
+
+	/// Replace the audio of the item at `index` with `bytes` in `encoding`,
+	/// like generated audio.
+	/// 
+	/// If it doesn't fail, marks this archive as being modified.
+	pub fn set_audio(&mut self, index: usize, bytes: Vec<u8>, encoding: EncodingType) -> Result<(), String> {
+		let item = match self.items.get_mut(index) {
+			Some(item) => item,
+			None => return Err(format!("There is no item at index {}", index))
+		};
+
+		item.set_audio_from_bytes(bytes, encoding)?;
+		self.modified = true;
+		Ok(())
+	}
+
+	/// Set the loop points of the item at `index`, in samples.
+	/// 
+	/// If it doesn't fail, marks this archive as being modified.
+	pub fn set_loop_points(&mut self, index: usize, loop_points: Option<(usize, usize)>) -> Result<(), String> {
+		let item = match self.items.get_mut(index) {
+			Some(item) => item,
+			None => return Err(format!("There is no item at index {}", index))
+		};
+
+		if let Some((start, end)) = loop_points {
+			if start >= end {
+				return Err("Loop beginning must be placed before loop end.".to_owned())
+			}
+		}
+
+		if *item.loop_points() != loop_points {
+			// The item is encoded again with the new loop points, which needs its audio
+			item.restore_audio()?;
+			item.clear_bytes();
+			item.loop_points_samples = loop_points;
+			item.loop_points_scaled = false;
+			self.modified = true
+		}
+		Ok(())
+	}
+
+	/// Release the audio of items that haven't been used for `idle` from memory,
+	/// except the item at `keep`, and of every item in the trash. Returns how many
+	/// were released.
+	/// 
+	/// See [ListItem::release_audio].
+	pub fn release_idle_audio(&mut self, idle: std::time::Duration, keep: Option<usize>, config: &dyn Config) -> usize {
+		let idle_items = self.items.iter_mut().enumerate()
+			.filter(|(index, item)| Some(*index) != keep && item.idle_for() >= idle)
+			.map(|(_, item)| item);
+		// Trashed items are only needed again if they're restored
+		let trashed = self.trash.iter_mut().map(|trashed| &mut trashed.item);
+		idle_items.chain(trashed)
+			.filter(|item| !item.is_released())
+			.map(|item| item.release_audio(config))
+			.filter(|released| *released)
+			.count()
+	}
+
+	/// Bring back the audio of the item at `index` if it was released from memory.
+	pub fn restore_audio(&mut self, index: usize) -> Result<(), String> {
+		match self.items.get_mut(index) {
+			Some(item) => item.restore_audio(),
+			None => Err(format!("There is no item at index {}", index))
+		}
+	}
+
+	/// Return the indices of items with loop points whose audio will be resampled
+	/// when it's encoded, which leaves the loop points in the wrong place.
+	pub fn resampled_loops(&self) -> Vec<usize> {
+		self.items.iter().enumerate()
+			.filter(|(_, item)| item.save_source() == SaveSource::Encode && item.loop_points().is_some() && !item.loop_points_scaled)
+			.filter(|(_, item)| item.sample_rate() != item.encoded_sample_rate())
+			.map(|(index, _)| index)
+			.collect()
+	}
+
+	/// Scale the loop points of the item at `index` by how much its audio will be
+	/// resampled when it's encoded, so they stay at the same time.
+	/// 
+	/// If it doesn't fail, marks this archive as being modified.
+	pub fn scale_loop_points(&mut self, index: usize) -> Result<(), String> {
+		let item = match self.items.get(index) {
+			Some(item) => item,
+			None => return Err(format!("There is no item at index {}", index))
+		};
+
+		let (from, to) = (item.sample_rate() as u64, item.encoded_sample_rate() as u64);
+		let loop_points = item.loop_points().map(|(start, end)| {
+			((start as u64 * to / from.max(1)) as usize, (end as u64 * to / from.max(1)) as usize)
+		});
+		info!("Scaling loop points of {} from {} Hz to {} Hz: {:?} to {:?}", item.name, from, to, item.loop_points(), loop_points);
+		self.set_loop_points(index, loop_points)?;
+		self.items[index].loop_points_scaled = true;
+		Ok(())
+	}
+
+	/// Return the tone ID after the highest one in this archive, following
+	/// the numbering of the file instead of reusing IDs of removed items.
+	pub fn next_free_id(&self) -> u32 {
+		self.items.iter().filter_map(|item| item.id).max().map(|id| id + 1).unwrap_or(0)
+	}
+
+	/// Return a description of each tone ID used by more than one item.
+	pub fn id_collisions(&self) -> Vec<String> {
+		let mut names: std::collections::BTreeMap<u32, Vec<&str>> = std::collections::BTreeMap::new();
+		for item in &self.items {
+			if let Some(id) = item.id {
+				names.entry(id).or_default().push(&item.name)
+			}
+		}
+		names.into_iter()
+			.filter(|(_, names)| names.len() > 1)
+			.map(|(id, names)| format!("Tone ID {} is used by {}", id, names.join(", ")))
+			.collect()
+	}
+
+	/// Return what saving this archive will do with each item.
+	/// 
+	/// This has to be called before saving, since saving caches the encoded items.
+	pub fn save_report(&self) -> SaveReport {
+		let mut report = SaveReport::default();
+		for item in &self.items {
+			match item.save_source() {
+				SaveSource::Original | SaveSource::Binary => report.untouched.push(item.name.clone()),
+				SaveSource::Empty => report.empty.push(item.name.clone()),
+				source @ (SaveSource::Encode | SaveSource::Cached) => {
+					if source == SaveSource::Encode {
+						report.fresh_encodes += 1
+					}
+					report.encoded.push(item.name.clone());
+					let (from, to) = (item.sample_rate(), item.encoded_sample_rate());
+					if from != to {
+						report.resampled.push((item.name.clone(), from, to))
+					}
+				}
+			}
+		}
+		report
+	}
+
+	/// Encode every item and return the bytes of a nus3audio file named `name`.
+	/// 
+	/// Items that fail to encode are written empty. `progress` is called with the
+	/// number of the item, the number of items and its name before each item is
+	/// encoded, and `cancel` is checked between items.
+	/// 
+	/// If nothing changed since the archive was opened, the file is returned as
+	/// it is on disk. Otherwise sections of the opened file that the nus3audio
+	/// crate doesn't write are put back, see [Archive::extra_sections].
+	pub fn to_bytes(&mut self, name: &str, config: &dyn Config, progress: &mut dyn FnMut(usize, usize, &str), cancel: &Cancel) -> Result<Vec<u8>, String> {
+		// A file with nothing changed is written back as it was, however it was laid out
+		if let Some(original) = self.unchanged_original() {
+			debug!("{} is unchanged, writing it as it was opened", name);
+			return Ok(original)
+		}
+
+		let mut nus3audio = Nus3audioFile::new();
+		let mut next_id = self.next_free_id();
+		let total = self.items.len();
+
+		for (number, item) in self.items.iter_mut().enumerate() {
+			cancel.check()?;
+			progress(number, total, &item.name);
+			let data = item.get_nus3_encoded_raw(name, &item.extension.to_string(), config).unwrap_or_else(|_| Vec::new());
+			let id = item.id.unwrap_or_else(|| {
+				next_id += 1;
+				next_id - 1
+			});
+			nus3audio.files.push(
+				nus3audio::AudioFile {
+					id,
+					name: item.name.to_owned(),
+					data
+				}
+			)
+		}
+
+		// Offsets in a nus3audio are 32 bits, so past 4 GiB they would wrap around
+		let audio_size: u64 = nus3audio.files.iter().map(|file| file.data.len() as u64).sum();
+		if audio_size > u32::MAX as u64 {
+			return Err(format!(
+				"The audio adds up to {}, more than the {} a nus3audio file can hold.",
+				human_readable_size(audio_size), human_readable_size(u32::MAX as u64)
+			))
+		}
+
+		let mut export: Vec<u8> = Vec::new();
+		nus3audio.write(&mut export);
+		structure::insert_sections(export, &self.extra_sections)
+	}
+
+	/// Return the bytes of the file this archive was opened from, if nothing
+	/// has changed since it was.
+	/// 
+	/// Saving marks the archive as unmodified, so once it has been modified
+	/// it's never taken to be unchanged again.
+	fn unchanged_original(&mut self) -> Option<Vec<u8>> {
+		self.unchanged_since_open &= !self.modified;
+		if !self.unchanged_since_open {
+			return None
+		}
+		fs::read(long_path(self.path.as_ref()?)).ok()
+	}
+
+	/// Save this archive to `path`, or the path it was opened from, and take
+	/// the name of the file it was saved to.
+	/// 
+	/// Marks this archive as being unmodified, and forgets the audio of replaced
+	/// items from before they were replaced. Returns any warnings about the
+	/// paired nus3bank, see [Archive::bank_warnings]. If `cancel` is set while
+	/// encoding, nothing is written, see [Archive::to_bytes].
+	pub fn save(&mut self, path: Option<PathBuf>, config: &dyn Config, progress: &mut dyn FnMut(usize, usize, &str), cancel: &Cancel) -> Result<Vec<String>, String> {
+		let path = match path.or_else(|| self.path.clone()) {
+			Some(path) => path.with_extension("nus3audio"),
+			None => return Err("No path has been set to save.".to_owned())
+		};
+		let name = path.file_name().unwrap().to_string_lossy().to_string();
+
+		let collisions = self.id_collisions();
+		if !collisions.is_empty() {
+			return Err(format!("Every item needs its own tone ID:\n{}", collisions.join("\n")))
+		}
+
+		let export = self.to_bytes(&name, config, progress, cancel)?;
+
+		info!("Writing {} to {:?}", name, path);
+
+		if let Err(error) = fs::write(long_path(&path), &export) {
+			Err(error.to_string())
+		} else {
+			self.name = name;
+			self.modified = false;
+			for item in &mut self.items {
+				item.forget_original_audio();
+				item.recovered = false
+			}
+			let mut warnings = self.bank_warnings(&path);
+			if let Err(error) = self.project.save(&path) {
+				warnings.push(format!("Could not write the sidecar file: {}", error))
+			}
+			Ok(warnings)
+		}
+	}
+
+	/// Toggle the bookmark of the item at `index`. Returns whether or not it's now bookmarked.
+	/// 
+	/// Bookmarks aren't part of the nus3audio file, so this doesn't mark the archive
+	/// as modified. The sidecar file is written right away if this archive has a path.
+	pub fn toggle_bookmark(&mut self, index: usize) -> Result<bool, String> {
+		let name = match self.items.get(index) {
+			Some(item) => item.name.clone(),
+			None => return Err(format!("There is no item at index {}", index))
+		};
+
+		let bookmarked = if self.project.bookmarks.remove(&name) {
+			false
+		} else {
+			self.project.bookmarks.insert(name);
+			true
+		};
+		self.save_project()?;
+		Ok(bookmarked)
+	}
+
+	/// Return the index of the next bookmarked item after `index`, wrapping around.
+	/// Goes backward instead if `backward`.
+	pub fn next_bookmark(&self, index: Option<usize>, backward: bool) -> Option<usize> {
+		let count = self.items.len();
+		if count == 0 { return None }
+		(1..=count)
+			.map(|offset| match (index, backward) {
+				(Some(index), false) => (index + offset) % count,
+				(Some(index), true) => (index + count * 2 - offset) % count,
+				(None, false) => offset - 1,
+				(None, true) => count - offset
+			})
+			.find(|index| self.project.bookmarks.contains(&self.items[*index].name))
+	}
+
+	/// Return the indices of the items in the order `sort` lists them.
+	/// Items that compare equal keep their order in the file.
+	pub fn sorted_order(&self, sort: Sort) -> Vec<usize> {
+		let mut order: Vec<usize> = (0..self.items.len()).collect();
+		let items = &self.items;
+		let compare = |a: &usize, b: &usize| {
+			let (index_a, index_b) = (a, b);
+			let (a, b) = (&items[*a], &items[*b]);
+			match sort.key {
+				SortKey::Index => index_a.cmp(index_b),
+				SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+				SortKey::Id => a.id.cmp(&b.id),
+				SortKey::Format => a.extension.to_string().cmp(&b.extension.to_string()),
+				SortKey::Size => a.projected_size().cmp(&b.projected_size())
+			}
+		};
+		// The sort is stable, so equal items stay in file order either way
+		if sort.descending {
+			order.sort_by(|a, b| compare(a, b).reverse())
+		} else {
+			order.sort_by(compare)
+		}
+		order
+	}
+
+	/// Split `order`, as returned by [Archive::sorted_order], into the groups
+	/// `grouping` puts the items in, each with its name.
+	/// 
+	/// Groups are in the order of their first item, and items keep their order
+	/// within them. Without a grouping, there are no groups.
+	pub fn groups(&self, order: &[usize], grouping: Grouping) -> Vec<(String, Vec<usize>)> {
+		let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+		if grouping == Grouping::None {
+			return groups
+		}
+		let mut positions = HashMap::new();
+		for &index in order {
+			let item = &self.items[index];
+			let name = match grouping {
+				Grouping::Prefix => match item.name.find('_') {
+					Some(end) => item.name[..=end].to_owned(),
+					None => "No prefix".to_owned()
+				},
+				Grouping::IdRange => match item.id {
+					Some(id) => {
+						let start = id - id % ID_GROUP_SIZE;
+						format!("Tone IDs {} to {}", start, start.saturating_add(ID_GROUP_SIZE - 1))
+					},
+					None => "No tone ID".to_owned()
+				},
+				Grouping::None => unreachable!()
+			};
+			let position = *positions.entry(name.clone()).or_insert_with(|| {
+				groups.push((name, Vec::new()));
+				groups.len() - 1
+			});
+			groups[position].1.push(index)
+		}
+		groups
+	}
+
+	/// Write the sidecar file of this archive, if it has a path.
+	pub fn save_project(&self) -> Result<(), String> {
+		match &self.path {
+			Some(path) => self.project.save(&path.with_extension("nus3audio")),
+			None => Ok(())
+		}
+	}
+
+	/// Return the projected size of this archive when written.
+	/// 
+	/// Items that still have to be encoded are estimated, see [ListItem::save_size].
+	/// Items whose size isn't known at all are left out, so the real size will be larger.
+	pub fn projected_size(&self) -> ProjectedSize {
+		let mut projected = ProjectedSize { bytes: NUS3AUDIO_OVERHEAD, ..Default::default() };
+		for item in &self.items {
+			projected.bytes += NUS3AUDIO_ITEM_OVERHEAD + item.name.len() as u64;
+			match item.projected_size() {
+				Some(item_size) => projected.bytes += item_size as u64,
+				None => match item.save_size() {
+					Some(item_size) => {
+						projected.bytes += item_size as u64;
+						projected.estimated += 1
+					},
+					None => projected.unknown += 1
+				}
+			}
+		}
+		projected
+	}
+
+	/// Compare this archive against the nus3bank next to `path`, if there is one.
+	/// 
+	/// Returns a warning for each way the bank's tone table diverges from this
+	/// archive, since the bank would need patching too.
+	pub fn bank_warnings(&self, path: &Path) -> Vec<String> {
+		let bank_path = path.with_extension("nus3bank");
+		let bytes = match fs::read(&bank_path) {
+			Ok(bytes) => bytes,
+			Err(_) => return Vec::new()
+		};
+
+		let tones = match tone_names(&bytes) {
+			Ok(tones) => tones,
+			Err(error) => {
+				warn!("Could not read tones of {:?}: {}", bank_path, error);
+				return Vec::new()
+			}
+		};
+
+		let mut warnings = Vec::new();
+		if tones.len() != self.items.len() {
+			warnings.push(format!("The nus3bank has {} tones, but this file has {} items.", tones.len(), self.items.len()))
+		}
+		for (index, (tone, item)) in tones.iter().zip(self.items.iter()).enumerate() {
+			match tone {
+				Some(tone) if *tone != item.name => warnings.push(format!("Item {} is named {}, but the nus3bank calls it {}.", index, item.name, tone)),
+				_ => {}
+			}
+		}
+
+		for warning in &warnings {
+			warn!("{}", warning)
+		}
+		warnings
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::item::{ estimate_idsp_size, estimate_lopus_size };
+
+	#[test]
+	fn projected_size_counts_added_audio() {
+		let mut archive = Archive::new();
+		let before = archive.projected_size();
+
+		let mut item = ListItem::new("added".to_owned());
+		item.set_audio_from_bytes(crate::codec::silence_wav(0.5, 48_000).unwrap(), EncodingType::WAV).unwrap();
+		archive.add(item);
+
+		let after = archive.projected_size();
+		assert_eq!(after.bytes - before.bytes, NUS3AUDIO_ITEM_OVERHEAD + "added".len() as u64 + estimate_idsp_size(24_000, 1) as u64);
+
+		// Audio that isn't WAV or isn't IDSP goes by the length read when it was added
+		let mut item = ListItem::new("lopus".to_owned());
+		item.set_audio_from_bytes(crate::codec::silence_wav(0.5, 48_000).unwrap(), EncodingType::WAV).unwrap();
+		item.extension = AudioExtension::Lopus;
+		archive.add(item);
+
+		let last = archive.projected_size();
+		assert_eq!(last.estimated, 1);
+		assert_eq!(last.bytes - after.bytes, NUS3AUDIO_ITEM_OVERHEAD + "lopus".len() as u64 + estimate_lopus_size(24_000, 48_000, 1) as u64);
+	}
+}
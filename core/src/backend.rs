@@ -0,0 +1,341 @@
+//! The external tools as [Codec] backends: VGAudioCli for the formats in
+//! nus3audio files, and vgmstream for decoding nearly anything else.
+
+use std::{
+	fs,
+	path::Path,
+	process::Command,
+	sync::atomic::{ AtomicUsize, Ordering }
+};
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use crate::{
+	codec::{ Codec, Pcm },
+	config::Config,
+	util::{ human_readable_size, long_path }
+};
+
+/// Formats VGAudioCli can read and write.
+const VGAUDIO_CLI_EXTENSIONS: &[&str] = &["idsp", "lopus", "dsp", "brstm", "bcstm", "bfstm", "hps", "adx", "hca", "genh", "at9"];
+
+/// The number of the next file VGAudioCli decodes to, so decodes running at
+/// the same time don't write to the same file.
+static NEXT_DECODE: AtomicUsize = AtomicUsize::new(0);
+
+/// Return the extension of `path` in lowercase, or an empty string.
+fn extension_of(path: &Path) -> String {
+	path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase()
+}
+
+/// VGAudioCli, for decoding and encoding IDSP, LOPUS and a few other formats.
+pub struct VGAudioCliCodec;
+
+impl Codec for VGAudioCliCodec {
+	fn name(&self) -> &'static str { "VGAudioCli" }
+
+	fn probe(&self, path: &Path, config: &dyn Config) -> bool {
+		!config.vgaudio_cli_path().is_empty() && VGAUDIO_CLI_EXTENSIONS.contains(&extension_of(path).as_str())
+	}
+
+	fn decode(&self, path: &Path, config: &dyn Config) -> Result<Pcm, String> {
+		// The WAV goes to the cache, since `path` may be a file of the user's with a WAV next to it
+		if let Err(error) = fs::create_dir_all(config.cache_dir()) {
+			return Err(format!("Error creating cache directory {:?}\n{}", config.cache_dir(), error))
+		}
+		let dest_file = config.cache_dir().join(format!("decode_{}_{}.wav", std::process::id(), NEXT_DECODE.fetch_add(1, Ordering::Relaxed)));
+		let decoded = vgaudio_cli_convert(path, &dest_file, None, config);
+		let _ = fs::remove_file(&dest_file);
+		Pcm::from_wav(&decoded?)
+	}
+
+	fn decode_to_wav(&self, path: &Path, dest: &Path, config: &dyn Config) -> Result<(), String> {
+		vgaudio_cli_run(path, dest, None, config)
+	}
+
+	fn can_encode(&self, extension: &str, config: &dyn Config) -> bool {
+		!config.vgaudio_cli_path().is_empty() && VGAUDIO_CLI_EXTENSIONS.contains(&extension)
+	}
+
+	fn encode(&self, pcm: &Pcm, loop_points: Option<(usize, usize)>, dest: &Path, config: &dyn Config) -> Result<Vec<u8>, String> {
+		let src_file = dest.with_extension("wav");
+		if let Err(error) = fs::write(&src_file, pcm.to_wav()?) {
+			return Err(format!("Error writing source file {:?}\n{}", src_file, error))
+		}
+		vgaudio_cli_convert(&src_file, dest, loop_points, config)
+	}
+
+	// VGAudioCli reads from disk anyway, so the file is used as it is
+	fn encode_wav(&self, wav: &Path, loop_points: Option<(usize, usize)>, dest: &Path, config: &dyn Config) -> Result<Vec<u8>, String> {
+		vgaudio_cli_convert(wav, dest, loop_points, config)
+	}
+}
+
+/// vgmstream, for decoding the huge number of formats it supports.
+pub struct VgmstreamCodec;
+
+impl Codec for VgmstreamCodec {
+	fn name(&self) -> &'static str { "vgmstream" }
+
+	fn probe(&self, _path: &Path, config: &dyn Config) -> bool {
+		// vgmstream is the only way to find out if vgmstream can decode something
+		!config.vgmstream_path().is_empty()
+	}
+
+	fn decode(&self, path: &Path, config: &dyn Config) -> Result<Pcm, String> {
+		Pcm::from_wav(&vgmstream_decode(path, config)?)
+	}
+
+	fn decode_to_wav(&self, path: &Path, dest: &Path, config: &dyn Config) -> Result<(), String> {
+		vgmstream_decode_to(path, dest, config)
+	}
+}
+
+/// Run VGAudioCli, convert `src_file` to `dest_file` and return it as bytes.
+/// 
+/// `loop_points` are given to VGAudioCli when encoding.
+pub fn vgaudio_cli_convert(src_file: &Path, dest_file: &Path, loop_points: Option<(usize, usize)>, config: &dyn Config) -> Result<Vec<u8>, String> {
+	vgaudio_cli_run(src_file, dest_file, loop_points, config)?;
+
+	match fs::read(dest_file) {
+		Ok(bytes) => {
+			debug!("Got VGAudioCli output (output is {})", human_readable_size(bytes.len() as u64));
+			Ok(bytes)
+		},
+		Err(error) => Err(format!("Error reading destination file {:?}\n{}", dest_file, error))
+	}
+}
+
+/// Run VGAudioCli and convert `src_file` to `dest_file`, leaving it on disk.
+/// 
+/// `loop_points` are given to VGAudioCli when encoding.
+pub fn vgaudio_cli_run(src_file: &Path, dest_file: &Path, loop_points: Option<(usize, usize)>, config: &dyn Config) -> Result<(), String> {
+	let vgaudio_cli_path = config.vgaudio_cli_path();
+	if vgaudio_cli_path.is_empty() {
+		return Err("VGAudiCli path is empty".to_owned())
+	}
+
+	let mut command: Command;
+	match config.vgaudio_cli_prepath() {
+		vgaudio_cli_prepath if !vgaudio_cli_prepath.is_empty() => {
+			// Add the prepath if it isn't empty
+			command = Command::new(vgaudio_cli_prepath);
+			command.arg(vgaudio_cli_path);
+		},
+		_ => {
+			command = Command::new(vgaudio_cli_path);
+		}
+	}
+
+	command.arg("-c")
+		.arg(long_path(src_file))
+		.arg(long_path(dest_file));
+	
+	// Add loop points if they exist
+	if let Some((from, to)) = loop_points {
+		command.arg("-l").arg(format!("{}-{}", from, to)).arg("--cbr").arg("--opusheader").arg("namco");
+	}
+
+	let output = crate::tool::output(&mut command, "VGAudioCli", config.tool_timeout(), true)?;
+
+	if let Some(code) = output.status.code() {
+		if code != 0 {
+			let mut error = format!("Attempted running VGAudioCli, found exit code {}\n", code);
+
+			let stdout = String::from_utf8(output.stdout);
+			let stderr = String::from_utf8(output.stderr);
+
+			if let Ok(out) = stdout {
+				if out.is_empty() {
+					error.push_str("stdout is empty\n")
+				} else {
+					error.push_str(&format!("stdout is:\n{}\n", out))
+				}
+			} else {
+				error.push_str("stdout couldn't be read\n")
+			}
+			if let Ok(err) = stderr {
+				if err.is_empty() {
+					error.push_str("stderr is empty")
+				} else {
+					error.push_str(&format!("stderr is:\n{}", err))
+				}
+			} else {
+				error.push_str("stderr couldn't be read")
+			}
+
+			return Err(error)
+		}
+
+		let stdout = String::from_utf8(output.stdout);
+		let stderr = String::from_utf8(output.stderr);
+
+		if let Ok(out) = stdout {
+			if out.is_empty() {
+				debug!("stdout is empty")
+			} else {
+				debug!("stdout is:\n{}", out)
+			}
+		} else {
+			debug!("stdout couldn't be read")
+		}
+		if let Ok(err) = stderr {
+			if err.is_empty() {
+				debug!("stderr is empty")
+			} else {
+				debug!("stderr is:\n{}", err)
+			}
+		} else {
+			debug!("stderr couldn't be read")
+		}
+	} else {
+		return Err("Attempted running VGAudioCli, didn't get any exit code".to_string())
+	}
+
+	Ok(())
+}
+
+/// Run vgmstream, decode `src_file` and return it as bytes.
+/// 
+/// The loop of a looping file isn't written out, see [crate::item::ListItem::loop_points_of]
+/// for reading its loop points.
+pub fn vgmstream_decode(src_file: &Path, config: &dyn Config) -> Result<Vec<u8>, String> {
+	let vgmstream_path = config.vgmstream_path();
+	if vgmstream_path.is_empty() {
+		return Err("vgmstream path is empty".to_owned())
+	}
+
+	// Create the command
+	let mut command = Command::new(vgmstream_path);
+	command.arg("-pi")
+	// -p: write the decoded WAV to stdout
+	// -i: play the file once to its end, so its loop is kept as loop points
+	//     instead of being played twice and faded out
+		.arg(long_path(src_file));
+
+	let output = run_vgmstream_decode(&mut command, config)?;
+
+	debug!("Decoded with vgmstream (output is {})", human_readable_size(output.len() as u64));
+
+	Ok(output)
+}
+
+/// Run vgmstream and decode `src_file` to the WAV file `dest_file`, without
+/// holding the decoded audio in memory. Like [vgmstream_decode], the loop isn't written out.
+pub fn vgmstream_decode_to(src_file: &Path, dest_file: &Path, config: &dyn Config) -> Result<(), String> {
+	let vgmstream_path = config.vgmstream_path();
+	if vgmstream_path.is_empty() {
+		return Err("vgmstream path is empty".to_owned())
+	}
+
+	// Create the command
+	let mut command = Command::new(vgmstream_path);
+	command.arg("-i")
+	// -i: play the file once to its end, see vgmstream_decode
+		.arg("-o")
+		.arg(long_path(dest_file))
+		.arg(long_path(src_file));
+
+	run_vgmstream_decode(&mut command, config)?;
+
+	debug!("Decoded with vgmstream to {:?}", dest_file);
+
+	Ok(())
+}
+
+/// Run a vgmstream decoding `command` and return its stdout, or an error
+/// with everything it printed if it failed.
+fn run_vgmstream_decode(command: &mut Command, config: &dyn Config) -> Result<Vec<u8>, String> {
+	let output = crate::tool::output(command, "vgmstream", config.tool_timeout(), false)?;
+
+	// Check the error code
+	if let Some(code) = output.status.code() {
+		if code != 0 {
+			let mut error = format!("Attempted running vgmstream, found exit code {}\n", code);
+
+			let stdout = String::from_utf8(output.stdout);
+			let stderr = String::from_utf8(output.stderr);
+
+			if let Ok(out) = stdout {
+				if out.is_empty() {
+					error.push_str("stdout is empty\n")
+				} else {
+					error.push_str(&format!("stdout is:\n{}\n", out))
+				}
+			} else {
+				error.push_str("stdout couldn't be read\n")
+			}
+			if let Ok(err) = stderr {
+				if err.is_empty() {
+					error.push_str("stderr is empty")
+				} else {
+					error.push_str(&format!("stderr is:\n{}", err))
+				}
+			} else {
+				error.push_str("stderr couldn't be read")
+			}
+
+			return Err(error)
+		}
+	}
+
+	Ok(output.stdout)
+}
+
+/// Run vgmstream, read metadata of `src_file` and return a [json::JsonValue].
+pub fn vgmstream_metadata(src_file: &Path, config: &dyn Config) -> Result<json::JsonValue, String> {
+	let vgmstream_path = config.vgmstream_path();
+	if vgmstream_path.is_empty() {
+		return Err("vgmstream path is empty".to_owned())
+	}
+
+	// Create the command
+	let mut command = Command::new(vgmstream_path);
+	command.arg("-mI")
+	// -m: print metadata only, don't decode
+	// -I: print requested file info as JSON
+		.arg(long_path(src_file));
+
+	let output = crate::tool::output(&mut command, "vgmstream", config.tool_timeout(), true)?;
+
+	// Check the error code
+	if let Some(code) = output.status.code() {
+		if code != 0 {
+			let mut error = format!("Attempted running vgmstream, found exit code {}\n", code);
+
+			let stdout = String::from_utf8(output.stdout);
+			let stderr = String::from_utf8(output.stderr);
+
+			if let Ok(out) = stdout {
+				if out.is_empty() {
+					error.push_str("stdout is empty\n")
+				} else {
+					error.push_str(&format!("stdout is:\n{}\n", out))
+				}
+			} else {
+				error.push_str("stdout couldn't be read\n")
+			}
+			if let Ok(err) = stderr {
+				if err.is_empty() {
+					error.push_str("stderr is empty")
+				} else {
+					error.push_str(&format!("stderr is:\n{}", err))
+				}
+			} else {
+				error.push_str("stderr couldn't be read")
+			}
+
+			return Err(error)
+		}
+	}
+
+	// Get string output
+	let text_output = match std::str::from_utf8(&output.stdout) {
+		Ok(output) => output,
+		Err(error) => return Err(format!("Error reading vgmstream output\n{}", error))
+	};
+	// Parse output as JSON
+	match json::parse(text_output) {
+		Ok(output) => Ok(output),
+		Err(error) => Err(format!("Error parsing vgmstream output\n{}", error))
+	}
+}
@@ -0,0 +1,130 @@
+//! Reading the tone table of the nus3bank paired with a nus3audio file.
+//! 
+//! Only as much of the format is read as is needed to compare the tones
+//! against the items of a nus3audio file.
+
+use crate::util::read_u32_le;
+
+/// Tones this size or smaller are placeholders without a name.
+const EMPTY_TONE_SIZE: usize = 0x0C;
+
+/// Return the names of the tones in a nus3bank file, in order.
+/// 
+/// Placeholder tones without a name are returned as [None].
+pub fn tone_names(bytes: &[u8]) -> Result<Vec<Option<String>>, String> {
+	let truncated = || "nus3bank is truncated".to_owned();
+
+	if bytes.get(..4) != Some(&b"NUS3"[..]) || bytes.get(0x08..0x10) != Some(&b"BANKTOC "[..]) {
+		return Err("Not a nus3bank file".to_owned())
+	}
+
+	// Sections follow the table of contents, each with a magic and size
+	let toc_size = read_u32_le(bytes, 0x10).ok_or_else(truncated)? as usize;
+	let mut offset = 0x14 + toc_size;
+	let tone = loop {
+		let magic = bytes.get(offset..offset + 4).ok_or_else(|| "nus3bank has no TONE section".to_owned())?;
+		let size = read_u32_le(bytes, offset + 4).ok_or_else(truncated)? as usize;
+		if magic == b"TONE" {
+			break bytes.get(offset + 8..offset + 8 + size).ok_or_else(truncated)?
+		}
+		offset += 8 + size
+	};
+
+	let count = read_u32_le(tone, 0).ok_or_else(truncated)? as usize;
+	let mut names = Vec::with_capacity(count);
+	for index in 0..count {
+		let entry_offset = read_u32_le(tone, 4 + index * 8).ok_or_else(truncated)? as usize;
+		let entry_size = read_u32_le(tone, 8 + index * 8).ok_or_else(truncated)? as usize;
+
+		if entry_size <= EMPTY_TONE_SIZE {
+			names.push(None);
+			continue
+		}
+
+		// The name is stored with its length, including a terminating null
+		let length = *tone.get(entry_offset + 6).ok_or_else(truncated)? as usize;
+		let name = tone.get(entry_offset + 7..entry_offset + 7 + length).ok_or_else(truncated)?;
+		let name = name.split(|byte| *byte == 0).next().unwrap_or_default();
+		names.push(Some(String::from_utf8_lossy(name).to_string()))
+	}
+
+	Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+	//! These nus3banks are laid out by hand from the format as far as [tone_names]
+	//! reads it. They aren't captured from game files, so they check the parsing
+	//! against our reading of the layout, not against real files.
+
+	use super::*;
+
+	/// Return a section with `magic` holding `data`.
+	fn section(magic: &[u8; 4], data: &[u8]) -> Vec<u8> {
+		let mut bytes = magic.to_vec();
+		bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+		bytes.extend_from_slice(data);
+		bytes
+	}
+
+	/// Return the data of a TONE section with a tone for each of `tones`,
+	/// named or a placeholder.
+	fn tone_section(tones: &[Option<&str>]) -> Vec<u8> {
+		let mut table = (tones.len() as u32).to_le_bytes().to_vec();
+		let mut entries = Vec::new();
+		let entries_start = 4 + tones.len() * 8;
+		for tone in tones {
+			let entry = match tone {
+				Some(name) => {
+					let mut entry = vec![0; 6];
+					entry.push(name.len() as u8 + 1);
+					entry.extend_from_slice(name.as_bytes());
+					entry.push(0);
+					entry.resize(entry.len().max(EMPTY_TONE_SIZE + 4), 0);
+					entry
+				},
+				None => vec![0; EMPTY_TONE_SIZE]
+			};
+			table.extend_from_slice(&((entries_start + entries.len()) as u32).to_le_bytes());
+			table.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+			entries.extend_from_slice(&entry)
+		}
+		table.extend_from_slice(&entries);
+		table
+	}
+
+	/// Return a nus3bank with the `sections` after its table of contents.
+	fn bank(sections: &[Vec<u8>]) -> Vec<u8> {
+		let mut bytes = b"NUS3".to_vec();
+		bytes.extend_from_slice(&0u32.to_le_bytes());
+		bytes.extend_from_slice(b"BANKTOC ");
+		bytes.extend_from_slice(&8u32.to_le_bytes());
+		bytes.extend_from_slice(&[0; 8]);
+		for section in sections {
+			bytes.extend_from_slice(section)
+		}
+		bytes
+	}
+
+	#[test]
+	fn names_in_order() {
+		let tones = [Some("se_jump"), None, Some("a_much_longer_tone_name")];
+		let bytes = bank(&[section(b"PROP", &[1; 4]), section(b"TONE", &tone_section(&tones))]);
+		assert_eq!(tone_names(&bytes), Ok(vec![Some("se_jump".to_owned()), None, Some("a_much_longer_tone_name".to_owned())]));
+		assert_eq!(tone_names(&bank(&[section(b"TONE", &tone_section(&[]))])), Ok(Vec::new()))
+	}
+
+	#[test]
+	fn errors() {
+		assert!(tone_names(b"NUS3\0\0\0\0AUDIINDX").is_err());
+		assert!(tone_names(&bank(&[section(b"PROP", &[1; 4])])).is_err());
+
+		// A tone table pointing past the end of the section
+		let mut tone = tone_section(&[Some("se_jump")]);
+		tone.truncate(tone.len() - 8);
+		assert!(tone_names(&bank(&[section(b"TONE", &tone)])).is_err());
+
+		let bytes = bank(&[section(b"TONE", &tone_section(&[Some("se_jump")]))]);
+		assert!(tone_names(&bytes[..bytes.len() - 4]).is_err())
+	}
+}
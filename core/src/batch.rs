@@ -0,0 +1,230 @@
+//! Applying the same operation to every nus3audio file in a folder, like
+//! replacing a jingle shared by every stage, and converting a folder of
+//! audio files to IDSP or LOPUS without a nus3audio at all.
+
+use std::{
+	fs,
+	path::{ Path, PathBuf }
+};
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use crate::{
+	cancel::{ Cancel, CANCELLED },
+	codec::{ EncodingType, Pcm },
+	config::Config,
+	item::{ AudioExtension, ListItem },
+	util::safe_file_name,
+	Archive
+};
+
+/// Name given to the cache directory of converted files, since they aren't in a nus3audio.
+const CONVERT_CACHE_NAME: &str = "convert";
+
+/// An operation on the item named `item` in each archive.
+pub enum Operation {
+	/// Replace the item's audio with the file at `path`.
+	Replace { item: String, path: PathBuf },
+	/// Export the item as WAV to `dir`, named after the archive and item.
+	Export { item: String, dir: PathBuf },
+	/// Set or clear the item's loop points, in samples.
+	SetLoop { item: String, loop_points: Option<(usize, usize)> }
+}
+
+impl Operation {
+	/// The name of the item this operation applies to.
+	pub fn item(&self) -> &str {
+		match self {
+			Self::Replace { item, .. } | Self::Export { item, .. } | Self::SetLoop { item, .. } => item
+		}
+	}
+
+	/// Whether or not this operation changes the archives it's applied to,
+	/// so they need to be saved.
+	pub fn modifies(&self) -> bool {
+		!matches!(self, Self::Export { .. })
+	}
+
+	/// Apply this operation to the item at `index` of `archive`.
+	fn apply(&self, archive: &mut Archive, index: usize, config: &dyn Config) -> Result<(), String> {
+		match self {
+			Self::Replace { path, .. } => archive.replace(index, path, config),
+			Self::Export { item, dir } => {
+				let wav = archive.items[index].get_audio_wav(None)?;
+				let stem = archive.name.trim_end_matches(".nus3audio");
+				let target = dir.join(format!("{}_{}.wav", safe_file_name(stem), safe_file_name(item)));
+				fs::write(&target, wav).map_err(|error| format!("Error writing {:?}\n{}", target, error))
+			},
+			Self::SetLoop { loop_points, .. } => archive.set_loop_points(index, *loop_points)
+		}
+	}
+}
+
+/// What happened during a batch run.
+#[derive(Default)]
+pub struct Report {
+	/// Archives the operation was applied to, or files that were converted.
+	pub applied: Vec<String>,
+	/// Archives without the item.
+	pub skipped: Vec<String>,
+	/// Archives or files that failed, with their error.
+	pub errors: Vec<(String, String)>,
+	/// Whether or not the run was cancelled before getting through the folder.
+	pub cancelled: bool
+}
+
+/// Return the nus3audio files in `dir`, sorted.
+pub fn archives_in(dir: &Path) -> Result<Vec<PathBuf>, String> {
+	let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+		.map_err(|error| format!("Could not read {:?}\n{}", dir, error))?
+		.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+		.filter(|path| path.extension().and_then(|e| e.to_str()) == Some("nus3audio"))
+		.collect();
+	paths.sort();
+	Ok(paths)
+}
+
+/// Apply `operation` to every nus3audio file in `dir`, saving those it changed
+/// to `out_dir` with the same names. The originals are never written to, so
+/// `out_dir` has to be a different folder if `operation` [modifies](Operation::modifies) them.
+/// 
+/// `progress` is called with the index of each archive, the number of archives and
+/// its path before it is processed. Once `cancel` is set, the archive being worked
+/// on is left as it was and the run stops.
+pub fn run(dir: &Path, out_dir: &Path, operation: &Operation, config: &dyn Config, mut progress: impl FnMut(usize, usize, &Path), cancel: &Cancel) -> Result<Report, String> {
+	if operation.modifies() {
+		fs::create_dir_all(out_dir).map_err(|error| format!("Could not create {:?}\n{}", out_dir, error))?;
+		if fs::canonicalize(dir).ok() == fs::canonicalize(out_dir).ok() {
+			return Err("Changed files have to be saved to a different folder than the originals.".to_owned())
+		}
+	}
+
+	let mut report = Report::default();
+	let paths = archives_in(dir)?;
+
+	for (number, path) in paths.iter().enumerate() {
+		if cancel.is_cancelled() { break }
+		progress(number, paths.len(), path);
+		let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+
+		// Check for the item before decoding everything
+		match Archive::template(path) {
+			Ok(template) if template.items.iter().any(|item| item.name == operation.item()) => {},
+			Ok(_) => {
+				report.skipped.push(name);
+				continue
+			},
+			Err(error) => {
+				report.errors.push((name, error));
+				continue
+			}
+		}
+
+		let mut archive = match Archive::open(path, config, &mut |_, _, _| (), cancel) {
+			Ok((archive, _)) => archive,
+			Err(error) if error == CANCELLED => break,
+			Err(error) => {
+				report.errors.push((name, error));
+				continue
+			}
+		};
+
+		let index = archive.items.iter().position(|item| item.name == operation.item()).expect("template had the item");
+		let result = operation.apply(&mut archive, index, config).and_then(|()| {
+			if archive.modified {
+				for warning in archive.save(Some(out_dir.join(&name)), config, &mut |_, _, _| (), cancel)? {
+					warn!("{}: {}", name, warning)
+				}
+			}
+			Ok(())
+		});
+
+		match result {
+			Ok(()) => report.applied.push(name),
+			Err(error) if error == CANCELLED => break,
+			Err(error) => {
+				error!("{}: {}", name, error);
+				report.errors.push((name, error))
+			}
+		}
+	}
+
+	report.cancelled = cancel.is_cancelled();
+	Ok(report)
+}
+
+/// Options for [convert].
+pub struct ConvertOptions {
+	/// The format to convert to. Must be IDSP or LOPUS.
+	pub format: AudioExtension,
+	/// Loop the whole of files that don't have loop points of their own.
+	pub loop_all: bool
+}
+
+/// Return the audio files in `dir` that can be converted, sorted.
+pub fn audio_files_in(dir: &Path) -> Result<Vec<PathBuf>, String> {
+	let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+		.map_err(|error| format!("Could not read {:?}\n{}", dir, error))?
+		.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+		.filter(|path| path.is_file())
+		.filter(|path| EncodingType::from_extension(path.extension().and_then(|e| e.to_str()).unwrap_or_default()) != EncodingType::Bin)
+		.collect();
+	paths.sort();
+	Ok(paths)
+}
+
+/// Convert the audio file at `path` to `options.format`, and return the encoded bytes.
+pub fn convert_file(path: &Path, options: &ConvertOptions, config: &dyn Config) -> Result<Vec<u8>, String> {
+	let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+	let mut item = ListItem::new(name);
+	item.extension = options.format.clone();
+	item.replace_from_file(CONVERT_CACHE_NAME, path, config)?;
+
+	if options.loop_all && item.loop_points().is_none() {
+		let pcm = Pcm::from_wav(&item.get_audio_wav(None)?)?;
+		let length = pcm.samples.len() / pcm.channels.max(1) as usize;
+		if length > 0 {
+			item.loop_points_samples = Some((0, length))
+		}
+	}
+
+	item.get_nus3_encoded_raw(CONVERT_CACHE_NAME, &options.format.to_string(), config)
+}
+
+/// Convert every audio file in `dir` to `options.format`, writing them to `out_dir`
+/// with the same names and the new extension.
+/// 
+/// `progress` is called with the index of each file, the number of files and its
+/// path before it is converted. Once `cancel` is set, the run stops after the file
+/// being converted.
+pub fn convert(dir: &Path, out_dir: &Path, options: &ConvertOptions, config: &dyn Config, mut progress: impl FnMut(usize, usize, &Path), cancel: &Cancel) -> Result<Report, String> {
+	if options.format == AudioExtension::Bin {
+		return Err("Files can only be converted to IDSP or LOPUS.".to_owned())
+	}
+	fs::create_dir_all(out_dir).map_err(|error| format!("Could not create {:?}\n{}", out_dir, error))?;
+
+	let mut report = Report::default();
+
+	let paths = audio_files_in(dir)?;
+
+	for (number, path) in paths.iter().enumerate() {
+		if cancel.is_cancelled() { break }
+		progress(number, paths.len(), path);
+		let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+		let target = out_dir.join(path.with_extension(options.format.to_string()).file_name().unwrap_or_default());
+
+		let result = convert_file(path, options, config).and_then(|bytes| {
+			fs::write(&target, bytes).map_err(|error| format!("Error writing {:?}\n{}", target, error))
+		});
+
+		match result {
+			Ok(()) => report.applied.push(name),
+			Err(error) => {
+				error!("{}: {}", name, error);
+				report.errors.push((name, error))
+			}
+		}
+	}
+
+	report.cancelled = cancel.is_cancelled();
+	Ok(report)
+}
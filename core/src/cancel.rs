@@ -0,0 +1,41 @@
+//! Stopping a long operation, like opening, saving or a batch run, partway
+//! through. The frontend keeps a clone of a [Cancel] and the operation checks
+//! it between steps, so the step that's running always finishes first.
+
+use std::sync::{
+	atomic::{ AtomicBool, Ordering },
+	Arc
+};
+
+/// The error an operation returns when it was cancelled.
+pub const CANCELLED: &str = "Cancelled";
+
+/// A flag asking an operation to stop. Clones share the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+	/// Create a flag that hasn't been set.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Ask the operation to stop at its next step.
+	pub fn cancel(&self) {
+		self.0.store(true, Ordering::Relaxed)
+	}
+
+	/// Whether or not the operation has been asked to stop.
+	pub fn is_cancelled(&self) -> bool {
+		self.0.load(Ordering::Relaxed)
+	}
+
+	/// Return [CANCELLED] as an error if the operation has been asked to stop.
+	pub fn check(&self) -> Result<(), String> {
+		if self.is_cancelled() {
+			Err(CANCELLED.to_owned())
+		} else {
+			Ok(())
+		}
+	}
+}
@@ -0,0 +1,1060 @@
+//! This is (intended to be) a small but helpful codec abstraction
+//! for the program to use. The GUI itself and the user shouldn't have
+//! to care which decoder or encoder is being used exactly.
+
+use std::{
+	cell::{RefCell, Ref},
+	fs::File,
+	io::{ Cursor, Read },
+	num::{ NonZeroU8, NonZeroU32, NonZeroUsize },
+	path::Path,
+	sync::{ Arc, RwLock },
+	time::Duration
+};
+use lazy_static::lazy_static;
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use rodio::Source;
+use crate::{
+	config::Config,
+	header::NativeHeader,
+	util::long_path
+};
+
+/// How much of an IDSP or LOPUS file is read to find its length, see [probe_duration].
+const PROBE_HEADER_SIZE: u64 = 0x1000;
+
+/// Enum of encoded file types.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EncodingType {
+	/// Ogg Vorbis audio.
+	Ogg,
+	/// FLAC audio.
+	FLAC,
+	/// WAV audio.
+	WAV,
+	/// MP3 audio.
+	MP3,
+	/// AIFF audio.
+	AIFF,
+	/// AAC audio, usually in an M4A container.
+	AAC,
+	/// Ogg Opus audio. Neither decoder handles this, so it is
+	/// decoded by vgmstream before it gets here.
+	Opus,
+	/// Special encoding type that specifies data which
+	/// could or should not be read as audio.
+	Bin
+}
+
+impl EncodingType {
+	/// Whether or not this encoding type could be decoded
+	/// with the current feature set.
+	/// 
+	/// This doesn't necessarily mean the decoding will be
+	/// successful, just that an encoding of this type
+	/// should be usabale.
+	pub fn can_be_decoded(&self) -> bool {
+		match self {
+			Self::Bin => false,
+			Self::Opus => false,
+			#[cfg(not(feature = "symphonia"))]
+			Self::AIFF | Self::AAC => false,
+			_ => true
+		}
+	}
+
+	/// Return the usual file extension of this encoding type.
+	pub fn extension(&self) -> &'static str {
+		match self {
+			Self::Ogg => "ogg",
+			Self::FLAC => "flac",
+			Self::WAV => "wav",
+			Self::MP3 => "mp3",
+			Self::AIFF => "aiff",
+			Self::AAC => "m4a",
+			Self::Opus => "opus",
+			Self::Bin => "bin"
+		}
+	}
+
+	/// Return an EncodingType from the given extension.
+	pub fn from_extension(extension: &str) -> Self {
+		match &extension.to_lowercase()[..] {
+			"ogg" => Self::Ogg,
+			"flac" => Self::FLAC,
+			"wav" => Self::WAV,
+			"mp3" => Self::MP3,
+			"aif" | "aiff" | "aifc" => Self::AIFF,
+			"m4a" | "mp4" | "aac" => Self::AAC,
+			"opus" => Self::Opus,
+			_ => Self::Bin
+		}
+	}
+}
+
+/// Read how long the audio file at `path` is from its headers, without
+/// decoding it. Returns None if its headers don't say, or it isn't audio.
+pub fn probe_duration(path: &Path) -> Option<Duration> {
+	let extension = path.extension()?.to_str()?.to_lowercase();
+	let (samples, sample_rate) = match extension.as_str() {
+		"idsp" | "lopus" => {
+			let mut bytes = Vec::new();
+			File::open(long_path(path)).ok()?.take(PROBE_HEADER_SIZE).read_to_end(&mut bytes).ok()?;
+			let header = NativeHeader::parse(&bytes).ok()?;
+			(header.sample_count as u64, header.sample_rate)
+		},
+		"wav" => {
+			let reader = hound::WavReader::open(long_path(path)).ok()?;
+			(reader.duration() as u64, reader.spec().sample_rate)
+		},
+		_ => return decoder_duration(path)
+	};
+	if samples == 0 || sample_rate == 0 {
+		return None
+	}
+	Some(Duration::from_secs_f64(samples as f64 / sample_rate as f64))
+}
+
+#[cfg(feature = "symphonia")]
+/// Read how long the audio file at `path` is with Symphonia, from the headers
+/// of its container. Many MP3 files don't say, for one.
+fn decoder_duration(path: &Path) -> Option<Duration> {
+	use symphonia::core::{
+		formats::FormatOptions,
+		io::MediaSourceStream,
+		meta::MetadataOptions,
+		probe::Hint
+	};
+
+	let stream = MediaSourceStream::new(Box::new(File::open(long_path(path)).ok()?), Default::default());
+	let mut hint = Hint::new();
+	if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+		hint.with_extension(extension);
+	}
+	let probed = symphonia::default::get_probe()
+		.format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+		.ok()?;
+	let params = &probed.format.default_track()?.codec_params;
+	match (params.n_frames, params.sample_rate) {
+		(Some(frames), Some(sample_rate)) if sample_rate > 0 => Some(Duration::from_secs_f64(frames as f64 / sample_rate as f64)),
+		_ => None
+	}
+}
+
+#[cfg(not(feature = "symphonia"))]
+/// Read how long the audio file at `path` is with rodio, which only knows
+/// for some formats.
+fn decoder_duration(path: &Path) -> Option<Duration> {
+	let file = std::io::BufReader::new(File::open(long_path(path)).ok()?);
+	rodio::Decoder::new(file).ok()?.total_duration()
+}
+
+/// An encoded file.
+/// 
+/// Its bytes are shared, so clones of it, and readers like those the audio is
+/// streamed from while it plays, don't copy them.
+#[derive(Clone)]
+pub struct EncodedFile {
+	pub bytes: Arc<[u8]>,
+	pub encoding: EncodingType,
+	channels: RefCell<Option<u16>>,
+	sample_rate: RefCell<Option<u32>>
+}
+
+impl EncodedFile {
+	/// Create a new encoded file from the given bytes.
+	pub fn from_bytes_with_encoding(bytes: Vec<u8>, encoding: EncodingType) -> Self {
+		Self {
+			bytes: bytes.into(),
+			encoding,
+			channels: RefCell::new(None),
+			sample_rate: RefCell::new(None)
+		}
+	}
+
+	/// Whether or not this file could be decoded with
+	/// the current feature set.
+	/// 
+	/// This doesn't necessarily mean the decoding will be
+	/// successful, just that an encoding of this type
+	/// should be usabale.
+	pub fn can_be_decoded(&self) -> bool {
+		self.encoding.can_be_decoded()
+	}
+
+	/// Decode this to interleaved 16-bit samples.
+	/// 
+	/// Uses Symphonia when the `symphonia` feature is enabled, and rodio otherwise.
+	pub fn decode(&self) -> Result<Vec<i16>, DecodeError> {
+		match self.encoding {
+			EncodingType::Bin => return Err(DecodeError::DecodeBin),
+			EncodingType::Opus => return Err(DecodeError::Unsupported(EncodingType::Opus)),
+			// WAV can be in many bit depths, which are handled here
+			EncodingType::WAV => return self.hound_decode(),
+			_ => {}
+		}
+
+		#[cfg(feature = "symphonia")]
+		return self.symphonia_decode();
+
+		#[cfg(not(feature = "symphonia"))]
+		return self.rodio_decode().map_err(DecodeError::RodioDecoder);
+	}
+
+	/// Return the channel count and sample rate of this file.
+	/// 
+	/// These are read from its headers where possible, so the file is only
+	/// decoded if they don't say.
+	pub fn info(&self) -> Result<(u16, u32), DecodeError> {
+		if let (Some(channels), Some(sample_rate)) = (*self.channels.borrow(), *self.sample_rate.borrow()) {
+			return Ok((channels, sample_rate))
+		}
+		match self.header_info()? {
+			Some((channels, sample_rate)) => {
+				let _ = self.channels.borrow_mut().replace(channels);
+				let _ = self.sample_rate.borrow_mut().replace(sample_rate);
+			},
+			None => { self.decode()?; }
+		}
+		Ok((self.channels.borrow().expect("channels with decoded audio file"), self.sample_rate.borrow().expect("sample rate with decoded audio file")))
+	}
+
+	/// Return the length of this file in samples per channel.
+	/// 
+	/// Like [EncodedFile::info], this is read from its headers where possible,
+	/// so the file is only decoded if they don't say.
+	pub fn sample_count(&self) -> Result<usize, DecodeError> {
+		if self.encoding == EncodingType::WAV {
+			return Ok(hound::WavReader::new(Cursor::new(&self.bytes)).map_err(DecodeError::Hound)?.duration() as usize)
+		}
+		if let Some(samples) = self.header_sample_count() {
+			return Ok(samples)
+		}
+		let (channels, _) = self.info()?;
+		Ok(self.decode()?.len() / channels.max(1) as usize)
+	}
+
+	/// Read the length of this file in samples per channel from its headers,
+	/// without decoding it. Returns None if the headers don't say.
+	fn header_sample_count(&self) -> Option<usize> {
+		if matches!(self.encoding, EncodingType::Bin | EncodingType::Opus) { return None }
+
+		#[cfg(feature = "symphonia")]
+		{
+			use symphonia::core::{
+				formats::FormatOptions,
+				io::MediaSourceStream,
+				meta::MetadataOptions,
+				probe::Hint
+			};
+
+			let stream = MediaSourceStream::new(Box::new(Cursor::new(self.bytes.clone())), Default::default());
+			let mut hint = Hint::new();
+			hint.with_extension(self.encoding.extension());
+			let probed = symphonia::default::get_probe()
+				.format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+				.ok()?;
+			probed.format.default_track()?.codec_params.n_frames.map(|frames| frames as usize)
+		}
+
+		// rodio only knows the duration for some formats
+		#[cfg(not(feature = "symphonia"))]
+		{
+			let decoder = rodio::Decoder::new(Cursor::new(self.bytes.clone())).ok()?;
+			let sample_rate = decoder.sample_rate();
+			decoder.total_duration().map(|duration| (duration.as_secs_f64() * sample_rate as f64).round() as usize)
+		}
+	}
+
+	/// Read the channel count and sample rate of this file from its headers,
+	/// without decoding it. Returns None if the headers don't say.
+	fn header_info(&self) -> Result<Option<(u16, u32)>, DecodeError> {
+		match self.encoding {
+			EncodingType::Bin => return Err(DecodeError::DecodeBin),
+			EncodingType::Opus => return Err(DecodeError::Unsupported(EncodingType::Opus)),
+			EncodingType::WAV => {
+				let spec = hound::WavReader::new(Cursor::new(&self.bytes)).map_err(DecodeError::Hound)?.spec();
+				return Ok(Some((spec.channels, spec.sample_rate)))
+			},
+			_ => {}
+		}
+
+		#[cfg(feature = "symphonia")]
+		{
+			use symphonia::core::{
+				formats::FormatOptions,
+				io::MediaSourceStream,
+				meta::MetadataOptions,
+				probe::Hint
+			};
+
+			let stream = MediaSourceStream::new(Box::new(Cursor::new(self.bytes.clone())), Default::default());
+			let mut hint = Hint::new();
+			hint.with_extension(self.encoding.extension());
+			let probed = symphonia::default::get_probe()
+				.format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+				.map_err(DecodeError::Symphonia)?;
+			let params = match probed.format.default_track() {
+				Some(track) => &track.codec_params,
+				None => return Err(DecodeError::NoAudio)
+			};
+			Ok(match (params.channels, params.sample_rate) {
+				(Some(channels), Some(sample_rate)) => Some((channels.count() as u16, sample_rate)),
+				_ => None
+			})
+		}
+
+		// rodio only decodes the first frame to fill these in
+		#[cfg(not(feature = "symphonia"))]
+		{
+			let decoder = rodio::Decoder::new(Cursor::new(self.bytes.clone())).map_err(DecodeError::RodioDecoder)?;
+			Ok(Some((decoder.channels(), decoder.sample_rate())))
+		}
+	}
+
+	/// Use hound to decode this as WAV. Anything other than 16-bit
+	/// integer samples is dithered to 16 bits.
+	fn hound_decode(&self) -> Result<Vec<i16>, DecodeError> {
+		let mut reader = hound::WavReader::new(Cursor::new(&self.bytes)).map_err(DecodeError::Hound)?;
+		let spec = reader.spec();
+
+		let samples: Vec<i16> = match (spec.sample_format, spec.bits_per_sample) {
+			(hound::SampleFormat::Int, 16) => reader.samples::<i16>().collect::<Result<_, _>>().map_err(DecodeError::Hound)?,
+			(hound::SampleFormat::Int, bits) if bits < 16 => reader.samples::<i32>()
+				.map(|sample| sample.map(|sample| (sample << (16 - bits)) as i16))
+				.collect::<Result<_, _>>().map_err(DecodeError::Hound)?,
+			(hound::SampleFormat::Int, bits) => {
+				debug!("Dithering {}-bit WAV to 16 bits", bits);
+				let scale = (1_i64 << (bits - 1)) as f64;
+				let mut dither = Dither(0x2545_f491);
+				reader.samples::<i32>()
+					.map(|sample| sample.map(|sample| dither.quantize(sample as f64 / scale)))
+					.collect::<Result<_, _>>().map_err(DecodeError::Hound)?
+			},
+			(hound::SampleFormat::Float, _) => {
+				debug!("Dithering floating point WAV to 16 bits");
+				let mut dither = Dither(0x2545_f491);
+				reader.samples::<f32>()
+					.map(|sample| sample.map(|sample| dither.quantize(sample as f64)))
+					.collect::<Result<_, _>>().map_err(DecodeError::Hound)?
+			}
+		};
+
+		let _ = self.channels.borrow_mut().replace(spec.channels);
+		let _ = self.sample_rate.borrow_mut().replace(spec.sample_rate);
+
+		Ok(samples)
+	}
+
+	#[cfg(feature = "symphonia")]
+	/// Use Symphonia to decode this.
+	fn symphonia_decode(&self) -> Result<Vec<i16>, DecodeError> {
+		use symphonia::core::{
+			audio::SampleBuffer,
+			codecs::DecoderOptions,
+			errors::Error,
+			formats::FormatOptions,
+			io::MediaSourceStream,
+			meta::MetadataOptions,
+			probe::Hint
+		};
+
+		let stream = MediaSourceStream::new(Box::new(Cursor::new(self.bytes.clone())), Default::default());
+		let mut hint = Hint::new();
+		hint.with_extension(self.encoding.extension());
+
+		let probed = symphonia::default::get_probe()
+			.format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+			.map_err(DecodeError::Symphonia)?;
+		let mut format = probed.format;
+
+		let track = match format.default_track() {
+			Some(track) => track,
+			None => return Err(DecodeError::NoAudio)
+		};
+		let track_id = track.id;
+		let mut channels = track.codec_params.channels.map(|channels| channels.count() as u16);
+		let mut sample_rate = track.codec_params.sample_rate;
+
+		let mut decoder = symphonia::default::get_codecs()
+			.make(&track.codec_params, &DecoderOptions::default())
+			.map_err(DecodeError::Symphonia)?;
+
+		let mut samples: Vec<i16> = Vec::new();
+		loop {
+			let packet = match format.next_packet() {
+				Ok(packet) => packet,
+				// This is how the end of the stream is reported
+				Err(Error::IoError(error)) if error.kind() == std::io::ErrorKind::UnexpectedEof => break,
+				Err(Error::ResetRequired) => break,
+				Err(error) => return Err(DecodeError::Symphonia(error))
+			};
+			if packet.track_id() != track_id { continue }
+
+			match decoder.decode(&packet) {
+				Ok(decoded) => {
+					let spec = *decoded.spec();
+					channels = Some(spec.channels.count() as u16);
+					sample_rate = Some(spec.rate);
+
+					let mut buffer = SampleBuffer::<i16>::new(decoded.capacity() as u64, spec);
+					buffer.copy_interleaved_ref(decoded);
+					samples.extend_from_slice(buffer.samples())
+				},
+				// Skip over corrupt packets
+				Err(Error::DecodeError(error)) => warn!("Skipping undecodable packet: {}", error),
+				Err(error) => return Err(DecodeError::Symphonia(error))
+			}
+		}
+
+		match (channels, sample_rate) {
+			(Some(channels), Some(sample_rate)) => {
+				let _ = self.channels.borrow_mut().replace(channels);
+				let _ = self.sample_rate.borrow_mut().replace(sample_rate);
+				Ok(samples)
+			},
+			_ => Err(DecodeError::NoAudio)
+		}
+	}
+
+	/// Attempt to use rodio to decode this.
+	/// 
+	/// Used when the `symphonia` feature is disabled.
+	pub fn rodio_decode(&self) -> Result<Vec<i16>, rodio::decoder::DecoderError> {
+		let cursor = Cursor::new(self.bytes.clone());
+
+		// It would be nice to use Kira for this,
+		// but it seems to coerce everything into dual channel,
+		// which isn't ideal.
+		// 
+		// This is only the fallback now, see symphonia_decode.
+		let decoder = rodio::Decoder::new(cursor);
+		if let Err(error) = decoder {
+			// This can't be decoded
+			return Err(error)
+		};
+		let decoder = decoder.unwrap();
+
+		let _ = self.channels.borrow_mut().replace(decoder.channels());
+		let _ = self.sample_rate.borrow_mut().replace(decoder.sample_rate());
+
+		Ok(decoder.collect())
+	}
+
+	/// Decode this, and then resample if needed.
+	pub fn decode_resample(&self) -> Result<Vec<i16>, DecodeError> {
+		let mut decoded = self.decode()?;
+
+		let decoder_sample_rate = self.sample_rate.borrow().expect("sample rate with decoded audio file");
+		let sample_rate = lopus_sample_rate(decoder_sample_rate);
+
+		let channel_count = self.channels.borrow().expect("channels with decoded audio file");
+
+		if decoder_sample_rate != sample_rate {
+			// Need to resample
+			decoded = resample(decoded, channel_count, decoder_sample_rate, sample_rate)
+		}
+
+		Ok(decoded)
+	}
+
+	/// Decode this file, and then convert to WAV with at most two channels, for preview.
+	/// 
+	/// Files with more than two channels are downmixed to stereo.
+	pub fn to_preview_wav(&self, end: Option<NonZeroUsize>) -> Result<Vec<u8>, DecodeError> {
+		let (channels, sample_rate) = self.info()?;
+		if channels <= 2 {
+			return self.to_wav(end)
+		}
+
+		let raw = self.decode()?;
+		let frames = end.map(usize::from).unwrap_or(usize::MAX);
+		let raw: Vec<i16> = raw.into_iter().take(frames.saturating_mul(channels as usize)).collect();
+
+		debug!("Downmixing {} channels to stereo for preview", channels);
+		write_wav(&downmix_to_stereo(&raw, channels), 2, sample_rate)
+	}
+
+	/// Decode this file, and then convert to WAV.
+	pub fn to_wav(&self, end: Option<NonZeroUsize>) -> Result<Vec<u8>, DecodeError> {
+		let wav = match self.encoding {
+			EncodingType::Bin => return Err(DecodeError::DecodeBin),
+			_ => {
+				let raw = self.decode()?;
+				// Get the raw slice if there is a specific sample limit
+				let raw = &raw[0..if let Some(end) = end {
+					let sample_length = usize::from(end) * self.channels.borrow().unwrap() as usize;
+					if raw.len() < sample_length { raw.len() } else { sample_length }
+				} else {
+					raw.len()
+				}];
+				// Finally, write the wav file
+				write_wav(raw, self.channels.borrow().unwrap(), self.sample_rate.borrow().unwrap())?
+			}
+		};
+
+		debug!("Got wav file from raw audio (wav size is {})", crate::util::human_readable_size(wav.len() as u64));
+
+		Ok(wav)
+	}
+
+	/// Encode this file to `encoding`.
+	/// 
+	/// Encoding to the same encoding as this file returns its bytes unchanged.
+	pub fn encode(&self, encoding: EncodingType) -> Result<Vec<u8>, EncodeError> {
+		if encoding == self.encoding && encoding != EncodingType::WAV {
+			return Ok(self.bytes.to_vec())
+		}
+
+		match encoding {
+			EncodingType::Bin => Err(EncodeError::EncodeBin),
+			EncodingType::WAV => match self.to_wav(None) {
+				Ok(bytes) => Ok(bytes),
+				Err(decode_error) => Err(EncodeError::DecodeError(decode_error))
+			},
+			EncodingType::Ogg => {
+				let (samples, channels, sample_rate) = self.decode_for_encode()?;
+				encode_vorbis(&samples, channels, sample_rate)
+			},
+			EncodingType::FLAC => {
+				let (samples, channels, sample_rate) = self.decode_for_encode()?;
+				encode_flac(&samples, channels, sample_rate)
+			},
+			EncodingType::MP3
+			| EncodingType::AIFF
+			| EncodingType::AAC
+			| EncodingType::Opus => Err(EncodeError::Unsupported(encoding))
+		}
+	}
+
+	/// Decode this file for encoding, returning the interleaved samples,
+	/// channel count and sample rate.
+	fn decode_for_encode(&self) -> Result<(Vec<i16>, u16, u32), EncodeError> {
+		let samples = match self.decode() {
+			Ok(samples) => samples,
+			Err(error) => return Err(EncodeError::DecodeError(error))
+		};
+		let channels = self.channels.borrow().expect("channels with decoded audio file");
+		let sample_rate = self.sample_rate.borrow().expect("sample rate with decoded audio file");
+		Ok((samples, channels, sample_rate))
+	}
+}
+
+/// Resample interleaved `samples` with `channels` channels from the sample rate
+/// `from` to the sample rate `to`.
+pub fn resample(samples: Vec<i16>, channels: u16, from: u32, to: u32) -> Vec<i16> {
+	match channels {
+		1 => {
+			let input = fon::Audio::<fon::chan::Ch16, 1>::with_i16_buffer(from, samples);
+
+			let mut output = fon::Audio::<fon::chan::Ch16, 1>::with_audio(to, &input);
+
+			output.as_i16_slice().to_vec()
+		},
+		2 => {
+			let input = fon::Audio::<fon::chan::Ch16, 2>::with_i16_buffer(from, samples);
+
+			let mut output = fon::Audio::<fon::chan::Ch16, 2>::with_audio(to, &input);
+
+			output.as_i16_slice().to_vec()
+		},
+		channels => {
+			// Resample each channel on its own, then interleave them again
+			let channels = channels as usize;
+			let resampled: Vec<Vec<i16>> = (0..channels)
+				.map(|channel| {
+					let mono: Vec<i16> = samples.iter().skip(channel).step_by(channels).copied().collect();
+					let input = fon::Audio::<fon::chan::Ch16, 1>::with_i16_buffer(from, mono);
+					let mut output = fon::Audio::<fon::chan::Ch16, 1>::with_audio(to, &input);
+					output.as_i16_slice().to_vec()
+				})
+				.collect();
+
+			let length = resampled.iter().map(|channel| channel.len()).min().unwrap_or(0);
+			(0..length)
+				.flat_map(|index| resampled.iter().map(move |channel| channel[index]))
+				.collect()
+		}
+	}
+}
+
+/// Return the sample rate audio at `sample_rate` has once encoded as LOPUS.
+/// 
+/// The lopus format only supports 8, 12, 16, 24 and 48 kHz, so anything else
+/// is resampled to the next one up.
+pub fn lopus_sample_rate(sample_rate: u32) -> u32 {
+	if sample_rate <= 8_000 {8_000}
+	else if sample_rate <= 12_000 {12_000}
+	else if sample_rate <= 16_000 {16_000}
+	else if sample_rate <= 24_000 {24_000}
+	else {48_000}
+}
+
+/// Encode interleaved 16-bit `samples` as Ogg Vorbis.
+fn encode_vorbis(samples: &[i16], channels: u16, sample_rate: u32) -> Result<Vec<u8>, EncodeError> {
+	let (channel_count, sampling_frequency) = match (NonZeroU8::new(channels as u8), NonZeroU32::new(sample_rate)) {
+		(Some(channels), Some(sample_rate)) => (channels, sample_rate),
+		_ => return Err(EncodeError::Vorbis("Audio has no channels or no sample rate".to_owned()))
+	};
+
+	// Vorbis wants planar floating point samples
+	let mut planar: Vec<Vec<f32>> = vec![Vec::with_capacity(samples.len() / channels as usize); channels as usize];
+	for frame in samples.chunks(channels as usize) {
+		for (channel, sample) in frame.iter().enumerate() {
+			planar[channel].push(*sample as f32 / 32_768.0)
+		}
+	}
+
+	let mut output: Vec<u8> = Vec::new();
+	let mut encoder = match vorbis_rs::VorbisEncoderBuilder::new(sampling_frequency, channel_count, &mut output).and_then(|mut builder| builder.build()) {
+		Ok(encoder) => encoder,
+		Err(error) => return Err(EncodeError::Vorbis(error.to_string()))
+	};
+	if let Err(error) = encoder.encode_audio_block(&planar) {
+		return Err(EncodeError::Vorbis(error.to_string()))
+	}
+	if let Err(error) = encoder.finish() {
+		return Err(EncodeError::Vorbis(error.to_string()))
+	}
+
+	debug!("Encoded Vorbis (output is {})", crate::util::human_readable_size(output.len() as u64));
+	Ok(output)
+}
+
+/// Encode interleaved 16-bit `samples` as FLAC.
+fn encode_flac(samples: &[i16], channels: u16, sample_rate: u32) -> Result<Vec<u8>, EncodeError> {
+	use flacenc::{ component::BitRepr, error::Verify };
+
+	let config = match flacenc::config::Encoder::default().into_verified() {
+		Ok(config) => config,
+		Err((_, error)) => return Err(EncodeError::Flac(format!("{:?}", error)))
+	};
+	let samples: Vec<i32> = samples.iter().map(|sample| *sample as i32).collect();
+	let source = flacenc::source::MemSource::from_samples(&samples, channels as usize, 16, sample_rate as usize);
+
+	let stream = match flacenc::encode_with_fixed_block_size(&config, source, config.block_size) {
+		Ok(stream) => stream,
+		Err(error) => return Err(EncodeError::Flac(format!("{:?}", error)))
+	};
+	let mut sink = flacenc::bitsink::ByteSink::new();
+	if let Err(error) = stream.write(&mut sink) {
+		return Err(EncodeError::Flac(format!("{:?}", error)))
+	}
+
+	debug!("Encoded FLAC (output is {})", crate::util::human_readable_size(sink.as_slice().len() as u64));
+	Ok(sink.as_slice().to_vec())
+}
+
+/// The longest audio that can be generated, in seconds.
+pub const MAX_GENERATED_SECONDS: f64 = 60.0 * 60.0;
+/// The highest sample rate audio can be generated at.
+pub const MAX_GENERATED_SAMPLE_RATE: u32 = 192_000;
+
+/// Return how many samples long `seconds` of generated audio at `sample_rate` is.
+/// 
+/// Returns an error if the duration isn't a number of seconds above zero and
+/// up to [MAX_GENERATED_SECONDS], or the sample rate isn't above zero and up
+/// to [MAX_GENERATED_SAMPLE_RATE].
+pub fn generated_length(seconds: f64, sample_rate: u32) -> Result<usize, String> {
+	if !seconds.is_finite() || seconds <= 0.0 {
+		return Err("The duration must be more than zero seconds.".to_owned())
+	}
+	if seconds > MAX_GENERATED_SECONDS {
+		return Err(format!("The duration can be at most {} seconds.", MAX_GENERATED_SECONDS))
+	}
+	if sample_rate == 0 || sample_rate > MAX_GENERATED_SAMPLE_RATE {
+		return Err(format!("The sample rate must be more than zero and at most {}.", MAX_GENERATED_SAMPLE_RATE))
+	}
+	Ok((seconds * sample_rate as f64) as usize)
+}
+
+/// Generate a mono, 16-bit WAV file containing a sine tone.
+/// 
+/// Returns an error if the length can't be generated, see [generated_length].
+pub fn sine_wav(frequency: f64, seconds: f64, sample_rate: u32) -> Result<Vec<u8>, String> {
+	if !frequency.is_finite() || frequency <= 0.0 {
+		return Err("The frequency must be more than zero.".to_owned())
+	}
+	let length = generated_length(seconds, sample_rate)?;
+	let samples: Vec<i16> = (0..length)
+		.map(|index| {
+			let time = index as f64 / sample_rate as f64;
+			((time * frequency * std::f64::consts::TAU).sin() * i16::MAX as f64 * 0.5) as i16
+		})
+		.collect();
+
+	write_wav(&samples, 1, sample_rate).map_err(|error| error.to_string())
+}
+
+/// Generate a mono, 16-bit WAV file containing noise.
+/// 
+/// Returns an error if the length can't be generated, see [generated_length].
+pub fn noise_wav(seconds: f64, sample_rate: u32) -> Result<Vec<u8>, String> {
+	let length = generated_length(seconds, sample_rate)?;
+	let mut noise = Dither(0x1f12_3bb5);
+	let samples: Vec<i16> = (0..length)
+		.map(|_| (noise.next() * i16::MAX as f64 * 0.5) as i16)
+		.collect();
+
+	write_wav(&samples, 1, sample_rate).map_err(|error| error.to_string())
+}
+
+/// Generate a mono, 16-bit WAV file containing silence.
+/// 
+/// Returns an error if the length can't be generated, see [generated_length].
+pub fn silence_wav(seconds: f64, sample_rate: u32) -> Result<Vec<u8>, String> {
+	let length = generated_length(seconds, sample_rate)?;
+	write_wav(&vec![0; length], 1, sample_rate).map_err(|error| error.to_string())
+}
+
+/// Downmix interleaved `samples` with `channels` channels to interleaved stereo.
+/// 
+/// Assumes the usual channel orders: quadraphonic (FL, FR, BL, BR) and 5.1 (FL, FR, C, LFE, SL, SR).
+/// For anything else, even channels go left and odd channels go right.
+pub fn downmix_to_stereo(samples: &[i16], channels: u16) -> Vec<i16> {
+	const SIDE: f64 = std::f64::consts::FRAC_1_SQRT_2;
+	let channels = channels as usize;
+	if channels == 0 { return Vec::new() }
+
+	// Weights of each channel into the (left, right) outputs
+	let weights: Vec<(f64, f64)> = match channels {
+		1 => vec![(1.0, 1.0)],
+		4 => vec![(1.0, 0.0), (0.0, 1.0), (SIDE, 0.0), (0.0, SIDE)],
+		6 => vec![(1.0, 0.0), (0.0, 1.0), (SIDE, SIDE), (0.0, 0.0), (SIDE, 0.0), (0.0, SIDE)],
+		_ => (0..channels).map(|channel| if channel % 2 == 0 { (1.0, 0.0) } else { (0.0, 1.0) }).collect()
+	};
+	// Keep the loudest possible sum from clipping
+	let left_total: f64 = weights.iter().map(|(left, _)| left).sum();
+	let right_total: f64 = weights.iter().map(|(_, right)| right).sum();
+
+	let mut output = Vec::with_capacity(samples.len() / channels * 2);
+	for frame in samples.chunks_exact(channels) {
+		let (mut left, mut right) = (0.0, 0.0);
+		for (sample, (left_weight, right_weight)) in frame.iter().zip(&weights) {
+			left += *sample as f64 * left_weight;
+			right += *sample as f64 * right_weight
+		}
+		output.push((left / left_total.max(1.0)) as i16);
+		output.push((right / right_total.max(1.0)) as i16)
+	}
+	output
+}
+
+/// Write interleaved 16-bit `samples` as a WAV file.
+pub fn write_wav(samples: &[i16], channels: u16, sample_rate: u32) -> Result<Vec<u8>, DecodeError> {
+	let spec = hound::WavSpec {
+		channels,
+		sample_rate,
+		bits_per_sample: 16,
+		sample_format: hound::SampleFormat::Int
+	};
+	let mut wav_file: Vec<u8> = Vec::new();
+	let mut writer = hound::WavWriter::new(Cursor::new(&mut wav_file), spec).map_err(DecodeError::Hound)?;
+	for sample in samples {
+		writer.write_sample(*sample).map_err(DecodeError::Hound)?
+	}
+	writer.finalize().map_err(DecodeError::Hound)?;
+	Ok(wav_file)
+}
+
+/// A tiny random number generator for dithering. Quality doesn't matter here, speed does.
+struct Dither (u32);
+
+impl Dither {
+	/// Return triangular noise between -1.0 and 1.0 (one 16-bit step either way).
+	fn next(&mut self) -> f64 {
+		let mut uniform = || {
+			// xorshift32
+			self.0 ^= self.0 << 13;
+			self.0 ^= self.0 >> 17;
+			self.0 ^= self.0 << 5;
+			self.0 as f64 / u32::MAX as f64
+		};
+		uniform() - uniform()
+	}
+
+	/// Convert `sample` (full scale is 1.0) to 16 bits with dither.
+	fn quantize(&mut self, sample: f64) -> i16 {
+		(sample * 32_767.0 + self.next()).round().clamp(i16::MIN as f64, i16::MAX as f64) as i16
+	}
+}
+
+/// Decoder errors.
+pub enum DecodeError {
+	/// Attempted to decode a file whose encoding
+	/// was not known.
+	DecodeBin,
+	/// rodio returned an error.
+	RodioDecoder (rodio::decoder::DecoderError),
+	#[cfg(feature = "symphonia")]
+	/// Symphonia returned an error.
+	Symphonia (symphonia::core::errors::Error),
+	/// There is no decoder for this encoding.
+	Unsupported (EncodingType),
+	/// hound returned an error.
+	Hound (hound::Error),
+	/// The file doesn't contain any audio.
+	NoAudio,
+	IO (std::io::Error)
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+			Self::DecodeBin => write!(f, "Can't decode a bin, which is not a real encoding"),
+			Self::RodioDecoder(rodio_error) => rodio_error.fmt(f),
+			#[cfg(feature = "symphonia")]
+			Self::Symphonia(symphonia_error) => symphonia_error.fmt(f),
+			Self::Unsupported(encoding) => write!(f, "Can't decode {}, there is no decoder for it", encoding.extension()),
+			Self::Hound(hound_error) => hound_error.fmt(f),
+			Self::NoAudio => write!(f, "The file doesn't contain any audio"),
+			Self::IO(io_error) => io_error.fmt(f)
+		}
+    }
+}
+
+/// Encoder errors.
+pub enum EncodeError {
+	/// Attempted to encode to bin, which is not
+	/// a real encoding.
+	EncodeBin,
+	/// The file couldn't be decoded.
+	DecodeError (DecodeError),
+	/// There is no encoder for this encoding.
+	Unsupported (EncodingType),
+	/// The Vorbis encoder returned an error.
+	Vorbis (String),
+	/// The FLAC encoder returned an error.
+	Flac (String)
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+			Self::EncodeBin => write!(f, "Can't encode to bin, which is not a real encoding"),
+			Self::DecodeError(decode_error) => write!(f, "Error decoding: {}", decode_error),
+			Self::Unsupported(encoding) => write!(f, "Can't encode to {}, there is no encoder for it", encoding.extension()),
+			Self::Vorbis(error) => write!(f, "Error encoding Vorbis: {}", error),
+			Self::Flac(error) => write!(f, "Error encoding FLAC: {}", error)
+		}
+    }
+}
+
+/// Interleaved 16-bit audio, as passed between [Codec] backends.
+pub struct Pcm {
+	pub samples: Vec<i16>,
+	pub channels: u16,
+	pub sample_rate: u32
+}
+
+impl Pcm {
+	/// Read PCM audio from a WAV file.
+	pub fn from_wav(wav: &[u8]) -> Result<Self, String> {
+		let file = EncodedFile::from_bytes_with_encoding(wav.to_vec(), EncodingType::WAV);
+		let samples = file.decode().map_err(|error| error.to_string())?;
+		let (channels, sample_rate) = file.info().map_err(|error| error.to_string())?;
+		Ok(Self { samples, channels, sample_rate })
+	}
+
+	/// Write this audio as a 16-bit WAV file.
+	pub fn to_wav(&self) -> Result<Vec<u8>, String> {
+		write_wav(&self.samples, self.channels, self.sample_rate).map_err(|error| error.to_string())
+	}
+}
+
+/// A backend that can decode files to PCM, and possibly encode PCM to files.
+/// 
+/// Backends work with files on disk, since the external tools do. New backends
+/// are added to the list tried by [decode_file] and [encode_file] with [register].
+pub trait Codec: Send + Sync {
+	/// The name of this backend, matching [Decoder](crate::Decoder)'s names where there is one.
+	fn name(&self) -> &'static str;
+
+	/// Whether this backend should be tried for decoding the file at `path`.
+	/// 
+	/// This should be quick, so it doesn't have to be sure decoding will work.
+	fn probe(&self, path: &Path, config: &dyn Config) -> bool;
+
+	/// Decode the file at `path`.
+	fn decode(&self, path: &Path, config: &dyn Config) -> Result<Pcm, String>;
+
+	/// Decode the file at `path` to the 16-bit WAV file at `dest`.
+	/// 
+	/// Backends that write their output to disk anyway can do so without
+	/// holding all of its audio in memory.
+	fn decode_to_wav(&self, path: &Path, dest: &Path, config: &dyn Config) -> Result<(), String> {
+		let pcm = self.decode(path, config)?;
+		let spec = hound::WavSpec {
+			channels: pcm.channels,
+			sample_rate: pcm.sample_rate,
+			bits_per_sample: 16,
+			sample_format: hound::SampleFormat::Int
+		};
+		let mut writer = hound::WavWriter::create(long_path(dest), spec).map_err(|error| format!("Error writing {:?}\n{}", dest, error))?;
+		for sample in &pcm.samples {
+			writer.write_sample(*sample).map_err(|error| format!("Error writing {:?}\n{}", dest, error))?
+		}
+		writer.finalize().map_err(|error| format!("Error writing {:?}\n{}", dest, error))
+	}
+
+	/// Whether this backend can encode files with `extension`.
+	fn can_encode(&self, _extension: &str, _config: &dyn Config) -> bool {
+		false
+	}
+
+	/// Encode `pcm` to the file at `dest` and return its bytes. The extension
+	/// of `dest` is the format to encode to.
+	fn encode(&self, _pcm: &Pcm, _loop_points: Option<(usize, usize)>, dest: &Path, _config: &dyn Config) -> Result<Vec<u8>, String> {
+		Err(format!("{} can't encode {:?}", self.name(), dest))
+	}
+
+	/// Encode the 16-bit WAV file at `wav` to the file at `dest`, like [Codec::encode].
+	/// 
+	/// Backends that read their input from disk anyway can use `wav` as it is,
+	/// instead of holding all of its audio in memory.
+	fn encode_wav(&self, wav: &Path, loop_points: Option<(usize, usize)>, dest: &Path, config: &dyn Config) -> Result<Vec<u8>, String> {
+		let bytes = std::fs::read(long_path(wav)).map_err(|error| format!("Error reading {:?}\n{}", wav, error))?;
+		self.encode(&Pcm::from_wav(&bytes)?, loop_points, dest, config)
+	}
+}
+
+/// The codecs built into this crate: Symphonia or rodio for decoding, and
+/// hound, vorbis_rs and flacenc for encoding.
+pub struct NativeCodec;
+
+impl Codec for NativeCodec {
+	fn name(&self) -> &'static str { "native" }
+
+	fn probe(&self, path: &Path, _config: &dyn Config) -> bool {
+		let encoding = EncodingType::from_extension(path.extension().and_then(|e| e.to_str()).unwrap_or_default());
+		encoding != EncodingType::Opus && encoding.can_be_decoded()
+	}
+
+	fn decode(&self, path: &Path, _config: &dyn Config) -> Result<Pcm, String> {
+		let bytes = std::fs::read(long_path(path)).map_err(|error| format!("Error reading {:?}\n{}", path, error))?;
+		let encoding = EncodingType::from_extension(path.extension().and_then(|e| e.to_str()).unwrap_or_default());
+		let file = EncodedFile::from_bytes_with_encoding(bytes, encoding);
+		let samples = file.decode().map_err(|error| error.to_string())?;
+		let (channels, sample_rate) = file.info().map_err(|error| error.to_string())?;
+		Ok(Pcm { samples, channels, sample_rate })
+	}
+
+	fn can_encode(&self, extension: &str, _config: &dyn Config) -> bool {
+		matches!(EncodingType::from_extension(extension), EncodingType::WAV | EncodingType::Ogg | EncodingType::FLAC)
+	}
+
+	fn encode(&self, pcm: &Pcm, _loop_points: Option<(usize, usize)>, dest: &Path, _config: &dyn Config) -> Result<Vec<u8>, String> {
+		let encoding = EncodingType::from_extension(dest.extension().and_then(|e| e.to_str()).unwrap_or_default());
+		let bytes = EncodedFile::from_bytes_with_encoding(pcm.to_wav()?, EncodingType::WAV).encode(encoding).map_err(|error| error.to_string())?;
+		std::fs::write(long_path(dest), &bytes).map_err(|error| format!("Error writing {:?}\n{}", dest, error))?;
+		Ok(bytes)
+	}
+}
+
+lazy_static! {
+	/// Codec backends, in the order they are tried.
+	static ref CODECS: RwLock<Vec<Box<dyn Codec>>> = RwLock::new(vec![
+		Box::new(NativeCodec),
+		Box::new(crate::backend::VGAudioCliCodec),
+		Box::new(crate::backend::VgmstreamCodec)
+	]);
+}
+
+/// Add a codec backend. It is tried before the backends already registered.
+pub fn register(codec: Box<dyn Codec>) {
+	CODECS.write().expect("codec registry lock").insert(0, codec)
+}
+
+/// Decode the file at `path` with the first backend that accepts it.
+/// 
+/// The backend named `preferred` is tried first, if it accepts the file.
+pub fn decode_file(path: &Path, preferred: Option<&str>, config: &dyn Config) -> Result<Pcm, String> {
+	let codecs = CODECS.read().expect("codec registry lock");
+	let mut candidates: Vec<&dyn Codec> = codecs.iter().map(|codec| codec.as_ref()).filter(|codec| codec.probe(path, config)).collect();
+	candidates.sort_by_key(|codec| Some(codec.name()) != preferred);
+
+	let mut errors = Vec::new();
+	for codec in candidates {
+		debug!("Decoding {:?} with {}", path, codec.name());
+		match codec.decode(path, config) {
+			Ok(pcm) => return Ok(pcm),
+			Err(error) => errors.push(format!("{}: {}", codec.name(), error))
+		}
+	}
+
+	if errors.is_empty() {
+		Err(format!("Nothing can decode {:?}", path))
+	} else {
+		Err(errors.join("\n"))
+	}
+}
+
+/// Decode the file at `path` to the 16-bit WAV file at `dest` with the first
+/// backend that accepts it, like [decode_file]. See [Codec::decode_to_wav].
+pub fn decode_file_to_wav(path: &Path, dest: &Path, preferred: Option<&str>, config: &dyn Config) -> Result<(), String> {
+	let codecs = CODECS.read().expect("codec registry lock");
+	let mut candidates: Vec<&dyn Codec> = codecs.iter().map(|codec| codec.as_ref()).filter(|codec| codec.probe(path, config)).collect();
+	candidates.sort_by_key(|codec| Some(codec.name()) != preferred);
+
+	let mut errors = Vec::new();
+	for codec in candidates {
+		debug!("Decoding {:?} to {:?} with {}", path, dest, codec.name());
+		match codec.decode_to_wav(path, dest, config) {
+			Ok(()) => return Ok(()),
+			Err(error) => errors.push(format!("{}: {}", codec.name(), error))
+		}
+	}
+
+	if errors.is_empty() {
+		Err(format!("Nothing can decode {:?}", path))
+	} else {
+		Err(errors.join("\n"))
+	}
+}
+
+/// Encode the 16-bit WAV file at `wav` to the file at `dest` with the first
+/// backend that can encode its extension, and return its bytes. See [Codec::encode_wav].
+pub fn encode_wav_file(wav: &Path, loop_points: Option<(usize, usize)>, dest: &Path, config: &dyn Config) -> Result<Vec<u8>, String> {
+	let extension = dest.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+	let codecs = CODECS.read().expect("codec registry lock");
+	match codecs.iter().find(|codec| codec.can_encode(&extension, config)) {
+		Some(codec) => {
+			debug!("Encoding {:?} from {:?} with {}", dest, wav, codec.name());
+			codec.encode_wav(wav, loop_points, dest, config)
+		},
+		None => Err(format!("Nothing can encode {} files", extension))
+	}
+}
+
+/// Copy the 16-bit WAV file at `src` to `dest`, up to `end` samples if given,
+/// a few samples at a time. Returns how many samples were copied.
+pub fn copy_wav(src: &Path, dest: &Path, end: Option<usize>) -> Result<usize, String> {
+	let mut reader = hound::WavReader::open(long_path(src)).map_err(|error| format!("Error reading {:?}\n{}", src, error))?;
+	let spec = reader.spec();
+	let frames = match end {
+		Some(end) => end.min(reader.duration() as usize),
+		None => reader.duration() as usize
+	};
+	let mut writer = hound::WavWriter::create(long_path(dest), spec).map_err(|error| format!("Error writing {:?}\n{}", dest, error))?;
+	for sample in reader.samples::<i16>().take(frames * spec.channels as usize) {
+		let sample = sample.map_err(|error| format!("Error reading {:?}\n{}", src, error))?;
+		writer.write_sample(sample).map_err(|error| format!("Error writing {:?}\n{}", dest, error))?
+	}
+	writer.finalize().map_err(|error| format!("Error writing {:?}\n{}", dest, error))?;
+	Ok(frames)
+}
+
+/// Encode `pcm` to the file at `dest` with the first backend that can encode
+/// its extension, and return its bytes.
+pub fn encode_file(pcm: &Pcm, loop_points: Option<(usize, usize)>, dest: &Path, config: &dyn Config) -> Result<Vec<u8>, String> {
+	let extension = dest.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+	let codecs = CODECS.read().expect("codec registry lock");
+	match codecs.iter().find(|codec| codec.can_encode(&extension, config)) {
+		Some(codec) => {
+			debug!("Encoding {:?} with {}", dest, codec.name());
+			codec.encode(pcm, loop_points, dest, config)
+		},
+		None => Err(format!("Nothing can encode {} files", extension))
+	}
+}
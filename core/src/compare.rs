@@ -0,0 +1,146 @@
+//! Comparing two versions of the same audio, like an item before and after it
+//! was encoded. The versions are lined up, one is subtracted from the other,
+//! and what's left over is how much the audio changed.
+
+use crate::codec::{ resample, Pcm };
+
+/// The furthest apart, in frames, the two versions are searched for where they line up.
+/// Encoders add at most a few hundred frames of delay.
+const MAX_OFFSET: usize = 2048;
+/// How many frames are used to find where the two versions line up.
+const ALIGN_WINDOW: usize = 16384;
+/// Anything quieter than this is skipped over at the start when lining up the
+/// two versions, since silence lines up anywhere.
+const SILENCE: f64 = 1.0 / 1024.0;
+
+/// The difference between two versions of the same audio, see [null_test].
+/// Levels are from 0 to 1 of full scale.
+#[derive(Clone, Debug)]
+pub struct NullTest {
+	/// How many frames later the other version starts than the reference.
+	/// Negative if it starts earlier.
+	pub offset: isize,
+	/// How many frames were compared, once lined up.
+	pub frames: usize,
+	/// The sample rate the two versions were compared at.
+	pub sample_rate: u32,
+	/// The largest difference between two samples.
+	pub peak: f64,
+	/// The RMS level of the difference.
+	pub rms: f64,
+	/// The RMS level of the reference.
+	pub signal_rms: f64
+}
+
+impl NullTest {
+	/// Describe the outcome in a few lines.
+	pub fn summary(&self) -> String {
+		if self.peak == 0.0 {
+			return format!("The two versions are identical over {:.2} seconds.", self.frames as f64 / self.sample_rate as f64)
+		}
+		format!(
+			"Compared {:.2} seconds, lined up {} frames apart.\nPeak difference: {:.1} dBFS\nRMS difference: {:.1} dBFS, {:.1} dB below the audio",
+			self.frames as f64 / self.sample_rate as f64,
+			self.offset,
+			decibels(self.peak),
+			decibels(self.rms),
+			decibels(self.signal_rms) - decibels(self.rms)
+		)
+	}
+}
+
+/// Return `level` in decibels relative to full scale.
+pub fn decibels(level: f64) -> f64 {
+	if level > 0.0 { 20.0 * level.log10() } else { f64::NEG_INFINITY }
+}
+
+/// Return the frames of `samples` with `channels` channels mixed down to one
+/// channel, from -1 to 1.
+fn mono(samples: &[i16], channels: usize) -> Vec<f64> {
+	samples.chunks_exact(channels)
+		.map(|frame| frame.iter().map(|&sample| sample as f64).sum::<f64>() / (channels as f64 * 32768.0))
+		.collect()
+}
+
+/// Return how many frames later `other` starts than `reference`, by finding
+/// where they match best near the start of the audio.
+fn find_offset(reference: &[f64], other: &[f64]) -> isize {
+	let start = reference.iter().position(|sample| sample.abs() > SILENCE).unwrap_or(0)
+		.min(reference.len().saturating_sub(ALIGN_WINDOW));
+	let window = &reference[start..(start + ALIGN_WINDOW).min(reference.len())];
+
+	let mut best = (0, f64::NEG_INFINITY);
+	for offset in -(MAX_OFFSET as isize)..=MAX_OFFSET as isize {
+		let score: f64 = window.iter().enumerate()
+			.filter_map(|(index, sample)| {
+				let other_index = (start + index) as isize + offset;
+				usize::try_from(other_index).ok().and_then(|other_index| other.get(other_index)).map(|other| sample * other)
+			})
+			.sum();
+		if score > best.1 {
+			best = (offset, score)
+		}
+	}
+	best.0
+}
+
+/// Line up `other` with `reference`, subtract them and measure the difference.
+/// 
+/// `other` is resampled to the sample rate of `reference` first if they differ.
+/// Audio with the same channels is compared channel by channel, otherwise both
+/// are mixed down to one channel first.
+pub fn null_test(reference: &Pcm, other: &Pcm) -> Result<NullTest, String> {
+	if reference.samples.is_empty() || other.samples.is_empty() {
+		return Err("There is no audio to compare.".to_owned())
+	}
+
+	let other_samples = if other.sample_rate != reference.sample_rate {
+		resample(other.samples.clone(), other.channels, other.sample_rate, reference.sample_rate)
+	} else {
+		other.samples.clone()
+	};
+
+	let reference_mono = mono(&reference.samples, reference.channels.max(1) as usize);
+	let other_mono = mono(&other_samples, other.channels.max(1) as usize);
+	let offset = find_offset(&reference_mono, &other_mono);
+
+	// Compare each channel if there are the same channels, otherwise the mixes
+	let (channels, reference_frames, other_frames): (usize, Vec<f64>, Vec<f64>) = if reference.channels == other.channels {
+		let scale = |samples: &[i16]| -> Vec<f64> { samples.iter().map(|&sample| sample as f64 / 32768.0).collect() };
+		(reference.channels.max(1) as usize, scale(&reference.samples), scale(&other_samples))
+	} else {
+		(1, reference_mono, other_mono)
+	};
+
+	let mut peak: f64 = 0.0;
+	let mut difference_power = 0.0;
+	let mut signal_power = 0.0;
+	let mut frames = 0;
+	for frame in 0..reference_frames.len() / channels {
+		let other_frame = match usize::try_from(frame as isize + offset) {
+			Ok(other_frame) if (other_frame + 1) * channels <= other_frames.len() => other_frame,
+			_ => continue
+		};
+		for channel in 0..channels {
+			let sample = reference_frames[frame * channels + channel];
+			let difference = sample - other_frames[other_frame * channels + channel];
+			peak = peak.max(difference.abs());
+			difference_power += difference * difference;
+			signal_power += sample * sample;
+		}
+		frames += 1
+	}
+
+	if frames == 0 {
+		return Err("The two versions don't overlap.".to_owned())
+	}
+	let samples = (frames * channels) as f64;
+	Ok(NullTest {
+		offset,
+		frames,
+		sample_rate: reference.sample_rate,
+		peak,
+		rms: (difference_power / samples).sqrt(),
+		signal_rms: (signal_power / samples).sqrt()
+	})
+}
@@ -0,0 +1,61 @@
+use std::{
+	path::Path,
+	time::Duration
+};
+use crate::item::AudioExtension;
+
+/// An external tool which can decode nus3audio formats.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Decoder {
+	Vgmstream,
+	VGAudioCli
+}
+
+impl Decoder {
+	/// All decoders, in the order they are shown to the user.
+	pub const ALL: [Decoder; 2] = [Decoder::Vgmstream, Decoder::VGAudioCli];
+}
+
+impl std::fmt::Display for Decoder {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Decoder::Vgmstream => write!(f, "vgmstream"),
+			Decoder::VGAudioCli => write!(f, "VGAudioCli")
+		}
+	}
+}
+
+impl std::str::FromStr for Decoder {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match &s.to_lowercase()[..] {
+			"vgmstream" => Ok(Decoder::Vgmstream),
+			"vgaudiocli" => Ok(Decoder::VGAudioCli),
+			_ => Err(())
+		}
+	}
+}
+
+/// Everything the core needs to know from the frontend's settings.
+/// 
+/// It's shared between the threads decoding items, so it has to be [Sync].
+pub trait Config: Sync {
+	/// Return the path to VGAudioCli's executable.
+	fn vgaudio_cli_path(&self) -> &str;
+	/// Return the .NET runtime used to run VGAudioCli, or an empty string to run it directly.
+	fn vgaudio_cli_prepath(&self) -> &str;
+	/// Return the path to vgmstream's executable.
+	fn vgmstream_path(&self) -> &str;
+	/// Return the time external tools may run before they are killed.
+	/// A duration of zero means there is no timeout.
+	fn tool_timeout(&self) -> Duration;
+	/// Return the decoder that should be tried first for audio in the `extension` format.
+	fn decoder_for(&self, extension: &AudioExtension) -> Decoder;
+	/// Whether or not decoded audio should be kept in the cache between sessions.
+	fn keep_cache(&self) -> bool;
+	/// Return the directory intermediate files are written to.
+	fn cache_dir(&self) -> &Path;
+	/// Return the format new items and audio replacing binary data are encoded as.
+	fn default_format(&self) -> AudioExtension;
+}
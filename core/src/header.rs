@@ -0,0 +1,298 @@
+//! Reading the headers of the audio formats found in nus3audio files,
+//! without needing any external tools.
+
+use crate::util::{ read_u16_be, read_u32_be, read_u32_le };
+
+/// Convert a DSP ADPCM nibble address to a sample position.
+/// 
+/// Each 8-byte frame holds a header byte (two nibbles) followed by 14 samples.
+fn dsp_nibble_to_sample(nibble: u32) -> u32 {
+	let frames = nibble / 16;
+	let remainder = nibble % 16;
+	frames * 14 + remainder.saturating_sub(2)
+}
+
+/// The parts of an IDSP or LOPUS header we care about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NativeHeader {
+	/// Number of channels.
+	pub channels: u32,
+	/// Sample rate.
+	pub sample_rate: u32,
+	/// Length in samples, or 0 if the header doesn't say.
+	pub sample_count: u32,
+	/// Loop points in samples, if the audio loops.
+	pub loop_points: Option<(usize, usize)>
+}
+
+/// Offset of the first channel's DSP header in an IDSP file.
+const IDSP_CHANNEL_HEADER_OFFSET: usize = 0x40;
+
+/// Magic of the Namco header wrapping Switch Opus data in nus3audio files.
+pub const NAMCO_OPUS_MAGIC: &[u8; 4] = b"OPUS";
+
+/// Magic of a bare Switch Opus header, as written by VGAudioCli.
+pub const SWITCH_OPUS_MAGIC: &[u8; 4] = &[0x01, 0x00, 0x00, 0x80];
+
+impl NativeHeader {
+	/// Parse the header of an IDSP or LOPUS file, depending on its magic.
+	pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+		match bytes.get(..4) {
+			Some(b"IDSP") => Self::parse_idsp(bytes),
+			Some(magic) if magic == NAMCO_OPUS_MAGIC || magic == SWITCH_OPUS_MAGIC => Self::parse_lopus(bytes),
+			_ => Err("Unknown header".to_owned())
+		}
+	}
+
+	/// Parse the header of an IDSP file.
+	pub fn parse_idsp(bytes: &[u8]) -> Result<Self, String> {
+		if bytes.len() < 4 || &bytes[..4] != b"IDSP" {
+			return Err("Not an IDSP file".to_owned())
+		}
+
+		let field = |offset| read_u32_be(bytes, offset).ok_or_else(|| "IDSP header is truncated".to_owned());
+		let channels = field(0x08)?;
+		let sample_rate = field(0x0C)?;
+		let sample_count = field(0x10)?;
+		let loop_start = field(0x14)?;
+		let loop_end = field(0x18)?;
+
+		if channels == 0 || sample_rate == 0 {
+			return Err("IDSP header has no channels or sample rate".to_owned())
+		}
+
+		let loop_points = if loop_end > loop_start {
+			Some((loop_start as usize, loop_end as usize))
+		} else {
+			// Some writers only fill in the loop of the channel's DSP header
+			let channel = IDSP_CHANNEL_HEADER_OFFSET;
+			match (read_u16_be(bytes, channel + 0x0C), read_u32_be(bytes, channel + 0x10), read_u32_be(bytes, channel + 0x14)) {
+				(Some(1), Some(start), Some(end)) if end > start => {
+					Some((dsp_nibble_to_sample(start) as usize, dsp_nibble_to_sample(end) as usize))
+				},
+				_ => None
+			}
+		};
+
+		Ok(Self { channels, sample_rate, sample_count, loop_points })
+	}
+
+	/// Parse the header of a LOPUS file.
+	/// 
+	/// nus3audio files wrap the Switch Opus header in a Namco header holding
+	/// the length and loop points. Files written by VGAudioCli may only have the
+	/// Switch Opus header, which has no length or loop points.
+	pub fn parse_lopus(bytes: &[u8]) -> Result<Self, String> {
+		let truncated = || "LOPUS header is truncated".to_owned();
+
+		let (sample_count, loop_points, switch_offset) = match bytes.get(..4) {
+			Some(magic) if magic == NAMCO_OPUS_MAGIC => {
+				let field = |offset| read_u32_be(bytes, offset).ok_or_else(truncated);
+				let sample_count = field(0x08)?;
+				let loop_start = field(0x10)?;
+				let loop_end = field(0x14)?;
+				let switch_offset = field(0x20)? as usize;
+
+				let loop_points = if loop_end > loop_start {
+					Some((loop_start as usize, loop_end as usize))
+				} else {
+					None
+				};
+				(sample_count, loop_points, switch_offset)
+			},
+			Some(magic) if magic == SWITCH_OPUS_MAGIC => (0, None, 0),
+			_ => return Err("Not a LOPUS file".to_owned())
+		};
+
+		if bytes.get(switch_offset..switch_offset + 4) != Some(&SWITCH_OPUS_MAGIC[..]) {
+			return Err("LOPUS file is missing its Switch Opus header".to_owned())
+		}
+
+		let channels = *bytes.get(switch_offset + 0x09).ok_or_else(truncated)? as u32;
+		let sample_rate = read_u32_le(bytes, switch_offset + 0x0C).ok_or_else(truncated)?;
+
+		if channels == 0 || sample_rate == 0 {
+			return Err("LOPUS header has no channels or sample rate".to_owned())
+		}
+
+		Ok(Self { channels, sample_rate, sample_count, loop_points })
+	}
+}
+
+/// Size of the Namco header written by [wrap_lopus].
+const NAMCO_OPUS_HEADER_SIZE: usize = 0x30;
+
+/// Return LOPUS `bytes` with the Namco header that nus3audio files need.
+/// 
+/// Files that already have it are returned unchanged. Files with a bare Switch
+/// Opus header are wrapped, using `sample_count` and `loop_points` for the new
+/// header. Anything else is an error.
+pub fn wrap_lopus(bytes: &[u8], sample_count: u32, loop_points: Option<(usize, usize)>) -> Result<Vec<u8>, String> {
+	match bytes.get(..4) {
+		Some(magic) if magic == NAMCO_OPUS_MAGIC => {
+			NativeHeader::parse_lopus(bytes)?;
+			Ok(bytes.to_vec())
+		},
+		Some(magic) if magic == SWITCH_OPUS_MAGIC => {
+			let channels = NativeHeader::parse_lopus(bytes)?.channels;
+			let (loop_start, loop_end) = match loop_points {
+				Some((start, end)) => match (u32::try_from(start), u32::try_from(end)) {
+					(Ok(start), Ok(end)) => (start, end),
+					_ => return Err(format!("The loop points {} to {} are past the most samples a LOPUS file can count.", start, end))
+				},
+				None => (0, 0)
+			};
+			let size = u32::try_from(bytes.len()).map_err(|_| "The audio is too large for a LOPUS file.".to_owned())?;
+
+			let mut wrapped = Vec::with_capacity(NAMCO_OPUS_HEADER_SIZE + bytes.len());
+			wrapped.extend_from_slice(NAMCO_OPUS_MAGIC);
+			for field in [0, sample_count, channels, loop_start, loop_end, 0, 0, NAMCO_OPUS_HEADER_SIZE as u32, size] {
+				wrapped.extend_from_slice(&field.to_be_bytes())
+			}
+			wrapped.resize(NAMCO_OPUS_HEADER_SIZE, 0);
+			wrapped.extend_from_slice(bytes);
+			Ok(wrapped)
+		},
+		_ => Err("Not a LOPUS file".to_owned())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	//! These headers are laid out by hand from the formats as vgmstream reads
+	//! them. They aren't captured from game files or VGAudioCli output, so they
+	//! check the parsing against our reading of the layout, not against real files.
+
+	use super::*;
+
+	/// Write `value` big-endian at `offset` of `bytes`.
+	fn put_u32_be(bytes: &mut [u8], offset: usize, value: u32) {
+		bytes[offset..offset + 4].copy_from_slice(&value.to_be_bytes())
+	}
+
+	/// Return an IDSP header with the given fields, and room for one channel's DSP header.
+	fn idsp(channels: u32, sample_rate: u32, sample_count: u32, loop_start: u32, loop_end: u32) -> Vec<u8> {
+		let mut bytes = vec![0; 0x100];
+		bytes[..4].copy_from_slice(b"IDSP");
+		put_u32_be(&mut bytes, 0x08, channels);
+		put_u32_be(&mut bytes, 0x0C, sample_rate);
+		put_u32_be(&mut bytes, 0x10, sample_count);
+		put_u32_be(&mut bytes, 0x14, loop_start);
+		put_u32_be(&mut bytes, 0x18, loop_end);
+		bytes
+	}
+
+	/// Return a bare Switch Opus header with the given fields.
+	fn switch_opus(channels: u8, sample_rate: u32) -> Vec<u8> {
+		let mut bytes = vec![0; 0x20];
+		bytes[..4].copy_from_slice(SWITCH_OPUS_MAGIC);
+		bytes[0x09] = channels;
+		bytes[0x0C..0x10].copy_from_slice(&sample_rate.to_le_bytes());
+		bytes
+	}
+
+	/// Return `switch` in a Namco header with the given fields, laid out by hand.
+	fn namco_opus(switch: &[u8], sample_count: u32, loop_start: u32, loop_end: u32) -> Vec<u8> {
+		let mut bytes = vec![0; 0x30];
+		bytes[..4].copy_from_slice(NAMCO_OPUS_MAGIC);
+		put_u32_be(&mut bytes, 0x08, sample_count);
+		put_u32_be(&mut bytes, 0x10, loop_start);
+		put_u32_be(&mut bytes, 0x14, loop_end);
+		put_u32_be(&mut bytes, 0x20, 0x30);
+		bytes.extend_from_slice(switch);
+		bytes
+	}
+
+	#[test]
+	fn idsp_header() {
+		let bytes = idsp(2, 48_000, 1_000, 100, 900);
+		let expected = NativeHeader { channels: 2, sample_rate: 48_000, sample_count: 1_000, loop_points: Some((100, 900)) };
+		assert_eq!(NativeHeader::parse_idsp(&bytes), Ok(expected));
+		assert_eq!(NativeHeader::parse(&bytes), Ok(expected));
+
+		let bytes = idsp(1, 32_000, 500, 0, 0);
+		assert_eq!(NativeHeader::parse_idsp(&bytes).unwrap().loop_points, None)
+	}
+
+	#[test]
+	fn idsp_channel_loop() {
+		let mut bytes = idsp(1, 48_000, 1_000, 0, 0);
+		let channel = IDSP_CHANNEL_HEADER_OFFSET;
+		bytes[channel + 0x0C..channel + 0x0E].copy_from_slice(&1u16.to_be_bytes());
+		// Nibble 18 is the first sample of the second frame, 165 the fourth of the eleventh
+		put_u32_be(&mut bytes, channel + 0x10, 18);
+		put_u32_be(&mut bytes, channel + 0x14, 165);
+		assert_eq!(NativeHeader::parse_idsp(&bytes).unwrap().loop_points, Some((14, 143)))
+	}
+
+	#[test]
+	fn idsp_errors() {
+		let bytes = idsp(2, 48_000, 1_000, 0, 0);
+		assert!(NativeHeader::parse_idsp(&bytes[..0x10]).is_err());
+		assert!(NativeHeader::parse_idsp(&idsp(0, 48_000, 1_000, 0, 0)).is_err());
+		assert!(NativeHeader::parse_idsp(&idsp(2, 0, 1_000, 0, 0)).is_err());
+		assert!(NativeHeader::parse_idsp(b"RIFF").is_err());
+		assert!(NativeHeader::parse(b"RIFF").is_err())
+	}
+
+	#[test]
+	fn lopus_header() {
+		let switch = switch_opus(2, 48_000);
+		let bytes = namco_opus(&switch, 4_800, 10, 4_000);
+		let expected = NativeHeader { channels: 2, sample_rate: 48_000, sample_count: 4_800, loop_points: Some((10, 4_000)) };
+		assert_eq!(NativeHeader::parse_lopus(&bytes), Ok(expected));
+		assert_eq!(NativeHeader::parse(&bytes), Ok(expected));
+
+		let bytes = namco_opus(&switch, 4_800, 0, 0);
+		assert_eq!(NativeHeader::parse_lopus(&bytes).unwrap().loop_points, None)
+	}
+
+	#[test]
+	fn bare_switch_opus_header() {
+		let expected = NativeHeader { channels: 1, sample_rate: 48_000, sample_count: 0, loop_points: None };
+		assert_eq!(NativeHeader::parse_lopus(&switch_opus(1, 48_000)), Ok(expected));
+		assert_eq!(NativeHeader::parse(&switch_opus(1, 48_000)), Ok(expected))
+	}
+
+	#[test]
+	fn lopus_errors() {
+		// The Namco header has to point at a Switch Opus header
+		let mut bytes = namco_opus(&switch_opus(2, 48_000), 4_800, 0, 0);
+		put_u32_be(&mut bytes, 0x20, 0x10);
+		assert!(NativeHeader::parse_lopus(&bytes).is_err());
+
+		assert!(NativeHeader::parse_lopus(&switch_opus(2, 48_000)[..0x0A]).is_err());
+		assert!(NativeHeader::parse_lopus(&namco_opus(&[], 4_800, 0, 0)).is_err());
+		assert!(NativeHeader::parse_lopus(&switch_opus(0, 48_000)).is_err());
+		assert!(NativeHeader::parse_lopus(b"IDSP").is_err())
+	}
+
+	#[test]
+	fn wrap_bare_switch_opus() {
+		let switch = switch_opus(2, 48_000);
+		let wrapped = wrap_lopus(&switch, 4_800, Some((10, 4_000))).unwrap();
+		assert_eq!(wrapped.len(), NAMCO_OPUS_HEADER_SIZE + switch.len());
+		assert_eq!(&wrapped[NAMCO_OPUS_HEADER_SIZE..], &switch[..]);
+		assert_eq!(read_u32_be(&wrapped, 0x24), Some(switch.len() as u32));
+		let expected = NativeHeader { channels: 2, sample_rate: 48_000, sample_count: 4_800, loop_points: Some((10, 4_000)) };
+		assert_eq!(NativeHeader::parse_lopus(&wrapped), Ok(expected));
+
+		let wrapped = wrap_lopus(&switch, 4_800, None).unwrap();
+		assert_eq!(NativeHeader::parse_lopus(&wrapped).unwrap().loop_points, None)
+	}
+
+	#[test]
+	fn wrap_namco_opus_unchanged() {
+		let bytes = namco_opus(&switch_opus(1, 48_000), 4_800, 10, 4_000);
+		assert_eq!(wrap_lopus(&bytes, 1, None), Ok(bytes))
+	}
+
+	#[test]
+	fn wrap_errors() {
+		assert!(wrap_lopus(b"IDSP", 0, None).is_err());
+		assert!(wrap_lopus(&switch_opus(0, 48_000), 0, None).is_err());
+		if cfg!(target_pointer_width = "64") {
+			assert!(wrap_lopus(&switch_opus(2, 48_000), 0, Some((0, (u32::MAX as u64 + 1) as usize))).is_err())
+		}
+	}
+}
@@ -0,0 +1,1481 @@
+use std::{
+	borrow::Cow,
+	cell::Cell,
+	fs,
+	io::{ Cursor, Read },
+	num::NonZeroUsize,
+	path::{ Path, PathBuf },
+	sync::atomic::{ AtomicUsize, Ordering },
+	time::{ Duration, Instant, SystemTime }
+};
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use crate::{
+	backend,
+	codec::{ self, EncodedFile, EncodingType, Pcm },
+	compare::{ self, NullTest },
+	config::Config,
+	header::{ wrap_lopus, NativeHeader, NAMCO_OPUS_MAGIC, SWITCH_OPUS_MAGIC },
+	util::{ hash_bytes, human_readable_size, long_path, safe_file_name }
+};
+
+/// Subdirectory of the cache holding decoded audio, keyed by the hash of the encoded bytes.
+const DECODED_CACHE: &str = "decoded";
+/// Subdirectory of the cache holding the audio of items released from memory, keyed by their cache ID.
+const RELEASED_CACHE: &str = "released";
+/// Subdirectory of the cache holding the audio of items too large to keep in memory, keyed by their cache ID.
+const SPILLED_CACHE: &str = "spilled";
+/// Decoded audio in the cache that hasn't been used for this long is removed
+/// by [prune_cache].
+const DECODED_CACHE_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// WAV audio larger than this, in bytes, is kept on disk instead of in memory,
+/// see [ListItem::spilled_audio].
+const SPILL_SIZE: usize = 128 * 1024 * 1024;
+
+/// Approximate size of an IDSP header per channel, for estimating sizes.
+const IDSP_HEADER_SIZE: usize = 0x60;
+/// Approximate size of a LOPUS file's headers.
+const LOPUS_HEADER_SIZE: usize = 0x80;
+/// Roughly the bitrate of LOPUS audio made by VGAudioCli, per channel, in bits per second.
+const LOPUS_BITRATE_PER_CHANNEL: usize = 64_000;
+/// Length of each LOPUS frame, in milliseconds. Each frame has a small header of its own.
+const LOPUS_FRAME_MS: usize = 20;
+/// Size of each LOPUS frame's header.
+const LOPUS_FRAME_HEADER_SIZE: usize = 8;
+
+/// Remove everything in `cache_dir` a new session has no use for, when the
+/// cache is kept between sessions.
+/// 
+/// Only decoded audio is ever used again, and that is removed too once it
+/// hasn't been used for a while. Intermediate files, and the audio of items
+/// released from memory or kept on disk, only belonged to the session that made them.
+pub fn prune_cache(cache_dir: &Path) -> std::io::Result<()> {
+	for entry in fs::read_dir(long_path(cache_dir))? {
+		let path = entry?.path();
+		if path.file_name().map(|name| name == DECODED_CACHE).unwrap_or(false) { continue }
+		if path.is_dir() {
+			fs::remove_dir_all(&path)?
+		} else {
+			fs::remove_file(&path)?
+		}
+	}
+
+	let decoded_dir = long_path(&cache_dir.join(DECODED_CACHE));
+	if !decoded_dir.is_dir() { return Ok(()) }
+	let mut removed = 0;
+	for entry in fs::read_dir(&decoded_dir)? {
+		let entry = entry?;
+		let stale = entry.metadata()?.modified()?.elapsed().map(|age| age > DECODED_CACHE_MAX_AGE).unwrap_or(false);
+		if stale {
+			fs::remove_file(entry.path())?;
+			removed += 1
+		}
+	}
+	if removed > 0 {
+		debug!("Removed {} stale files from the decoded cache", removed)
+	}
+	Ok(())
+}
+
+/// Estimate the size of `samples` samples of audio with `channels` channels
+/// encoded as IDSP, in bytes.
+/// 
+/// This is exact but for the headers, since DSP ADPCM always takes 8 bytes per 14 samples.
+pub fn estimate_idsp_size(samples: usize, channels: usize) -> usize {
+	let (samples, channels) = (samples as u64, channels as u64);
+	let size = IDSP_HEADER_SIZE as u64 * channels + (samples + 13) / 14 * 8 * channels;
+	usize::try_from(size).unwrap_or(usize::MAX)
+}
+
+/// Estimate the size of `samples` samples of audio at `sample_rate` with
+/// `channels` channels encoded as LOPUS, in bytes.
+/// 
+/// This is a rough guess from the duration and the bitrate VGAudioCli uses.
+pub fn estimate_lopus_size(samples: usize, sample_rate: u32, channels: usize) -> usize {
+	// In 64 bits, since an hour of audio already overflows `samples * 1000` in 32
+	let milliseconds = samples as u64 * 1000 / sample_rate.max(1) as u64;
+	let frames = (milliseconds + LOPUS_FRAME_MS as u64 - 1) / LOPUS_FRAME_MS as u64;
+	let size = LOPUS_HEADER_SIZE as u64
+		+ milliseconds * LOPUS_BITRATE_PER_CHANNEL as u64 * channels as u64 / 8000
+		+ frames * LOPUS_FRAME_HEADER_SIZE as u64;
+	usize::try_from(size).unwrap_or(usize::MAX)
+}
+
+/// The most samples an IDSP or LOPUS header can count, since they are counted in 32 bits.
+pub const MAX_SAMPLE_COUNT: usize = u32::MAX as usize;
+
+/// Return `samples` as it is stored in IDSP and LOPUS headers, or an error
+/// saying the audio of `name` is too long for them.
+pub fn header_sample_count(samples: usize, name: &str) -> Result<u32, String> {
+	u32::try_from(samples).map_err(|_| format!(
+		"{} is {} samples long, more than the {} IDSP and LOPUS can hold.",
+		name, samples, MAX_SAMPLE_COUNT
+	))
+}
+
+/// The next cache ID to give to a [ListItem].
+static NEXT_CACHE_ID: AtomicUsize = AtomicUsize::new(0);
+/// The next audio stamp to hand out, see [ListItem::audio_stamp].
+static NEXT_AUDIO_STAMP: AtomicUsize = AtomicUsize::new(0);
+
+/// [nus3audio] has AudioFile::filename to do exactly this, but
+/// VGAudioCli seems to create lopus files without the header
+/// that nus3audio expects
+///
+/// Therefore, we rewrite that function here, also accepting a bare
+/// Switch Opus header. Anything else is an error.
+pub fn extension_of_encoded(encoded: &[u8]) -> Result<AudioExtension, String> {
+	match encoded.get(..4) {
+		None => Err("Not a valid file".to_owned()),
+		Some(b"IDSP") => Ok(AudioExtension::Idsp),
+		Some(magic) if magic == NAMCO_OPUS_MAGIC || magic == SWITCH_OPUS_MAGIC => Ok(AudioExtension::Lopus),
+		Some(_) => Err("Unknown audio format".to_owned())
+	}
+}
+
+/// Possible (valid) formats for audio in a nus3audio file.
+#[derive(Clone, PartialEq, Eq)]
+pub enum AudioExtension {
+	Idsp,
+	Lopus,
+	Bin
+}
+
+impl std::fmt::Display for AudioExtension {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			AudioExtension::Idsp => write!(f, "idsp"),
+			AudioExtension::Lopus => write!(f, "lopus"),
+			AudioExtension::Bin => write!(f, "bin")
+		}
+	}
+}
+
+impl std::str::FromStr for AudioExtension {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"idsp" => Ok(AudioExtension::Idsp),
+			"lopus" => Ok(AudioExtension::Lopus),
+			"bin" => Ok(AudioExtension::Bin),
+			_ => Err(format!("Unknown nus3audio format {}", s))
+		}
+	}
+}
+
+/// How much of an item's audio to export.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExportRange {
+	/// All of the audio, even past the loop end.
+	Full,
+	/// Only the audio between the loop points.
+	LoopRegion,
+	/// From the start of the audio to the loop end.
+	UpToLoopEnd
+}
+
+/// Where the bytes of an item come from when it's saved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveSource {
+	/// The bytes it was opened with, untouched.
+	Original,
+	/// Bytes encoded earlier in this session.
+	Cached,
+	/// The audio will be encoded while saving.
+	Encode,
+	/// Binary data, written as is.
+	Binary,
+	/// Nothing, the item is written empty.
+	Empty
+}
+
+impl std::fmt::Display for SaveSource {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			SaveSource::Original => write!(f, "Original bytes"),
+			SaveSource::Cached => write!(f, "Cached encode"),
+			SaveSource::Encode => write!(f, "Fresh encode"),
+			SaveSource::Binary => write!(f, "Binary data"),
+			SaveSource::Empty => write!(f, "Empty")
+		}
+	}
+}
+
+/// An item in an [Archive](crate::Archive).
+pub struct ListItem {
+	/// The name of this audio.
+	pub name: String,
+	/// The tone ID of this audio in the nus3audio file.
+	/// None until the item is added to an archive, which gives it the next free ID.
+	pub id: Option<u32>,
+	/// The extension of the audio in this nus3audio file.
+	pub extension: AudioExtension,
+	/// Raw file.
+	pub audio_file: Option<EncodedFile>,
+	/// Converted bytes.
+	/// There is no guarantee that this data is in any particular format.
+	bytes_raw: Option<Vec<u8>>,
+	/// Whether or not `bytes_raw` are the bytes this item was opened with.
+	bytes_original: bool,
+	/// Loop points of this sound in samples.
+	pub loop_points_samples: Option<(usize, usize)>,
+	/// Whether or not the loop points have been scaled to the sample rate this
+	/// audio will have once it's encoded, see [ListItem::encoded_sample_rate].
+	pub loop_points_scaled: bool,
+	/// Length in samples of the sound.
+	pub length_in_samples: usize,
+	/// Sample rate of the sound.
+	sample_rate: u32,
+	/// Number of channels
+	channels: u16,
+	/// Unique ID of this item for the session, used to keep its cache files
+	/// apart from other items, even those with the same name.
+	cache_id: usize,
+	/// Unique ID of this item's audio for the session, see [ListItem::audio_stamp].
+	audio_stamp: usize,
+	/// A preview of this item's audio made ahead of time, with the audio stamp
+	/// and loop end it was made for. See [ListItem::set_preview].
+	preview: Option<(usize, Option<usize>, Vec<u8>)>,
+	/// Where the audio of this item was written when it was released from
+	/// memory, and its encoding. See [ListItem::release_audio].
+	released: Option<(PathBuf, EncodingType)>,
+	/// Where the audio of this item is kept as 16-bit WAV because it's too
+	/// large to keep in memory. See [ListItem::spilled_audio].
+	spilled: Option<PathBuf>,
+	/// The audio this item had before it was first replaced since it was opened
+	/// or last saved, to compare the replacement with. See [ListItem::original_waveform].
+	original_audio: Option<EncodedFile>,
+	/// When the audio of this item was last needed.
+	last_used: Cell<Instant>,
+	/// Whether or not this item was salvaged from a damaged file by
+	/// [Archive::recover](crate::Archive::recover), so it may be cut short or run long.
+	pub recovered: bool
+}
+
+impl ListItem {
+	/// Return a new [ListItem].
+	pub fn new(name: String) -> Self {
+		Self {
+			name,
+			id: None,
+			extension: AudioExtension::Idsp,
+			audio_file: None,
+			bytes_raw: None,
+			bytes_original: false,
+			loop_points_samples: None,
+			loop_points_scaled: false,
+			length_in_samples: 0,
+			sample_rate: 12_000,
+			channels: 1,
+			cache_id: NEXT_CACHE_ID.fetch_add(1, Ordering::Relaxed),
+			audio_stamp: NEXT_AUDIO_STAMP.fetch_add(1, Ordering::Relaxed),
+			preview: None,
+			released: None,
+			spilled: None,
+			original_audio: None,
+			last_used: Cell::new(Instant::now()),
+			recovered: false
+		}
+	}
+
+	/// Return the cache directory for this item's intermediate files.
+	fn cache_dir(&self, nus3audio_name: &str, config: &dyn Config) -> PathBuf {
+		long_path(&config.cache_dir().join(safe_file_name(nus3audio_name)).join(format!("{}_{}", self.cache_id, safe_file_name(&self.name))))
+	}
+
+	/// Return the loop points in samples.
+	pub fn loop_points(&self) -> &Option<(usize, usize)> {
+		&self.loop_points_samples
+	}
+
+	/// Return the ending loop point
+	pub fn loop_end(&self) -> Option<usize> {
+		self.loop_points_samples.map(|(_, end)| end)
+	}
+
+	/// Return the loudest sample in each of `columns` stretches of this item's audio,
+	/// from 0 to 1, up to `end` samples if given. This is for drawing a coarse waveform.
+	/// 
+	/// Only audio held as WAV is read, since anything else would be decoded in full.
+	/// Audio kept on disk is read from there.
+	pub fn waveform(&self, columns: usize, end: Option<usize>) -> Option<Vec<f32>> {
+		if let Some(spilled) = &self.spilled {
+			return Self::peaks_of_wav(hound::WavReader::open(spilled).ok()?, columns, end).map(|(peaks, _)| peaks)
+		}
+		Self::peaks_of(&*self.load_audio().ok()??, columns, end).map(|(peaks, _)| peaks)
+	}
+
+	/// Return the waveform of the audio this item had before it was replaced,
+	/// like [ListItem::waveform], and its length in seconds.
+	/// 
+	/// None if the audio hasn't been replaced since the item was opened or last saved.
+	pub fn original_waveform(&self, columns: usize) -> Option<(Vec<f32>, f64)> {
+		Self::peaks_of(self.original_audio.as_ref()?, columns, None)
+	}
+
+	/// Whether or not this item was replaced since it was opened or last saved,
+	/// and still has the audio it had before to compare with, see [ListItem::null_test_original].
+	pub fn has_original_audio(&self) -> bool {
+		self.original_audio.is_some()
+	}
+
+	/// Forget the audio this item had before it was replaced, once the
+	/// replacement has been saved.
+	pub fn forget_original_audio(&mut self) {
+		self.original_audio = None
+	}
+
+	/// Keep the audio this item has now as its original audio, if there isn't one yet.
+	fn keep_original_audio(&mut self) {
+		if self.original_audio.is_none() {
+			self.original_audio = self.current_audio()
+		}
+	}
+
+	/// Return a copy of the audio of this item held in memory, read back if it
+	/// was released. Audio kept on disk is left there, since it's too large to copy.
+	fn current_audio(&self) -> Option<EncodedFile> {
+		match &self.audio_file {
+			Some(file) => Some(file.clone()),
+			None if self.released.is_some() => self.load_audio().ok().flatten().map(Cow::into_owned),
+			None => None
+		}
+	}
+
+	/// Return the coarse waveform of `file`, see [ListItem::waveform], and the
+	/// length in seconds of the audio it covers.
+	fn peaks_of(file: &EncodedFile, columns: usize, end: Option<usize>) -> Option<(Vec<f32>, f64)> {
+		if file.encoding != EncodingType::WAV { return None }
+		Self::peaks_of_wav(hound::WavReader::new(Cursor::new(&file.bytes)).ok()?, columns, end)
+	}
+
+	/// Return the coarse waveform of the WAV audio `reader` reads, like [ListItem::peaks_of].
+	fn peaks_of_wav<R: Read>(mut reader: hound::WavReader<R>, columns: usize, end: Option<usize>) -> Option<(Vec<f32>, f64)> {
+		let spec = reader.spec();
+		let channels = spec.channels.max(1) as usize;
+		let frames = match end {
+			Some(end) => end.min(reader.duration() as usize),
+			None => reader.duration() as usize
+		};
+		if frames == 0 || columns == 0 { return None }
+
+		let samples: Box<dyn Iterator<Item = f32>> = match spec.sample_format {
+			hound::SampleFormat::Int => {
+				let scale = (1_i64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+				Box::new(reader.samples::<i32>().map(move |sample| sample.unwrap_or(0) as f32 / scale))
+			},
+			hound::SampleFormat::Float => Box::new(reader.samples::<f32>().map(|sample| sample.unwrap_or(0.0)))
+		};
+
+		let mut peaks = vec![0.0_f32; columns];
+		for (index, sample) in samples.take(frames * channels).enumerate() {
+			let column = index / channels * columns / frames;
+			peaks[column] = peaks[column].max(sample.abs().min(1.0));
+		}
+		Some((peaks, frames as f64 / spec.sample_rate.max(1) as f64))
+	}
+
+	/// Return the loop points in samples.
+	pub fn loop_points_samples(&self) -> Option<(i64, i64)> {
+		if let Some((begin, end)) = &self.loop_points_samples {
+			Some((
+				*begin as i64,
+				*end as i64
+			))
+		} else {
+			None
+		}
+	}
+
+	/// Attach new audio to this item.
+	/// 
+	/// Its length, channels and sample rate are read from the headers of the
+	/// new audio, so size estimates don't go by the audio it replaced.
+	pub fn set_audio_from_bytes(&mut self, bytes: Vec<u8>, encoding: EncodingType) -> Result<(), String> {
+		self.keep_original_audio();
+		let decoded = EncodedFile::from_bytes_with_encoding(bytes, encoding);
+		if let Ok((channels, sample_rate)) = decoded.info() {
+			self.channels = channels;
+			self.sample_rate = sample_rate
+		}
+		self.length_in_samples = decoded.sample_count().unwrap_or(0);
+		self.audio_file = Some(decoded);
+		self.audio_changed();
+		self.loop_points_samples = None;
+		self.loop_points_scaled = false;
+		self.bytes_raw = None;
+		Ok(())
+	}
+
+	/// Mix the audio of this item down to one channel, keeping its loop points.
+	/// 
+	/// The length of the item is worked out again from the mixed audio.
+	/// Does nothing if the item already has one channel.
+	pub fn downmix_to_mono(&mut self) -> Result<(), String> {
+		let pcm = Pcm::from_wav(&self.get_audio_wav(None)?)?;
+		if pcm.channels <= 1 {
+			return Ok(())
+		}
+
+		let channels = pcm.channels as usize;
+		let samples: Vec<i16> = pcm.samples.chunks(channels)
+			.map(|frame| (frame.iter().map(|sample| *sample as i32).sum::<i32>() / channels as i32) as i16)
+			.collect();
+		let wav = codec::write_wav(&samples, 1, pcm.sample_rate).map_err(|error| error.to_string())?;
+
+		let loop_points = self.loop_points_samples;
+		self.set_audio_from_bytes(wav, EncodingType::WAV)?;
+		self.loop_points_samples = loop_points;
+		self.channels = 1;
+		self.length_in_samples = samples.len();
+		Ok(())
+	}
+
+	/// Gets the sound from an encoded IDSP or LOPUS file.
+	/// 
+	/// More specifically, it will attempt to decode bytes with VGAudio CLI or vgmstream.
+	/// The bytes are taken to be the ones the archive was opened with, which
+	/// [ListItem::replace_from_file] undoes for files of the user's.
+	pub fn from_encoded(&mut self, nus3audio_name: &str, encoded: Vec<u8>, config: &dyn Config) -> Result<(), String> {
+		// Audio too large to keep in memory goes straight to disk, instead of being spilled once it's decoded
+		if let Ok(header) = NativeHeader::parse(&encoded) {
+			if header.sample_count as usize * header.channels as usize * 2 > SPILL_SIZE {
+				match self.from_encoded_spilled(nus3audio_name, &encoded, &header, config) {
+					Ok(()) => return Ok(()),
+					Err(error) => warn!("Could not decode {} straight to disk, decoding it in memory: {}", self.name, error)
+				}
+			}
+		}
+
+		let hash = hash_bytes(&encoded);
+
+		let decoded = match Self::cached_decode(hash, config) {
+			Some(cached) => Ok(cached),
+			None => {
+				match extension_of_encoded(&encoded) {
+					Ok(extension) => {
+						let target_dir = self.cache_dir(nus3audio_name, config);
+
+						let src_file = target_dir.join(format!("{}.{}", safe_file_name(&self.name), extension));
+
+						if let Err(error) = Self::create_target_dir(&target_dir) {
+							return Err(format!("Error creating cache subdirectory {:?}\n{}", target_dir, error))
+						};
+
+						if let Err(error) = fs::write(&src_file, &encoded) {
+							return Err(format!("Error writing source file {:?}\n{}", src_file, error))
+						};
+
+						self.decode(&src_file, config).map(|raw| {
+							let loop_points = Self::loop_points_of(&src_file, config);
+							Self::store_decode(hash, &raw, loop_points, config);
+							(raw, loop_points)
+						})
+					},
+					// Don't bother the decoders with data we don't recognise
+					Err(error) => Err(error)
+				}
+			}
+		};
+
+		match decoded {
+			Ok((raw, loop_points)) => {
+				// This should be in wav format now
+				let sample_count = Self::wav_sample_count(&raw);
+				let audio_file = EncodedFile::from_bytes_with_encoding(raw, EncodingType::WAV);
+
+				// Check that the wav could be read
+				match audio_file.info() {
+					Ok((channels, sample_rate)) => {
+						// Give bare Switch Opus files the Namco header they need in-game
+						let encoded = if matches!(extension_of_encoded(&encoded), Ok(AudioExtension::Lopus)) {
+							wrap_lopus(&encoded, sample_count, loop_points)?
+						} else { encoded };
+						self.bytes_raw = Some(encoded);
+						self.bytes_original = true;
+						self.audio_file = Some(audio_file);
+						self.audio_changed();
+						self.channels = channels;
+						self.sample_rate = sample_rate;
+						self.loop_points_samples = loop_points;
+						self.length_in_samples = sample_count as usize;
+						self.spill_if_large(config);
+						if let Ok(header) = NativeHeader::parse(self.bytes_raw.as_ref().unwrap()) {
+							if header.sample_count != 0 {
+								self.length_in_samples = header.sample_count as usize
+							}
+						}
+
+						Ok(())
+					},
+					Err(error) => Err(format!("Error reading returned wav\n{}", error))
+				}
+			},
+			Err(error) => {
+				// Could not be decoded, assume this is binary data
+			warn!("Error decoding file: {}
+  This is not fatal, this file's bytes have been loaded directly. If this is not desired, make sure this file is a known format and is not corrupted.", error);
+				self.bytes_raw = None;
+				self.audio_file = Some(EncodedFile::from_bytes_with_encoding(encoded, EncodingType::Bin));
+				self.audio_changed();
+				self.extension = AudioExtension::Bin;
+				self.loop_points_samples = None;
+				Ok(())
+			}
+		}
+	}
+
+	/// Decode an encoded IDSP or LOPUS file straight to where the audio of this
+	/// item is kept on disk, for audio `header` says is larger than [SPILL_SIZE].
+	/// See [ListItem::spilled_audio].
+	fn from_encoded_spilled(&mut self, nus3audio_name: &str, encoded: &[u8], header: &NativeHeader, config: &dyn Config) -> Result<(), String> {
+		let extension = extension_of_encoded(encoded)?;
+		let target_dir = self.cache_dir(nus3audio_name, config);
+		let src_file = target_dir.join(format!("{}.{}", safe_file_name(&self.name), extension));
+
+		if let Err(error) = Self::create_target_dir(&target_dir) {
+			return Err(format!("Error creating cache subdirectory {:?}\n{}", target_dir, error))
+		};
+		if let Err(error) = fs::write(&src_file, encoded) {
+			return Err(format!("Error writing source file {:?}\n{}", src_file, error))
+		};
+
+		// Forget the old audio first, since it may be kept at the same place
+		self.audio_changed();
+		let spilled_dir = long_path(&config.cache_dir().join(SPILLED_CACHE));
+		let spilled_file = spilled_dir.join(format!("{}.wav", self.cache_id));
+		if let Err(error) = fs::create_dir_all(&spilled_dir) {
+			return Err(format!("Error creating directory {:?}\n{}", spilled_dir, error))
+		}
+		self.decode_to(&src_file, &spilled_file, config)?;
+
+		let (spec, sample_count) = match hound::WavReader::open(&spilled_file) {
+			Ok(reader) => (reader.spec(), reader.duration()),
+			Err(error) => {
+				let _ = fs::remove_file(&spilled_file);
+				return Err(format!("Error reading returned wav\n{}", error))
+			}
+		};
+		if spec.sample_format != hound::SampleFormat::Int || spec.bits_per_sample != 16 {
+			let _ = fs::remove_file(&spilled_file);
+			return Err(format!("Decoded audio is {}-bit, not 16-bit", spec.bits_per_sample))
+		}
+
+		let loop_points = Self::loop_points_of(&src_file, config);
+		// Give bare Switch Opus files the Namco header they need in-game
+		let encoded = if matches!(extension, AudioExtension::Lopus) {
+			wrap_lopus(encoded, sample_count, loop_points)?
+		} else { encoded.to_vec() };
+
+		debug!("Decoded the audio of {} straight to disk at {:?}", self.name, spilled_file);
+		self.bytes_raw = Some(encoded);
+		self.bytes_original = true;
+		self.audio_file = None;
+		self.channels = spec.channels;
+		self.sample_rate = spec.sample_rate;
+		self.loop_points_samples = loop_points;
+		self.length_in_samples = if header.sample_count != 0 { header.sample_count as usize } else { sample_count as usize };
+		self.spilled = Some(spilled_file);
+		Ok(())
+	}
+
+	/// Return the decoded audio and loop points for the encoded bytes with `hash`,
+	/// if the cache is kept between sessions and they were decoded before.
+	fn cached_decode(hash: u64, config: &dyn Config) -> Option<(Vec<u8>, Option<(usize, usize)>)> {
+		if !config.keep_cache() { return None }
+
+		let wav_file = long_path(&config.cache_dir().join(DECODED_CACHE).join(format!("{:016x}.wav", hash)));
+		let loop_file = wav_file.with_extension("toml");
+
+		let raw = fs::read(&wav_file).ok()?;
+		// Mark it as used, so it isn't pruned
+		for file in [&wav_file, &loop_file] {
+			let _ = fs::File::options().write(true).open(file).and_then(|file| file.set_modified(SystemTime::now()));
+		}
+		let loop_points = match fs::read_to_string(&loop_file).ok().and_then(|s| toml::from_str::<toml::map::Map<String, toml::Value>>(&s).ok()) {
+			Some(map) => match (map.get("start"), map.get("end")) {
+				(Some(toml::Value::Integer(start)), Some(toml::Value::Integer(end))) => Some((*start as usize, *end as usize)),
+				_ => None
+			},
+			None => None
+		};
+
+		debug!("Found cached decode {:?}", wav_file);
+		Some((raw, loop_points))
+	}
+
+	/// Store decoded audio and loop points for the encoded bytes with `hash`,
+	/// if the cache is kept between sessions. Failing to do so is not an error.
+	fn store_decode(hash: u64, raw: &[u8], loop_points: Option<(usize, usize)>, config: &dyn Config) {
+		if !config.keep_cache() { return }
+
+		let target_dir = long_path(&config.cache_dir().join(DECODED_CACHE));
+		if let Err(error) = fs::create_dir_all(&target_dir) {
+			warn!("Could not create decoded cache directory {:?}: {}", target_dir, error);
+			return
+		}
+
+		let wav_file = target_dir.join(format!("{:016x}.wav", hash));
+		if let Err(error) = fs::write(&wav_file, raw) {
+			warn!("Could not write decoded cache file {:?}: {}", wav_file, error);
+			return
+		}
+
+		if let Some((start, end)) = loop_points {
+			let mut map = toml::map::Map::new();
+			map.insert("start".to_owned(), toml::Value::Integer(start as i64));
+			map.insert("end".to_owned(), toml::Value::Integer(end as i64));
+			if let Ok(string) = toml::to_string(&map) {
+				let _ = fs::write(wav_file.with_extension("toml"), string);
+			}
+		}
+	}
+
+	/// Return where the bytes of this item will come from when it's saved.
+	pub fn save_source(&self) -> SaveSource {
+		match (&self.audio_file, &self.bytes_raw) {
+			(None, Some(_)) if (self.released.is_some() || self.spilled.is_some()) && self.bytes_original => SaveSource::Original,
+			(None, Some(_)) if self.released.is_some() || self.spilled.is_some() => SaveSource::Cached,
+			// Audio kept on disk is encoded from there
+			(None, None) if self.spilled.is_some() => SaveSource::Encode,
+			(None, _) => SaveSource::Empty,
+			(Some(_), Some(_)) if self.bytes_original => SaveSource::Original,
+			(Some(_), Some(_)) => SaveSource::Cached,
+			// Binary data in an audio format fails to encode, so it's written empty
+			(Some(file), None) if file.encoding == EncodingType::Bin => if self.extension == AudioExtension::Bin {
+				SaveSource::Binary
+			} else {
+				SaveSource::Empty
+			},
+			(Some(_), None) => SaveSource::Encode
+		}
+	}
+
+	/// Return the size of this item's bytes when it's saved, see [ListItem::save_source].
+	/// 
+	/// Audio that still has to be encoded is estimated, see [ListItem::estimate_size].
+	pub fn save_size(&self) -> Option<usize> {
+		match self.save_source() {
+			SaveSource::Original | SaveSource::Cached => self.bytes_raw.as_ref().map(|bytes| bytes.len()),
+			SaveSource::Binary => self.audio_file.as_ref().map(|file| file.bytes.len()),
+			SaveSource::Encode => self.estimate_size(&self.extension, self.loop_end(), None),
+			SaveSource::Empty => Some(0)
+		}
+	}
+
+	/// Return a hash of the bytes this item will be written with, see [hash_bytes],
+	/// or None if they aren't known until it's encoded.
+	/// 
+	/// Items with the same hash are written the same, so comparing hashes shows
+	/// which items differ between two builds of a nus3audio file.
+	pub fn content_hash(&self) -> Option<u64> {
+		match self.save_source() {
+			SaveSource::Original | SaveSource::Cached => self.bytes_raw.as_deref().map(hash_bytes),
+			SaveSource::Binary => self.audio_file.as_ref().map(|file| hash_bytes(&file.bytes)),
+			SaveSource::Encode => None,
+			SaveSource::Empty => Some(hash_bytes(&[]))
+		}
+	}
+
+	/// Whether or not this item has encoded bytes ready to be written.
+	pub fn has_encoded(&self) -> bool {
+		self.bytes_raw.is_some()
+	}
+
+	/// Return the size this item will take up when written, in bytes.
+	/// 
+	/// Items that are already encoded return their exact size. IDSP items from WAV
+	/// audio are estimated, since DSP ADPCM always takes 8 bytes per 14 samples.
+	/// Otherwise, the size isn't known until the item is encoded.
+	pub fn projected_size(&self) -> Option<usize> {
+		if let Some(bytes) = &self.bytes_raw {
+			return Some(bytes.len())
+		}
+
+		let audio_file = self.audio_file.as_ref()?;
+		match (&self.extension, audio_file.encoding) {
+			(_, EncodingType::Bin) => Some(audio_file.bytes.len()),
+			(AudioExtension::Idsp, EncodingType::WAV) => {
+				let reader = hound::WavReader::new(Cursor::new(&audio_file.bytes)).ok()?;
+				let channels = reader.spec().channels as usize;
+				let samples = match self.loop_end() {
+					Some(end) => end.min(reader.duration() as usize),
+					None => reader.duration() as usize
+				};
+				Some(estimate_idsp_size(samples, channels))
+			},
+			_ => None
+		}
+	}
+
+	/// Estimate the size of this item's audio when encoded as `extension`,
+	/// up to `loop_end` if it loops, in bytes, without running the encoder.
+	/// `channels` is how many channels it will have, if not its own.
+	/// 
+	/// IDSP is exact, LOPUS is a rough guess, see [estimate_idsp_size] and
+	/// [estimate_lopus_size]. Returns None if the item has no audio.
+	pub fn estimate_size(&self, extension: &AudioExtension, loop_end: Option<usize>, channels: Option<usize>) -> Option<usize> {
+		match &self.audio_file {
+			Some(file) if file.encoding == EncodingType::Bin || *extension == AudioExtension::Bin => return Some(file.bytes.len()),
+			Some(_) => (),
+			// Audio kept on disk is always WAV, with its length known
+			None if self.spilled.is_some() => (),
+			None => return None
+		}
+
+		let channels = channels.unwrap_or(self.channels() as usize);
+		let samples = match loop_end {
+			Some(end) => end.min(self.length_in_samples),
+			None => self.length_in_samples
+		};
+		match extension {
+			AudioExtension::Idsp => Some(estimate_idsp_size(samples, channels)),
+			_ => Some(estimate_lopus_size(samples, self.sample_rate(), channels))
+		}
+	}
+
+	/// Replace the audio of this item with the file at `path`.
+	/// 
+	/// IDSP and LOPUS files are decoded with the external tools, while other files
+	/// are kept in their original encoding. Loop points are read from the file if possible.
+	pub fn replace_from_file(&mut self, nus3audio_name: &str, path: &Path, config: &dyn Config) -> Result<(), String> {
+		let previous = if self.original_audio.is_none() { self.current_audio() } else { None };
+		let bytes = match fs::read(long_path(path)) {
+			Ok(bytes) => bytes,
+			Err(error) => return Err(format!("Could not read file:\n{}", error))
+		};
+
+		let result = if let Some(extension) = path.extension() {
+			match extension.to_str() {
+				Some("idsp") => { self.from_encoded(nus3audio_name, bytes, config) },
+				// Standard Ogg Opus files are sometimes named .lopus
+				Some("lopus") if bytes.starts_with(b"OggS") => match backend::vgmstream_decode(path, config) {
+					Ok(wav) => self.set_audio_from_bytes(wav, EncodingType::WAV),
+					Err(error) => Err(format!("Ogg Opus files are decoded with vgmstream, which failed:\n{}", error))
+				},
+				Some("lopus") if !matches!(extension_of_encoded(&bytes), Ok(AudioExtension::Lopus)) => Err("Not a valid LOPUS file".to_owned()),
+				Some("lopus") => { self.from_encoded(nus3audio_name, bytes, config) },
+				// Neither rodio nor Symphonia can decode Opus, but vgmstream can
+				Some(e) if EncodingType::from_extension(e) == EncodingType::Opus => match backend::vgmstream_decode(path, config) {
+					Ok(wav) => self.set_audio_from_bytes(wav, EncodingType::WAV),
+					Err(error) => Err(format!("Opus files are decoded with vgmstream, which failed:\n{}", error))
+				},
+				Some(e) if EncodingType::from_extension(e) != EncodingType::Bin => self.set_audio_from_bytes(bytes, EncodingType::from_extension(e)),
+				_ => self.set_unknown_from_bytes(path, bytes, config)
+			}
+		} else { self.set_unknown_from_bytes(path, bytes, config) };
+
+		if let Err(error) = result {
+			return Err(format!("Could not decode file as audio:\n{}", error))
+		}
+		// Encoded bytes of a file of the user's aren't what the archive was opened with
+		self.bytes_original = false;
+		if self.original_audio.is_none() {
+			self.original_audio = previous
+		}
+
+		// Binary data can't hold audio, so plain audio replacing it takes the default format
+		if self.extension == AudioExtension::Bin && self.audio_file.as_ref().map(|file| file.encoding != EncodingType::Bin).unwrap_or(false) {
+			self.extension = config.default_format()
+		}
+
+		self.loop_points_samples = Self::loop_points_of(path, config);
+		self.loop_points_scaled = false;
+		self.spill_if_large(config);
+		Ok(())
+	}
+
+	/// Take the audio of `other`, an item made to try out a replacement, in
+	/// place of this item's audio, as if [ListItem::replace_from_file] had been
+	/// called with the same file. The name, tone ID and cache of this item are kept.
+	/// 
+	/// The cache directory of `other` is removed, since nothing in it is needed.
+	pub fn take_audio(&mut self, nus3audio_name: &str, other: ListItem, config: &dyn Config) {
+		other.remove_cache_dir(nus3audio_name, config);
+		if self.original_audio.is_none() {
+			self.original_audio = self.current_audio()
+		}
+		self.drop_released();
+		if let Some(spilled) = self.spilled.take() {
+			let _ = fs::remove_file(spilled);
+		}
+		self.extension = other.extension;
+		self.audio_file = other.audio_file;
+		self.bytes_raw = other.bytes_raw;
+		self.bytes_original = other.bytes_original;
+		self.loop_points_samples = other.loop_points_samples;
+		self.loop_points_scaled = other.loop_points_scaled;
+		self.length_in_samples = other.length_in_samples;
+		self.sample_rate = other.sample_rate;
+		self.channels = other.channels;
+		self.audio_stamp = other.audio_stamp;
+		self.preview = other.preview;
+		self.released = other.released;
+		self.spilled = other.spilled;
+		self.last_used.set(Instant::now())
+	}
+
+	/// Remove everything this item, which was never added to an archive, left
+	/// in the cache: its cache directory and any audio kept on disk.
+	pub fn discard(mut self, nus3audio_name: &str, config: &dyn Config) {
+		self.remove_cache_dir(nus3audio_name, config);
+		if let Some(spilled) = self.spilled.take() {
+			let _ = fs::remove_file(spilled);
+		}
+		self.drop_released()
+	}
+
+	/// Remove the cache directory of this item's intermediate files, if it has one.
+	fn remove_cache_dir(&self, nus3audio_name: &str, config: &dyn Config) {
+		let target_dir = self.cache_dir(nus3audio_name, config);
+		if target_dir.is_dir() {
+			if let Err(error) = fs::remove_dir_all(&target_dir) {
+				warn!("Could not remove {:?}: {}", target_dir, error)
+			}
+		}
+	}
+
+	/// Attach the file at `path` with an unknown format to this item.
+	/// 
+	/// Every codec backend that accepts the file is tried, which includes vgmstream
+	/// and the huge number of formats it supports. If none of them can decode the
+	/// file, the bytes are attached as binary data.
+	fn set_unknown_from_bytes(&mut self, path: &Path, bytes: Vec<u8>, config: &dyn Config) -> Result<(), String> {
+		match codec::decode_file(path, None, config).and_then(|pcm| pcm.to_wav()) {
+			Ok(wav) => self.set_audio_from_bytes(wav, EncodingType::WAV),
+			Err(error) => {
+				debug!("Couldn't decode {:?}, loading it as binary data: {}", path, error);
+				self.set_audio_from_bytes(bytes, EncodingType::Bin)
+			}
+		}
+	}
+
+	/// Write the decoded audio of this item to its cache directory as WAV,
+	/// and return the path of the file.
+	pub fn write_cached_wav(&self, nus3audio_name: &str, config: &dyn Config) -> Result<PathBuf, String> {
+		let target_dir = self.cache_dir(nus3audio_name, config);
+		if let Err(error) = Self::create_target_dir(&target_dir) {
+			return Err(format!("Error creating cache subdirectory {:?}\n{}", target_dir, error))
+		};
+
+		let wav_file = target_dir.join(format!("{}.wav", safe_file_name(&self.name)));
+		// Audio kept on disk is copied from there, instead of read into memory
+		if let Some(spilled) = &self.spilled {
+			fs::copy(spilled, &wav_file).map_err(|error| format!("Error writing file {:?}\n{}", wav_file, error))?;
+			return Ok(wav_file)
+		}
+		let raw = self.get_audio_wav(None)?;
+		fs::write(&wav_file, raw).map_err(|error| format!("Error writing file {:?}\n{}", wav_file, error))?;
+		Ok(wav_file)
+	}
+
+	/// Write the bytes this item is saved with to its cache directory, named
+	/// after the item with its format as the extension, and return the path of the file.
+	/// 
+	/// The item is encoded first if it isn't already.
+	pub fn write_cached_encoded(&mut self, nus3audio_name: &str, config: &dyn Config) -> Result<PathBuf, String> {
+		let extension = self.extension.to_string();
+		let bytes = self.get_nus3_encoded_raw(nus3audio_name, &extension, config)?;
+		let target_dir = self.cache_dir(nus3audio_name, config);
+		if let Err(error) = Self::create_target_dir(&target_dir) {
+			return Err(format!("Error creating cache subdirectory {:?}\n{}", target_dir, error))
+		};
+
+		let file = target_dir.join(format!("{}.{}", safe_file_name(&self.name), extension));
+		fs::write(&file, bytes).map_err(|error| format!("Error writing file {:?}\n{}", file, error))?;
+		Ok(file)
+	}
+
+	/// Removes the bytes from this item.
+	/// 
+	/// Does nothing if the audio is released, since the item couldn't be encoded again.
+	pub fn clear_bytes(&mut self) {
+		// The audio is encoded again from what was released
+		if let Err(error) = self.restore_audio() {
+			warn!("Not clearing the bytes of {}: {}", self.name, error);
+			return
+		}
+		self.bytes_raw = None
+	}
+
+	/// Return the audio from this item in WAV format.
+	/// 
+	/// Optionally take the length in samples that should be used.
+	pub fn get_audio_wav(&self, end: Option<usize>) -> Result<Vec<u8>, String> {
+		if let Some(file) = self.load_audio()? {
+			let end = end.and_then(|e| NonZeroUsize::new(e));
+			match file.to_wav(end) {
+				Ok(wav) => Ok(wav),
+				Err(error) => Err(format!("{}", error))
+			}
+		} else {
+			if self.bytes_raw.is_none() {
+				Err("Selected item is empty".to_owned())
+			} else {
+				Err("Selected item could not be decoded".to_owned())
+			}
+		}
+	}
+
+	/// Return the part of the audio from this item given by `range` in WAV format.
+	/// 
+	/// Items without loop points always return all of their audio.
+	pub fn get_audio_wav_range(&self, range: ExportRange) -> Result<Vec<u8>, String> {
+		match (range, self.loop_points_samples) {
+			(ExportRange::UpToLoopEnd, Some((_, end))) => self.get_audio_wav(Some(end)),
+			(ExportRange::LoopRegion, Some((start, end))) => {
+				let mut pcm = Pcm::from_wav(&self.get_audio_wav(Some(end))?)?;
+				let skip = (start * pcm.channels as usize).min(pcm.samples.len());
+				pcm.samples.drain(..skip);
+				pcm.to_wav()
+			},
+			_ => self.get_audio_wav(None)
+		}
+	}
+
+	/// Return the part of the audio from this item given by `range` encoded as `encoding`.
+	pub fn get_audio_encoded(&self, encoding: EncodingType, range: ExportRange) -> Result<Vec<u8>, String> {
+		if range != ExportRange::Full && self.loop_points_samples.is_some() {
+			let wav = self.get_audio_wav_range(range)?;
+			EncodedFile::from_bytes_with_encoding(wav, EncodingType::WAV).encode(encoding).map_err(|error| error.to_string())
+		} else if let Some(file) = self.load_audio()? {
+			file.encode(encoding).map_err(|error| error.to_string())
+		} else if self.bytes_raw.is_none() {
+			Err("Selected item is empty".to_owned())
+		} else {
+			Err("Selected item could not be decoded".to_owned())
+		}
+	}
+
+	/// Return the audio from this item in WAV format with at most two channels, for preview.
+	/// 
+	/// Optionally take the length in samples that should be used.
+	pub fn get_preview_wav(&self, end: Option<usize>) -> Result<Vec<u8>, String> {
+		if let Some(file) = self.load_audio()? {
+			let end = end.and_then(NonZeroUsize::new);
+			file.to_preview_wav(end).map_err(|error| error.to_string())
+		} else if self.bytes_raw.is_none() {
+			Err("Selected item is empty".to_owned())
+		} else {
+			Err("Selected item could not be decoded".to_owned())
+		}
+	}
+
+	/// Return the stamp of this item's audio. It's unique for the session and changes
+	/// whenever the audio does, so work done on a copy of the audio can be checked.
+	pub fn audio_stamp(&self) -> usize {
+		self.audio_stamp
+	}
+
+	/// Give this item's audio a new stamp, and drop anything made from the old audio.
+	fn audio_changed(&mut self) {
+		self.audio_stamp = NEXT_AUDIO_STAMP.fetch_add(1, Ordering::Relaxed);
+		self.preview = None;
+		self.drop_released();
+		if let Some(spilled) = self.spilled.take() {
+			let _ = fs::remove_file(spilled);
+		}
+	}
+
+	/// Forget the audio this item released from memory, and remove it from the cache.
+	fn drop_released(&mut self) {
+		if let Some((released, _)) = self.released.take() {
+			let _ = fs::remove_file(released);
+		}
+	}
+
+	/// Return the audio of this item, read back from disk if it's kept there,
+	/// see [ListItem::spilled_audio], or was released from memory, see
+	/// [ListItem::release_audio]. None if it has no audio in any of those places.
+	/// 
+	/// Every use of the audio goes through here, so it's marked as used.
+	fn load_audio(&self) -> Result<Option<Cow<'_, EncodedFile>>, String> {
+		self.last_used.set(Instant::now());
+		if let (None, Some((released, encoding))) = (&self.audio_file, &self.released) {
+			return match fs::read(released) {
+				Ok(bytes) => Ok(Some(Cow::Owned(EncodedFile::from_bytes_with_encoding(bytes, *encoding)))),
+				Err(error) => Err(format!("Could not read the audio of {} from {:?}\n{}", self.name, released, error))
+			}
+		}
+		match (&self.audio_file, &self.spilled) {
+			(Some(file), _) => Ok(Some(Cow::Borrowed(file))),
+			(None, Some(spilled)) => match fs::read(spilled) {
+				Ok(bytes) => Ok(Some(Cow::Owned(EncodedFile::from_bytes_with_encoding(bytes, EncodingType::WAV)))),
+				Err(error) => Err(format!("Could not read the audio of {} from {:?}\n{}", self.name, spilled, error))
+			},
+			(None, None) => Ok(None)
+		}
+	}
+
+	/// Keep the audio of this item on disk instead of in memory if it's WAV
+	/// larger than [SPILL_SIZE], so very long items don't use up the memory.
+	/// 
+	/// Only 16-bit audio is kept there, which is what decoding makes, since
+	/// it's handed to the encoder as it is.
+	fn spill_if_large(&mut self, config: &dyn Config) {
+		let file = match &self.audio_file {
+			Some(file) if file.encoding == EncodingType::WAV && file.bytes.len() > SPILL_SIZE => file,
+			_ => return
+		};
+		let reader = match hound::WavReader::new(Cursor::new(&file.bytes)) {
+			Ok(reader) => reader,
+			Err(_) => return
+		};
+		let spec = reader.spec();
+		if spec.sample_format != hound::SampleFormat::Int || spec.bits_per_sample != 16 { return }
+
+		// Not in the item's cache directory, which is emptied whenever it's encoded
+		let target_dir = long_path(&config.cache_dir().join(SPILLED_CACHE));
+		let spilled_file = target_dir.join(format!("{}.wav", self.cache_id));
+		if let Err(error) = fs::create_dir_all(&target_dir).and_then(|_| fs::write(&spilled_file, &file.bytes)) {
+			warn!("Could not keep the audio of {} on disk at {:?}: {}", self.name, spilled_file, error);
+			return
+		}
+
+		debug!("Keeping the {} of audio of {} on disk at {:?}", human_readable_size(file.bytes.len() as u64), self.name, spilled_file);
+		self.length_in_samples = reader.duration() as usize;
+		self.channels = spec.channels;
+		self.sample_rate = spec.sample_rate;
+		self.audio_file = None;
+		self.preview = None;
+		self.spilled = Some(spilled_file)
+	}
+
+	/// Return where the audio of this item is kept on disk as 16-bit WAV, if
+	/// it's too large to keep in memory. It's read from there whenever it's
+	/// needed, and is streamed where it can be, like when it's played or encoded.
+	pub fn spilled_audio(&self) -> Option<&Path> {
+		self.spilled.as_deref()
+	}
+
+	/// Write the audio of this item to the cache and drop it from memory,
+	/// if it isn't needed to save the item. Returns whether or not it was released.
+	/// 
+	/// Anything that uses the audio reads it back by itself, and
+	/// [ListItem::restore_audio] brings it back into memory for good.
+	pub fn release_audio(&mut self, config: &dyn Config) -> bool {
+		// Only items with encoded bytes can be saved without their audio
+		if self.released.is_some() || self.bytes_raw.is_none() { return false }
+		let file = match &self.audio_file {
+			Some(file) => file,
+			None => return false
+		};
+		let encoding = file.encoding;
+		// Remember these, since they're read from the audio otherwise
+		let (channels, sample_rate) = (self.channels(), self.sample_rate());
+
+		// Not in the item's cache directory, which is emptied whenever it's encoded
+		let target_dir = long_path(&config.cache_dir().join(RELEASED_CACHE));
+		let released_file = target_dir.join(format!("{}.audio", self.cache_id));
+		if let Err(error) = fs::create_dir_all(&target_dir).and_then(|_| fs::write(&released_file, &file.bytes)) {
+			warn!("Could not release the audio of {} to {:?}: {}", self.name, released_file, error);
+			return false
+		}
+
+		trace!("Released the audio of {} to {:?}", self.name, released_file);
+		self.released = Some((released_file, encoding));
+		self.channels = channels;
+		self.sample_rate = sample_rate;
+		self.audio_file = None;
+		self.preview = None;
+		true
+	}
+
+	/// Whether or not the audio of this item was released from memory, see [ListItem::release_audio].
+	pub fn is_released(&self) -> bool {
+		self.released.is_some()
+	}
+
+	/// Whether or not this item has audio, in memory or on disk.
+	pub fn has_audio(&self) -> bool {
+		self.audio_file.is_some() || self.released.is_some() || self.spilled.is_some()
+	}
+
+	/// Bring back the audio of this item if it was released from memory, and
+	/// mark it as just used.
+	pub fn restore_audio(&mut self) -> Result<(), String> {
+		self.last_used.set(Instant::now());
+		let (released_file, encoding) = match self.released.take() {
+			Some(released) => released,
+			None => return Ok(())
+		};
+
+		match fs::read(&released_file) {
+			Ok(bytes) => {
+				trace!("Restored the audio of {} from {:?}", self.name, released_file);
+				self.audio_file = Some(EncodedFile::from_bytes_with_encoding(bytes, encoding));
+				Ok(())
+			},
+			Err(error) => {
+				self.released = Some((released_file.clone(), encoding));
+				Err(format!("Could not read the audio of {} from {:?}\n{}", self.name, released_file, error))
+			}
+		}
+	}
+
+	/// Return how long it's been since the audio of this item was last used.
+	pub fn idle_for(&self) -> Duration {
+		self.last_used.get().elapsed()
+	}
+
+	/// Return the preview of this item's audio up to `end`, like [ListItem::get_preview_wav].
+	/// 
+	/// The preview made ahead of time by [ListItem::set_preview] is used if there is one.
+	pub fn preview_wav(&mut self, end: Option<usize>) -> Result<Vec<u8>, String> {
+		match self.preview.take() {
+			Some((stamp, preview_end, wav)) if stamp == self.audio_stamp && preview_end == end => Ok(wav),
+			_ => self.get_preview_wav(end)
+		}
+	}
+
+	/// Keep `wav`, a preview of this item's audio up to `end` made from the audio
+	/// with `stamp`, for [ListItem::preview_wav]. It's dropped if the audio has changed since.
+	pub fn set_preview(&mut self, stamp: usize, end: Option<usize>, wav: Vec<u8>) {
+		if stamp == self.audio_stamp {
+			self.preview = Some((stamp, end, wav))
+		}
+	}
+
+	/// Whether or not this item has a preview of its audio up to `end` made ahead of time.
+	pub fn has_preview(&self, end: Option<usize>) -> bool {
+		matches!(&self.preview, Some((stamp, preview_end, _)) if *stamp == self.audio_stamp && *preview_end == end)
+	}
+
+	/// Drop the preview of this item's audio made ahead of time, if there is one.
+	pub fn drop_preview(&mut self) {
+		self.preview = None
+	}
+
+	/// Return the number of channels of this item's audio.
+	/// 
+	/// This comes from the headers of its audio, so it doesn't decode it.
+	pub fn channels(&self) -> u16 {
+		self.audio_file.as_ref().and_then(|file| file.info().ok()).map(|(channels, _)| channels).unwrap_or(self.channels)
+	}
+
+	/// Return the sample rate of this item's audio.
+	/// 
+	/// This comes from the headers of its audio, so it doesn't decode it.
+	pub fn sample_rate(&self) -> u32 {
+		self.audio_file.as_ref().and_then(|file| file.info().ok()).map(|(_, sample_rate)| sample_rate).unwrap_or(self.sample_rate)
+	}
+
+	/// Return the sample rate of this item's audio once it's encoded as its extension.
+	/// 
+	/// LOPUS only supports a few sample rates, see [codec::lopus_sample_rate].
+	pub fn encoded_sample_rate(&self) -> u32 {
+		match self.extension {
+			AudioExtension::Lopus => codec::lopus_sample_rate(self.sample_rate()),
+			_ => self.sample_rate()
+		}
+	}
+
+	/// Set the format this item is saved in.
+	/// 
+	/// Loop points already scaled to the sample rate of the encoded audio (see
+	/// [ListItem::loop_points_scaled]) are scaled again to that of the new format,
+	/// so they stay at the same time.
+	pub fn set_extension(&mut self, extension: AudioExtension) {
+		let from = self.encoded_sample_rate() as u64;
+		self.extension = extension;
+		if self.loop_points_scaled {
+			let to = self.encoded_sample_rate() as u64;
+			if from != to {
+				self.loop_points_samples = self.loop_points_samples.map(|(start, end)| {
+					((start as u64 * to / from.max(1)) as usize, (end as u64 * to / from.max(1)) as usize)
+				});
+			}
+			self.loop_points_scaled = to != self.sample_rate() as u64
+		}
+	}
+
+	/// Return the bytes associated with this item. If it has audio but no bytes, the audio is converted according to `extension`.
+	pub fn get_nus3_encoded_raw(&mut self, nus3audio_name: &str, extension: &str, config: &dyn Config) -> Result<Vec<u8>, String> {
+		if self.audio_file.is_none() && self.released.is_none() && self.spilled.is_none() { return Err("Audio of selected item is empty".to_owned()) }
+
+		if let Some(bytes) = &self.bytes_raw {
+			trace!("Encoded audio already exists for {}, returning it", self.name);
+			return Ok(bytes.clone())
+		} else {
+			if self.audio_file.as_ref().map(|file| file.encoding == EncodingType::Bin).unwrap_or(false) {
+				if self.extension != AudioExtension::Bin {
+					return Err("Item is not in bin format, but imported file is".to_owned())
+				} else {
+					trace!("{} is set to a binary file, returning it", self.name);
+					return Ok(self.audio_file.as_ref().unwrap().bytes.to_vec())
+				}
+			}
+			// Need to convert the file
+			trace!("Encoded audio does not already exist for {}, encoding it", self.name);
+			let target_dir = self.cache_dir(nus3audio_name, config);
+			let file_name = safe_file_name(&self.name);
+			let dest_file = target_dir.join(format!("{}.{}", file_name, extension));
+
+			if let Err(error) = Self::create_target_dir(&target_dir) {
+				return Err(format!("Error creating cache subdirectory {:?}\n{}", target_dir, error))
+			};
+
+			let (encoded, sample_count) = match &self.spilled {
+				// Audio kept on disk goes to the encoder from there, cut at the loop end
+				Some(spilled) => match self.loop_end() {
+					Some(end) => {
+						let src_file = dest_file.with_extension("source.wav");
+						let sample_count = codec::copy_wav(spilled, &src_file, Some(end))?;
+						(codec::encode_wav_file(&src_file, self.loop_points_samples, &dest_file, config)?, header_sample_count(sample_count, &self.name)?)
+					},
+					None => (codec::encode_wav_file(spilled, self.loop_points_samples, &dest_file, config)?, header_sample_count(self.length_in_samples, &self.name)?)
+				},
+				None => {
+					let (pcm, sample_count) = match self.get_audio_wav(self.loop_end()) {
+						Ok(bytes) => (Pcm::from_wav(&bytes)?, Self::wav_sample_count(&bytes)),
+						Err(error) => return Err(format!("Error decoding audio\n{}", error))
+					};
+					(codec::encode_file(&pcm, self.loop_points_samples, &dest_file, config)?, sample_count)
+				}
+			};
+			// VGAudioCli only writes the Namco header when asked to, so make sure it's there
+			self.bytes_original = false;
+			self.bytes_raw = Some(if extension == "lopus" {
+				wrap_lopus(&encoded, sample_count, self.loop_points_samples)?
+			} else { encoded });
+
+			debug!("Encoded {} to {:?}", self.name, dest_file);
+
+			Ok(self.bytes_raw.as_ref().unwrap().clone())
+		}
+	}
+
+	/// Encode this item if it isn't already, decode the result again and compare
+	/// it with the audio it was encoded from, see [compare::null_test].
+	/// 
+	/// An item opened from a nus3audio and left alone has nothing to compare,
+	/// since its audio was decoded from the bytes it's written with.
+	pub fn null_test(&mut self, nus3audio_name: &str, config: &dyn Config) -> Result<NullTest, String> {
+		if self.extension == AudioExtension::Bin {
+			return Err("Binary data isn't audio, so there is nothing to compare.".to_owned())
+		}
+		if self.bytes_original && self.bytes_raw.is_some() {
+			return Err(format!("{} hasn't been replaced, so its audio is decoded from the bytes it's saved with and there is nothing to compare.", self.name))
+		}
+		let reference = Pcm::from_wav(&self.get_audio_wav(self.loop_end())?)?;
+		let encoded = self.get_nus3_encoded_raw(nus3audio_name, &self.extension.to_string(), config)?;
+
+		let decoded = match Self::cached_decode(hash_bytes(&encoded), config) {
+			Some((wav, _)) => wav,
+			None => {
+				// Next to the encoded file, which emptying the directory would remove
+				let target_dir = self.cache_dir(nus3audio_name, config);
+				if let Err(error) = fs::create_dir_all(&target_dir) {
+					return Err(format!("Error creating cache subdirectory {:?}\n{}", target_dir, error))
+				}
+				let src_file = target_dir.join(format!("{}_null_test.{}", safe_file_name(&self.name), self.extension));
+				if let Err(error) = fs::write(&src_file, &encoded) {
+					return Err(format!("Error writing source file {:?}\n{}", src_file, error))
+				}
+				self.decode(&src_file, config)?
+			}
+		};
+
+		compare::null_test(&reference, &Pcm::from_wav(&decoded)?)
+	}
+
+	/// Compare the audio this item had before it was replaced with its audio
+	/// now, see [compare::null_test]. The replacement is lined up with the original.
+	pub fn null_test_original(&self) -> Result<NullTest, String> {
+		let original = self.original_audio.as_ref()
+			.ok_or_else(|| format!("{} hasn't been replaced since it was opened or last saved.", self.name))?;
+		let original = Pcm::from_wav(&original.to_wav(None).map_err(|error| error.to_string())?)?;
+		let replacement = Pcm::from_wav(&self.get_audio_wav(None)?)?;
+		compare::null_test(&original, &replacement)
+	}
+
+	/// Return the length in samples of `wav`, or 0 if it can't be read.
+	fn wav_sample_count(wav: &[u8]) -> u32 {
+		hound::WavReader::new(Cursor::new(wav)).map(|reader| reader.duration()).unwrap_or(0)
+	}
+
+	/// Try to empty and create the target directory. This should be in the cache directory,
+	/// to avoid deleting something we shouldn't.
+	pub fn create_target_dir(target_dir: &Path) -> Result<(), std::io::Error> {
+		if target_dir.exists() {
+			if target_dir.is_dir() {
+				let contents = target_dir.read_dir()?;
+				for item in contents {
+					let item_path = item?.path();
+					if item_path.is_dir() {
+						fs::remove_dir_all(item_path)?
+					} else {
+						fs::remove_file(item_path)?
+					}
+				}
+			} else {
+				fs::remove_file(target_dir)?;
+				fs::create_dir(target_dir)?
+			}
+		} else {
+			fs::create_dir_all(target_dir)?
+		}
+		Ok(())
+	}
+
+	/// Decode `src_file` to a WAV file as bytes.
+	/// 
+	/// The decoder configured for this file's format is tried first, then any
+	/// other codec backend that accepts it.
+	fn decode(&self, src_file: &Path, config: &dyn Config) -> Result<Vec<u8>, String> {
+		let extension = match src_file.extension().and_then(|e| e.to_str()) {
+			Some("idsp") => AudioExtension::Idsp,
+			Some("lopus") => AudioExtension::Lopus,
+			_ => AudioExtension::Bin
+		};
+
+		let preferred = config.decoder_for(&extension).to_string();
+		codec::decode_file(src_file, Some(&preferred), config).and_then(|pcm| pcm.to_wav())
+	}
+
+	/// Decode `src_file` to the 16-bit WAV file `dest`, like [ListItem::decode]
+	/// but without holding the audio in memory where the decoder allows it.
+	fn decode_to(&self, src_file: &Path, dest: &Path, config: &dyn Config) -> Result<(), String> {
+		let extension = match src_file.extension().and_then(|e| e.to_str()) {
+			Some("idsp") => AudioExtension::Idsp,
+			Some("lopus") => AudioExtension::Lopus,
+			_ => AudioExtension::Bin
+		};
+
+		let preferred = config.decoder_for(&extension).to_string();
+		codec::decode_file_to_wav(src_file, dest, Some(&preferred), config)
+	}
+
+	/// Return the header of `src_file` if it is in a format that can be read natively.
+	fn native_header_of(src_file: &Path) -> Option<NativeHeader> {
+		match src_file.extension().and_then(|e| e.to_str()) {
+			Some("idsp") | Some("lopus") => fs::read(long_path(src_file)).ok().and_then(|bytes| NativeHeader::parse(&bytes).ok()),
+			_ => None
+		}
+	}
+
+	/// Return loop points associated with `src_file`.
+	/// 
+	/// IDSP and LOPUS headers are read natively. Otherwise, this requires vgmstream to be
+	/// present and working, and will silently fail if it isn't.
+	pub fn loop_points_of(src_file: &Path, config: &dyn Config) -> Option<(usize, usize)> {
+		// Check if we can read the header ourselves
+		if let Some(header) = Self::native_header_of(src_file) {
+			return header.loop_points
+		}
+
+		// Check if we can get metadata from this file
+		if let Ok(metadata) = backend::vgmstream_metadata(src_file, config) {
+			// Check if the metadata has the "loopingInfo" object
+			if let json::JsonValue::Object(loop_info) = &metadata["loopingInfo"] {
+				// Check that the "start" and "end" numbers can be read as usize
+				if let (Some(start), Some(end)) = (loop_info["start"].as_usize(), loop_info["end"].as_usize()) {
+					// Check that the end is placed after the start
+					if end > start {
+						return Some((start, end))
+					}
+				}
+			}
+		}
+
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{ codec::Codec, config::Decoder };
+
+	/// A [Config] with no external tools and a cache directory of its own.
+	struct TestConfig (PathBuf);
+
+	impl TestConfig {
+		fn new(name: &str) -> Self {
+			Self(std::env::temp_dir().join(format!("simple-nus3audio-test-{}-{}", name, std::process::id())))
+		}
+	}
+
+	impl Config for TestConfig {
+		fn vgaudio_cli_path(&self) -> &str { "" }
+		fn vgaudio_cli_prepath(&self) -> &str { "" }
+		fn vgmstream_path(&self) -> &str { "" }
+		fn tool_timeout(&self) -> Duration { Duration::ZERO }
+		fn decoder_for(&self, _extension: &AudioExtension) -> Decoder { Decoder::VGAudioCli }
+		fn keep_cache(&self) -> bool { false }
+		fn cache_dir(&self) -> &Path { &self.0 }
+		fn default_format(&self) -> AudioExtension { AudioExtension::Idsp }
+	}
+
+	/// Stands in for the external tools, decoding any IDSP file to a second of noise.
+	struct TestIdspCodec;
+
+	impl Codec for TestIdspCodec {
+		fn name(&self) -> &'static str { "test IDSP" }
+
+		fn probe(&self, path: &Path, _config: &dyn Config) -> bool {
+			path.extension().map(|extension| extension == "idsp").unwrap_or(false)
+		}
+
+		fn decode(&self, _path: &Path, _config: &dyn Config) -> Result<Pcm, String> {
+			Ok(Pcm { samples: (0..12_000u32).map(|sample| (sample.wrapping_mul(2_654_435_761) >> 18) as i16).collect(), channels: 1, sample_rate: 12_000 })
+		}
+	}
+
+	/// Return an item with half a second of silence, replaced by an IDSP file
+	/// written to the cache directory of `config`.
+	fn replaced_with_idsp(config: &TestConfig) -> ListItem {
+		codec::register(Box::new(TestIdspCodec));
+		fs::create_dir_all(&config.0).unwrap();
+		let path = config.0.join("replacement.idsp");
+		let mut idsp = b"IDSP".to_vec();
+		idsp.resize(0x100, 0);
+		fs::write(&path, &idsp).unwrap();
+
+		let mut item = ListItem::new("item".to_owned());
+		item.set_audio_from_bytes(codec::silence_wav(0.5, 12_000).unwrap(), EncodingType::WAV).unwrap();
+		item.replace_from_file("test", &path, config).unwrap();
+		item
+	}
+
+	#[test]
+	fn replaced_from_encoded_file() {
+		let config = TestConfig::new("replaced-from-encoded");
+		let item = replaced_with_idsp(&config);
+		let _ = fs::remove_dir_all(&config.0);
+
+		// The file's bytes are saved as they are, but aren't the ones it was opened with
+		assert!(matches!(item.save_source(), SaveSource::Cached));
+		assert_eq!(item.length_in_samples, 12_000)
+	}
+
+	#[test]
+	fn null_test_of_encoded_replacement() {
+		let config = TestConfig::new("null-test-encoded");
+		let mut item = replaced_with_idsp(&config);
+		let test = item.null_test("test", &config);
+		let _ = fs::remove_dir_all(&config.0);
+
+		// Replaced, so it isn't refused like an item left as it was opened
+		let test = test.unwrap();
+		assert_eq!(test.frames, 12_000);
+		assert_eq!(test.peak, 0.0);
+		assert!(item.null_test_original().is_ok())
+	}
+
+	#[test]
+	fn replaced_audio_sets_length() {
+		let mut item = ListItem::new("item".to_owned());
+		item.set_audio_from_bytes(codec::silence_wav(1.0, 48_000).unwrap(), EncodingType::WAV).unwrap();
+		assert_eq!(item.length_in_samples, 48_000);
+
+		// The estimate goes by the new audio, not the audio it replaced
+		item.set_audio_from_bytes(codec::silence_wav(0.05, 32_000).unwrap(), EncodingType::WAV).unwrap();
+		assert_eq!(item.length_in_samples, 1_600);
+		assert_eq!(item.channels(), 1);
+		assert_eq!(item.sample_rate(), 32_000);
+		assert_eq!(item.estimate_size(&AudioExtension::Idsp, None, None), Some(estimate_idsp_size(1_600, 1)));
+		assert_eq!(item.save_size(), Some(estimate_idsp_size(1_600, 1)));
+	}
+}
@@ -0,0 +1,29 @@
+//! The core of simple-nus3audio-gui: the nus3audio archive model, the codec
+//! abstraction, and running the external tools (VGAudioCli and vgmstream).
+//! 
+//! Nothing in here knows about the GUI. Frontends provide their settings
+//! through the [Config] trait.
+
+#[cfg(feature = "smash-arc")]
+pub mod arc;
+pub mod archive;
+pub mod backend;
+pub mod bank;
+pub mod batch;
+pub mod cancel;
+pub mod codec;
+pub mod compare;
+pub mod config;
+pub mod header;
+pub mod item;
+pub mod manifest;
+pub mod project;
+pub mod reel;
+pub mod structure;
+pub mod tool;
+pub mod util;
+
+pub use archive::{ Archive, ProjectedSize, SaveReport, PARSE_ERROR };
+pub use cancel::Cancel;
+pub use config::{ Config, Decoder };
+pub use item::{ AudioExtension, ExportRange, ListItem, SaveSource };
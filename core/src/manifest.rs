@@ -0,0 +1,248 @@
+//! A list of the items of a nus3audio file that can be edited in a spreadsheet
+//! or by a script, then read back in to rename, reorder and re-ID items in bulk.
+//! 
+//! The manifest is CSV, with a header and one row per item:
+//! 
+//! ```csv
+//! index,id,name,format,hash
+//! 0,0,bgm_title,idsp,3b2f8e61a0c4d917
+//! 1,1,bgm_results,lopus,
+//! ```
+//! 
+//! `index` is where the item was when the manifest was written, and is how rows
+//! are matched back to items. Reading a manifest puts the items in the order of
+//! its rows, with the names and IDs in them. Items without a row keep their
+//! order after the others. An empty `id` keeps the item's tone ID.
+//! 
+//! `format` and `hash` are only there to be read. The hash is of the bytes the
+//! item is written with (see [ListItem::content_hash](crate::ListItem::content_hash)),
+//! and is empty for items that haven't been encoded yet. Comparing the manifests
+//! of two builds shows which items differ.
+//! 
+//! Fields can be quoted, and quoted fields can span lines, as spreadsheets
+//! write them. Only CSV is read, not JSON.
+
+use std::collections::HashSet;
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use crate::Archive;
+
+const INDEX: &str = "index";
+const ID: &str = "id";
+const NAME: &str = "name";
+const FORMAT: &str = "format";
+const HASH: &str = "hash";
+
+/// What reading a manifest changed, see [apply].
+#[derive(Clone, Debug, Default)]
+pub struct Changes {
+	/// How many items were renamed.
+	pub renamed: usize,
+	/// How many items were given a new tone ID.
+	pub reidentified: usize,
+	/// How many items ended up somewhere else in the list.
+	pub moved: usize
+}
+
+impl Changes {
+	/// Whether or not anything changed.
+	pub fn is_empty(&self) -> bool {
+		self.renamed == 0 && self.reidentified == 0 && self.moved == 0
+	}
+}
+
+/// One row of a manifest.
+struct Row {
+	/// Where the item was when the manifest was written.
+	index: usize,
+	/// The item's new tone ID, if the row has one.
+	id: Option<u32>,
+	/// The item's new name.
+	name: String
+}
+
+/// Quote `field` if it would otherwise be read back wrong.
+fn quote(field: &str) -> String {
+	if field.contains([',', '"', '\n']) {
+		format!("\"{}\"", field.replace('"', "\"\""))
+	} else {
+		field.to_owned()
+	}
+}
+
+/// Split CSV `text` into its records, unquoting their fields. Each record
+/// comes with the number of the line it starts on, counting from 1.
+/// 
+/// A quoted field can span lines. Blank lines are skipped.
+fn records(text: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+	let mut records = Vec::new();
+	let mut fields = vec![String::new()];
+	let (mut line_number, mut start) = (1, 1);
+	let mut quoted = false;
+	let mut chars = text.chars().peekable();
+	while let Some(c) = chars.next() {
+		let field = fields.last_mut().expect("there is always a field");
+		match c {
+			'"' if quoted && chars.peek() == Some(&'"') => {
+				field.push('"');
+				chars.next();
+			},
+			'"' => quoted = !quoted,
+			',' if !quoted => fields.push(String::new()),
+			'\r' if !quoted && chars.peek() == Some(&'\n') => (),
+			'\n' if !quoted => {
+				let record = std::mem::replace(&mut fields, vec![String::new()]);
+				if record.len() > 1 || !record[0].trim().is_empty() {
+					records.push((start, record))
+				}
+				line_number += 1;
+				start = line_number
+			},
+			c => {
+				if c == '\n' {
+					line_number += 1
+				}
+				field.push(c)
+			}
+		}
+	}
+	if quoted {
+		return Err(format!("Line {}: a quoted field isn't closed.", start))
+	}
+	if fields.len() > 1 || !fields[0].trim().is_empty() {
+		records.push((start, fields))
+	}
+	Ok(records)
+}
+
+/// Return the manifest of `archive`.
+pub fn write(archive: &Archive) -> String {
+	let mut text = format!("{},{},{},{},{}\n", INDEX, ID, NAME, FORMAT, HASH);
+	for (index, item) in archive.items.iter().enumerate() {
+		let id = item.id.map(|id| id.to_string()).unwrap_or_default();
+		let hash = item.content_hash().map(|hash| format!("{:016x}", hash)).unwrap_or_default();
+		text.push_str(&format!("{},{},{},{},{}\n", index, id, quote(&item.name), item.extension, hash))
+	}
+	text
+}
+
+/// Read the rows of the manifest in `text`, checking them against `archive`.
+fn read(archive: &Archive, text: &str) -> Result<Vec<Row>, String> {
+	let mut records = records(text)?.into_iter();
+	let header: Vec<String> = match records.next() {
+		Some((_, fields)) => fields.into_iter().map(|field| field.trim().to_lowercase()).collect(),
+		None => return Err("The manifest is empty.".to_owned())
+	};
+	let column = |name: &str| header.iter().position(|field| field == name);
+	let index_column = column(INDEX).ok_or_else(|| format!("The manifest has no {} column.", INDEX))?;
+	let (id_column, name_column) = (column(ID), column(NAME));
+
+	let mut rows = Vec::new();
+	let mut seen = HashSet::new();
+	for (line_number, fields) in records {
+		let field = |column: Option<usize>| column.and_then(|column| fields.get(column)).map(|field| field.trim()).unwrap_or_default();
+
+		let index = field(Some(index_column)).parse::<usize>()
+			.map_err(|_| format!("Line {}: {:?} isn't an index.", line_number, field(Some(index_column))))?;
+		let item = archive.items.get(index)
+			.ok_or_else(|| format!("Line {}: there is no item {}, there are {} items.", line_number, index, archive.items.len()))?;
+		if !seen.insert(index) {
+			return Err(format!("Line {}: item {} is in the manifest twice.", line_number, index))
+		}
+
+		let id = match field(id_column) {
+			"" => None,
+			id => Some(id.parse::<u32>().map_err(|_| format!("Line {}: {:?} isn't a tone ID.", line_number, id))?)
+		};
+		let name = match field(name_column) {
+			"" => item.name.clone(),
+			name => name.to_owned()
+		};
+		rows.push(Row { index, id, name })
+	}
+
+	Ok(rows)
+}
+
+/// Rename, reorder and re-ID the items of `archive` as the manifest in `text` says.
+/// 
+/// The manifest is checked before anything is changed, so an error leaves
+/// `archive` as it was. Marks `archive` as modified if anything changed.
+pub fn apply(archive: &mut Archive, text: &str) -> Result<Changes, String> {
+	let rows = read(archive, text)?;
+
+	// Items without a row keep their order after the ones with one
+	let listed: HashSet<usize> = rows.iter().map(|row| row.index).collect();
+	let order: Vec<usize> = rows.iter().map(|row| row.index)
+		.chain((0..archive.items.len()).filter(|index| !listed.contains(index)))
+		.collect();
+
+	// Work out the final names and IDs, to catch duplicates before changing anything
+	let mut names = Vec::with_capacity(order.len());
+	let mut ids = Vec::with_capacity(order.len());
+	for &index in &order {
+		let row = rows.iter().find(|row| row.index == index);
+		let item = &archive.items[index];
+		names.push(row.map(|row| row.name.clone()).unwrap_or_else(|| item.name.clone()));
+		ids.push(row.and_then(|row| row.id).or(item.id));
+	}
+	let mut seen_names = HashSet::new();
+	if let Some(name) = names.iter().find(|name| !seen_names.insert(name.as_str())) {
+		return Err(format!("More than one item would be named {}.", name))
+	}
+	let mut seen_ids = HashSet::new();
+	if let Some(id) = ids.iter().flatten().find(|id| !seen_ids.insert(**id)) {
+		return Err(format!("More than one item would have the tone ID {}.", id))
+	}
+
+	let mut changes = Changes::default();
+	let mut renames = Vec::new();
+	let mut old_items: Vec<Option<_>> = archive.items.drain(..).map(Some).collect();
+	for (position, ((&index, name), id)) in order.iter().zip(names).zip(ids).enumerate() {
+		let mut item = old_items[index].take().expect("every index is in the order once");
+		if position != index {
+			changes.moved += 1
+		}
+		if item.name != name {
+			renames.push((item.name.clone(), name.clone()));
+			item.name = name;
+			changes.renamed += 1
+		}
+		if item.id != id {
+			item.id = id;
+			changes.reidentified += 1
+		}
+		archive.items.push(item)
+	}
+
+	archive.project.rename_all(&renames);
+	if !changes.is_empty() {
+		archive.modified = true
+	}
+	debug!("Applied a manifest to {}: {:?}", archive.name, changes);
+	Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn quoted_fields() {
+		let text = "index,name\r\n0,\"a, \"\"b\"\"\"\n\n1,\"two\nlines\"\n2,c";
+		let parsed = records(text).unwrap();
+		assert_eq!(parsed.len(), 4);
+		assert_eq!(parsed[1], (2, vec!["0".to_owned(), "a, \"b\"".to_owned()]));
+		assert_eq!(parsed[2], (4, vec!["1".to_owned(), "two\nlines".to_owned()]));
+		assert_eq!(parsed[3], (6, vec!["2".to_owned(), "c".to_owned()]));
+		assert!(records("index,name\n0,\"open").is_err())
+	}
+
+	#[test]
+	fn quote_round_trip() {
+		for field in ["plain", "with,comma", "with \"quotes\"", "two\nlines"] {
+			let (_, fields) = records(&quote(field)).unwrap().remove(0);
+			assert_eq!(fields, vec![field.to_owned()])
+		}
+	}
+}
@@ -0,0 +1,289 @@
+//! Information about a nus3audio file that isn't part of the file itself,
+//! like bookmarked items and where items were replaced from. It's kept in a sidecar file next to the nus3audio,
+//! so the nus3audio stays exactly what the game expects.
+//! 
+//! The sidecar is toml, with items referred to by name:
+//! 
+//! ```toml
+//! bookmarks = ["bgm_title", "bgm_results"]
+//! 
+//! [notes]
+//! bgm_title = "Arranged by a friend, credit them in the release"
+//! 
+//! [sources]
+//! bgm_title = "C:/Music/title theme.flac"
+//! 
+//! [sort]
+//! key = "name"
+//! descending = false
+//! group = "prefix"
+//! ```
+
+use std::{
+	collections::{ BTreeMap, BTreeSet },
+	fs,
+	path::{ Path, PathBuf }
+};
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use crate::util::long_path;
+
+const BOOKMARKS: &str = "bookmarks";
+const NOTES: &str = "notes";
+const SOURCES: &str = "sources";
+const SORT: &str = "sort";
+const SORT_KEY: &str = "key";
+const SORT_DESCENDING: &str = "descending";
+const SORT_GROUP: &str = "group";
+
+/// What the items of a nus3audio file can be listed by, see [Sort].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortKey {
+	/// Their order in the file.
+	#[default]
+	Index,
+	/// Their names.
+	Name,
+	/// Their tone IDs.
+	Id,
+	/// Their formats.
+	Format,
+	/// How big they'll be when saved.
+	Size
+}
+
+impl SortKey {
+	/// Every sort key, in the order they are shown.
+	pub const ALL: [SortKey; 5] = [SortKey::Index, SortKey::Name, SortKey::Id, SortKey::Format, SortKey::Size];
+
+	/// The name of this sort key in the sidecar.
+	fn key(self) -> &'static str {
+		match self {
+			Self::Index => "index",
+			Self::Name => "name",
+			Self::Id => "id",
+			Self::Format => "format",
+			Self::Size => "size"
+		}
+	}
+}
+
+/// How the items of a nus3audio file can be grouped in the list, see [Sort].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Grouping {
+	/// They aren't grouped.
+	#[default]
+	None,
+	/// By the start of their names up to the first underscore, like `vc_` or `se_`.
+	Prefix,
+	/// By ranges of their tone IDs, see [ID_GROUP_SIZE](crate::archive::ID_GROUP_SIZE).
+	IdRange
+}
+
+impl Grouping {
+	/// Every grouping, in the order they are shown.
+	pub const ALL: [Grouping; 3] = [Grouping::None, Grouping::Prefix, Grouping::IdRange];
+
+	/// The name of this grouping in the sidecar.
+	fn key(self) -> &'static str {
+		match self {
+			Self::None => "none",
+			Self::Prefix => "prefix",
+			Self::IdRange => "id_range"
+		}
+	}
+}
+
+/// How the items of a nus3audio file are listed. This only changes the order
+/// they are shown in, never the order they are written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sort {
+	/// What the items are sorted by.
+	pub key: SortKey,
+	/// Whether or not the largest come first.
+	pub descending: bool,
+	/// What the items are grouped by. Groups are in the order of their first item.
+	pub group: Grouping
+}
+
+/// The sidecar of a nus3audio file.
+#[derive(Clone, Debug, Default)]
+pub struct Project {
+	/// Names of bookmarked items.
+	pub bookmarks: BTreeSet<String>,
+	/// Free-text notes about items, by item name.
+	pub notes: BTreeMap<String, String>,
+	/// The file each item was last replaced from, by item name.
+	pub sources: BTreeMap<String, String>,
+	/// How the items are listed.
+	pub sort: Sort
+}
+
+/// What a [Project] holds about a single item, see [Project::forget].
+#[derive(Clone, Debug, Default)]
+pub struct Entry {
+	/// Whether or not the item is bookmarked.
+	pub bookmarked: bool,
+	/// The notes about the item.
+	pub note: Option<String>,
+	/// The file the item was last replaced from.
+	pub source: Option<String>
+}
+
+impl Project {
+	/// Return the path of the sidecar of the nus3audio file at `path`.
+	pub fn sidecar_path(path: &Path) -> PathBuf {
+		path.with_extension("nus3audio.toml")
+	}
+
+	/// Read the sidecar of the nus3audio file at `path`.
+	/// 
+	/// A nus3audio file without a sidecar has an empty one.
+	pub fn load(path: &Path) -> Result<Self, String> {
+		let text = match fs::read_to_string(long_path(&Self::sidecar_path(path))) {
+			Ok(text) => text,
+			Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+			Err(error) => return Err(error.to_string())
+		};
+		let table = toml::from_str::<toml::map::Map<String, toml::Value>>(&text).map_err(|error| error.to_string())?;
+
+		let strings = |key: &str| match table.get(key) {
+			Some(toml::Value::Table(strings)) => strings.iter()
+				.filter_map(|(name, value)| value.as_str().map(|value| (name.to_owned(), value.to_owned())))
+				.collect(),
+			_ => BTreeMap::new()
+		};
+
+		Ok(Self {
+			bookmarks: match table.get(BOOKMARKS) {
+				Some(toml::Value::Array(bookmarks)) => bookmarks.iter().filter_map(|name| name.as_str().map(|name| name.to_owned())).collect(),
+				_ => BTreeSet::new()
+			},
+			notes: strings(NOTES),
+			sources: strings(SOURCES),
+			sort: match table.get(SORT) {
+				Some(toml::Value::Table(sort)) => Sort {
+					key: sort.get(SORT_KEY).and_then(|key| key.as_str())
+						.and_then(|key| SortKey::ALL.into_iter().find(|sort_key| sort_key.key() == key))
+						.unwrap_or_default(),
+					descending: sort.get(SORT_DESCENDING).and_then(|descending| descending.as_bool()).unwrap_or(false),
+					group: sort.get(SORT_GROUP).and_then(|group| group.as_str())
+						.and_then(|group| Grouping::ALL.into_iter().find(|grouping| grouping.key() == group))
+						.unwrap_or_default()
+				},
+				_ => Sort::default()
+			}
+		})
+	}
+
+	/// Whether or not there is anything to write in this sidecar.
+	pub fn is_empty(&self) -> bool {
+		self.bookmarks.is_empty() && self.notes.is_empty() && self.sources.is_empty() && self.sort == Sort::default()
+	}
+
+	/// Write this sidecar next to the nus3audio file at `path`.
+	/// 
+	/// An empty sidecar removes the file instead, so it doesn't clutter the folder.
+	pub fn save(&self, path: &Path) -> Result<(), String> {
+		let sidecar = long_path(&Self::sidecar_path(path));
+		if self.is_empty() {
+			return match fs::remove_file(&sidecar) {
+				Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.to_string()),
+				_ => Ok(())
+			}
+		}
+
+		let mut table = toml::map::Map::new();
+		table.insert(BOOKMARKS.to_owned(), toml::Value::Array(self.bookmarks.iter().map(|name| toml::Value::String(name.to_owned())).collect()));
+		table.insert(NOTES.to_owned(), toml::Value::Table(self.notes.iter().map(|(name, note)| (name.to_owned(), toml::Value::String(note.to_owned()))).collect()));
+		table.insert(SOURCES.to_owned(), toml::Value::Table(self.sources.iter().map(|(name, source)| (name.to_owned(), toml::Value::String(source.to_owned()))).collect()));
+		if self.sort != Sort::default() {
+			let mut sort = toml::map::Map::new();
+			sort.insert(SORT_KEY.to_owned(), toml::Value::String(self.sort.key.key().to_owned()));
+			sort.insert(SORT_DESCENDING.to_owned(), toml::Value::Boolean(self.sort.descending));
+			sort.insert(SORT_GROUP.to_owned(), toml::Value::String(self.sort.group.key().to_owned()));
+			table.insert(SORT.to_owned(), toml::Value::Table(sort));
+		}
+
+		debug!("Writing sidecar {:?}", sidecar);
+		let text = toml::to_string(&table).map_err(|error| error.to_string())?;
+		fs::write(&sidecar, text).map_err(|error| error.to_string())
+	}
+
+	/// Return the notes about the item `name`, or an empty string.
+	pub fn note(&self, name: &str) -> &str {
+		self.notes.get(name).map(|note| note.as_str()).unwrap_or_default()
+	}
+
+	/// Set the notes about the item `name`. Empty notes are removed.
+	pub fn set_note(&mut self, name: &str, note: &str) {
+		if note.trim().is_empty() {
+			self.notes.remove(name);
+		} else {
+			self.notes.insert(name.to_owned(), note.to_owned());
+		}
+	}
+
+	/// Forget everything about the item `name`, like when it's removed,
+	/// and return what was forgotten.
+	pub fn forget(&mut self, name: &str) -> Entry {
+		Entry {
+			bookmarked: self.bookmarks.remove(name),
+			note: self.notes.remove(name),
+			source: self.sources.remove(name)
+		}
+	}
+
+	/// Remember `entry` about the item `name` again, like when it's restored.
+	pub fn remember(&mut self, name: &str, entry: Entry) {
+		if entry.bookmarked {
+			self.bookmarks.insert(name.to_owned());
+		}
+		if let Some(note) = entry.note {
+			self.notes.insert(name.to_owned(), note);
+		}
+		if let Some(source) = entry.source {
+			self.sources.insert(name.to_owned(), source);
+		}
+	}
+
+	/// Remember that the item `name` was replaced from the file at `path`.
+	pub fn set_source(&mut self, name: &str, path: &Path) {
+		self.sources.insert(name.to_owned(), path.to_string_lossy().to_string());
+	}
+
+	/// Keep everything about the item `old`, now that it's called `new`.
+	pub fn rename(&mut self, old: &str, new: &str) {
+		if self.bookmarks.remove(old) {
+			self.bookmarks.insert(new.to_owned());
+		}
+		if let Some(note) = self.notes.remove(old) {
+			self.notes.insert(new.to_owned(), note);
+		}
+		if let Some(source) = self.sources.remove(old) {
+			self.sources.insert(new.to_owned(), source);
+		}
+	}
+
+	/// Keep everything about each item renamed from `.0` to `.1`, all at once,
+	/// so items can trade names.
+	pub fn rename_all(&mut self, renames: &[(String, String)]) {
+		let mut bookmarks = Vec::new();
+		let mut notes = Vec::new();
+		let mut sources = Vec::new();
+		for (old, new) in renames {
+			if self.bookmarks.remove(old) {
+				bookmarks.push(new.to_owned())
+			}
+			if let Some(note) = self.notes.remove(old) {
+				notes.push((new.to_owned(), note))
+			}
+			if let Some(source) = self.sources.remove(old) {
+				sources.push((new.to_owned(), source))
+			}
+		}
+		self.bookmarks.extend(bookmarks);
+		self.notes.extend(notes);
+		self.sources.extend(sources);
+	}
+}
@@ -0,0 +1,90 @@
+//! Rendering every item of an archive back-to-back into one WAV file,
+//! for reviewing a whole nus3audio file outside of the program.
+
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use crate::{
+	codec::{ write_wav, Pcm },
+	Archive
+};
+
+/// Sample rate of the preview reel. Items are resampled to this.
+const REEL_SAMPLE_RATE: u32 = 48_000;
+/// Length of the gap between items, in seconds.
+const GAP_SECONDS: f64 = 0.5;
+/// Length of the beep before each item, in seconds.
+const BEEP_SECONDS: f64 = 0.1;
+/// Frequency of the beep before each item, in Hz.
+const BEEP_FREQUENCY: f64 = 1_000.0;
+
+/// What to put between items in a preview reel.
+#[derive(Clone, Copy, Default)]
+pub struct ReelOptions {
+	/// Put half a second of silence after each item.
+	pub gaps: bool,
+	/// Put a short beep before each item.
+	pub beeps: bool
+}
+
+/// Return `pcm` as interleaved stereo at the reel's sample rate.
+/// 
+/// `pcm` must have one or two channels, like preview audio.
+fn to_reel_format(pcm: Pcm) -> Vec<i16> {
+	let stereo: Vec<i16> = match pcm.channels {
+		1 => pcm.samples.iter().flat_map(|sample| [*sample, *sample]).collect(),
+		_ => pcm.samples
+	};
+	if pcm.sample_rate == REEL_SAMPLE_RATE {
+		return stereo
+	}
+
+	let input = fon::Audio::<fon::chan::Ch16, 2>::with_i16_buffer(pcm.sample_rate, stereo);
+	let mut output = fon::Audio::<fon::chan::Ch16, 2>::with_audio(REEL_SAMPLE_RATE, &input);
+	output.as_i16_slice().to_vec()
+}
+
+/// Return `seconds` of interleaved stereo, either silent or a beep.
+fn separator(seconds: f64, beep: bool) -> Vec<i16> {
+	let length = (seconds * REEL_SAMPLE_RATE as f64) as usize;
+	(0..length)
+		.flat_map(|index| {
+			let sample = if beep {
+				let time = index as f64 / REEL_SAMPLE_RATE as f64;
+				((time * BEEP_FREQUENCY * std::f64::consts::TAU).sin() * i16::MAX as f64 * 0.25) as i16
+			} else { 0 };
+			[sample, sample]
+		})
+		.collect()
+}
+
+/// Render every item of `archive` that can be decoded into one stereo WAV file,
+/// in order.
+/// 
+/// Items that can't be decoded are left out, and returned with their error.
+pub fn render(archive: &Archive, options: ReelOptions) -> Result<(Vec<u8>, Vec<(String, String)>), String> {
+	let mut samples: Vec<i16> = Vec::new();
+	let mut skipped = Vec::new();
+
+	for item in &archive.items {
+		let name = format!("{}.{}", item.name, item.extension);
+		let pcm = match item.get_preview_wav(item.loop_end()).and_then(|wav| Pcm::from_wav(&wav)) {
+			Ok(pcm) => pcm,
+			Err(error) => {
+				debug!("Leaving {} out of the preview reel: {}", name, error);
+				skipped.push((name, error));
+				continue
+			}
+		};
+
+		if options.beeps {
+			samples.extend(separator(BEEP_SECONDS, true))
+		}
+		samples.extend(to_reel_format(pcm));
+		if options.gaps {
+			samples.extend(separator(GAP_SECONDS, false))
+		}
+	}
+
+	let wav = write_wav(&samples, 2, REEL_SAMPLE_RATE).map_err(|error| error.to_string())?;
+	Ok((wav, skipped))
+}
@@ -0,0 +1,438 @@
+//! Reading the layout of a nus3audio file section by section, without parsing
+//! it into items, to see what's wrong with files other tools wrote incorrectly.
+//! 
+//! A nus3audio file is a `NUS3` header followed by sections, each a magic and
+//! a little-endian size:
+//! 
+//! - `AUDIINDX`: how many items there are
+//! - `TNID`: the tone ID of each item
+//! - `NMOF`: where each item's name is in `TNNM`
+//! - `ADOF`: where each item's audio is in the file, and how long it is
+//! - `TNNM`: the item names
+//! - `JUNK`: padding
+//! - `PACK`: the audio itself
+//! 
+//! Other sections are kept when a file is opened and written back when it's
+//! saved, see [extra_sections] and [insert_sections].
+
+/// The magic at the start of every nus3audio file.
+const NUS3_MAGIC: &[u8; 4] = b"NUS3";
+/// The one section magic eight bytes long rather than four.
+const AUDIINDX_MAGIC: &[u8; 8] = b"AUDIINDX";
+
+use nus3audio::{ AudioFile, Nus3audioFile };
+use crate::header::{ NativeHeader, NAMCO_OPUS_MAGIC };
+use crate::util::read_u32_le;
+
+/// The sections written by the nus3audio crate. Any others are kept as they are.
+const KNOWN_SECTIONS: [&str; 7] = ["AUDIINDX", "TNID", "NMOF", "ADOF", "TNNM", "JUNK", "PACK"];
+
+/// Read the null-terminated name at `offset`, if there is one.
+fn read_name(bytes: &[u8], offset: usize) -> Option<String> {
+	let rest = bytes.get(offset..)?;
+	let end = rest.iter().position(|&byte| byte == 0)?;
+	Some(String::from_utf8_lossy(&rest[..end]).into_owned())
+}
+
+/// A section of a nus3audio file.
+#[derive(Clone, Debug)]
+pub struct Section {
+	/// The section's magic, like `TNID`.
+	pub magic: String,
+	/// Where the section's magic starts in the file.
+	pub offset: usize,
+	/// The size of the section's data, as written in the file.
+	pub size: u32
+}
+
+impl Section {
+	/// Where the section's data starts in the file.
+	pub fn data_offset(&self) -> usize {
+		self.offset + self.magic.len() + 4
+	}
+}
+
+/// A section of a nus3audio file that isn't written when saving, kept to be
+/// put back, see [insert_sections].
+#[derive(Clone, Debug)]
+pub struct ExtraSection {
+	/// The magic of the written section it came after, or None if it came right after the header.
+	pub after: Option<String>,
+	/// The whole section, magic and size included.
+	pub bytes: Vec<u8>
+}
+
+/// One item of a nus3audio file, as its sections describe it.
+#[derive(Clone, Debug, Default)]
+pub struct Entry {
+	/// The tone ID from `TNID`.
+	pub id: Option<u32>,
+	/// Where the name starts in the file, from `NMOF`.
+	pub name_offset: Option<u32>,
+	/// The name at that offset, if it could be read.
+	pub name: Option<String>,
+	/// Where the audio starts in the file, from `ADOF`.
+	pub data_offset: Option<u32>,
+	/// How long the audio is, from `ADOF`.
+	pub data_size: Option<u32>
+}
+
+/// The layout of a nus3audio file, see [inspect].
+#[derive(Clone, Debug, Default)]
+pub struct Structure {
+	/// The size of the file.
+	pub file_size: usize,
+	/// The size after the `NUS3` header, as written in the file.
+	pub declared_size: u32,
+	/// The item count from `AUDIINDX`.
+	pub count: Option<u32>,
+	/// Every section, in the order they're in.
+	pub sections: Vec<Section>,
+	/// Every item, as far as the sections could be read.
+	pub entries: Vec<Entry>,
+	/// Anything that doesn't add up.
+	pub problems: Vec<String>
+}
+
+impl Structure {
+	/// Return the first section with `magic`, if there is one.
+	pub fn section(&self, magic: &str) -> Option<&Section> {
+		self.sections.iter().find(|section| section.magic == magic)
+	}
+
+	/// Read `values_per_entry` u32 values for each item from the start of the
+	/// section with `magic`, noting it if the section is missing or too short.
+	fn read_table(&mut self, bytes: &[u8], magic: &str, values_per_entry: usize) -> Vec<u32> {
+		let section = match self.section(magic) {
+			Some(section) => section.clone(),
+			None => {
+				self.problems.push(format!("There is no {} section.", magic));
+				return Vec::new()
+			}
+		};
+		let count = self.count.unwrap_or(0) as usize;
+		let needed = count * values_per_entry * 4;
+		if (section.size as usize) < needed {
+			self.problems.push(format!("{} is {} bytes, but {} items need {} bytes.", magic, section.size, count, needed))
+		}
+		(0..count * values_per_entry)
+			.map_while(|index| read_u32_le(bytes, section.data_offset() + index * 4))
+			.collect()
+	}
+}
+
+/// Read the layout of the nus3audio file in `bytes`.
+/// 
+/// Only a file without a `NUS3` header is an error. Anything else that's wrong
+/// is listed in [Structure::problems], with as much read as could be.
+pub fn inspect(bytes: &[u8]) -> Result<Structure, String> {
+	if bytes.get(..4) != Some(NUS3_MAGIC) {
+		return Err("This isn't a nus3audio file, it doesn't start with NUS3.".to_owned())
+	}
+	let mut structure = Structure {
+		file_size: bytes.len(),
+		declared_size: read_u32_le(bytes, 4).unwrap_or(0),
+		..Structure::default()
+	};
+	if structure.declared_size as usize + 8 != bytes.len() {
+		structure.problems.push(format!("The header says the file is {} bytes, but it's {} bytes.", structure.declared_size as usize + 8, bytes.len()))
+	}
+
+	// Walk the sections until the end of the file
+	let mut offset = 8;
+	while offset < bytes.len() {
+		let magic_length = if bytes.get(offset..offset + 8) == Some(AUDIINDX_MAGIC) { 8 } else { 4 };
+		let size = match (bytes.get(offset..offset + magic_length), read_u32_le(bytes, offset + magic_length)) {
+			(Some(magic), Some(size)) => {
+				structure.sections.push(Section { magic: String::from_utf8_lossy(magic).into_owned(), offset, size });
+				size
+			},
+			_ => {
+				structure.problems.push(format!("{} bytes at {:#x} are too short to be a section.", bytes.len() - offset, offset));
+				break
+			}
+		};
+		let end = offset + magic_length + 4 + size as usize;
+		if end > bytes.len() {
+			structure.problems.push(format!("The section at {:#x} ends at {:#x}, past the end of the file.", offset, end));
+			break
+		}
+		offset = end
+	}
+
+	structure.count = structure.section("AUDIINDX").and_then(|section| read_u32_le(bytes, section.data_offset()));
+	if structure.count.is_none() {
+		structure.problems.push("There is no item count in an AUDIINDX section.".to_owned());
+		return Ok(structure)
+	}
+
+	let ids = structure.read_table(bytes, "TNID", 1);
+	let name_offsets = structure.read_table(bytes, "NMOF", 1);
+	let data = structure.read_table(bytes, "ADOF", 2);
+	// A broken count could be anything, so only list items something was read for
+	let listed = (structure.count.unwrap_or(0) as usize).min(ids.len().max(name_offsets.len()).max((data.len() + 1) / 2));
+	structure.entries = (0..listed)
+		.map(|index| Entry {
+			id: ids.get(index).copied(),
+			name_offset: name_offsets.get(index).copied(),
+			name: name_offsets.get(index).and_then(|&offset| read_name(bytes, offset as usize)),
+			data_offset: data.get(index * 2).copied(),
+			data_size: data.get(index * 2 + 1).copied()
+		})
+		.collect();
+
+	// Every item's audio should be inside PACK
+	if let Some(pack) = structure.section("PACK").cloned() {
+		let (start, end) = (pack.data_offset(), pack.data_offset() + pack.size as usize);
+		for (index, entry) in structure.entries.iter().enumerate() {
+			if let (Some(offset), Some(size)) = (entry.data_offset, entry.data_size) {
+				if (offset as usize) < start || offset as usize + size as usize > end {
+					structure.problems.push(format!("The audio of item {} ({:#x} to {:#x}) isn't inside PACK.", index, offset, offset as usize + size as usize))
+				}
+			}
+		}
+	} else {
+		structure.problems.push("There is no PACK section.".to_owned())
+	}
+
+	Ok(structure)
+}
+
+/// Return the sections of the nus3audio file in `bytes` that aren't written
+/// when saving, so [insert_sections] can put them back.
+pub fn extra_sections(bytes: &[u8]) -> Vec<ExtraSection> {
+	let structure = match inspect(bytes) {
+		Ok(structure) => structure,
+		Err(_) => return Vec::new()
+	};
+	let mut extra = Vec::new();
+	let mut after = None;
+	for section in &structure.sections {
+		if KNOWN_SECTIONS.contains(&section.magic.as_str()) {
+			after = Some(section.magic.clone())
+		} else {
+			extra.push(ExtraSection { after: after.clone(), bytes: bytes[section.offset..section.data_offset() + section.size as usize].to_vec() })
+		}
+	}
+	extra
+}
+
+/// Put the `extra` sections back into the nus3audio file in `bytes`, each after
+/// the section it came after, or at the end if there is no such section. The
+/// offsets in `NMOF` and `ADOF` and the size in the header are moved to match.
+pub fn insert_sections(bytes: Vec<u8>, extra: &[ExtraSection]) -> Result<Vec<u8>, String> {
+	if extra.is_empty() {
+		return Ok(bytes)
+	}
+	let structure = inspect(&bytes)?;
+
+	let mut insertions: Vec<(usize, &[u8])> = extra.iter()
+		.map(|section| {
+			let position = match &section.after {
+				None => 8,
+				Some(magic) => structure.section(magic).map(|after| after.data_offset() + after.size as usize).unwrap_or(bytes.len())
+			};
+			(position, section.bytes.as_slice())
+		})
+		.collect();
+	// Sections inserted at the same place keep the order they were in
+	insertions.sort_by_key(|(position, _)| *position);
+	let shift = |offset: usize| -> usize {
+		offset + insertions.iter().filter(|(position, _)| *position <= offset).map(|(_, section)| section.len()).sum::<usize>()
+	};
+
+	let mut output = Vec::with_capacity(bytes.len() + extra.iter().map(|section| section.bytes.len()).sum::<usize>());
+	let mut copied = 0;
+	for (position, section) in &insertions {
+		output.extend_from_slice(&bytes[copied..*position]);
+		output.extend_from_slice(section);
+		copied = *position
+	}
+	output.extend_from_slice(&bytes[copied..]);
+
+	// These offsets are from the start of the file, so everything after an inserted section moved
+	let count = structure.count.unwrap_or(0) as usize;
+	for (magic, values_per_entry) in [("NMOF", 1), ("ADOF", 2)] {
+		let section = match structure.section(magic) {
+			Some(section) => section,
+			None => continue
+		};
+		// Only the offsets move, not the sizes after them in ADOF
+		for index in (0..count * values_per_entry).step_by(values_per_entry) {
+			let at = section.data_offset() + index * 4;
+			let offset = match read_u32_le(&bytes, at) {
+				Some(offset) => offset,
+				None => break
+			};
+			let moved = u32::try_from(shift(offset as usize)).map_err(|_| "The file would be too large with its extra sections.".to_owned())?;
+			let new_at = shift(at);
+			output[new_at..new_at + 4].copy_from_slice(&moved.to_le_bytes())
+		}
+	}
+
+	let size = u32::try_from(output.len() - 8).map_err(|_| "The file would be too large with its extra sections.".to_owned())?;
+	output[4..8].copy_from_slice(&size.to_le_bytes());
+	Ok(output)
+}
+
+/// Salvage whatever audio can be found in the damaged nus3audio file in `bytes`.
+/// 
+/// The item tables are used for the items they still point at audio with.
+/// The rest of the file is searched for IDSP and LOPUS headers too, so items
+/// a damaged table entry lost aren't dropped, and each one found runs up to
+/// the next header or table item. Names and tone IDs are taken from the tables
+/// when none of their items could be used but there are as many of them as items found.
+pub fn recover(bytes: &[u8]) -> Nus3audioFile {
+	let mut nus3audio = Nus3audioFile::new();
+	let entries = inspect(bytes).map(|structure| structure.entries).unwrap_or_default();
+
+	// The tables may still be right even if something else isn't
+	let mut covered: Vec<(usize, usize)> = Vec::new();
+	for (index, entry) in entries.iter().enumerate() {
+		let data = match (entry.data_offset, entry.data_size) {
+			(Some(offset), Some(size)) => bytes.get(offset as usize..offset as usize + size as usize).map(|data| (offset as usize, data)),
+			_ => None
+		};
+		if let Some((offset, data)) = data.filter(|(_, data)| NativeHeader::parse(data).is_ok()) {
+			covered.push((offset, offset + data.len()));
+			nus3audio.files.push(AudioFile {
+				id: entry.id.unwrap_or(index as u32),
+				name: entry.name.clone().unwrap_or_else(|| format!("recovered_{}", index)),
+				data: data.to_vec()
+			})
+		}
+	}
+
+	// Find the audio the tables don't point at by its headers. Bare Switch Opus
+	// headers are skipped, since every LOPUS item has one inside its Namco header
+	let starts: Vec<usize> = (0..bytes.len().saturating_sub(4))
+		.filter(|&offset| {
+			let magic = &bytes[offset..offset + 4];
+			(magic == b"IDSP" || magic == NAMCO_OPUS_MAGIC)
+				&& !covered.iter().any(|&(from, to)| (from..to).contains(&offset))
+				&& NativeHeader::parse(&bytes[offset..]).is_ok()
+		})
+		.collect();
+	let use_tables = covered.is_empty() && entries.len() == starts.len();
+	let first_id = nus3audio.files.iter().map(|file| file.id + 1).max().unwrap_or(0);
+	for (index, &start) in starts.iter().enumerate() {
+		let end = starts.get(index + 1).copied().into_iter()
+			.chain(covered.iter().map(|&(from, _)| from).filter(|&from| from > start))
+			.min()
+			.unwrap_or(bytes.len());
+		let entry = entries.get(index).filter(|_| use_tables);
+		let number = nus3audio.files.len();
+		nus3audio.files.push(AudioFile {
+			id: entry.and_then(|entry| entry.id).unwrap_or(first_id + index as u32),
+			name: entry.and_then(|entry| entry.name.clone()).unwrap_or_else(|| format!("recovered_{}", number)),
+			data: bytes[start..end].to_vec()
+		})
+	}
+	nus3audio
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Return a nus3audio file with two items, as the nus3audio crate writes it.
+	fn written() -> Vec<u8> {
+		let mut nus3audio = Nus3audioFile::new();
+		nus3audio.files.push(AudioFile { id: 0, name: "first".to_owned(), data: vec![1; 32] });
+		nus3audio.files.push(AudioFile { id: 1, name: "second".to_owned(), data: vec![2; 48] });
+		let mut bytes = Vec::new();
+		nus3audio.write(&mut bytes);
+		bytes
+	}
+
+	/// Return a section with `magic` holding `data`.
+	fn section(magic: &[u8; 4], data: &[u8]) -> Vec<u8> {
+		let mut bytes = magic.to_vec();
+		bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+		bytes.extend_from_slice(data);
+		bytes
+	}
+
+	#[test]
+	fn no_extra_sections() {
+		let bytes = written();
+		assert!(extra_sections(&bytes).is_empty());
+		assert_eq!(insert_sections(bytes.clone(), &[]).unwrap(), bytes)
+	}
+
+	#[test]
+	fn extra_section_before_pack() {
+		let bytes = written();
+		let before = inspect(&bytes).unwrap();
+		let extra = ExtraSection { after: Some("TNNM".to_owned()), bytes: section(b"EXTR", &[7; 8]) };
+
+		let with = insert_sections(bytes.clone(), &[extra.clone()]).unwrap();
+		assert_eq!(with.len(), bytes.len() + extra.bytes.len());
+		let after = inspect(&with).unwrap();
+		assert_eq!(after.problems, before.problems);
+
+		let tnnm = before.section("TNNM").unwrap();
+		assert_eq!(after.section("EXTR").unwrap().offset, tnnm.data_offset() + tnnm.size as usize);
+
+		// Every item still points at its own name and audio
+		assert_eq!(after.entries.len(), 2);
+		for (moved, entry) in after.entries.iter().zip(&before.entries) {
+			assert_eq!(moved.name, entry.name);
+			let (offset, size) = (entry.data_offset.unwrap() as usize, entry.data_size.unwrap() as usize);
+			let (moved_offset, moved_size) = (moved.data_offset.unwrap() as usize, moved.data_size.unwrap() as usize);
+			assert_eq!(moved_offset, offset + extra.bytes.len());
+			assert_eq!(&with[moved_offset..moved_offset + moved_size], &bytes[offset..offset + size])
+		}
+	}
+
+	#[test]
+	fn extra_sections_round_trip() {
+		let extra = [
+			ExtraSection { after: None, bytes: section(b"HEAD", &[1; 4]) },
+			ExtraSection { after: Some("TNNM".to_owned()), bytes: section(b"EXTR", &[7; 8]) }
+		];
+		let opened = insert_sections(written(), &extra).unwrap();
+
+		let kept = extra_sections(&opened);
+		assert_eq!(kept.len(), 2);
+		assert_eq!(kept[0].after, None);
+		assert_eq!(kept[1].after.as_deref(), Some("TNNM"));
+
+		// Saving without changes writes the same bytes as were opened
+		assert_eq!(insert_sections(written(), &kept).unwrap(), opened)
+	}
+
+	/// Return an IDSP file with a header for one channel and `fill` for audio.
+	fn idsp(fill: u8) -> Vec<u8> {
+		let mut bytes = b"IDSP".to_vec();
+		bytes.resize(0x80, fill);
+		bytes[0x08..0x0C].copy_from_slice(&1u32.to_be_bytes());
+		bytes[0x0C..0x10].copy_from_slice(&48_000u32.to_be_bytes());
+		bytes[0x10..0x14].copy_from_slice(&196u32.to_be_bytes());
+		bytes[0x14..0x1C].copy_from_slice(&[0; 8]);
+		bytes
+	}
+
+	#[test]
+	fn recover_partly_damaged_table() {
+		let mut nus3audio = Nus3audioFile::new();
+		for (id, name) in ["first", "second", "third"].iter().enumerate() {
+			nus3audio.files.push(AudioFile { id: id as u32, name: name.to_string(), data: idsp(id as u8 + 1) });
+		}
+		let mut bytes = Vec::new();
+		nus3audio.write(&mut bytes);
+
+		// Point the second item's table entry at nothing
+		let adof = inspect(&bytes).unwrap().section("ADOF").unwrap().data_offset();
+		bytes[adof + 8..adof + 12].copy_from_slice(&0u32.to_le_bytes());
+
+		let recovered = recover(&bytes);
+		assert_eq!(recovered.files.len(), 3);
+		assert_eq!(recovered.files[0].name, "first");
+		assert_eq!(recovered.files[1].name, "third");
+		// The lost item is still found by its header, with an ID of its own
+		assert!(recovered.files[2].data.starts_with(&idsp(2)));
+		assert!(!recovered.files[2].data.windows(4).skip(1).any(|window| window == b"IDSP"));
+		assert_eq!(recovered.files[2].id, 3)
+	}
+}
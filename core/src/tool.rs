@@ -0,0 +1,151 @@
+//! Running the external tools (VGAudioCli and vgmstream).
+//! 
+//! Wine and mono in particular have a habit of hanging forever,
+//! so every invocation goes through here to get a timeout.
+//! Output is also passed to the output hook as it arrives,
+//! so a frontend can show it live.
+
+use std::{
+	io::Read,
+	process::{ Command, Output, Stdio },
+	sync::Mutex,
+	thread,
+	time::{ Duration, Instant }
+};
+use lazy_static::lazy_static;
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+
+/// How long to wait between checks on whether the process has exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+lazy_static! {
+	/// Called with tool output as it arrives. May be called from any thread.
+	static ref OUTPUT_HOOK: Mutex<Option<fn(&str)>> = Mutex::new(None);
+	/// Called repeatedly on the calling thread while waiting for a tool to finish.
+	static ref POLL_HOOK: Mutex<Option<fn()>> = Mutex::new(None);
+}
+
+/// Set the function called with tool output (and the command lines run) as it arrives.
+pub fn set_output_hook(hook: fn(&str)) {
+	if let Ok(mut slot) = OUTPUT_HOOK.lock() {
+		slot.replace(hook);
+	}
+}
+
+/// Set the function called repeatedly while waiting for a tool to finish.
+pub fn set_poll_hook(hook: fn()) {
+	if let Ok(mut slot) = POLL_HOOK.lock() {
+		slot.replace(hook);
+	}
+}
+
+/// Pass `text` to the output hook, if there is one.
+fn echo(text: &str) {
+	let hook = OUTPUT_HOOK.lock().ok().and_then(|slot| *slot);
+	if let Some(hook) = hook {
+		hook(text)
+	}
+}
+
+/// Call the poll hook, if there is one.
+pub(crate) fn poll() {
+	let hook = POLL_HOOK.lock().ok().and_then(|slot| *slot);
+	if let Some(hook) = hook {
+		hook()
+	}
+}
+
+/// Run `command` to completion and collect its output, like [Command::output].
+/// 
+/// If `timeout` is not zero and the process runs longer than it,
+/// the process is killed and an error is returned. `name` is used for error messages.
+/// 
+/// stderr is always echoed to the output hook. stdout is only echoed if `echo_stdout`
+/// is set, since some invocations write audio there.
+pub fn output(command: &mut Command, name: &str, timeout: Duration, echo_stdout: bool) -> Result<Output, String> {
+	debug!("Running {:?}", command);
+	echo(&format!("$ {:?}\n", command));
+
+	let mut child = match command
+		.stdin(Stdio::null())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+	{
+		Ok(child) => child,
+		Err(error) => {
+			echo(&format!("[could not start {}: {}]\n", name, error));
+			poll();
+			return Err(format!("Error running {}\n{}", name, error))
+		}
+	};
+
+	// Read the pipes on their own threads so a chatty process can't fill them and block
+	let stdout_reader = child.stdout.take().map(|pipe| read_to_end_thread(pipe, echo_stdout));
+	let stderr_reader = child.stderr.take().map(|pipe| read_to_end_thread(pipe, true));
+
+	let start = Instant::now();
+	let status = loop {
+		match child.try_wait() {
+			Ok(Some(status)) => break Ok(status),
+			Ok(None) => {
+				if !timeout.is_zero() && start.elapsed() >= timeout {
+					warn!("{} timed out after {:?}, killing it", name, timeout);
+					let _ = child.kill();
+					let _ = child.wait();
+					echo(&format!("[{} killed after {} seconds]\n", name, timeout.as_secs()));
+					break Err(format!("{} did not finish within {} seconds and was stopped.\nIf this keeps happening, check that {} is configured correctly, or raise the tool timeout.", name, timeout.as_secs(), name))
+				}
+				poll();
+				thread::sleep(POLL_INTERVAL)
+			},
+			Err(error) => {
+				let _ = child.kill();
+				let _ = child.wait();
+				echo(&format!("[could not wait for {}: {}]\n", name, error));
+				break Err(format!("Error waiting for {}\n{}", name, error))
+			}
+		}
+	};
+
+	// The pipes close once the process is gone, which ends the reader threads
+	let stdout = stdout_reader.map(|reader| reader.join().unwrap_or_default()).unwrap_or_default();
+	let stderr = stderr_reader.map(|reader| reader.join().unwrap_or_default()).unwrap_or_default();
+	let status = match status {
+		Ok(status) => status,
+		Err(error) => {
+			poll();
+			return Err(error)
+		}
+	};
+
+	match status.code() {
+		Some(code) => echo(&format!("[{} exited with code {}]\n", name, code)),
+		None => echo(&format!("[{} exited without a code]\n", name))
+	}
+	poll();
+
+	Ok(Output { status, stdout, stderr })
+}
+
+/// Spawn a thread that reads `pipe` until it is closed,
+/// passing it to the output hook if `echo_output` is set.
+fn read_to_end_thread<R: Read + Send + 'static>(mut pipe: R, echo_output: bool) -> thread::JoinHandle<Vec<u8>> {
+	thread::spawn(move || {
+		let mut buffer = Vec::new();
+		let mut chunk = [0; 4096];
+		loop {
+			match pipe.read(&mut chunk) {
+				Ok(0) | Err(_) => break,
+				Ok(length) => {
+					if echo_output {
+						echo(&String::from_utf8_lossy(&chunk[..length]))
+					}
+					buffer.extend_from_slice(&chunk[..length])
+				}
+			}
+		}
+		buffer
+	})
+}
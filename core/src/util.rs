@@ -0,0 +1,363 @@
+/// Return a human-readable data size from a number of bytes.
+pub fn human_readable_size(bytes: u64) -> String {
+	if bytes < 1_000 {
+		format!("{} bytes", bytes)
+	} else {
+		let kilobytes = bytes as f64 / 1_000.0;
+		if kilobytes < 1_000.0 {
+			format!("{} KB", kilobytes)
+		} else {
+			let megabytes = kilobytes / 1_000.0;
+			// This is as high as we'll go for now.
+			format!("{} MB", megabytes)
+		}
+	}
+}
+
+/// Return a human-readable length of time, like `3:07` or `1:02:45`.
+pub fn human_readable_duration(duration: std::time::Duration) -> String {
+	let seconds = duration.as_secs();
+	if seconds < 3600 {
+		format!("{}:{:02}", seconds / 60, seconds % 60)
+	} else {
+		format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+	}
+}
+
+/// Return a 64-bit FNV-1a hash of `bytes`.
+/// 
+/// This is stable between runs and builds, unlike the hasher in std,
+/// so it can be used to name files that outlive a session.
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+	let mut hash: u64 = 0xcbf29ce484222325;
+	for byte in bytes {
+		hash ^= *byte as u64;
+		hash = hash.wrapping_mul(0x100000001b3)
+	}
+	hash
+}
+
+/// The longest file name (without extension) [safe_file_name] will return.
+const SAFE_FILE_NAME_MAX_LENGTH: usize = 64;
+
+/// Return a version of `name` that is safe to use as a file name on any platform.
+/// 
+/// Path separators and characters Windows doesn't allow are replaced. Names that
+/// would still be troublesome (empty, reserved on Windows, non-ASCII or very long)
+/// are replaced with a name derived from their hash instead.
+pub fn safe_file_name(name: &str) -> String {
+	let sanitized: String = name.chars()
+		.map(|c| match c {
+			'<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+			c if c.is_control() => '_',
+			c => c
+		})
+		.collect();
+	// Windows doesn't like trailing dots or spaces
+	let sanitized = sanitized.trim_end_matches(|c| c == '.' || c == ' ');
+
+	let stem = sanitized.split('.').next().unwrap_or("").to_uppercase();
+	let reserved = matches!(&stem[..], "CON" | "PRN" | "AUX" | "NUL")
+		|| ((stem.starts_with("COM") || stem.starts_with("LPT")) && stem.len() == 4 && stem.as_bytes()[3].is_ascii_digit());
+
+	if sanitized.is_empty() || reserved || !sanitized.is_ascii() || sanitized.len() > SAFE_FILE_NAME_MAX_LENGTH {
+		format!("item_{:016x}", hash_bytes(name.as_bytes()))
+	} else {
+		sanitized.to_owned()
+	}
+}
+
+/// What a file name template can refer to for one item, see [expand_name_template].
+pub struct NameFields<'a> {
+	/// The position of the item in its nus3audio, counting from 0.
+	pub index: usize,
+	/// The tone ID of the item, if it has one yet.
+	pub id: Option<u32>,
+	/// The name of the item.
+	pub name: &'a str,
+	/// The format of the item, like `idsp`.
+	pub format: &'a str,
+	/// The extension of the file being written, like `wav`.
+	pub ext: &'a str
+}
+
+/// Return the file name `template` gives the item described by `fields`.
+/// 
+/// Fields are written in braces, like `{index:03}_{id}_{name}.{ext}`. A width
+/// after a colon pads the field with spaces, or with zeros if it starts with 0.
+/// `{name}` is made safe with [safe_file_name], so every item stays in the same
+/// folder and the file can be written on any platform.
+/// An item without a tone ID has an empty `{id}`.
+pub fn expand_name_template(template: &str, fields: &NameFields) -> Result<String, String> {
+	let mut expanded = String::new();
+	let mut rest = template;
+
+	while let Some(start) = rest.find('{') {
+		expanded.push_str(&rest[..start]);
+		let end = rest[start..].find('}').ok_or_else(|| format!("A {{ in {:?} isn't closed.", template))? + start;
+		let field = &rest[start + 1..end];
+		rest = &rest[end + 1..];
+
+		let (key, width) = match field.split_once(':') {
+			Some((key, width)) => (key, Some(width)),
+			None => (field, None)
+		};
+		let value = match key {
+			"index" => fields.index.to_string(),
+			"id" => match fields.id {
+				Some(id) => id.to_string(),
+				None => continue
+			},
+			"name" => safe_file_name(fields.name),
+			"format" => fields.format.to_owned(),
+			"ext" => fields.ext.to_owned(),
+			_ => return Err(format!("{{{}}} isn't a field. Use {{index}}, {{id}}, {{name}}, {{format}} or {{ext}}.", key))
+		};
+		match width {
+			Some(width) => {
+				let padding = width.parse::<usize>().map_err(|_| format!("{:?} in {{{}}} isn't a width.", width, field))?;
+				let padding = padding.saturating_sub(value.chars().count());
+				let fill = if width.starts_with('0') { '0' } else { ' ' };
+				expanded.push_str(&fill.to_string().repeat(padding));
+				expanded.push_str(&value)
+			},
+			None => expanded.push_str(&value)
+		}
+	}
+	expanded.push_str(rest);
+
+	if expanded.trim().is_empty() {
+		return Err(format!("{:?} makes an empty file name.", template))
+	}
+	Ok(expanded)
+}
+
+/// Whether or not `template` gives every item of a nus3audio its own file name,
+/// by using its index, tone ID or name.
+pub fn name_template_is_unique(template: &str) -> bool {
+	["{index", "{id", "{name"].iter().any(|field| template.contains(field))
+}
+
+/// Return the paths of files in `text`, like a path copied from a file manager,
+/// or the text of files dropped on a window.
+/// 
+/// Each line is one path. `file://` URIs are decoded, and quotes around a path
+/// (as Windows adds with "Copy as path") are removed. Blank lines and comments
+/// in URI lists are skipped.
+pub fn paths_from_text(text: &str) -> Vec<std::path::PathBuf> {
+	text.lines()
+		.map(|line| line.trim().trim_matches('"'))
+		.filter(|line| !line.is_empty() && !line.starts_with('#'))
+		.map(|line| match line.strip_prefix("file://") {
+			// Drop the host, which is usually empty or localhost
+			Some(uri) => {
+				let path = if uri.starts_with('/') { uri } else { uri.find('/').map(|slash| &uri[slash..]).unwrap_or(uri) };
+				// Windows paths look like /C:/Users/...
+				let path = match path.as_bytes() {
+					[b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => &path[1..],
+					_ => path
+				};
+				std::path::PathBuf::from(percent_decode(path))
+			},
+			None => std::path::PathBuf::from(line)
+		})
+		.collect()
+}
+
+/// Paths at least this long are given the long path prefix by [long_path].
+/// Windows' limit is 260 characters, but directories have to leave room for
+/// an 8.3 file name in them.
+#[cfg(target_os = "windows")]
+const LONG_PATH: usize = 248;
+/// The prefix that lets Windows paths be longer than its usual limit.
+const VERBATIM_PREFIX: &str = "\\\\?\\";
+/// The prefix that lets network share paths be longer than Windows' usual limit.
+const VERBATIM_UNC_PREFIX: &str = "\\\\?\\UNC\\";
+
+/// Return `path` in a form Windows can use even if it's longer than its usual
+/// limit, by giving it the `\\?\` prefix (`\\?\UNC\` for network shares).
+/// 
+/// The prefix turns off the rest of Windows' path handling, so the path is
+/// made absolute and `.` and `..` are resolved first. Paths short enough not to
+/// need it are returned as they are, since not every tool understands it.
+/// Other platforms have no such limit, so this returns `path` as is there.
+pub fn long_path(path: &std::path::Path) -> std::path::PathBuf {
+	#[cfg(target_os = "windows")]
+	{
+		use std::path::{ Component, Prefix };
+
+		let absolute = if path.is_absolute() {
+			path.to_path_buf()
+		} else {
+			match std::env::current_dir() {
+				Ok(dir) => dir.join(path),
+				Err(_) => return path.to_path_buf()
+			}
+		};
+		if absolute.as_os_str().len() < LONG_PATH {
+			return path.to_path_buf()
+		}
+
+		let mut prefix = String::new();
+		let mut parts: Vec<std::ffi::OsString> = Vec::new();
+		for component in absolute.components() {
+			match component {
+				Component::Prefix(component_prefix) => match component_prefix.kind() {
+					Prefix::Disk(letter) => prefix = format!("{}{}:", VERBATIM_PREFIX, letter as char),
+					Prefix::UNC(server, share) => prefix = format!("{}{}\\{}", VERBATIM_UNC_PREFIX, server.to_string_lossy(), share.to_string_lossy()),
+					// Already verbatim, or a device, neither of which are changed
+					_ => return absolute
+				},
+				Component::RootDir | Component::CurDir => (),
+				Component::ParentDir => { parts.pop(); },
+				Component::Normal(part) => parts.push(part.to_os_string())
+			}
+		}
+		if prefix.is_empty() {
+			return absolute
+		}
+
+		let mut long = std::ffi::OsString::from(prefix);
+		for part in parts {
+			long.push("\\");
+			long.push(part)
+		}
+		std::path::PathBuf::from(long)
+	}
+	#[cfg(not(target_os = "windows"))]
+	{
+		path.to_path_buf()
+	}
+}
+
+/// Return the `file://` URI of `path`, which should be absolute, as files are
+/// given when dragged to another program. The reverse of [paths_from_text].
+pub fn file_uri(path: &std::path::Path) -> String {
+	// Other programs don't know the long path prefixes, see [long_path]
+	let path = path.to_string_lossy();
+	let path = match path.strip_prefix(VERBATIM_UNC_PREFIX) {
+		Some(share) => format!("\\\\{}", share),
+		None => path.strip_prefix(VERBATIM_PREFIX).unwrap_or(&path).to_owned()
+	};
+	let path = path.replace('\\', "/");
+	let mut uri = String::from("file://");
+	// Windows paths start with a drive letter rather than a slash
+	if !path.starts_with('/') {
+		uri.push('/')
+	}
+	for byte in path.bytes() {
+		match byte {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => uri.push(byte as char),
+			byte => uri.push_str(&format!("%{:02X}", byte))
+		}
+	}
+	uri
+}
+
+/// Decode the `%XX` escapes in `text`, as in a URI. Invalid escapes are kept as is.
+fn percent_decode(text: &str) -> String {
+	let bytes = text.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut index = 0;
+	while index < bytes.len() {
+		let escaped = match bytes.get(index + 1..index + 3) {
+			Some(hex) if bytes[index] == b'%' => std::str::from_utf8(hex).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+			_ => None
+		};
+		match escaped {
+			Some(byte) => {
+				decoded.push(byte);
+				index += 3
+			},
+			None => {
+				decoded.push(bytes[index]);
+				index += 1
+			}
+		}
+	}
+	String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Read a big-endian u32 at `offset`, if there are enough bytes.
+pub fn read_u32_be(bytes: &[u8], offset: usize) -> Option<u32> {
+	bytes.get(offset..offset + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Read a big-endian u16 at `offset`, if there are enough bytes.
+pub fn read_u16_be(bytes: &[u8], offset: usize) -> Option<u16> {
+	bytes.get(offset..offset + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+/// Read a little-endian u32 at `offset`, if there are enough bytes.
+pub fn read_u32_le(bytes: &[u8], offset: usize) -> Option<u32> {
+	bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sizes_and_durations() {
+		assert_eq!(human_readable_size(999), "999 bytes");
+		assert_eq!(human_readable_size(1_500), "1.5 KB");
+		assert_eq!(human_readable_size(2_000_000), "2 MB");
+		assert_eq!(human_readable_duration(std::time::Duration::from_secs(187)), "3:07");
+		assert_eq!(human_readable_duration(std::time::Duration::from_secs(3765)), "1:02:45");
+	}
+
+	#[test]
+	fn hash_is_fnv1a() {
+		assert_eq!(hash_bytes(b""), 0xcbf29ce484222325);
+		assert_eq!(hash_bytes(b"a"), 0xaf63dc4c8601ec8c);
+	}
+
+	#[test]
+	fn read_integers() {
+		let bytes = [0x12, 0x34, 0x56, 0x78, 0x9A];
+		assert_eq!(read_u32_be(&bytes, 0), Some(0x1234_5678));
+		assert_eq!(read_u32_le(&bytes, 1), Some(0x9A78_5634));
+		assert_eq!(read_u16_be(&bytes, 3), Some(0x789A));
+		assert_eq!(read_u32_le(&bytes, 2), None);
+		assert_eq!(read_u16_be(&bytes, 4), None)
+	}
+
+	#[test]
+	fn safe_file_names() {
+		assert_eq!(safe_file_name("a/b:c"), "a_b_c");
+		assert_eq!(safe_file_name("name. "), "name");
+		assert!(safe_file_name("CON").starts_with("item_"));
+		assert!(safe_file_name("com1.txt").starts_with("item_"));
+		assert_eq!(safe_file_name("COM10"), "COM10");
+		assert!(safe_file_name("").starts_with("item_"));
+		assert!(safe_file_name(&"a".repeat(65)).starts_with("item_"))
+	}
+
+	#[test]
+	fn name_templates() {
+		let fields = NameFields { index: 3, id: Some(7), name: "a/b", format: "idsp", ext: "wav" };
+		assert_eq!(expand_name_template("{index:03}_{id}_{name}.{ext}", &fields).unwrap(), "003_7_a_b.wav");
+		assert_eq!(expand_name_template("{format:6}|", &fields).unwrap(), "  idsp|");
+		assert!(expand_name_template("{bogus}", &fields).is_err());
+		assert!(expand_name_template("{index", &fields).is_err());
+		assert_eq!(expand_name_template("{name}", &NameFields { name: "a:b?", ..fields }).unwrap(), "a_b_");
+
+		let fields = NameFields { id: None, ..fields };
+		assert_eq!(expand_name_template("{id}x", &fields).unwrap(), "x");
+		assert!(expand_name_template("{id}", &fields).is_err());
+		assert!(name_template_is_unique("{index:03}.{ext}"));
+		assert!(!name_template_is_unique("{format}.{ext}"))
+	}
+
+	#[test]
+	fn paths_and_uris() {
+		let paths = paths_from_text("file:///home/user/a%20b.wav\n\"C:\\x.wav\"\n# comment\n\nfile:///C:/Users/c.wav");
+		assert_eq!(paths, vec![
+			std::path::PathBuf::from("/home/user/a b.wav"),
+			std::path::PathBuf::from("C:\\x.wav"),
+			std::path::PathBuf::from("C:/Users/c.wav")
+		]);
+		assert_eq!(file_uri(std::path::Path::new("/home/user/a b.wav")), "file:///home/user/a%20b.wav");
+		assert_eq!(percent_decode("100%"), "100%")
+	}
+}
@@ -0,0 +1,166 @@
+//! Dialogs for applying one operation across a folder of nus3audio files,
+//! and for converting a folder of audio files to IDSP or LOPUS.
+
+use std::path::PathBuf;
+use fltk::{
+	dialog::{ FileDialogType, NativeFileChooser },
+	window::Window
+};
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use simple_nus3audio_core::{
+	batch::{ self, ConvertOptions, Operation, Report },
+	AudioExtension,
+	Cancel
+};
+use crate::{
+	layout::{ alert, choice2, input },
+	progress::ProgressDialog,
+	settings::Settings
+};
+
+/// Ask for a directory with `title`, returning it if one was chosen.
+fn choose_dir(title: &str) -> Option<PathBuf> {
+	let mut dialog = NativeFileChooser::new(FileDialogType::BrowseDir);
+	dialog.set_title(title);
+	dialog.show();
+	let dir = dialog.filename();
+	if dir.is_dir() { Some(dir) } else { None }
+}
+
+/// Ask which operation to apply, and what to apply it to.
+fn choose_operation(window: &Window) -> Option<Operation> {
+	fltk::dialog::message_title("Batch process folder");
+	let kind = choice2(window, "What should be done to each nus3audio file?", "Replace item", "Export item", "Set loop")?;
+
+	fltk::dialog::message_title("Batch process folder");
+	let item = input(window, "Name of the item, without extension:", "")?;
+	let item = item.trim().to_owned();
+	if item.is_empty() {
+		return None
+	}
+
+	match kind {
+		0 => {
+			let mut dialog = NativeFileChooser::new(FileDialogType::BrowseFile);
+			dialog.set_filter(crate::AUDIO_FILES_DECODE_FILTER);
+			dialog.show();
+			let path = dialog.filename();
+			if path.is_file() { Some(Operation::Replace { item, path }) } else { None }
+		},
+		1 => choose_dir("Export to").map(|dir| Operation::Export { item, dir }),
+		_ => {
+			fltk::dialog::message_title("Batch process folder");
+			let loop_points = input(window, "Loop points in samples, like 1000-48000.\nLeave empty to remove the loop.", "")?;
+			let loop_points = match loop_points.trim() {
+				"" => None,
+				text => match text.split_once('-').map(|(start, end)| (start.trim().parse(), end.trim().parse())) {
+					Some((Ok(start), Ok(end))) => Some((start, end)),
+					_ => {
+						fltk::dialog::message_title("Error");
+						alert(window, &format!("{} are not valid loop points.", text));
+						return None
+					}
+				}
+			};
+			Some(Operation::SetLoop { item, loop_points })
+		}
+	}
+}
+
+/// Ask for a folder and an operation, and apply it to every nus3audio file in the folder.
+pub fn run_dialog(window: &Window, settings: &Settings) {
+	let dir = match choose_dir("Folder of nus3audio files") {
+		Some(dir) => dir,
+		None => return
+	};
+	let operation = match choose_operation(window) {
+		Some(operation) => operation,
+		None => return
+	};
+	let out_dir = match &operation {
+		Operation::Export { dir, .. } => dir.clone(),
+		_ => match choose_dir("Save changed files to") {
+			Some(out_dir) => out_dir,
+			None => return
+		}
+	};
+
+	let cancel = Cancel::new();
+	let mut progress: Option<ProgressDialog> = None;
+	let report = batch::run(&dir, &out_dir, &operation, settings, |number, total, path| {
+		crate::console::log(&format!("[{}] {:?}\n", number + 1, path));
+		crate::console::pump();
+		progress.get_or_insert_with(|| ProgressDialog::cancellable(window, "Batch process folder", total, &cancel))
+			.set(number, &format!("{} ({} of {})", path.file_name().unwrap_or_default().to_string_lossy(), number + 1, total))
+	}, &cancel);
+	if let Some(progress) = progress {
+		progress.close()
+	}
+
+	show_report(window, report, |report| format!("Applied to {} files, skipped {} without the item.", report.applied.len(), report.skipped.len()))
+}
+
+/// Ask for a folder of audio files, a format and where to put them, and convert them all.
+pub fn convert_dialog(window: &Window, settings: &Settings) {
+	let dir = match choose_dir("Folder of audio files") {
+		Some(dir) => dir,
+		None => return
+	};
+
+	fltk::dialog::message_title("Batch convert folder");
+	let format = match choice2(window, "Convert each audio file to which format?", "IDSP", "LOPUS", "Cancel") {
+		Some(0) => AudioExtension::Idsp,
+		Some(1) => AudioExtension::Lopus,
+		_ => return
+	};
+
+	fltk::dialog::message_title("Batch convert folder");
+	let loop_all = match choice2(window, "Files with loop points keep them.\nShould files without loop points loop from start to end?", "Don't loop", "Loop them", "Cancel") {
+		Some(0) => false,
+		Some(1) => true,
+		_ => return
+	};
+
+	let out_dir = match choose_dir("Write converted files to") {
+		Some(out_dir) => out_dir,
+		None => return
+	};
+
+	let options = ConvertOptions { format, loop_all };
+	let cancel = Cancel::new();
+	let mut progress: Option<ProgressDialog> = None;
+	let report = batch::convert(&dir, &out_dir, &options, settings, |number, total, path| {
+		crate::console::log(&format!("[{}] {:?}\n", number + 1, path));
+		crate::console::pump();
+		progress.get_or_insert_with(|| ProgressDialog::cancellable(window, "Batch convert folder", total, &cancel))
+			.set(number, &format!("{} ({} of {})", path.file_name().unwrap_or_default().to_string_lossy(), number + 1, total))
+	}, &cancel);
+	if let Some(progress) = progress {
+		progress.close()
+	}
+
+	show_report(window, report, |report| format!("Converted {} files.", report.applied.len()))
+}
+
+/// Show the outcome of a batch run, summarized by `summarize`, along with any errors.
+fn show_report(window: &Window, report: Result<Report, String>, summarize: impl Fn(&Report) -> String) {
+	match report {
+		Ok(report) => {
+			let mut summary = summarize(&report);
+			if report.cancelled {
+				summary = format!("Cancelled. {}", summary)
+			}
+			for (name, error) in &report.errors {
+				summary.push_str(&format!("\n\n{}:\n{}", name, error))
+			}
+			let title = if report.cancelled { "Cancelled" } else if report.errors.is_empty() { "Done" } else { "Finished with errors" };
+			fltk::dialog::message_title(title);
+			alert(window, &summary)
+		},
+		Err(error) => {
+			fltk::dialog::message_title("Error");
+			alert(window, &error)
+		}
+	}
+}
@@ -0,0 +1,146 @@
+//! Command-line options, and subcommands that run without opening a window.
+//! 
+//! `--log-level <level>` sets how much is logged, in place of `RUST_LOG`.
+//! `--log-file <path>` logs to a file instead of stderr, and `--config <path>`
+//! reads and writes the settings at `path` instead of the usual place. These
+//! come before any subcommand.
+//! 
+//! `convert <folder> <output folder> <idsp|lopus> [--loop-all]` converts every
+//! audio file in a folder, like Tools → Batch convert folder.
+
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use std::{
+	fs::File,
+	path::PathBuf,
+	str::FromStr
+};
+use simple_nus3audio_core::{
+	batch::{ self, ConvertOptions },
+	AudioExtension,
+	Cancel
+};
+use crate::settings::Settings;
+
+/// Usage of the options read by [Options::parse].
+const OPTIONS_USAGE: &str = "Usage: simple-nus3audio-gui [--log-level <off|error|warn|info|debug|trace>] [--log-file <path>] [--config <path>] [subcommand]";
+/// What is logged without `--log-level` or `RUST_LOG`.
+const DEFAULT_LOG_FILTER: &str = "error,simple_nus3audio_gui=warn";
+/// Usage of the convert subcommand.
+const CONVERT_USAGE: &str = "Usage: simple-nus3audio-gui convert <folder> <output folder> <idsp|lopus> [--loop-all]";
+
+/// Options given before any subcommand, see [Options::parse].
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+	/// How much this program logs, if not the default.
+	pub log_level: Option<log::LevelFilter>,
+	/// The file to log to instead of stderr.
+	pub log_file: Option<PathBuf>,
+	/// The settings file to use instead of the usual one.
+	pub config: Option<PathBuf>
+}
+
+impl Options {
+	/// Take the options out of `args` (without the program name), leaving the
+	/// subcommand and its arguments.
+	/// 
+	/// Returns the usage as the error if an option is wrong.
+	pub fn parse(args: &mut Vec<String>) -> Result<Self, String> {
+		let mut options = Self::default();
+		while let Some(option) = args.first().cloned() {
+			let value = match option.as_str() {
+				"--log-level" | "--log-file" | "--config" => match args.get(1) {
+					Some(value) => value.clone(),
+					None => return Err(format!("{} needs a value\n{}", option, OPTIONS_USAGE))
+				},
+				_ => break
+			};
+			match option.as_str() {
+				"--log-level" => options.log_level = Some(log::LevelFilter::from_str(&value)
+					.map_err(|_| format!("{} is not a log level\n{}", value, OPTIONS_USAGE))?),
+				"--log-file" => options.log_file = Some(PathBuf::from(value)),
+				_ => options.config = Some(PathBuf::from(value))
+			}
+			args.drain(..2);
+		}
+		Ok(options)
+	}
+
+	/// Start logging as these options say.
+	/// 
+	/// Without a log level, `RUST_LOG` is used as usual. A log level applies to
+	/// this program and its core, other crates only log errors.
+	pub fn init_logging(&self) -> Result<(), String> {
+		let mut builder = match self.log_level {
+			Some(level) => {
+				let mut builder = env_logger::Builder::new();
+				builder.parse_filters(&format!("error,simple_nus3audio_gui={level},simple_nus3audio_core={level}", level = level));
+				builder
+			},
+			None => env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(DEFAULT_LOG_FILTER))
+		};
+		if let Some(path) = &self.log_file {
+			let file = File::create(path).map_err(|error| format!("Error creating the log file {:?}:\n{}", path, error))?;
+			builder.target(env_logger::Target::Pipe(Box::new(file)));
+		}
+		builder.init();
+		Ok(())
+	}
+}
+
+/// Run the subcommand in `args` (without the program name), if there is one.
+/// 
+/// Returns the exit code, or None if the window should be opened as usual.
+pub fn run(args: &[String]) -> Option<i32> {
+	match args.first().map(|arg| arg.as_str()) {
+		Some("convert") => Some(convert(&args[1..])),
+		_ => None
+	}
+}
+
+/// Run the convert subcommand with `args`.
+fn convert(args: &[String]) -> i32 {
+	let loop_all = args.iter().any(|arg| arg == "--loop-all");
+	let positional: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+
+	let (dir, out_dir, format) = match &positional[..] {
+		[dir, out_dir, format] => (PathBuf::from(dir), PathBuf::from(out_dir), format.to_lowercase()),
+		_ => {
+			eprintln!("{}", CONVERT_USAGE);
+			return 2
+		}
+	};
+	let format = match format.parse::<AudioExtension>() {
+		Ok(AudioExtension::Bin) | Err(_) => {
+			eprintln!("{} is not idsp or lopus\n{}", format, CONVERT_USAGE);
+			return 2
+		},
+		Ok(format) => format
+	};
+
+	let settings = Settings::new_default();
+	// Don't reset the cache here, a window may be using it
+	if let Err(error) = Settings::create_cache() {
+		eprintln!("Error creating the cache directory:\n{}", error);
+		return 1
+	}
+
+	let options = ConvertOptions { format, loop_all };
+	let report = batch::convert(&dir, &out_dir, &options, &settings, |number, _, path| {
+		println!("[{}] {:?}", number + 1, path)
+	}, &Cancel::new());
+
+	match report {
+		Ok(report) => {
+			println!("Converted {} files.", report.applied.len());
+			for (name, error) in &report.errors {
+				eprintln!("{}:\n{}", name, error)
+			}
+			if report.errors.is_empty() { 0 } else { 1 }
+		},
+		Err(error) => {
+			eprintln!("{}", error);
+			1
+		}
+	}
+}
@@ -0,0 +1,94 @@
+//! A window showing the output of the external tools as they run.
+//! 
+//! Output can be logged from any thread, but is only moved into
+//! the window's buffer on the main thread by [pump].
+
+use std::{
+	cell::RefCell,
+	sync::Mutex
+};
+use fltk::{
+	prelude::*,
+	app,
+	enums::Font,
+	text::{ TextBuffer, TextDisplay },
+	window::Window
+};
+use lazy_static::lazy_static;
+
+/// The most text the console will hold before trimming the oldest lines.
+const MAX_LENGTH: i32 = 1_000_000;
+
+lazy_static! {
+	/// Text waiting to be moved into the console buffer.
+	static ref PENDING: Mutex<String> = Mutex::new(String::new());
+}
+
+thread_local! {
+	/// The console buffer. Only set on the main thread.
+	static BUFFER: RefCell<Option<(TextBuffer, TextDisplay)>> = RefCell::new(None);
+}
+
+/// Append `text` to the console. Can be called from any thread.
+pub fn log(text: &str) {
+	if let Ok(mut pending) = PENDING.lock() {
+		pending.push_str(text)
+	}
+}
+
+/// Move any pending text into the console window, and redraw it.
+/// 
+/// Does nothing if not called from the main thread.
+pub fn pump() {
+	BUFFER.with(|buffer| {
+		// Other threads, like those decoding items, leave the text for the main thread
+		if let Some((buffer, display)) = buffer.borrow_mut().as_mut() {
+			let text = match PENDING.lock() {
+				Ok(mut pending) if !pending.is_empty() => std::mem::take(&mut *pending),
+				_ => return
+			};
+			buffer.append(&text);
+			// Trim the beginning if this has gotten too long
+			if buffer.length() > MAX_LENGTH {
+				let cut = buffer.line_start(buffer.length() - MAX_LENGTH);
+				buffer.remove(0, cut)
+			}
+			let lines = display.count_lines(0, buffer.length(), true);
+			display.scroll(lines, 0);
+			app::flush()
+		}
+	})
+}
+
+/// The tool console window.
+pub struct Console {
+	window: Window
+}
+
+impl Console {
+	/// Create the (hidden) console window.
+	pub fn new() -> Self {
+		let mut window = Window::new(0, 0, 600, 300, "Tool Console");
+		window.make_resizable(true);
+		let buffer = TextBuffer::default();
+		let mut display = TextDisplay::new(0, 0, 600, 300, "");
+		display.set_buffer(buffer.clone());
+		display.set_text_font(Font::Courier);
+		display.set_tooltip("Output of VGAudioCli and vgmstream");
+		window.resizable(&display);
+		window.end();
+
+		BUFFER.with(|cell| cell.replace(Some((buffer, display))));
+
+		Self { window }
+	}
+
+	/// Show the console window near `parent`.
+	pub fn show(&mut self, parent: &Window) {
+		if !self.window.shown() {
+			self.window.set_pos(crate::layout::get_x(parent), crate::layout::get_y(parent))
+		}
+		self.window.show();
+		pump()
+	}
+}
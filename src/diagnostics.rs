@@ -0,0 +1,117 @@
+//! A report of the environment the program runs in, like the versions of the
+//! external tools and whether audio can be played, to paste into bug reports.
+
+use std::{
+	path::Path,
+	process::Command
+};
+use fltk::{
+	prelude::*,
+	app,
+	button::Button,
+	enums::Font,
+	text::{ TextBuffer, TextDisplay },
+	window::Window
+};
+use crate::{
+	layout::{ MARGIN, RADIO_HEIGHT },
+	settings::{ self, Settings, CACHEDIR, CONFIG }
+};
+
+/// Width of the diagnostics window.
+const WIDTH: i32 = 600;
+/// Height of the diagnostics window.
+const HEIGHT: i32 = 400;
+/// Width of each button.
+const BUTTON_WIDTH: i32 = 110;
+
+/// Describe `path` as a tool path, with where it came from and whether it exists.
+fn describe_path(path: &str, key: &str) -> String {
+	if path.is_empty() {
+		return "(not set)".to_owned()
+	}
+	let source = match settings::env_override(key) {
+		Some((variable, _)) => format!(", from {}", variable),
+		None => String::new()
+	};
+	// Bare names like "mono" are looked up in the PATH, so only check actual paths
+	let found = if Path::new(path).components().count() > 1 && !Path::new(path).exists() { ", not found" } else { "" };
+	format!("{}{}{}", path, source, found)
+}
+
+/// Return the first line `runtime --version` prints, or why it couldn't be run.
+fn runtime_version(runtime: &str, settings: &Settings) -> String {
+	let mut command = Command::new(runtime);
+	command.arg("--version");
+	match simple_nus3audio_core::tool::output(&mut command, runtime, settings.tool_timeout(), true) {
+		Ok(output) => {
+			let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+			text.lines().find(|line| !line.trim().is_empty()).unwrap_or("(printed nothing)").trim().to_owned()
+		},
+		Err(error) => format!("Error: {}", error.lines().next().unwrap_or_default())
+	}
+}
+
+/// Collect the diagnostics report. `audio` is the state of the audio device,
+/// see [Playback::audio_status](crate::playback::Playback::audio_status).
+/// 
+/// This runs the external tools, so it can take as long as their timeout.
+pub fn report(settings: &Settings, audio: &str) -> String {
+	let mut lines = vec![
+		format!("{} version {}", crate::NAME, env!("CARGO_PKG_VERSION")),
+		format!("OS: {} ({}, {})", std::env::consts::OS, std::env::consts::FAMILY, std::env::consts::ARCH),
+		format!("Settings: {:?}", CONFIG.as_path()),
+		format!("Cache: {:?}{}", CACHEDIR.as_path(), if settings.keep_cache() { ", kept between sessions" } else { "" }),
+		format!("Tool timeout: {} seconds", settings.tool_timeout().as_secs()),
+		String::new()
+	];
+
+	lines.push(format!("VGAudioCli: {}", describe_path(settings.vgaudio_cli_path(), settings::VGAUDIO_CLI_PATH)));
+	let runtime = settings.vgaudio_cli_prepath();
+	if !runtime.is_empty() {
+		lines.push(format!("Runtime: {}", describe_path(runtime, settings::VGAUDIO_CLI_PREPATH)));
+		lines.push(format!("    {}", runtime_version(runtime, settings)));
+	}
+	lines.push(format!("    {}", crate::setup::test_vgaudio_cli(settings).unwrap_or_else(|error| format!("Error: {}", error))));
+	lines.push(format!("vgmstream: {}", describe_path(settings.vgmstream_path(), settings::VGMSTREAM_PATH)));
+	lines.push(format!("    {}", crate::setup::test_vgmstream(settings).unwrap_or_else(|error| format!("Error: {}", error))));
+	lines.push(String::new());
+
+	lines.push(format!("Audio: {}", audio));
+	lines.join("\n")
+}
+
+/// Show `report` near `parent`, with a button to copy it.
+pub fn show(report: &str, parent: &Window) {
+	let mut window = Window::new(crate::layout::get_x(parent), crate::layout::get_y(parent), WIDTH, HEIGHT, "Diagnostics");
+	window.make_resizable(true);
+	window.make_modal(true);
+
+	let mut buffer = TextBuffer::default();
+	buffer.set_text(report);
+	let mut display = TextDisplay::new(MARGIN, MARGIN, WIDTH - MARGIN * 2, HEIGHT - RADIO_HEIGHT - MARGIN * 3, "");
+	display.set_buffer(buffer);
+	display.set_text_font(Font::Courier);
+	display.set_tooltip("Include this when reporting a problem");
+
+	let y = HEIGHT - RADIO_HEIGHT - MARGIN;
+	let mut copy_button = Button::new(WIDTH - (MARGIN + BUTTON_WIDTH) * 2, y, BUTTON_WIDTH, RADIO_HEIGHT, "Copy");
+	copy_button.set_tooltip("Copy the whole report to the clipboard");
+	copy_button.set_callback({
+		let report = report.to_owned();
+		move |_| app::copy(&report)
+	});
+	let mut close_button = Button::new(WIDTH - MARGIN - BUTTON_WIDTH, y, BUTTON_WIDTH, RADIO_HEIGHT, "Close");
+	close_button.set_callback({
+		let mut window = window.clone();
+		move |_| window.hide()
+	});
+
+	window.resizable(&display);
+	window.end();
+	window.show();
+
+	while window.shown() {
+		app::wait();
+	}
+}
@@ -0,0 +1,103 @@
+//! Dialogs for exporting audio out of the working nus3audio.
+
+use std::fs;
+use fltk::{
+	dialog::{ FileDialogType, NativeFileChooser },
+	window::Window
+};
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use simple_nus3audio_core::{
+	reel::{ self, ReelOptions },
+	util::{ expand_name_template, name_template_is_unique, NameFields },
+	Archive,
+	ExportRange
+};
+use crate::{
+	layout::{ alert, choice2, input },
+	settings::Settings
+};
+
+/// Ask how much of looping audio should be exported.
+/// 
+/// Returns None if the dialog was closed.
+pub fn choose_range(window: &Window) -> Option<ExportRange> {
+	fltk::dialog::message_title("Export");
+	match choice2(window, "How much of looping audio should be exported?", "Full file", "Loop region", "Up to loop end")? {
+		0 => Some(ExportRange::Full),
+		1 => Some(ExportRange::LoopRegion),
+		_ => Some(ExportRange::UpToLoopEnd)
+	}
+}
+
+/// Ask how each exported item should be named, starting from the last template
+/// used, and remember the answer in `settings`.
+/// 
+/// Returns None if the dialog was closed.
+pub fn choose_name_template(window: &Window, settings: &mut Settings) -> Option<String> {
+	let mut template = settings.export_name_template().to_owned();
+	loop {
+		fltk::dialog::message_title("Export");
+		template = input(window, "File name of each item.\nFields are {index}, {id}, {name}, {format} and {ext}, and {index:03} pads with zeros:", &template)?
+			.trim().to_owned();
+
+		// Try it on a made up item, to catch mistakes before anything is written
+		let example = NameFields { index: 0, id: Some(0), name: "example", format: "idsp", ext: "wav" };
+		let error = match expand_name_template(&template, &example) {
+			Ok(_) if !name_template_is_unique(&template) => "Every item would get the same file name. Use {index}, {id} or {name}.".to_owned(),
+			Ok(_) => break,
+			Err(error) => error
+		};
+		fltk::dialog::message_title("Error");
+		alert(window, &error)
+	}
+
+	settings.set_export_name_template(&template);
+	Some(template)
+}
+
+/// Ask what should go between items and where to save it, and export every
+/// item of `archive` back-to-back as one WAV file.
+pub fn preview_reel_dialog(window: &Window, archive: &Archive) {
+	fltk::dialog::message_title("Export preview reel");
+	let options = match choice2(window, "What should go between items?", "Nothing", "Gaps", "Gaps and beeps") {
+		Some(0) => ReelOptions { gaps: false, beeps: false },
+		Some(1) => ReelOptions { gaps: true, beeps: false },
+		Some(_) => ReelOptions { gaps: true, beeps: true },
+		None => return
+	};
+
+	let mut save_dialog = NativeFileChooser::new(FileDialogType::BrowseSaveFile);
+	save_dialog.set_filter("WAV files\t*.wav");
+	let _ = save_dialog.set_preset_file(&format!("{}.wav", archive.name.trim_end_matches(".nus3audio")));
+	save_dialog.show();
+
+	if save_dialog.filename().to_string_lossy().is_empty() {
+		return
+	}
+	let target = save_dialog.filename().with_extension("wav");
+
+	let (wav, skipped) = match reel::render(archive, options) {
+		Ok(rendered) => rendered,
+		Err(error) => {
+			error!("{}", error);
+			fltk::dialog::message_title("Error");
+			alert(window, &error);
+			return
+		}
+	};
+
+	info!("Exporting preview reel to {:?}", target);
+	if let Err(error) = fs::write(&target, wav) {
+		error!("{}", error);
+		fltk::dialog::message_title("Error");
+		alert(window, &format!("Error writing file:\n{}", error));
+		return
+	}
+
+	if !skipped.is_empty() {
+		let skipped: Vec<String> = skipped.into_iter().map(|(name, error)| format!("{}: {}", name, error)).collect();
+		fltk::dialog::message_title("Warning");
+		alert(window, &format!("The following items were left out:\n{}", skipped.join("\n")))
+	}
+}
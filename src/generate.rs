@@ -0,0 +1,141 @@
+//! Dialogs for generating audio to put in items, like silence for
+//! muting vanilla sounds or reserving slots, and test tones for finding
+//! which in-game event plays which slot.
+
+use fltk::window::Window;
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use simple_nus3audio_core::codec::{ self, EncodingType };
+use crate::{
+	layout::{ alert, choice2, input },
+	list::{ List, ListItem },
+	playback::Playback
+};
+
+/// Sample rate suggested for generated audio.
+const SAMPLE_RATE_DEFAULT: &str = "48000";
+
+/// Where generated audio should go.
+pub enum Target {
+	/// Into the selected item.
+	Selected,
+	/// Into this many new items.
+	New (usize)
+}
+
+/// Ask for a value with `message`, parsing it as `T`.
+/// 
+/// Shows an error and returns None if it can't be parsed.
+fn parse_input<T: std::str::FromStr>(window: &Window, title: &str, message: &str, default: &str) -> Option<T> {
+	fltk::dialog::message_title(title);
+	let text = input(window, message, default)?;
+	match text.trim().parse() {
+		Ok(value) => Some(value),
+		Err(_) => {
+			fltk::dialog::message_title("Error");
+			alert(window, &format!("{} is not a valid value.", text.trim()));
+			None
+		}
+	}
+}
+
+/// Ask whether generated audio should fill the selected item or new ones.
+fn choose_target(window: &Window, title: &str, has_selection: bool) -> Option<Target> {
+	let fill = if has_selection {
+		fltk::dialog::message_title(title);
+		match choice2(window, "Put the generated audio in the selected item, or in new items?", "Selected item", "New items", "Cancel") {
+			Some(0) => true,
+			Some(1) => false,
+			_ => return None
+		}
+	} else { false };
+
+	if fill {
+		return Some(Target::Selected)
+	}
+
+	match parse_input::<usize>(window, title, "How many new items?", "1")? {
+		0 => None,
+		count => Some(Target::New(count))
+	}
+}
+
+/// Show `generated` as an error if it is one, or return its value.
+fn or_alert<T>(window: &Window, generated: Result<T, String>) -> Option<T> {
+	match generated {
+		Ok(value) => Some(value),
+		Err(error) => {
+			fltk::dialog::message_title("Error");
+			alert(window, &error);
+			None
+		}
+	}
+}
+
+/// Ask for a duration in seconds and a sample rate, see [codec::generated_length].
+fn choose_length(window: &Window, title: &str) -> Option<(f64, u32)> {
+	let seconds: f64 = parse_input(window, title, "Duration in seconds:", "1.0")?;
+	or_alert(window, codec::generated_length(seconds, 1))?;
+	let sample_rate: u32 = parse_input(window, title, "Sample rate:", SAMPLE_RATE_DEFAULT)?;
+	or_alert(window, codec::generated_length(seconds, sample_rate))?;
+	Some((seconds, sample_rate))
+}
+
+/// Ask where silence should go and how long it should be, and return it as a WAV file.
+pub fn silence_dialog(window: &Window, has_selection: bool) -> Option<(Target, Vec<u8>)> {
+	const TITLE: &str = "Generate silence";
+	let target = choose_target(window, TITLE, has_selection)?;
+	let (seconds, sample_rate) = choose_length(window, TITLE)?;
+	let wav = or_alert(window, codec::silence_wav(seconds, sample_rate))?;
+	Some((target, wav))
+}
+
+/// Ask where a test tone should go and what it should sound like, and return it as a WAV file.
+pub fn tone_dialog(window: &Window, has_selection: bool) -> Option<(Target, Vec<u8>)> {
+	const TITLE: &str = "Generate tone";
+	let target = choose_target(window, TITLE, has_selection)?;
+
+	fltk::dialog::message_title(TITLE);
+	let sine = match choice2(window, "What kind of tone?", "Sine", "Noise", "Cancel") {
+		Some(0) => true,
+		Some(1) => false,
+		_ => return None
+	};
+	let frequency: Option<f64> = if sine {
+		let frequency: f64 = parse_input(window, TITLE, "Frequency in Hz:", "440")?;
+		if !frequency.is_finite() || frequency <= 0.0 {
+			fltk::dialog::message_title("Error");
+			alert(window, "The frequency must be more than zero.");
+			return None
+		}
+		Some(frequency)
+	} else { None };
+
+	let (seconds, sample_rate) = choose_length(window, TITLE)?;
+	let wav = or_alert(window, match frequency {
+		Some(frequency) => codec::sine_wav(frequency, seconds, sample_rate),
+		None => codec::noise_wav(seconds, sample_rate)
+	})?;
+	Some((target, wav))
+}
+
+/// Put the generated `wav` where `target` says. New items are named `prefix`
+/// followed by their number.
+/// 
+/// If it doesn't fail, marks the list as being modified.
+pub fn apply(file_list: &mut List, playback: &mut Playback, target: Target, wav: Vec<u8>, prefix: &str) -> Result<(), String> {
+	match target {
+		Target::Selected => {
+			let (index, _) = file_list.selected().ok_or_else(|| "Nothing is selected.".to_owned())?;
+			file_list.replace_with(index, playback, |archive| archive.set_audio(index, wav, EncodingType::WAV))?
+		},
+		Target::New(count) => for _ in 0..count {
+			let name = format!("{}_{}", prefix, file_list.archive.items.len() + 1);
+			let mut item = ListItem::new(name.clone());
+			item.set_audio_from_bytes(wav.clone(), EncodingType::WAV)?;
+			file_list.add_item(item, &format!("{}.idsp", name));
+			file_list.update_label_of(file_list.archive.items.len() - 1)
+		}
+	}
+	Ok(())
+}
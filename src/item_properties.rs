@@ -6,15 +6,22 @@ use fltk::{
 		RadioRoundButton,
 		CheckButton
 	},
-	enums::Event,
+	enums::{ Align, CallbackTrigger, Event },
+	frame::Frame,
+	output::Output,
 	input::{
 		Input,
-		IntInput
+		IntInput,
+		MultilineInput
 	},
 	window::Window
 };
 #[allow(unused_imports)]
 use log::{ trace, debug, info, warn, error };
+use simple_nus3audio_core::{
+	item::MAX_SAMPLE_COUNT,
+	util::human_readable_size
+};
 use crate::{
 	list::{
 		AudioExtension,
@@ -23,7 +30,8 @@ use crate::{
 	layout::{
 		self,
 		alert
-	}
+	},
+	settings::Settings
 };
 
 #[derive(Clone)]
@@ -31,22 +39,46 @@ enum PropMessage {
 	ReLay,
 	BinRadio,
 	ToggleLoop,
+	Estimate,
 	Save
 }
 
+/// Show the estimated size of `item` encoded with the choices in the properties window.
+fn update_estimate(item: &ListItem, estimate_frame: &mut Frame, extension: AudioExtension, loop_end: Option<usize>, mono: bool) {
+	let estimate = item.estimate_size(&extension, loop_end, if mono { Some(1) } else { None });
+	let label = match estimate {
+		Some(size) => format!("About {} encoded", human_readable_size(size as u64)),
+		None => "No audio to estimate".to_owned()
+	};
+	estimate_frame.set_label(&label)
+}
+
 /// Configure a single item. Returns a boolean representing whether or not the item was modified.
-pub fn configure(item: &mut ListItem, parent: &Window) -> bool {
+/// 
+/// `notes` about the item are edited too, but they aren't part of the item.
+/// `source` is the file the item was last replaced from, if it's known.
+/// The size of the window is remembered in `settings`.
+pub fn configure(item: &mut ListItem, notes: &mut String, source: Option<&str>, parent: &Window, settings: &mut Settings) -> bool {
 	let (s, r) = app::channel();
 
-	let mut window = Window::new(parent.x(), parent.y(), 350, 125, Some("Properties"))
+	let (width, height) = settings.properties_window_size().unwrap_or((350, 325));
+	let mut window = Window::new(parent.x(), parent.y(), width.max(350), height.max(325), Some("Properties"))
 		.with_label(&format!("Properties of {}", &item.name));
 	window.make_resizable(true);
-	window.size_range(350, 170, 0, 0);
+	window.size_range(350, 325, 0, 0);
 
 	let mut name_input = Input::default();
 	name_input.set_tooltip("Unique name of the sound");
 	name_input.set_value(&item.name);
 
+	// Show where the audio came from
+	let mut source_output = Output::default();
+	source_output.set_tooltip("File this sound was last replaced from");
+	source_output.set_value(&match source {
+		Some(source) => format!("Replaced from {}", source),
+		None => "Not replaced from a file".to_owned()
+	});
+
 	// Create the two radio buttons for format
 	let mut idsp_radio = RadioRoundButton::default()
 		.with_label("IDSP format");
@@ -66,6 +98,17 @@ pub fn configure(item: &mut ListItem, parent: &Window) -> bool {
 	bin_radio.toggle(item.extension == AudioExtension::Bin);
 	bin_radio.emit(s.clone(), PropMessage::BinRadio);
 
+	// Create the downmix toggle, which shows how many channels there are
+	let channels = item.channels();
+	let mut downmix_toggle = CheckButton::default()
+		.with_label(&format!("Mix {} channels to mono", channels));
+	downmix_toggle.set_tooltip("Mix the channels of this sound into one, which halves the size of stereo sounds");
+	downmix_toggle.emit(s.clone(), PropMessage::Estimate);
+	if channels <= 1 || !item.has_audio() || item.extension == AudioExtension::Bin {
+		downmix_toggle.set_label(&format!("{} channel", channels));
+		downmix_toggle.deactivate()
+	}
+
 	// Create the loop toggle button
 	let mut loop_toggle = CheckButton::default()
 		.with_label("Loop audio");
@@ -81,6 +124,8 @@ pub fn configure(item: &mut ListItem, parent: &Window) -> bool {
 	let mut loop_to_input = IntInput::default()
 		.with_label("Loop to");
 	loop_to_input.set_tooltip("End of the loop in samples, when it reaches this point it loops back to the beginning of the loop");
+	loop_to_input.set_trigger(CallbackTrigger::Changed);
+	loop_to_input.emit(s.clone(), PropMessage::Estimate);
 
 	// Set the value of the loop things
 	if let Some((from, to)) = item.loop_points() {
@@ -93,6 +138,19 @@ pub fn configure(item: &mut ListItem, parent: &Window) -> bool {
 		loop_to_input.deactivate()
 	}
 
+	// Create the notes input
+	let mut notes_input = MultilineInput::default()
+		.with_label("Notes");
+	notes_input.set_align(Align::TopLeft);
+	notes_input.set_tooltip("Anything worth remembering about this sound, like where it came from.\nKept next to the nus3audio file, not in it");
+	notes_input.set_value(notes);
+
+	// Create the size estimate
+	let mut estimate_frame = Frame::default();
+	estimate_frame.set_align(Align::Left | Align::Inside);
+	estimate_frame.set_tooltip("Estimated size of this sound when saved with these properties. LOPUS sizes are a rough guess");
+	update_estimate(item, &mut estimate_frame, item.extension.clone(), item.loop_end(), false);
+
 	// Create the button to apply changes
 	let mut save_button = Button::default()
 		.with_label("Ok");
@@ -108,7 +166,7 @@ pub fn configure(item: &mut ListItem, parent: &Window) -> bool {
 	});
 
 	window.end();
-	layout::lay_prop_widgets(&mut window, &mut name_input, &mut idsp_radio, &mut lopus_radio,  &mut bin_radio, &mut loop_toggle, &mut loop_from_input, &mut loop_to_input, &mut save_button);
+	layout::lay_prop_widgets(&mut window, &mut name_input, &mut source_output, &mut idsp_radio, &mut lopus_radio,  &mut bin_radio, &mut downmix_toggle, &mut loop_toggle, &mut loop_from_input, &mut loop_to_input, &mut notes_input, &mut estimate_frame, &mut save_button);
 	window.show();
 
 	let mut apply = false;
@@ -118,7 +176,7 @@ pub fn configure(item: &mut ListItem, parent: &Window) -> bool {
 		app::wait();
 		if let Some(e) = r.recv() {
 			match e {
-				PropMessage::ReLay => layout::lay_prop_widgets(&mut window, &mut name_input, &mut idsp_radio, &mut lopus_radio, &mut bin_radio, &mut loop_toggle, &mut loop_from_input, &mut loop_to_input, &mut save_button),
+				PropMessage::ReLay => layout::lay_prop_widgets(&mut window, &mut name_input, &mut source_output, &mut idsp_radio, &mut lopus_radio, &mut bin_radio, &mut downmix_toggle, &mut loop_toggle, &mut loop_from_input, &mut loop_to_input, &mut notes_input, &mut estimate_frame, &mut save_button),
 				PropMessage::BinRadio => {
 					if bin_radio.is_toggled() {
 						loop_toggle.set_checked(false);
@@ -140,15 +198,21 @@ pub fn configure(item: &mut ListItem, parent: &Window) -> bool {
 						loop_to_input.set_value(&item.length_in_samples.to_string())
 					}
 				},
+				PropMessage::Estimate => {},
 				PropMessage::Save => {
-					// usize can't be signed
-					if loop_from_input.value().contains('-') || loop_to_input.value().contains('-') {
-						alert(&window, "Loop points must be positive.");
-						continue
-					}
 					if loop_toggle.is_checked() {
+						// Anything negative, empty or past what the headers can count fails to parse
+						let (loop_from, loop_to) = match (loop_from_input.value().parse::<u32>(), loop_to_input.value().parse::<u32>()) {
+							(Ok(from), Ok(to)) => (from, to),
+							_ => {
+								fltk::dialog::message_title("Error");
+								alert(&window, &format!("Loop points must be whole numbers of samples from 0 to {}.", MAX_SAMPLE_COUNT));
+								continue
+							}
+						};
 						// End can't be before beginning
-						if loop_from_input.value().parse::<usize>().unwrap_or(0) >= loop_to_input.value().parse().unwrap_or(0) {
+						if loop_from >= loop_to {
+							fltk::dialog::message_title("Error");
 							alert(&window, "Loop beginning must be placed before loop end.");
 							continue
 						}
@@ -157,11 +221,20 @@ pub fn configure(item: &mut ListItem, parent: &Window) -> bool {
 					window.hide()
 				}
 			}
+
+			let extension = if idsp_radio.is_toggled() { AudioExtension::Idsp }
+				else if lopus_radio.is_toggled() { AudioExtension::Lopus }
+				else { AudioExtension::Bin };
+			let loop_end = if loop_toggle.is_checked() { loop_to_input.value().parse().ok() } else { None };
+			update_estimate(item, &mut estimate_frame, extension, loop_end, downmix_toggle.is_checked())
 		}
 	}
 
+	settings.set_properties_window_size(window.width(), window.height());
+
 	// Window has been closed, so now apply the settings
 	if apply {
+		*notes = notes_input.value();
 		let new_name = name_input.value();
 		let new_extension = {
 			if idsp_radio.is_toggled() { AudioExtension::Idsp }
@@ -174,19 +247,30 @@ pub fn configure(item: &mut ListItem, parent: &Window) -> bool {
 			None
 		};
 
+		let downmixed = downmix_toggle.is_checked() && match item.downmix_to_mono() {
+			Ok(()) => true,
+			Err(error) => {
+				error!("{}", error);
+				fltk::dialog::message_title("Error");
+				alert(parent, &format!("Could not mix this sound to mono:\n{}", error));
+				false
+			}
+		};
+
 		trace!("Name changed? {}", new_name != item.name);
 		trace!("Extension changed? {}", new_extension != item.extension);
 		trace!("Loop points changed? {}", new_loop != *item.loop_points());
+		trace!("Mixed to mono? {}", downmixed);
 
-		if item.name == new_name && item.extension == new_extension && *item.loop_points() == new_loop {
+		if item.name == new_name && item.extension == new_extension && *item.loop_points() == new_loop && !downmixed {
 			false
 		} else {
 			if item.extension != new_extension || *item.loop_points() != new_loop {
 				item.clear_bytes();
 			}
 			item.name = new_name;
-			item.extension = new_extension;
 			item.loop_points_samples = new_loop;
+			item.set_extension(new_extension);
 			true
 		}
 	} else { false }
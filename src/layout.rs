@@ -8,20 +8,29 @@ use fltk::{
 	button::{
 		Button,
 		RadioRoundButton,
-		CheckButton
+		CheckButton,
+		ToggleButton
 	},
 	dialog::{
 		alert as fltk_alert,
 		choice2 as fltk_choice2,
 		input as fltk_input
 	},
-	menu::MenuBar,
+	menu::{
+		Choice,
+		MenuBar
+	},
 	input::{
 		Input,
-		IntInput
+		IntInput,
+		MultilineInput
 	},
+	frame::Frame,
+	misc::Progress,
+	output::Output,
+	group::Scroll,
 	// tree::Tree,
-	valuator::HorFillSlider,
+	valuator::{ Dial, HorFillSlider },
 	window::Window
 };
 
@@ -31,10 +40,12 @@ pub const MARGIN: i32 = 5;
 pub const MENUBAR_HEIGHT: i32 = 30;
 /// Height of radios, doesn't need to grow
 pub const RADIO_HEIGHT: i32 = 25;
+/// Width of the speed and pitch choices next to the slider
+pub const RATE_WIDTH: i32 = 70;
 
 /// Lays out widgets given the window size.
 #[allow(clippy::too_many_arguments)]
-pub fn lay_widgets(window: &mut Window, menu: &mut MenuBar, play: &mut Button, slider: &mut HorFillSlider, list: &mut Browser) {
+pub fn lay_widgets(window: &mut Window, menu: &mut MenuBar, toolbar: &mut [Button], play: &mut Button, loop_start: &mut Button, slider: &mut HorFillSlider, pan: &mut Dial, channels: &mut [ToggleButton], speed: &mut Choice, pitch: &mut Choice, list: &mut Browser, budget: &mut Progress) {
 	let window_width = window.width();
 	let window_height = window.height();
 
@@ -47,15 +58,50 @@ pub fn lay_widgets(window: &mut Window, menu: &mut MenuBar, play: &mut Button, s
 	menu.set_pos(0, 0);
 	menu.set_size(window_width, unallocated.y_consume(increment));
 
+	// The toolbar sits under the menu, if it's shown
+	if toolbar.first().map(|button| button.visible()).unwrap_or(false) {
+		let mut x = MARGIN;
+		for button in toolbar.iter_mut() {
+			button.set_pos(x, unallocated.y + MARGIN);
+			// Toolbar buttons are squares too
+			button.set_size(increment, increment);
+			x += increment + MARGIN
+		}
+		unallocated.y_bump(increment + MARGIN)
+	}
+
 	// Now we need the margin
 	play.set_pos(MARGIN, unallocated.y + MARGIN);
 	// Play button will always be a square
 	play.set_size(increment, increment);
-	// Place the slider next to the play button
-	slider.set_pos(MARGIN * 2 + increment, unallocated.y + MARGIN);
-	slider.set_size(window_width - MARGIN * 3 - increment, increment);
+	// So is the play from loop start button next to it
+	loop_start.set_pos(MARGIN * 2 + increment, unallocated.y + MARGIN);
+	loop_start.set_size(increment, increment);
+	// Place the slider next to the play buttons
+	slider.set_pos(MARGIN * 3 + increment * 2, unallocated.y + MARGIN);
+	let channels_width = (increment + MARGIN) * (channels.len() as i32 + 1);
+	slider.set_size(window_width - MARGIN * 6 - increment * 2 - channels_width - RATE_WIDTH * 2, increment);
+	// The pan dial, the channel toggles, then the speed and pitch, sit to the right of the slider
+	let mut x = slider.x() + slider.w() + MARGIN;
+	pan.set_pos(x, unallocated.y + MARGIN);
+	pan.set_size(increment, increment);
+	x += increment + MARGIN;
+	for toggle in channels.iter_mut() {
+		toggle.set_pos(x, unallocated.y + MARGIN);
+		toggle.set_size(increment, increment);
+		x += increment + MARGIN
+	}
+	speed.set_pos(x, unallocated.y + MARGIN);
+	speed.set_size(RATE_WIDTH, increment);
+	pitch.set_pos(speed.x() + RATE_WIDTH + MARGIN, unallocated.y + MARGIN);
+	pitch.set_size(RATE_WIDTH, increment);
 	unallocated.y_bump(increment + MARGIN);
 
+	// The budget bar sits at the bottom
+	budget.set_pos(MARGIN, window_height - RADIO_HEIGHT - MARGIN);
+	budget.set_size(window_width - MARGIN * 2, RADIO_HEIGHT);
+	unallocated.height -= RADIO_HEIGHT + MARGIN;
+
 	// Now we can finally place the list
 	list.set_pos(MARGIN, unallocated.y + MARGIN);
 	list.set_size(window_width - MARGIN * 2, unallocated.height - MARGIN * 2);
@@ -66,7 +112,7 @@ pub fn lay_widgets(window: &mut Window, menu: &mut MenuBar, play: &mut Button, s
 
 /// Lays out property widgets given the window size.
 #[allow(clippy::too_many_arguments)]
-pub fn lay_prop_widgets(window: &mut Window, name_input: &mut Input, idsp_radio: &mut RadioRoundButton, lopus_radio: &mut RadioRoundButton, bin_radio: &mut RadioRoundButton, loop_toggle: &mut CheckButton, loop_from_input: &mut IntInput, loop_to_input: &mut IntInput, save_button: &mut Button) {
+pub fn lay_prop_widgets(window: &mut Window, name_input: &mut Input, source_output: &mut Output, idsp_radio: &mut RadioRoundButton, lopus_radio: &mut RadioRoundButton, bin_radio: &mut RadioRoundButton, downmix_toggle: &mut CheckButton, loop_toggle: &mut CheckButton, loop_from_input: &mut IntInput, loop_to_input: &mut IntInput, notes_input: &mut MultilineInput, estimate_frame: &mut Frame, save_button: &mut Button) {
 	let window_width = window.width();
 	let window_height = window.height();
 
@@ -81,9 +127,17 @@ pub fn lay_prop_widgets(window: &mut Window, name_input: &mut Input, idsp_radio:
 	name_input.set_size(window_width - MARGIN * 2, increment);
 	unallocated.y_bump(increment + MARGIN);
 
+	// The source path goes under the name, just as wide
+	source_output.set_pos(MARGIN, unallocated.y + MARGIN);
+	source_output.set_size(window_width - MARGIN * 2, increment);
+	unallocated.y_bump(increment + MARGIN);
+
 	// Place the save button
 	save_button.set_pos(MARGIN, unallocated.height - MARGIN);
 	save_button.set_size((window_width / 2) - 75 - MARGIN * 2, increment);
+	// The size estimate sits next to it
+	estimate_frame.set_pos(save_button.x() + save_button.width() + MARGIN, save_button.y());
+	estimate_frame.set_size(window_width - save_button.width() - MARGIN * 3, increment);
 
 	// Place the radios
 	idsp_radio.set_pos(MARGIN, unallocated.y + MARGIN);
@@ -92,6 +146,9 @@ pub fn lay_prop_widgets(window: &mut Window, name_input: &mut Input, idsp_radio:
 	lopus_radio.set_size((window_width / 2) - MARGIN * 2, RADIO_HEIGHT);
 	bin_radio.set_pos(MARGIN, unallocated.y + RADIO_HEIGHT * 2 + MARGIN * 3);
 	bin_radio.set_size((window_width / 2) - MARGIN * 2, RADIO_HEIGHT);
+	// The downmix toggle goes under the radios
+	downmix_toggle.set_pos(MARGIN, unallocated.y + RADIO_HEIGHT * 3 + MARGIN * 4);
+	downmix_toggle.set_size((window_width / 2) - MARGIN * 2, RADIO_HEIGHT);
 	unallocated.x_bump(window_width / 2);
 
 	// Place the loop toggle
@@ -107,6 +164,65 @@ pub fn lay_prop_widgets(window: &mut Window, name_input: &mut Input, idsp_radio:
 	loop_to_input.set_pos(unallocated.x + MARGIN + 20, unallocated.y + MARGIN);
 	loop_to_input.set_size(unallocated.x - 20 - MARGIN * 2, increment);
 
+	// The notes fill what's left between the radios and the save button,
+	// leaving room for their label above them
+	let notes_y = (downmix_toggle.y() + RADIO_HEIGHT).max(loop_to_input.y() + increment) + RADIO_HEIGHT + MARGIN;
+	notes_input.set_pos(MARGIN, notes_y);
+	notes_input.set_size(window_width - MARGIN * 2, (save_button.y() - MARGIN - notes_y).max(increment));
+
+	// Finally, redraw the window
+	window.redraw()
+}
+
+/// Width of the labels to the left of the preference choices
+pub const PREF_LABEL_WIDTH: i32 = 120;
+
+/// Lays out preference widgets given the window size.
+/// 
+/// Each choice is given its own row, with its label to the left.
+pub fn lay_pref_widgets(window: &mut Window, choices: &mut [Choice], save_button: &mut Button) {
+	let window_width = window.width();
+	let window_height = window.height();
+
+	// Keep track of the window's space
+	let mut unallocated = Rect { x: 0, y: 0, width: window_width, height: window_height };
+
+	for choice in choices.iter_mut() {
+		choice.set_pos(PREF_LABEL_WIDTH + MARGIN, unallocated.y + MARGIN);
+		choice.set_size(window_width - PREF_LABEL_WIDTH - MARGIN * 2, RADIO_HEIGHT);
+		unallocated.y_bump(RADIO_HEIGHT + MARGIN)
+	}
+
+	// Place the save button at the bottom
+	save_button.set_pos(MARGIN, window_height - RADIO_HEIGHT - MARGIN);
+	save_button.set_size(window_width / 2 - MARGIN * 2, RADIO_HEIGHT);
+
+	// Finally, redraw the window
+	window.redraw()
+}
+
+/// Lays out shortcut editor widgets given the window size.
+/// 
+/// Each input is given its own row inside `scroll`, with its label to the left.
+pub fn lay_shortcut_widgets(window: &mut Window, scroll: &mut Scroll, inputs: &mut [Input], save_button: &mut Button) {
+	let window_width = window.width();
+	let window_height = window.height();
+
+	// The save button sits at the bottom, the scroll takes the rest
+	scroll.set_pos(0, 0);
+	scroll.set_size(window_width, window_height - RADIO_HEIGHT - MARGIN * 2);
+
+	// Leave room for the scrollbar
+	let input_width = window_width - PREF_LABEL_WIDTH - MARGIN * 3 - scroll.scrollbar_size().max(15);
+	let mut y = scroll.y() - scroll.yposition();
+	for input in inputs.iter_mut() {
+		input.resize(PREF_LABEL_WIDTH + MARGIN, y + MARGIN, input_width, RADIO_HEIGHT);
+		y += RADIO_HEIGHT + MARGIN
+	}
+
+	save_button.set_pos(MARGIN, window_height - RADIO_HEIGHT - MARGIN);
+	save_button.set_size(window_width / 2 - MARGIN * 2, RADIO_HEIGHT);
+
 	// Finally, redraw the window
 	window.redraw()
 }
@@ -151,3 +267,10 @@ pub fn input(window: &Window, message: &str, default: &str) -> Option<String> {
 
 	fltk_input(x, y, message, default)
 }
+
+/// Return `text` as a browser line or column that's shown as is.
+/// 
+/// "@." ends the format characters, so text starting with "@" isn't read as one.
+pub fn browser_text(text: &str) -> String {
+	format!("@.{}", text)
+}
@@ -1,11 +1,10 @@
 use std::{
-	fs,
-	io::Cursor,
-	num::NonZeroUsize,
+	cell::{ Cell, RefCell },
+	collections::HashSet,
 	path::{ Path, PathBuf },
-	process::Command
+	rc::Rc,
+	time::Duration
 };
-use nus3audio::Nus3audioFile;
 use fltk::{
 	prelude::{
 		BrowserExt,
@@ -13,722 +12,814 @@ use fltk::{
 		WidgetExt
 	},
 	browser::Browser,
-	dialog::{ FileDialogType, NativeFileChooser }
+	dialog::{ FileDialogType, NativeFileChooser },
+	enums::{ CallbackTrigger, Color, Event, Key },
+	misc::Progress,
+	window::Window
 };
 #[allow(unused_imports)]
 use log::{ trace, debug, info, warn, error };
-use crate::{
-	codec::{ EncodedFile, EncodingType },
-	settings::CACHEDIR,
-	util::human_readable_size
+use simple_nus3audio_core::{
+	codec::{ probe_duration, EncodingType },
+	project::Sort,
+	util::{ file_uri, human_readable_duration, human_readable_size },
+	Archive,
+	Cancel,
+	SaveReport
+};
+use crate::layout::browser_text;
+use crate::settings::DialogKind;
+pub use simple_nus3audio_core::item::{
+	extension_of_encoded,
+	AudioExtension,
+	ListItem
 };
 
-/// [nus3audio] has AudioFile::filename to do exactly this, but
-/// VGAudioCli seems to create lopus files without the header
-/// that nus3audio expects
-///
-/// Therefore, we rewrite that function here minus the fallback
-/// to .bin. Yes, this is a hack.
-pub fn extension_of_encoded(encoded: &[u8]) -> Result<AudioExtension, String> {
-	Ok(
-		if encoded.len() < 4 {
-			return Err("Not a valid file".to_owned())
-		} else if encoded[..4].eq(b"IDSP") {
-			AudioExtension::Idsp
-		} else {
-			AudioExtension::Lopus
-		}
-	)
-}
-
-/// Possible (valid) formats for audio in a nus3audio file.
-#[derive(Clone, PartialEq, Eq)]
-pub enum AudioExtension {
-	Idsp,
-	Lopus,
-	Bin
+/// How long the audio of an item is kept in memory after it was last used.
+const AUDIO_RETENTION: Duration = Duration::from_secs(60);
+/// How often items are checked for audio to release, in seconds.
+const RELEASE_FREQUENCY: f64 = 15.0;
+/// The most long files named when asking whether to import them, see [long_imports].
+const MAX_LISTED_LONG_IMPORTS: usize = 10;
+/// Format characters every group line starts with. Item lines always start
+/// with a color instead, so this tells them apart.
+const GROUP_FORMAT: &str = "@b@.";
+
+/// What is on a line of the list.
+#[derive(Clone, Copy)]
+enum Row {
+	/// The group at this index, see [Archive::groups].
+	Group(usize),
+	/// The item at this index.
+	Item(usize)
 }
 
-impl std::fmt::Display for AudioExtension {
-	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-		match self {
-			AudioExtension::Idsp => write!(f, "idsp"),
-			AudioExtension::Lopus => write!(f, "lopus"),
-			AudioExtension::Bin => write!(f, "bin")
+/// Find the audio files among `paths` that are longer than the threshold in
+/// `settings`, from their headers alone, since that usually means the wrong
+/// file was picked. Returns their indices in `paths` and a warning naming
+/// them, or None if there are none.
+pub fn long_imports(paths: &[PathBuf], settings: &crate::Settings) -> Option<(Vec<usize>, String)> {
+	let minutes = settings.long_import_minutes();
+	if minutes == 0 { return None }
+	let threshold = Duration::from_secs(minutes as u64 * 60);
+
+	let mut indices = Vec::new();
+	let mut warning = format!("Audio over {} minutes is usually the wrong file, and takes a while and a lot of memory to decode.", minutes);
+	for (index, path) in paths.iter().enumerate() {
+		let duration = match probe_duration(path) {
+			Some(duration) if duration > threshold => duration,
+			_ => continue
+		};
+		if indices.len() < MAX_LISTED_LONG_IMPORTS {
+			let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+			warning.push_str(&format!("\n{} is {} long", name, human_readable_duration(duration)))
 		}
+		indices.push(index)
 	}
+	if indices.len() > MAX_LISTED_LONG_IMPORTS {
+		warning.push_str(&format!("\n...and {} more", indices.len() - MAX_LISTED_LONG_IMPORTS))
+	}
+
+	if indices.is_empty() { return None }
+	warn!("{}", warning);
+	Some((indices, warning))
 }
 
 /// A particular list.
 pub struct List {
-	/// The name of this nus3audio file.
-	pub name: String,
-	/// The path of this list's original nus3audio file.
-	pub path: Option<PathBuf>,
-	/// Items in this nus3audio file.
-	pub items: Vec<ListItem>,
-	/// Whether or not this list has been modified. This is used to track unsaved changes.
-	pub modified: bool,
+	/// The nus3audio file shown by this list.
+	pub archive: Archive,
+	/// The largest this file should be when saved, in bytes.
+	budget: Option<u64>,
 	/// The browser widget representing the file.
 	widget: Browser,
-	/// The last browse directory of the replace dialog
-	browser_path: Option<PathBuf>
+	/// The bar showing how much of the size budget is used.
+	budget_bar: Progress,
+	/// Text pasted or dropped into the list that hasn't been taken yet, see [List::paste].
+	pasted: Rc<RefCell<Option<String>>>,
+	/// Whether or not an item is being dragged out of the list, see [List::drag_out].
+	dragging_out: Rc<Cell<bool>>,
+	/// What is on each line, in the order the archive's sort lists them.
+	rows: Vec<Row>,
+	/// The line of each item, counting from 0, or None if its group is
+	/// collapsed. The reverse of `rows`.
+	lines: Vec<Option<usize>>,
+	/// The groups the items are in, with their names, if they are grouped.
+	groups: Vec<(String, Vec<usize>)>,
+	/// Names of the groups showing their items. Groups start out collapsed.
+	expanded: HashSet<String>,
+	/// Whether or not each item shows its content hash, see [ListItem::content_hash].
+	show_hashes: bool,
+	/// Where to send messages.
+	sender: fltk::app::Sender<crate::Message>
 }
 
 impl List {
-	// pub fn new(sender: fltk::app::Sender<crate::Message>) -> Self {
-	pub fn new() -> Self {
+	pub fn new(sender: fltk::app::Sender<crate::Message>) -> Self {
 		let mut widget = Browser::new(0, 0, 0, 0, "");
 		widget.set_type(fltk::browser::BrowserType::Hold);
-		// widget.set_callback(move |c| c.emit(sender, crate::Message::ListInteracted));
-		Self {
-			name: String::new(),
-			path: None,
-			items: Vec::new(),
-			modified: false,
+		widget.visible_focus(true);
+		widget.set_tooltip("Items in this nus3audio file. Blue is IDSP, green is LOPUS and gray is BIN.\nItalic items are not yet encoded, red ones could not be decoded and struck out ones are empty.\nEnter plays, Left and Right seek.");
+		widget.set_trigger(CallbackTrigger::Changed);
+		widget.set_callback(move |_| sender.send(crate::Message::Selected));
+		let pasted = Rc::new(RefCell::new(None));
+		let dragging_out = Rc::new(Cell::new(false));
+		widget.handle({
+			let pasted = pasted.clone();
+			let dragging_out = dragging_out.clone();
+			// Dropped files arrive as a paste right after the drop
+			let dropping = Cell::new(false);
+			move |browser, event| match event {
+				Event::KeyDown => Self::handle_key(browser, fltk::app::event_key(), sender),
+				// Dragging the selected item past the edge of the list takes it out
+				Event::Drag if !dragging_out.get() && browser.value() != 0 && !fltk::app::event_inside_widget(browser) => {
+					dragging_out.set(true);
+					sender.send(crate::Message::DragOut);
+					true
+				},
+				// An item dragged out isn't dropped back in
+				Event::DndEnter if dragging_out.get() => false,
+				// Clicking a group shows or hides its items
+				Event::Released => {
+					if Self::is_group_line(browser, browser.value()) {
+						sender.send(crate::Message::ToggleGroup)
+					}
+					false
+				},
+				Event::DndEnter | Event::DndDrag | Event::DndLeave => true,
+				Event::DndRelease => {
+					dropping.set(true);
+					true
+				},
+				Event::Paste => {
+					pasted.replace(Some(fltk::app::event_text()));
+					sender.send(if dropping.replace(false) { crate::Message::Dropped } else { crate::Message::Pasted });
+					true
+				},
+				_ => false
+			}
+		});
+		let mut budget_bar = Progress::new(0, 0, 0, 0, "");
+		budget_bar.set_selection_color(Color::Green);
+		budget_bar.set_tooltip("Projected size of this file when saved, and how much of its size budget that is.\nItems that still have to be encoded are estimated");
+		let mut list = Self {
+			archive: Archive::new(),
+			budget: None,
 			widget,
-			browser_path: None
-		}
+			budget_bar,
+			pasted,
+			dragging_out,
+			rows: Vec::new(),
+			lines: Vec::new(),
+			groups: Vec::new(),
+			expanded: HashSet::new(),
+			show_hashes: false,
+			sender
+		};
+		list.update_budget();
+		list
 	}
 
-	/// Remove an item from this list by index.
+	/// Handle `key` being pressed while the list has focus. Returns true if it was used.
 	/// 
-	/// Marks this list as being modified.
-	pub fn remove(&mut self, index: usize) {
-		self.items.remove(index);
-		self.widget.remove(index as i32 + 1);
-		self.modified = true;
-	}
+	/// Up and Down are already handled by the browser itself.
+	fn handle_key(browser: &mut Browser, key: Key, sender: fltk::app::Sender<crate::Message>) -> bool {
+		let size = browser.size();
+		if size == 0 { return false }
+		// Roughly how many lines fit in the browser
+		let page = (browser.h() / (browser.text_size() + 4)).max(1);
+		let current = browser.value();
+
+		let line = match key {
+			Key::Home => 1,
+			Key::End => size,
+			Key::PageUp => (current - page).max(1),
+			Key::PageDown => (current + page).min(size),
+			Key::Enter | Key::KPEnter => {
+				sender.send(if Self::is_group_line(browser, current) { crate::Message::ToggleGroup } else { crate::Message::PlayPause });
+				return true
+			},
+			Key::Left => {
+				sender.send(crate::Message::SeekBackward);
+				return true
+			},
+			Key::Right => {
+				sender.send(crate::Message::SeekForward);
+				return true
+			},
+			_ => return false
+		};
 
-	/// Clear the items in this list.
-	/// 
-	/// Marks this list as being unmodified.
-	pub fn clear(&mut self) {
-		self.items.clear();
-		self.widget.clear();
-		self.modified = false
+		browser.select(line);
+		browser.middle_line(line);
+		browser.do_callback();
+		true
 	}
 
-	/// Replace a sound at `index` via a file dialog.
-	/// 
-	/// If it doesn't fail, marks this list as being modified.
-	pub fn replace(&mut self, index: usize, settings: &crate::Settings) -> Result<(), String> {
-		let list_item = match self.items.get_mut(index) {
-			Some(item) => item,
-			None => return Err("Failed to find internal list item.\nYou shouldn't be seeing this during normal use.".to_owned())
-		};
-
-		let mut open_dialog = NativeFileChooser::new(FileDialogType::BrowseFile);
-		open_dialog.set_filter(crate::AUDIO_FILES_DECODE_FILTER);
-		// Set the default path to the last path used
-		if let Some(path) = &self.browser_path {
-			let _ = open_dialog.set_directory(path);
-		}
-		open_dialog.show();
+	/// Whether or not `line` of `browser` is a group, see [GROUP_FORMAT].
+	fn is_group_line(browser: &Browser, line: i32) -> bool {
+		line > 0 && browser.text(line).map(|text| text.starts_with(GROUP_FORMAT)).unwrap_or(false)
+	}
 
-		if open_dialog.filename().exists() {
-			// Set the last path used to the path we just used
-			self.browser_path = open_dialog.filename().parent().map(|path| path.to_owned());
+	/// Show `archive` in this list, replacing whatever was there before.
+	pub fn load(&mut self, archive: Archive) {
+		self.widget.clear();
+		self.archive = archive;
+		self.expanded.clear();
+		self.budget = None;
+		self.reload();
+		self.sender.send(crate::Message::SortChanged)
+	}
 
-			let bytes = fs::read(open_dialog.filename());
-			if let Err(error) = bytes {
-				return Err(format!("Could not read file:\n{}", error))
-			}
-			let bytes = bytes.unwrap();
-
-			let result = if let Some(extension) = open_dialog.filename().extension() {
-				match extension.to_str() {
-					Some("idsp") => { list_item.from_encoded(&self.name, bytes, settings) },
-					Some("lopus") => { list_item.from_encoded(&self.name, bytes, settings) },
-					Some(e) => list_item.set_audio_from_bytes(bytes, EncodingType::from_extension(e)),
-					None => list_item.set_audio_from_bytes(bytes, EncodingType::Bin)
+	/// Show the items of this list's archive again, after they were moved
+	/// around or renamed in bulk. They are sorted again too.
+	pub fn reload(&mut self) {
+		self.refresh_order();
+		self.widget.clear();
+		for row in &self.rows {
+			match *row {
+				Row::Group(_) => self.widget.add(GROUP_FORMAT),
+				Row::Item(index) => {
+					let item = &self.archive.items[index];
+					self.widget.add(&format!("{}.{}", item.name, item.extension))
 				}
-			} else { list_item.set_audio_from_bytes(bytes, EncodingType::Bin) };
-
-			if let Err(error) = result {
-				return Err(format!("Could not decode file as audio:\n{}", error))
 			}
-
-			list_item.loop_points_samples = ListItem::loop_points_of(&open_dialog.filename(), settings);
-			self.modified = true;
-
-			Ok(())
-		} else {
-			Ok(())
 		}
+		for (line, row) in self.rows.iter().enumerate() {
+			if let Row::Group(group) = *row {
+				let (name, items) = &self.groups[group];
+				let sign = if self.expanded.contains(name) { '-' } else { '+' };
+				self.widget.set_text(line as i32 + 1, &format!("{}[{}] {} ({} items)", GROUP_FORMAT, sign, name, items.len()))
+			}
+		}
+		self.update_labels();
+		self.widget.redraw()
 	}
 
-	/// Save this nus3audio to the file at `self.path`.
-	/// 
-	/// Marks this list as being unmodified.
-	pub fn save_nus3audio(&mut self, path: Option<PathBuf>, settings: &crate::settings::Settings) -> Result<(), String> {
-		let path = if let Some(path) = path { path } else { self.path.clone().expect("No path has been set to save.") }
-			.with_extension("nus3audio");
-		let name = path.file_name().unwrap().to_string_lossy().to_string();
-		let mut nus3audio = Nus3audioFile::new();
-
-		for (index, list_item) in self.items.iter_mut().enumerate() {
-			let data = list_item.get_nus3_encoded_raw(&name, &list_item.extension.to_string(), settings).unwrap_or_else(|_| Vec::new());
-			nus3audio.files.push(
-				nus3audio::AudioFile {
-					id: index as u32,
-					name: list_item.name.to_owned(),
-					data
-				}
-			)
+	/// Work out which line each item is on again, see [Archive::sorted_order]
+	/// and [Archive::groups].
+	fn refresh_order(&mut self) {
+		let sort = self.archive.project.sort;
+		let order = self.archive.sorted_order(sort);
+		self.lines = vec![None; order.len()];
+		self.groups = self.archive.groups(&order, sort.group);
+		self.rows.clear();
+		if self.groups.is_empty() {
+			self.rows.extend(order.into_iter().map(Row::Item))
 		}
-
-		let mut export: Vec<u8> = Vec::new();
-		nus3audio.write(&mut export);
-
-		// Update label, after potentially encoding some items
-		// that were empty previously
-		for index in 0..self.items.len() {
-			self.update_label_of(index)
+		for (group, (name, items)) in self.groups.iter().enumerate() {
+			self.rows.push(Row::Group(group));
+			if self.expanded.contains(name) {
+				self.rows.extend(items.iter().copied().map(Row::Item))
+			}
 		}
-
-		info!("Writing {} to {:?}", name, path);
-
-		if let Err(error) = fs::write(path, &export) {
-			Err(error.to_string())
-		} else {
-			self.modified = false;
-			Ok(())
+		for (line, row) in self.rows.iter().enumerate() {
+			if let Row::Item(index) = *row {
+				self.lines[index] = Some(line)
+			}
 		}
 	}
 
-	/// Redraw the widget of this list.
-	pub fn redraw(&mut self) {
-		self.widget.redraw()
+	/// Return the browser line of the item at `index`, counting from 1, or
+	/// None if its group is collapsed.
+	fn line_of(&self, index: usize) -> Option<i32> {
+		self.lines.get(index).copied().unwrap_or(Some(index)).map(|line| line as i32 + 1)
 	}
 
-	/// Returns the selected value of this list, if one is selected.
-	pub fn selected(&mut self) -> Option<(usize, String)> {
-		let value = self.widget.value();
-		// 0 is returned if there is no value selected, but
-		// I'm not sure if this value is ever negative
-		if value != 0 { Some((value as usize - 1, self.widget.text(value).unwrap())) } else { None }
+	/// Whether or not the items are listed in another order than the file's,
+	/// or grouped. Adding or removing items then shows every item again, since
+	/// their places may have changed since they were last sorted.
+	fn is_sorted(&self) -> bool {
+		self.archive.project.sort != Sort::default()
 	}
 
-	pub fn get_label_of(&self, line: usize) -> Option<String> {
-		self.widget.text(line as i32 + 1)
+	/// Return how the items are listed.
+	pub fn sort(&self) -> Sort {
+		self.archive.project.sort
 	}
 
-	pub fn set_label_of(&mut self, line: usize, text: &str) {
-		let mut text = text.to_owned();
-		// Append a status if the item isn't complete
-		match 
-			(self.items[line].audio_file.is_some(),
-			self.items[line].bytes_raw.is_some(),
-			self.items[line].audio_file.as_ref().map(|file| file.encoding == EncodingType::Bin).unwrap_or(false))
-		{
-			(true, true, false) => {},
-			(true, false, false) => text.push_str(" (Not yet encoded)"),
-			(true, false, true) => text.push_str(" (Could not decode)"),
-			(false, true, _) => text.push_str(" (Could not decode)"),
-			(false, false, _) => text.push_str(" (Empty)"),
-			(true, true, true) => unreachable!()
+	/// List the items as `sort` says, keeping the selection, and remember it
+	/// in the sidecar.
+	pub fn set_sort(&mut self, sort: Sort) -> Result<(), String> {
+		let selected = self.selected().map(|(index, _)| index);
+		self.archive.project.sort = sort;
+		self.reload();
+		if let Some(index) = selected {
+			self.select(index)
 		}
-		self.widget.set_text(line as i32 + 1, &text)
+		self.sender.send(crate::Message::SortChanged);
+		self.archive.save_project()
 	}
 
-	pub fn update_label_of(&mut self, line: usize) {
-		self.set_label_of(line, &format!("{}.{}", self.items[line].name, self.items[line].extension))
+	/// Show or hide the items of the group on the selected line. Returns false
+	/// if there is no group there.
+	pub fn toggle_selected_group(&mut self) -> bool {
+		let line = self.widget.value();
+		let group = match self.rows.get((line as usize).wrapping_sub(1)) {
+			Some(Row::Group(group)) => *group,
+			_ => return false
+		};
+		let name = self.groups[group].0.clone();
+		if !self.expanded.remove(&name) {
+			self.expanded.insert(name);
+		}
+		let position = self.widget.position();
+		self.reload();
+		self.widget.set_position(position);
+		// Lines before the group didn't change, so it's still on the same line
+		self.widget.select(line);
+		true
 	}
 
-	/// Returns the [&mut Browser] widget of this List.
-	pub fn get_widget_mut(&mut self) -> &mut Browser {
-		&mut self.widget
+	/// Show or hide the items of every group.
+	pub fn expand_all(&mut self, expand: bool) {
+		self.expanded = if expand { self.groups.iter().map(|(name, _)| name.clone()).collect() } else { HashSet::new() };
+		let selected = self.selected().map(|(index, _)| index);
+		self.reload();
+		if let Some(index) = selected.filter(|_| expand) {
+			self.select(index)
+		}
 	}
 
-	/// Adds an item to the list.
+	/// Remove an item from this list by index, moving it to the trash.
 	/// 
 	/// Marks this list as being modified.
-	pub fn add_item(&mut self, item: ListItem, name: &str) {
-		self.items.push(item);
-		self.widget.add(name);
-		self.modified = true
-	}
-}
-
-/// An item in a [List].
-pub struct ListItem {
-	/// The name of this audio.
-	pub name: String,
-	/// The extension of the audio in this nus3audio file.
-	pub extension: AudioExtension,
-	/// Raw file.
-	pub audio_file: Option<EncodedFile>,
-	/// Converted bytes.
-	/// There is no guarantee that this data is in any particular format.
-	bytes_raw: Option<Vec<u8>>,
-	/// Loop points of this sound in samples.
-	pub loop_points_samples: Option<(usize, usize)>,
-	/// Length in samples of the sound.
-	pub length_in_samples: usize,
-	/// Sample rate of the sound.
-	sample_rate: u32,
-	/// Number of channels
-	channels: u16
-}
-
-impl ListItem {
-	/// Return a new [ListItem].
-	pub fn new(name: String) -> Self {
-		Self {
-			name,
-			extension: AudioExtension::Idsp,
-			audio_file: None,
-			bytes_raw: None,
-			loop_points_samples: None,
-			length_in_samples: 0,
-			sample_rate: 12_000,
-			channels: 1
+	pub fn remove(&mut self, index: usize) {
+		let line = self.line_of(index);
+		self.archive.remove(index);
+		if self.is_sorted() {
+			return self.reload()
 		}
+		if let Some(line) = line {
+			self.widget.remove(line)
+		}
+		self.refresh_order();
+		self.update_budget()
 	}
 
-	/// Return the loop points in samples.
-	pub fn loop_points(&self) -> &Option<(usize, usize)> {
-		&self.loop_points_samples
-	}
-
-	/// Return the ending loop point
-	pub fn loop_end(&self) -> Option<usize> {
-		self.loop_points_samples.map(|(_, end)| end)
+	/// Remove every item from this list, moving them to the trash.
+	/// Unlike [List::clear], this keeps the file open.
+	/// 
+	/// Marks this list as being modified if there were any items.
+	pub fn remove_all(&mut self) {
+		self.archive.remove_all();
+		self.widget.clear();
+		self.refresh_order();
+		self.update_budget()
 	}
 
-	/// Return the loop points in samples.
-	pub fn loop_points_samples(&self) -> Option<(i64, i64)> {
-		if let Some((begin, end)) = &self.loop_points_samples {
-			Some((
-				*begin as i64,
-				*end as i64
-			))
-		} else {
-			None
+	/// Put the item at `trash_index` in the trash back, see [Archive::restore],
+	/// and return its index.
+	/// 
+	/// If it doesn't fail, marks this list as being modified.
+	pub fn restore(&mut self, trash_index: usize) -> Result<usize, String> {
+		let index = self.archive.restore(trash_index)?;
+		if self.is_sorted() {
+			self.reload();
+			return Ok(index)
+		}
+		self.refresh_order();
+		if let Some(line) = self.line_of(index) {
+			self.widget.insert(line, "")
 		}
+		self.update_label_of(index);
+		Ok(index)
 	}
 
-	/// Attach new audio to this item.
-	pub fn set_audio_from_bytes(&mut self, bytes: Vec<u8>, encoding: EncodingType) -> Result<(), String> {
-		let decoded = EncodedFile::from_bytes_with_encoding(bytes, encoding);
-		self.audio_file = Some(decoded);
-		self.loop_points_samples = None;
-		self.bytes_raw = None;
-		Ok(())
+	/// Clear the items in this list.
+	/// 
+	/// Marks this list as being unmodified.
+	pub fn clear(&mut self) {
+		self.archive.clear();
+		self.expanded.clear();
+		self.budget = None;
+		self.widget.clear();
+		self.refresh_order();
+		self.update_budget();
+		self.sender.send(crate::Message::SortChanged)
 	}
 
-	/// Gets the sound from an encoded IDSP or LOPUS file.
+	/// Replace a sound at `index` with the file at `path`, or one picked with a
+	/// file dialog if there is no `path`.
+	/// 
+	/// The file is played with `playback` first, and only used once it's
+	/// confirmed, so picking the wrong file doesn't change anything. Dialogs are
+	/// shown near `window`.
 	/// 
-	/// More specifically, it will attempt to decode bytes with VGAudio CLI or vgmstream.
-	pub fn from_encoded(&mut self, nus3audio_name: &str, encoded: Vec<u8>, settings: &crate::settings::Settings) -> Result<(), String> {
-		let target_dir = CACHEDIR.join(nus3audio_name);
-		
-		let src_file = target_dir.join(&self.name).with_extension(extension_of_encoded(&encoded)?.to_string());
-
-		if let Err(error) = Self::create_target_dir(&target_dir) {
-			return Err(format!("Error creating cache subdirectory {:?}\n{}", target_dir, error))
+	/// If it doesn't fail, marks this list as being modified.
+	pub fn replace(&mut self, index: usize, path: Option<PathBuf>, settings: &mut crate::Settings, window: &Window, playback: &mut crate::playback::Playback) -> Result<(), String> {
+		let item = match self.archive.items.get(index) {
+			Some(item) => item,
+			None => return Err("Failed to find internal list item.\nYou shouldn't be seeing this during normal use.".to_owned())
 		};
+		let (name, extension) = (item.name.clone(), item.extension.clone());
+
+		let mut next_path = path;
+		loop {
+			let path = match next_path.take() {
+				Some(path) => path,
+				None => {
+					let mut open_dialog = NativeFileChooser::new(FileDialogType::BrowseFile);
+					open_dialog.set_filter(crate::AUDIO_FILES_DECODE_FILTER);
+					// Set the default path to the last path used
+					if let Some(path) = settings.last_directory(DialogKind::Audio) {
+						let _ = open_dialog.set_directory(&path);
+					}
+					open_dialog.show();
 
-		if let Err(error) = fs::write(&src_file, &encoded) {
-			return Err(format!("Error writing source file {:?}\n{}", src_file, error))
-		};
+					let path = open_dialog.filename();
+					if !path.exists() {
+						return Ok(())
+					}
+					// Set the last path used to the path we just used
+					settings.set_last_directory(DialogKind::Audio, &path);
+					path
+				}
+			};
 
-		match self.decode(&src_file, settings) {
-			Ok(raw) => {
-				// This should be in wav format now
-				let loop_points = Self::loop_points_of(&src_file, settings);
-
-				let wav_result = wav::read(&mut Cursor::new(&raw));
-
-				// Check that the wav could be read
-				match wav_result {
-					Ok((header, bitdepth)) => {
-						if let Err(bitdepth) = bitdepth.try_into_sixteen() {
-							return Err(format!("Error reading returned wav\nWrong bit depth found: {:?}", bitdepth))
-						};
-						self.bytes_raw = Some(encoded);
-						self.audio_file = Some(EncodedFile::from_bytes_with_encoding(raw, EncodingType::WAV));
-						self.channels = header.channel_count;
-						self.sample_rate = header.sampling_rate;
-						self.loop_points_samples = loop_points;
-
-						Ok(())
-					},
-					Err(error) => Err(format!("Error reading returned wav\n{}", error))
+			// Catch the wrong file before spending minutes decoding it
+			if let Some((_, warning)) = long_imports(std::slice::from_ref(&path), settings) {
+				fltk::dialog::message_title("Long Audio");
+				match crate::layout::choice2(window, &format!("{}\nUse it anyway?", warning), "Use it", "Pick another", "Cancel") {
+					Some(0) => (),
+					Some(1) => continue,
+					_ => return Ok(())
 				}
-			},
-			Err(error) => {
-				// Could not be decoded, assume this is binary data
-			warn!("Error decoding file: {}
-  This is not fatal, this file's bytes have been loaded directly. If this is not desired, make sure this file is a known format and is not corrupted.", error);
-				self.bytes_raw = None;
-				self.audio_file = Some(EncodedFile::from_bytes_with_encoding(encoded, EncodingType::Bin));
-				self.extension = AudioExtension::Bin;
-				self.loop_points_samples = None;
-				Ok(())
 			}
-		}
-	}
 
-	/// Removes the bytes from this item.
-	pub fn clear_bytes(&mut self) {
-		self.bytes_raw = None
-	}
+			// Hear the file before it goes in the archive
+			let mut candidate = ListItem::new(name.clone());
+			candidate.extension = extension.clone();
+			if let Err(error) = candidate.replace_from_file(&self.archive.name, &path, settings) {
+				candidate.discard(&self.archive.name, settings);
+				return Err(error)
+			}
+			let played = playback.audition(&mut candidate);
+			if let Err(error) = &played {
+				warn!("Could not play {:?}: {}", path, error)
+			}
 
-	/// Return the audio from this item in WAV format.
-	/// 
-	/// Optionally take the length in samples that should be used.
-	pub fn get_audio_wav(&self, end: Option<usize>) -> Result<Vec<u8>, String> {
-		if let Some(file) = &self.audio_file {
-			let end = end.and_then(|e| NonZeroUsize::new(e));
-			match file.to_wav(end) {
-				Ok(wav) => Ok(wav),
-				Err(error) => Err(format!("{}", error))
+			let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+			let heard = match (&played, candidate.loop_points()) {
+				(Err(_), _) => "It could not be played.".to_owned(),
+				(Ok(()), Some((start, end))) => format!("Playing it, looping from sample {} to {}.", start, end),
+				(Ok(()), None) => "Playing it, without a loop.".to_owned()
+			};
+			fltk::dialog::message_title("Replace");
+			let choice = crate::layout::choice2(window, &format!("Replace {} with {}?\n{}", name, file_name, heard), "Confirm", "Pick another", "Cancel");
+			playback.stop_sink();
+
+			if choice == Some(0) {
+				return self.replace_with(index, playback, |archive| archive.replace_with_item(index, candidate, &path, settings))
 			}
-		} else {
-			if self.bytes_raw.is_none() {
-				Err("Selected item is empty".to_owned())
-			} else {
-				Err("Selected item could not be decoded".to_owned())
+			candidate.discard(&self.archive.name, settings);
+			if choice != Some(1) {
+				return Ok(())
 			}
 		}
 	}
 
-	/// Return the bytes associated with this item. If it has audio but no bytes, the audio is converted according to `extension`.
-	pub fn get_nus3_encoded_raw(&mut self, nus3audio_name: &str, extension: &str, settings: &crate::settings::Settings) -> Result<Vec<u8>, String> {
-		if self.audio_file.is_none() { return Err("Audio of selected item is empty".to_owned()) }
+	/// Replace the audio of the item at `index` with `replace`, stopping it first
+	/// if it's playing, then update its label.
+	/// 
+	/// Every replacement of an item's audio should go through this, so playback
+	/// never goes on with stale audio. See [Playback::on_replace](crate::playback::Playback::on_replace).
+	pub fn replace_with(&mut self, index: usize, playback: &mut crate::playback::Playback, replace: impl FnOnce(&mut Archive) -> Result<(), String>) -> Result<(), String> {
+		playback.on_replace(index);
+		replace(&mut self.archive)?;
+		self.update_label_of(index);
+		Ok(())
+	}
 
-		if let Some(bytes) = &self.bytes_raw {
-			trace!("Encoded audio already exists for {}, returning it", self.name);
-			return Ok(bytes.clone())
-		} else {
-			if self.audio_file.as_ref().unwrap().encoding == EncodingType::Bin {
-				if self.extension != AudioExtension::Bin {
-					return Err("Item is not in bin format, but imported file is".to_owned())
-				} else {
-					trace!("{} is set to a binary file, returning it", self.name);
-					return Ok(self.audio_file.as_ref().unwrap().bytes.clone())
+	/// Save this nus3audio to `path`, or the file at `self.archive.path`.
+	/// 
+	/// Marks this list as being unmodified. Returns any warnings about the
+	/// paired nus3bank or the size budget, and what was done with each item. `progress` and `cancel` are passed on
+	/// to [Archive::save].
+	pub fn save_nus3audio(&mut self, path: Option<PathBuf>, settings: &crate::settings::Settings, progress: &mut dyn FnMut(usize, usize, &str), cancel: &Cancel) -> Result<(Vec<String>, SaveReport), String> {
+		let target = path.clone().or_else(|| self.archive.path.clone()).map(|path| path.with_extension("nus3audio"));
+		let report = self.archive.save_report();
+		let result = self.archive.save(path, settings, progress, cancel).map(|mut warnings| {
+			let size = target.and_then(|target| std::fs::metadata(target).ok()).map(|metadata| metadata.len());
+			if let (Some(budget), Some(size)) = (self.budget, size) {
+				if size > budget {
+					warnings.push(format!("This file is {}, over its size budget of {}.", human_readable_size(size), human_readable_size(budget)))
 				}
 			}
-			// Need to convert the file
-			trace!("Encoded audio does not already exist for {}, encoding it", self.name);
-			let target_dir = CACHEDIR.join(nus3audio_name);
-			let dest_file = target_dir.join(&self.name).with_extension(extension);
-			let src_file = dest_file.with_extension("wav");
-
-			if let Err(error) = Self::create_target_dir(&target_dir) {
-				return Err(format!("Error creating cache subdirectory {:?}\n{}", target_dir, error))
-			};
+			(warnings, report)
+		});
 
-			match self.get_audio_wav(self.loop_end()) {
-				Ok(bytes) => {
-					if let Err(error) = fs::write(&src_file, bytes) {
-						return Err(format!("Error writing source file {:?}\n{}", src_file, error))
-					}
-				},
-				Err(error) => return Err(format!("Error decoding audio\n{}", error))
-			}
+		// Update label, after potentially encoding some items
+		// that were empty previously
+		self.update_labels();
 
-			self.bytes_raw = Some(self.vgaudio_cli_decode(&src_file, &dest_file, settings)?);
+		result
+	}
 
-			debug!("Encoded {:?} to {:?}", src_file, dest_file);
+	/// Redraw the widget of this list.
+	pub fn redraw(&mut self) {
+		self.widget.redraw()
+	}
 
-			Ok(self.bytes_raw.as_ref().unwrap().clone())
+	/// Release the audio of items that haven't been used in a while from memory,
+	/// except the selected one.
+	pub fn release_idle_audio(&mut self, settings: &crate::settings::Settings) {
+		let keep = self.selected().map(|(index, _)| index);
+		let released = self.archive.release_idle_audio(AUDIO_RETENTION, keep, settings);
+		if released > 0 {
+			debug!("Released the audio of {} items", released)
 		}
 	}
 
-	/// Try to empty and create the target directory. This should be in the cache directory,
-	/// to avoid deleting something we shouldn't.
-	pub fn create_target_dir(target_dir: &Path) -> Result<(), std::io::Error> {
-		if target_dir.exists() {
-			if target_dir.is_dir() {
-				let contents = target_dir.read_dir()?;
-				for item in contents {
-					let item_path = item?.path();
-					if item_path.is_dir() {
-						fs::remove_dir_all(item_path)?
-					} else {
-						fs::remove_file(item_path)?
-					}
-				}
-			} else {
-				fs::remove_file(target_dir)?;
-				fs::create_dir(target_dir)?
+	/// Queue the next release of idle audio, see [List::release_idle_audio].
+	pub fn queue_release(sender: fltk::app::Sender<crate::Message>) {
+		fltk::app::add_timeout3(RELEASE_FREQUENCY, move |_| sender.send(crate::Message::ReleaseAudio));
+	}
+
+	/// Select the item at `index` and scroll it into view, showing the items
+	/// of its group if it's collapsed.
+	pub fn select(&mut self, index: usize) {
+		if self.line_of(index).is_none() {
+			if let Some((name, _)) = self.groups.iter().find(|(_, items)| items.contains(&index)) {
+				self.expanded.insert(name.clone());
+				self.reload()
 			}
-		} else {
-			fs::create_dir(target_dir)?
 		}
-		Ok(())
+		let line = match self.line_of(index) {
+			Some(line) => line,
+			None => return
+		};
+		self.widget.select(line);
+		self.widget.middle_line(line);
+		self.widget.do_callback();
+		let _ = self.widget.take_focus();
 	}
 
-	/// Decode `src_file` to a WAV file as bytes.
+	/// Find the item `query` refers to: a tone ID after a `#`, like `#12`, the
+	/// item's index counted from 0, or the start of its name.
 	/// 
-	/// Might use vgmstream or VGAudio Cli depending on which one is available to use.
-	fn decode(&self, src_file: &Path, settings: &crate::settings::Settings) -> Result<Vec<u8>, String> {
-		if settings.prefer_vgmstream_decode() {
-			if !settings.vgmstream_path().is_empty() {
-				Self::vgmstream_decode(src_file, settings)
-			} else {
-				self.vgaudio_cli_decode(src_file, &src_file.with_extension("wav"), settings)
-			}
-		} else {
-			if !settings.vgaudio_cli_path().is_empty() {
-				self.vgaudio_cli_decode(src_file, &src_file.with_extension("wav"), settings)
-			} else {
-				Self::vgmstream_decode(src_file, settings)
-			}
+	/// Names are searched from after the selected item, wrapping around,
+	/// so finding the same prefix again goes to the next match. A number
+	/// past the last index is searched for as a name.
+	pub fn find(&mut self, query: &str) -> Option<usize> {
+		let query = query.trim();
+		if let Some(id) = query.strip_prefix('#') {
+			let id = id.trim().parse::<u32>().ok()?;
+			return self.archive.items.iter().position(|item| item.id == Some(id))
+		}
+		if let Some(index) = query.parse::<usize>().ok().filter(|index| *index < self.archive.items.len()) {
+			return Some(index)
 		}
+
+		let query = query.to_lowercase();
+		let count = self.archive.items.len();
+		let start = self.selected().map(|(index, _)| index + 1).unwrap_or(0);
+		(0..count).map(|offset| (start + offset) % count).find(|index| {
+			let item = &self.archive.items[*index];
+			item.name.to_lowercase().starts_with(&query)
+		})
+	}
+
+	/// Returns the selected value of this list, if one is selected.
+	pub fn selected(&mut self) -> Option<(usize, String)> {
+		let value = self.widget.value();
+		// 0 is returned if there is no value selected, but
+		// I'm not sure if this value is ever negative
+		// The text of the line has format characters, so use the item's own name
+		if value != 0 {
+			let index = match self.rows.get(value as usize - 1)? {
+				Row::Item(index) => *index,
+				Row::Group(_) => return None
+			};
+			self.archive.items.get(index).map(|item| (index, format!("{}.{}", item.name, item.extension)))
+		} else { None }
 	}
 
-	/// Return loop points associated with `src_file`.
+	/// Set the text of the item at index `line`, prefixed with FLTK format characters
+	/// showing its format and state at a glance:
 	/// 
-	/// Requires vgmstream to be present and working, and will silently fail otherwise.
-	pub fn loop_points_of(src_file: &Path, settings: &crate::settings::Settings) -> Option<(usize, usize)> {
-		// Check if we can get metadata from this file
-		if let Ok(metadata) = Self::vgmstream_metadata(src_file, settings) {
-			// Check if the metadata has the "loopingInfo" object
-			if let json::JsonValue::Object(loop_info) = &metadata["loopingInfo"] {
-				// Check that the "start" and "end" numbers can be read as usize
-				if let (Some(start), Some(end)) = (loop_info["start"].as_usize(), loop_info["end"].as_usize()) {
-					// Check that the end is placed after the start
-					if end > start {
-						return Some((start, end))
-					}
-				}
-			}
-		}
+	/// - The color is the format: blue for IDSP, green for LOPUS, gray for BIN
+	/// - Italic items are not yet encoded
+	/// - Bold red items could not be decoded
+	/// - Struck out items are empty
+	/// 
+	/// Bookmarked items are prefixed with an asterisk. Items in collapsed
+	/// groups aren't shown, so they are left alone.
+	pub fn set_label_of(&mut self, line: usize, text: &str) {
+		let row = match self.line_of(line) {
+			Some(row) => row,
+			None => return
+		};
+		let item = &self.archive.items[line];
+		let color = match item.extension {
+			AudioExtension::Idsp => Color::DarkBlue,
+			AudioExtension::Lopus => Color::DarkGreen,
+			AudioExtension::Bin => Color::Dark3
+		};
+		let state = match
+			(item.has_audio(),
+			item.has_encoded(),
+			item.audio_file.as_ref().map(|file| file.encoding == EncodingType::Bin).unwrap_or(false))
+		{
+			(true, true, false) => String::new(),
+			(true, false, false) => "@i".to_owned(),
+			(true, false, true) | (false, true, _) => format!("@b@C{}", Color::Red.bits()),
+			(false, false, _) => "@-".to_owned(),
+			(true, true, true) => unreachable!()
+		};
+		let bookmark = if self.archive.project.bookmarks.contains(&item.name) { "* " } else { "" };
+		self.widget.set_text(row, &format!("@C{}{}{}", color.bits(), state, browser_text(&format!("{}{}", bookmark, text))))
+	}
 
-		None
+	/// Show the item at `index` as it is now, and update the budget bar.
+	pub fn update_label_of(&mut self, index: usize) {
+		self.relabel(index);
+		self.update_budget()
 	}
 
-	/// Run VGAudioCli, convert `src_file` to `dest_file` and return it as bytes.
-	fn vgaudio_cli_decode(&self, src_file: &Path, dest_file: &Path, settings: &crate::settings::Settings) -> Result<Vec<u8>, String> {
-		let vgaudio_cli_path = settings.vgaudio_cli_path();
-		if vgaudio_cli_path.is_empty() {
-			return Err("VGAudiCli path is empty".to_owned())
+	/// Show every item as it is now, and update the budget bar once, since
+	/// working out the projected size goes through every item.
+	pub fn update_labels(&mut self) {
+		for index in 0..self.archive.items.len() {
+			self.relabel(index)
 		}
+		self.update_budget()
+	}
 
-		let mut command: Command;
-		match settings.vgaudio_cli_prepath() {
-			vgaudio_cli_prepath if !vgaudio_cli_prepath.is_empty() => {
-				// Add the prepath if it isn't empty
-				command = Command::new(vgaudio_cli_prepath);
-				command.arg(vgaudio_cli_path);
-			},
-			_ => {
-				command = Command::new(vgaudio_cli_path);
-			}
+	/// Show the item at `line` as it is now, without updating the budget bar.
+	fn relabel(&mut self, line: usize) {
+		let item = &self.archive.items[line];
+		let mut text = format!("{}.{}", item.name, item.extension);
+		if item.recovered {
+			text.push_str(" [recovered]")
 		}
-
-		command.arg("-c")
-			.arg(src_file.as_os_str())
-			.arg(dest_file.as_os_str());
-		
-		// Add loop points if they exist
-		if let Some((from, to)) = self.loop_points_samples {
-			command.arg("-l").arg(format!("{}-{}", from, to)).arg("--cbr").arg("--opusheader").arg("namco");
+		// Items that aren't encoded yet have no hash
+		if let Some(hash) = item.content_hash().filter(|_| self.show_hashes) {
+			text.push_str(&format!("  {:016x}", hash))
 		}
+		self.set_label_of(line, &text)
+	}
 
-		debug!("Running {:?}", command);
-
-		let output = command.output();
-
-		let output = if let Err(error) = output {
-			return Err(format!("Error running VGAudioCli\n{}", error))
-		} else {
-			output.unwrap()
-		};
-
-		if let Some(code) = output.status.code() {
-			if code != 0 {
-				let mut error = format!("Attempted running VGAudioCli, found exit code {}\n", code);
-
-				let stdout = String::from_utf8(output.stdout);
-				let stderr = String::from_utf8(output.stderr);
-
-				if let Ok(out) = stdout {
-					if out.is_empty() {
-						error.push_str("stdout is empty\n")
-					} else {
-						error.push_str(&format!("stdout is:\n{}\n", out))
-					}
-				} else {
-					error.push_str("stdout couldn't be read\n")
-				}
-				if let Ok(err) = stderr {
-					if err.is_empty() {
-						error.push_str("stderr is empty")
-					} else {
-						error.push_str(&format!("stderr is:\n{}", err))
-					}
-				} else {
-					error.push_str("stderr couldn't be read")
-				}
+	/// Show or hide the content hash of each item after its name, so the items
+	/// that differ between two builds of a nus3audio file can be spotted.
+	pub fn set_show_hashes(&mut self, show_hashes: bool) {
+		self.show_hashes = show_hashes;
+		self.update_labels()
+	}
 
-				return Err(error)
-			}
+	/// Return the size budget of this list, in bytes.
+	pub fn budget(&self) -> Option<u64> {
+		self.budget
+	}
 
-			let stdout = String::from_utf8(output.stdout);
-			let stderr = String::from_utf8(output.stderr);
+	/// Set the size budget of this list, in bytes.
+	pub fn set_budget(&mut self, budget: Option<u64>) {
+		self.budget = budget;
+		self.update_budget()
+	}
 
-			if let Ok(out) = stdout {
-				if out.is_empty() {
-					debug!("stdout is empty")
-				} else {
-					debug!("stdout is:\n{}", out)
-				}
-			} else {
-				debug!("stdout couldn't be read")
-			}
-			if let Ok(err) = stderr {
-				if err.is_empty() {
-					debug!("stderr is empty")
-				} else {
-					debug!("stderr is:\n{}", err)
-				}
-			} else {
-				debug!("stderr couldn't be read")
-			}
+	/// Return a warning if the projected size of this list is over its budget.
+	pub fn over_budget(&self) -> Option<String> {
+		let budget = self.budget?;
+		let size = self.archive.projected_size().bytes;
+		if size > budget {
+			Some(format!("This file is projected to be {}, over its size budget of {}.", human_readable_size(size), human_readable_size(budget)))
 		} else {
-			return Err("Attempted running VGAudioCli, didn't get any exit code".to_string())
-		}
-
-		match fs::read(dest_file) {
-			Ok(bytes) => {
-				debug!("Got VGAudioCli output (output is {})", human_readable_size(bytes.len() as u64));
-				Ok(bytes)
-			},
-			Err(error) => Err(format!("Error reading destination file {:?}\n{}", dest_file, error))
+			None
 		}
 	}
 
-	/// Run vgmstream, decode `src_file` and return it as bytes.
-	fn vgmstream_decode(src_file: &Path, settings: &crate::settings::Settings) -> Result<Vec<u8>, String> {
-		let vgmstream_path = settings.vgmstream_path();
-		if vgmstream_path.is_empty() {
-			return Err("vgmstream path is empty".to_owned())
+	/// Return a warning if the audio of the items at `indices` will be resampled
+	/// when it's encoded, leaving their loop points in the wrong place.
+	pub fn resample_warning(&self, indices: &[usize]) -> Option<String> {
+		if indices.is_empty() { return None }
+		let mut warning = "The sample rate of these items will be changed, so their loop points will be wrong:".to_owned();
+		for &index in indices {
+			let item = &self.archive.items[index];
+			warning.push_str(&format!("\n{}: {} Hz to {} Hz", item.name, item.sample_rate(), item.encoded_sample_rate()));
 		}
+		warn!("{}", warning);
+		Some(warning)
+	}
 
-		// Create the command
-		let mut command = Command::new(vgmstream_path);
-		command.arg("-p")
-		// -m: print metadata only, don't decode
-		// -I: print requested file info as JSON
-			.arg(src_file);
-
-		debug!("Running {:?}", command);
-
-		// Run the command
-		let output = command.output();
-
-		let output = if let Err(error) = output {
-			return Err(format!("Error running vgmstream\n{}", error))
-		} else {
-			output.unwrap()
+	/// Warn about the items whose loop points will be wrong once their audio is
+	/// resampled, see [List::resample_warning], and offer to scale them. Only the
+	/// item at `only` is checked if given.
+	/// 
+	/// `keep` labels the choice to go on without scaling, and `back` the choice to
+	/// stop, if there is one. Returns false if `back` was chosen.
+	pub fn check_resampled_loops(&mut self, window: &Window, only: Option<usize>, keep: &str, back: &str) -> bool {
+		let resampled: Vec<usize> = self.archive.resampled_loops().into_iter()
+			.filter(|&index| only.map(|only| only == index).unwrap_or(true))
+			.collect();
+		let warning = match self.resample_warning(&resampled) {
+			Some(warning) => warning,
+			None => return true
 		};
-
-		// Check the error code
-		if let Some(code) = output.status.code() {
-			if code != 0 {
-				let mut error = format!("Attempted running vgmstream, found exit code {}\n", code);
-
-				let stdout = String::from_utf8(output.stdout);
-				let stderr = String::from_utf8(output.stderr);
-
-				if let Ok(out) = stdout {
-					if out.is_empty() {
-						error.push_str("stdout is empty\n")
-					} else {
-						error.push_str(&format!("stdout is:\n{}\n", out))
-					}
-				} else {
-					error.push_str("stdout couldn't be read\n")
-				}
-				if let Ok(err) = stderr {
-					if err.is_empty() {
-						error.push_str("stderr is empty")
-					} else {
-						error.push_str(&format!("stderr is:\n{}", err))
-					}
-				} else {
-					error.push_str("stderr couldn't be read")
-				}
-
-				return Err(error)
-			}
+		fltk::dialog::message_title("Warning");
+		match crate::layout::choice2(window, &warning, "Scale loop points", keep, back) {
+			Some(0) => {
+				self.scale_loop_points(&resampled);
+				true
+			},
+			Some(1) => true,
+			_ => back.is_empty()
 		}
-
-		debug!("Decoded with vgmstream (output is {})", human_readable_size(output.stdout.len() as u64));
-
-		Ok(output.stdout)
 	}
 
-	/// Run vgmstream, read metadata of `src_file` and return a [json::JsonValue].
-	fn vgmstream_metadata(src_file: &Path, settings: &crate::settings::Settings) -> Result<json::JsonValue, String> {
-		let vgmstream_path = settings.vgmstream_path();
-		if vgmstream_path.is_empty() {
-			return Err("vgmstream path is empty".to_owned())
+	/// Scale the loop points of the items at `indices` to match their resampled audio.
+	pub fn scale_loop_points(&mut self, indices: &[usize]) {
+		for &index in indices {
+			if let Err(error) = self.archive.scale_loop_points(index) {
+				error!("{}", error)
+			}
+			self.relabel(index)
 		}
+		self.update_budget()
+	}
 
-		// Create the command
-		let mut command = Command::new(vgmstream_path);
-		command.arg("-mI")
-		// -m: print metadata only, don't decode
-		// -I: print requested file info as JSON
-			.arg(src_file);
-
-		debug!("Running {:?}", command);
-
-		// Run the command
-		let output = command.output();
-
-		let output = if let Err(error) = output {
-			return Err(format!("Error running vgmstream\n{}", error))
-		} else {
-			output.unwrap()
+	/// Update the budget bar with the projected size of this list.
+	pub fn update_budget(&mut self) {
+		let projected = self.archive.projected_size();
+		let size = projected.bytes;
+		let about = if projected.unknown > 0 { "At least " } else if projected.estimated > 0 { "About " } else { "" };
+		let pending = match projected.estimated {
+			0 => String::new(),
+			1 => ", 1 item to encode".to_owned(),
+			count => format!(", {} items to encode", count)
 		};
+		match self.budget {
+			Some(budget) => {
+				self.budget_bar.set_maximum(budget as f64);
+				self.budget_bar.set_value((size as f64).min(budget as f64));
+				self.budget_bar.set_selection_color(if size > budget { Color::Red } else { Color::Green });
+				self.budget_bar.set_label(&format!("{}{} of {}{}", about, human_readable_size(size), human_readable_size(budget), pending))
+			},
+			None => {
+				self.budget_bar.set_maximum(1.0);
+				self.budget_bar.set_value(0.0);
+				self.budget_bar.set_label(&format!("{}{}, no size budget{}", about, human_readable_size(size), pending))
+			}
+		}
+		self.budget_bar.redraw()
+	}
 
-		// Check the error code
-		if let Some(code) = output.status.code() {
-			if code != 0 {
-				let mut error = format!("Attempted running vgmstream, found exit code {}\n", code);
+	/// Returns the [&mut Browser] and budget [&mut Progress] widgets of this List.
+	pub fn get_widgets_mut(&mut self) -> (&mut Browser, &mut Progress) {
+		(&mut self.widget, &mut self.budget_bar)
+	}
 
-				let stdout = String::from_utf8(output.stdout);
-				let stderr = String::from_utf8(output.stderr);
+	/// Ask for the text in the clipboard. It arrives later, with [Message::Pasted](crate::Message::Pasted),
+	/// and can then be taken with [List::take_pasted].
+	pub fn paste(&self) {
+		fltk::app::paste_text(&self.widget)
+	}
 
-				if let Ok(out) = stdout {
-					if out.is_empty() {
-						error.push_str("stdout is empty\n")
-					} else {
-						error.push_str(&format!("stdout is:\n{}\n", out))
-					}
-				} else {
-					error.push_str("stdout couldn't be read\n")
-				}
-				if let Ok(err) = stderr {
-					if err.is_empty() {
-						error.push_str("stderr is empty")
-					} else {
-						error.push_str(&format!("stderr is:\n{}", err))
-					}
-				} else {
-					error.push_str("stderr couldn't be read")
-				}
+	/// Take the text last pasted into the list, if any.
+	pub fn take_pasted(&self) -> Option<String> {
+		self.pasted.take()
+	}
 
-				return Err(error)
-			}
+	/// Drag `file` out of the list to wherever the mouse button is let go, like
+	/// a file manager, which copies it there. Without a `file`, the drag is
+	/// dropped instead. This follows [Message::DragOut](crate::Message::DragOut).
+	/// 
+	/// Returns once the file was dropped.
+	pub fn drag_out(&self, file: Option<&Path>) {
+		if let Some(file) = file {
+			fltk::app::copy2(&file_uri(file));
+			fltk::app::dnd();
 		}
+		self.dragging_out.set(false)
+	}
 
-		// Get string output
-		let text_output = match std::str::from_utf8(&output.stdout) {
-			Ok(output) => output,
-			Err(error) => return Err(format!("Error reading vgmstream output\n{}", error))
-		};
-		// Parse output as JSON
-		match json::parse(text_output) {
-			Ok(output) => Ok(output),
-			Err(error) => Err(format!("Error parsing vgmstream output\n{}", error))
+	/// Adds an item to the list.
+	/// 
+	/// Marks this list as being modified.
+	pub fn add_item(&mut self, item: ListItem, name: &str) {
+		self.archive.add(item);
+		if self.is_sorted() {
+			return self.reload()
 		}
+		self.widget.add("");
+		self.refresh_order();
+		self.set_label_of(self.archive.items.len() - 1, name);
+		self.update_budget()
+	}
+
+	/// Add the audio file at `path` as a new item, named after the file.
+	/// 
+	/// If it doesn't fail, marks this list as being modified.
+	pub fn add_from_file(&mut self, path: &Path, settings: &crate::Settings) -> Result<(), String> {
+		let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+		let mut item = ListItem::new(name);
+		item.extension = settings.default_format();
+		item.replace_from_file(&self.archive.name, path, settings)?;
+
+		let label = format!("{}.{}", item.name, item.extension);
+		self.archive.project.set_source(&item.name, path);
+		self.add_item(item, &label);
+		self.update_label_of(self.archive.items.len() - 1);
+		Ok(())
 	}
 }
@@ -1,10 +1,29 @@
-mod codec;
+mod batch;
+mod cli;
+mod console;
+mod diagnostics;
+mod export;
+mod generate;
 mod item_properties;
 mod layout;
 mod list;
 mod playback;
+mod preferences;
+mod prefetch;
+mod progress;
+mod script;
+mod update;
 mod util;
+mod watch;
+mod whats_new;
 mod settings;
+mod setup;
+mod save_preview;
+mod shortcuts;
+mod status;
+mod structure;
+mod toolbar;
+mod trash;
 
 use fltk::{
 	prelude::*,
@@ -13,14 +32,24 @@ use fltk::{
 		NativeFileChooser, FileDialogType
 	},
 	enums::{
-		Cursor, Event, FrameType, Shortcut
+		Cursor, Event, FrameType, Key, Shortcut
 	},
 	menu::{
 		MenuBar, MenuFlag
 	},
 	window::Window
 };
-use nus3audio::Nus3audioFile;
+use simple_nus3audio_core::{
+	cancel::{ Cancel, CANCELLED },
+	codec::EncodingType,
+	manifest,
+	project::{ Grouping, Sort, SortKey },
+	util::{ expand_name_template, human_readable_size, paths_from_text, safe_file_name, NameFields },
+	Archive,
+	ExportRange,
+	SaveSource,
+	PARSE_ERROR
+};
 #[allow(unused_imports)]
 use log::{ trace, debug, info, warn, error };
 use std::fs;
@@ -31,7 +60,10 @@ use crate::{
 		ListItem
 	},
 	playback::Playback,
-	settings::Settings
+	settings::{
+		DialogKind,
+		Settings
+	}
 };
 
 #[derive(Clone, Copy)]
@@ -40,15 +72,56 @@ pub enum Message {
 	ReLay,
 	/// Clear the working nus3audio.
 	New,
+	/// List the items by this key, see [List::set_sort].
+	SortBy(SortKey),
+	/// Reverse the order the items are listed in.
+	ToggleSortDescending,
+	/// Group the items like this, see [List::set_sort].
+	GroupBy(Grouping),
+	/// Show or hide the items of the selected group, see [List::toggle_selected_group].
+	ToggleGroup,
+	/// Show the items of every group.
+	ExpandAllGroups,
+	/// Hide the items of every group.
+	CollapseAllGroups,
+	/// How the items are listed changed, so the menu should show it.
+	SortChanged,
+	/// Start a new nus3audio with the items of a vanilla one.
+	NewFromTemplate,
 	/// Open a nus3audio.
 	Open,
+	/// Open a nus3audio from inside data.arc.
+	#[cfg(feature = "smash-arc")]
+	OpenFromArc,
 	/// Play.
 	PlayPause,
+	/// Play the selected item from its loop start.
+	PlayFromLoopStart,
 	/// Stop the currently playing sound.
 	Stop,
+	/// The selected item changed.
+	Selected,
+	/// A preview made ahead of time is ready.
+	Prefetched,
+	/// Release the audio of items that haven't been used in a while from memory.
+	ReleaseAudio,
 	/// Update the seek bar.
 	Update,
 	Seek,
+	/// Seek backward a few seconds.
+	SeekBackward,
+	/// Seek forward a few seconds.
+	SeekForward,
+	/// The pan dial of the preview was turned.
+	PlaybackPan,
+	/// A channel of the preview was toggled.
+	PlaybackChannels,
+	/// The speed or pitch of the preview changed.
+	PlaybackRate,
+	/// Preview at the normal speed and pitch again.
+	ResetPlaybackRate,
+	/// Play the loop of previews this many times before the rest, or forever if 0.
+	PreviewLoops(u32),
 	/// Save the working nus3audio.
 	Save,
 	/// Save the nus3audio to a new location.
@@ -57,14 +130,52 @@ pub enum Message {
 	ExportSingle,
 	/// Export everything.
 	ExportAll,
+	/// Export every sound back-to-back as one WAV file.
+	ExportPreviewReel,
+	/// Save the nus3audio to its place in a mod folder.
+	ExportToModFolder,
+	/// Show what saving would write, without saving.
+	PreviewSave,
+	/// Open the folder of the nus3audio in the file manager.
+	ShowInFileManager,
+	/// Open the cache folder with the decoded selected sound in the file manager.
+	ShowSoundInFileManager,
 	/// Add a single sound.
 	Add,
+	/// Add every audio file in a folder as new sounds.
+	AddFolder,
+	/// Add the files waiting to be added as new sounds, in order.
+	AddFiles,
+	/// Fill the selected sound or new sounds with silence.
+	GenerateSilence,
+	/// Fill the selected sound or new sounds with a test tone.
+	GenerateTone,
 	/// Remove the selected sound.
 	Remove,
+	/// Remove every sound, after typing a confirmation.
+	RemoveAll,
+	/// Show the items removed this session, to put them back.
+	ShowTrash,
 	/// Open sound properties window.
 	Properties,
+	/// Select an item by index, tone ID or name.
+	JumpTo,
+	/// Bookmark the selected item, or remove its bookmark.
+	ToggleBookmark,
+	/// Select the next bookmarked item.
+	NextBookmark,
+	/// Select the previous bookmarked item.
+	PreviousBookmark,
 	/// Replace a single sound.
 	Replace,
+	/// Replace a single sound with the file whose path is in the clipboard.
+	ReplaceFromClipboard,
+	/// Text was pasted into the list, see [List::paste].
+	Pasted,
+	/// Files were dropped on the list.
+	Dropped,
+	/// The selected item is being dragged out of the list, see [List::drag_out].
+	DragOut,
 	/// Configure the VGAudioCli path.
 	ConfigureVGAudioCliPath,
 	#[cfg(not(target_os = "windows"))]
@@ -77,8 +188,58 @@ pub enum Message {
 	ConfigureRuntimePath,
 	/// Configure the vgmstream path.
 	ConfigureVgmstreamPath,
-	/// Show the welcome message again.
+	/// Open the preferences window.
+	Preferences,
+	/// Open the keyboard shortcut editor.
+	ConfigureShortcuts,
+	/// Configure the external tool timeout.
+	ConfigureToolTimeout,
+	/// Configure the UI scale.
+	ConfigureUiScale,
+	/// Configure the font size.
+	ConfigureFontSize,
+	/// Toggle keeping the cache between sessions.
+	ToggleKeepCache,
+	/// Toggle showing the toolbar.
+	ToggleToolbar,
+	/// Toggle showing the content hash of each item in the list.
+	ToggleHashes,
+	/// Toggle making previews of the items around the selected one ahead of time.
+	TogglePrefetch,
+	/// Set the size budget of the working nus3audio.
+	ConfigureSizeBudget,
+	/// Show the external tool console.
+	ShowConsole,
+	/// Show a report of the environment, for bug reports.
+	ShowDiagnostics,
+	/// Run a tone through encoding, decoding and playback, and report what works.
+	SelfTest,
+	/// Run a script over the working nus3audio.
+	RunScript,
+	/// Write a manifest of the items in the working nus3audio.
+	ExportManifest,
+	/// Rename, reorder and re-ID items from a manifest.
+	ImportManifest,
+	/// Compare the selected item with its encoded audio, or with the audio it replaced.
+	NullTest,
+	/// Show the sections of the open nus3audio file as written on disk.
+	InspectStructure,
+	/// Toggle watching a folder for replacements.
+	ToggleWatchFolder,
+	/// Apply one operation to a folder of nus3audio files.
+	BatchProcess,
+	/// Convert a folder of audio files to IDSP or LOPUS.
+	BatchConvert,
+	/// Check the folder watched by the watcher of this generation.
+	WatchPoll(usize),
+	/// Show the setup wizard again.
 	WelcomeGreeting,
+	/// Check GitHub for a newer release.
+	CheckForUpdates,
+	/// Toggle checking for updates on startup.
+	ToggleCheckUpdates,
+	/// An update check finished. True if the user asked for it.
+	UpdateChecked(bool),
 	/// Open the online manual.
 	Manual,
 	/// Quit the application.
@@ -92,33 +253,102 @@ const MANUAL_URL: &str = "https://github.com/junetried/simple-nus3audio-gui/wiki
 
 /// Filter for audio files we can decode for the FLTK file dialog.
 const AUDIO_FILES_DECODE_FILTER: &str =
-"All audio files	*.{ogg,flac,wav,mp3,idsp,lopus}
+"All audio files	*.{ogg,flac,wav,mp3,aif,aiff,m4a,aac,opus,idsp,lopus}
 OGG files	*.ogg
 FLAC files	*.flac
 WAV files	*.wav
 MP3 files	*.mp3
+AIFF files	*.{aif,aiff}
+M4A/AAC files	*.{m4a,aac}
+Opus files	*.opus
 IDSP files	*.idsp
-LOPUS files	*.lopus";
+LOPUS files	*.lopus
+Other game formats (vgmstream)	*.{brstm,bcstm,bfstm,bwav,hca,adx,at3,at9,xma,wem,fsb,nlsd,dsp}
+All files	*";
 
 /// Filter for audio files we can encode for the FLTK file dialog.
 const AUDIO_FILES_ENCODE_FILTER: &str =
 "WAV files	*.wav
 IDSP files	*.idsp
-LOPUS files	*.lopus";
+LOPUS files	*.lopus
+OGG files	*.ogg
+FLAC files	*.flac";
 
 /// Filter for nus3audio files.
 const NUS3AUDIO_FILTER: &str = "NUS3AUDIO files	*.nus3audio";
 
+/// The items of the sort menu, and what each sorts the list by.
+const SORT_MENU: [(&str, SortKey); 5] = [
+	("&Edit/Sort list by/&File order\t", SortKey::Index),
+	("&Edit/Sort list by/&Name\t", SortKey::Name),
+	("&Edit/Sort list by/Tone &ID\t", SortKey::Id),
+	("&Edit/Sort list by/F&ormat\t", SortKey::Format),
+	("&Edit/Sort list by/&Size\t", SortKey::Size)
+];
+/// The item of the sort menu that reverses the order.
+const SORT_DESCENDING_MENU: &str = "&Edit/Sort list by/&Descending\t";
+/// The items of the group menu, and what each groups the list by.
+const GROUP_MENU: [(&str, Grouping); 3] = [
+	("&Edit/Group list by/N&othing\t", Grouping::None),
+	("&Edit/Group list by/Name &prefix\t", Grouping::Prefix),
+	("&Edit/Group list by/Tone &ID range\t", Grouping::IdRange)
+];
+
+/// The items of the loop preview menu, and how many times each plays the loop.
+const LOOP_MENU: [(&str, u32); 5] = [
+	("&Playback/&Loop preview/&Forever\t", 0),
+	("&Playback/&Loop preview/&Once, then the rest\t", 1),
+	("&Playback/&Loop preview/&Twice, then the rest\t", 2),
+	("&Playback/&Loop preview/T&hree times, then the rest\t", 3),
+	("&Playback/&Loop preview/Fo&ur times, then the rest\t", 4)
+];
+
+/// The most dropped files named when asking whether to add them.
+const MAX_LISTED_FILES: usize = 15;
+
 fn main() {
-	env_logger::Builder::from_env(
-		env_logger::Env::default()
-			.default_filter_or("error,simple_nus3audio_gui=warn")
-	).init();
+	// Options have to be read before anything is logged or any settings are read
+	let mut args: Vec<String> = std::env::args().skip(1).collect();
+	let options = match cli::Options::parse(&mut args) {
+		Ok(options) => options,
+		Err(usage) => {
+			eprintln!("{}", usage);
+			std::process::exit(2)
+		}
+	};
+	if let Err(error) = options.init_logging() {
+		eprintln!("{}", error);
+		std::process::exit(1)
+	}
 	info!("{} version {}", NAME, env!("CARGO_PKG_VERSION"));
+	if let Some(path) = options.config {
+		info!("Using the settings in {:?}", path);
+		settings::set_config_path(path)
+	}
+
+	// Subcommands don't need a window
+	if let Some(code) = cli::run(&args) {
+		std::process::exit(code)
+	}
+
 	let app = app::App::default();
 	let (s, r) = app::channel();
+
+	let mut settings = Settings::new_default();
+	// The font size has to be set before any widgets are made
+	settings.apply_scale();
+
 	let mut window = Window::new(0, 0, 250, 200, NAME);
 	window.size_range(200, 150, 0, 0);
+	// Open where the window was last closed, as long as that's still on a screen
+	if let Some((x, y, width, height)) = settings.main_window_geometry() {
+		let (screen_x, screen_y, screen_width, screen_height) = app::screen_xywh(app::screen_num(x, y));
+		if x >= screen_x && y >= screen_y && x < screen_x + screen_width && y < screen_y + screen_height {
+			window.resize(x, y, width.max(200), height.max(150))
+		} else {
+			window.set_size(width.max(200), height.max(150))
+		}
+	}
 
 	// Menu
 	let mut menu = MenuBar::default();
@@ -131,6 +361,13 @@ fn main() {
 		s,
 		Message::New,
 	);
+	menu.add_emit(
+		"&File/New from &template...\t",
+		Shortcut::Ctrl | Shortcut::Shift | 'n',
+		MenuFlag::Normal,
+		s,
+		Message::NewFromTemplate,
+	);
 	menu.add_emit(
 		"&File/&Open nus3audio\t",
 		Shortcut::Ctrl | 'o',
@@ -138,6 +375,14 @@ fn main() {
 		s,
 		Message::Open,
 	);
+	#[cfg(feature = "smash-arc")]
+	menu.add_emit(
+		"&File/Open from &data.arc...\t",
+		Shortcut::Ctrl | Shortcut::Shift | 'o',
+		MenuFlag::Normal,
+		s,
+		Message::OpenFromArc,
+	);
 	menu.add_emit(
 		"&File/&Save nus3audio\t",
 		Shortcut::Ctrl | 's',
@@ -166,6 +411,41 @@ fn main() {
 		s,
 		Message::ExportAll,
 	);
+	menu.add_emit(
+		"&File/Export preview &reel...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ExportPreviewReel,
+	);
+	menu.add_emit(
+		"&File/Export to &mod folder...\t",
+		Shortcut::Ctrl | 'm',
+		MenuFlag::Normal,
+		s,
+		Message::ExportToModFolder,
+	);
+	menu.add_emit(
+		"&File/Pre&view save...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::PreviewSave,
+	);
+	menu.add_emit(
+		"&File/Show in file mana&ger\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ShowInFileManager,
+	);
+	menu.add_emit(
+		"&File/Show sound in file manager\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ShowSoundInFileManager,
+	);
 	menu.add_emit(
 		"&File/&Quit\t",
 		Shortcut::Ctrl | 'q',
@@ -180,6 +460,27 @@ fn main() {
 		s,
 		Message::Add,
 	);
+	menu.add_emit(
+		"&Edit/Add &folder...\t",
+		Shortcut::Ctrl | Shortcut::Shift | '=',
+		MenuFlag::Normal,
+		s,
+		Message::AddFolder,
+	);
+	menu.add_emit(
+		"&Edit/Generate s&ilence...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::GenerateSilence,
+	);
+	menu.add_emit(
+		"&Edit/Generate to&ne...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::GenerateTone,
+	);
 	menu.add_emit(
 		"&Edit/Re&move selected sound\t",
 		Shortcut::Ctrl | '-',
@@ -187,6 +488,20 @@ fn main() {
 		s,
 		Message::Remove,
 	);
+	menu.add_emit(
+		"&Edit/Remove all sounds...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::RemoveAll,
+	);
+	menu.add_emit(
+		"&Edit/Trash...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ShowTrash,
+	);
 	menu.add_emit(
 		"&Edit/Sound &properties...\t",
 		Shortcut::Ctrl | 'p',
@@ -194,6 +509,34 @@ fn main() {
 		s,
 		Message::Properties,
 	);
+	menu.add_emit(
+		"&Edit/&Jump to item...\t",
+		Shortcut::Ctrl | 'g',
+		MenuFlag::Normal,
+		s,
+		Message::JumpTo,
+	);
+	menu.add_emit(
+		"&Edit/Toggle boo&kmark\t",
+		Shortcut::Ctrl | 'b',
+		MenuFlag::Normal,
+		s,
+		Message::ToggleBookmark,
+	);
+	menu.add_emit(
+		"&Edit/Next bookmark\t",
+		Shortcut::from_key(Key::F2),
+		MenuFlag::Normal,
+		s,
+		Message::NextBookmark,
+	);
+	menu.add_emit(
+		"&Edit/Previous bookmark\t",
+		Shortcut::Shift | Shortcut::from_key(Key::F2),
+		MenuFlag::Normal,
+		s,
+		Message::PreviousBookmark,
+	);
 	menu.add_emit(
 		"&Edit/&Replace single sound...\t",
 		Shortcut::Ctrl | 'r',
@@ -201,6 +544,13 @@ fn main() {
 		s,
 		Message::Replace,
 	);
+	menu.add_emit(
+		"&Edit/Replace from clipboard pat&h\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ReplaceFromClipboard,
+	);
 	menu.add_emit(
 		"&Edit/&Configure VGAudioCli path...\t",
 		Shortcut::empty(),
@@ -223,6 +573,115 @@ fn main() {
 		s,
 		Message::ConfigureVgmstreamPath,
 	);
+	menu.add_emit(
+		"&Edit/Pre&ferences...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::Preferences,
+	);
+	menu.add_emit(
+		"&Edit/Keyboard &shortcuts...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ConfigureShortcuts,
+	);
+	menu.add_emit(
+		"&Edit/Configure tool &timeout...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ConfigureToolTimeout,
+	);
+	menu.add_emit(
+		"&Edit/Configure UI sc&ale...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ConfigureUiScale,
+	);
+	menu.add_emit(
+		"&Edit/Configure font si&ze...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ConfigureFontSize,
+	);
+	menu.add_emit(
+		"&Edit/Set size &budget...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ConfigureSizeBudget,
+	);
+	menu.add_emit(
+		"&Edit/&Keep cache between sessions\t",
+		Shortcut::empty(),
+		MenuFlag::Toggle,
+		s,
+		Message::ToggleKeepCache,
+	);
+	menu.add_emit(
+		"&Edit/Show tool&bar\t",
+		Shortcut::empty(),
+		MenuFlag::Toggle,
+		s,
+		Message::ToggleToolbar,
+	);
+	menu.add_emit(
+		"&Edit/Show content hash&es\t",
+		Shortcut::empty(),
+		MenuFlag::Toggle,
+		s,
+		Message::ToggleHashes,
+	);
+	menu.add_emit(
+		"&Edit/Prefetch nei&ghbouring items\t",
+		Shortcut::empty(),
+		MenuFlag::Toggle,
+		s,
+		Message::TogglePrefetch,
+	);
+	for (path, key) in SORT_MENU {
+		menu.add_emit(
+			path,
+			Shortcut::empty(),
+			MenuFlag::Radio,
+			s,
+			Message::SortBy(key),
+		);
+	}
+	menu.add_emit(
+		SORT_DESCENDING_MENU,
+		Shortcut::empty(),
+		MenuFlag::Toggle,
+		s,
+		Message::ToggleSortDescending,
+	);
+	for (path, group) in GROUP_MENU {
+		menu.add_emit(
+			path,
+			Shortcut::empty(),
+			MenuFlag::Radio,
+			s,
+			Message::GroupBy(group),
+		);
+	}
+	menu.add_emit(
+		"&Edit/Group list by/E&xpand all\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ExpandAllGroups,
+	);
+	menu.add_emit(
+		"&Edit/Group list by/&Collapse all\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::CollapseAllGroups,
+	);
 	menu.add_emit(
 		"&Playback/&Play\t",
 		Shortcut::from_char(' '),
@@ -230,6 +689,13 @@ fn main() {
 		s,
 		Message::PlayPause,
 	);
+	menu.add_emit(
+		"&Playback/Play from loop s&tart\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::PlayFromLoopStart,
+	);
 	menu.add_emit(
 		"&Playback/&Stop\t",
 		Shortcut::empty(),
@@ -238,12 +704,119 @@ fn main() {
 		Message::Stop,
 	);
 	menu.add_emit(
-		"&Help/&VGAudioCli\t",
+		"&Playback/Seek &backward\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::SeekBackward,
+	);
+	menu.add_emit(
+		"&Playback/Seek &forward\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::SeekForward,
+	);
+	menu.add_emit(
+		"&Playback/&Reset speed and pitch\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ResetPlaybackRate,
+	);
+	for (path, count) in LOOP_MENU {
+		menu.add_emit(
+			path,
+			Shortcut::empty(),
+			MenuFlag::Radio,
+			s,
+			Message::PreviewLoops(count),
+		);
+	}
+	menu.add_emit(
+		"&Tools/&Run script...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::RunScript,
+	);
+	menu.add_emit(
+		"&Tools/Export &manifest...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ExportManifest,
+	);
+	menu.add_emit(
+		"&Tools/&Import manifest...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ImportManifest,
+	);
+	menu.add_emit(
+		"&Tools/&Null test selected item...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::NullTest,
+	);
+	menu.add_emit(
+		"&Tools/Inspect file &structure...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::InspectStructure,
+	);
+	menu.add_emit(
+		"&Tools/&Batch process folder...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::BatchProcess,
+	);
+	menu.add_emit(
+		"&Tools/Batch &convert folder...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::BatchConvert,
+	);
+	menu.add_emit(
+		"&Tools/&Watch folder for replacements...\t",
+		Shortcut::empty(),
+		MenuFlag::Toggle,
+		s,
+		Message::ToggleWatchFolder,
+	);
+	menu.add_emit(
+		"&Help/&Setup wizard...\t",
 		Shortcut::empty(),
 		MenuFlag::Normal,
 		s,
 		Message::WelcomeGreeting,
 	);
+	menu.add_emit(
+		"&Help/Tool &console...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ShowConsole,
+	);
+	menu.add_emit(
+		"&Help/&Diagnostics...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::ShowDiagnostics,
+	);
+	menu.add_emit(
+		"&Help/Run self t&est...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::SelfTest,
+	);
 	menu.add_emit(
 		"&Help/User &manual...\t",
 		Shortcut::empty(),
@@ -251,6 +824,20 @@ fn main() {
 		s,
 		Message::Manual,
 	);
+	menu.add_emit(
+		"&Help/Check for &updates...\t",
+		Shortcut::empty(),
+		MenuFlag::Normal,
+		s,
+		Message::CheckForUpdates,
+	);
+	menu.add_emit(
+		"&Help/Check for updates on s&tartup\t",
+		Shortcut::empty(),
+		MenuFlag::Toggle,
+		s,
+		Message::ToggleCheckUpdates,
+	);
 	menu.add_emit(
 		&format!("&Help/Version {}\t", env!("CARGO_PKG_VERSION")),
 		Shortcut::empty(),
@@ -259,11 +846,14 @@ fn main() {
 		Message::NoOp,
 	);
 
+	// The toolbar starts hidden, until the settings say otherwise
+	let mut toolbar = toolbar::Toolbar::new(s);
+
 	// Playback
 	let mut playback = Playback::new(s);
 
 	// This will contain all the list items
-	let mut file_list: List = List::new();
+	let mut file_list: List = List::new(s);
 
 	let mut start_input = fltk::input::IntInput::default();
 	start_input.set_tooltip("Loop start position in samples");
@@ -274,10 +864,16 @@ fn main() {
 	window.end();
 	window.show();
 
+	// The tool console starts hidden
+	let mut console = console::Console::new();
+	simple_nus3audio_core::tool::set_output_hook(console::log);
+	simple_nus3audio_core::tool::set_poll_hook(console::pump);
+
 	// Now we need to lay the window out!
 	{
-		let (play_widget, slider_widget) = playback.get_widgets_mut();
-		layout::lay_widgets(&mut window, &mut menu, play_widget, slider_widget, file_list.get_widget_mut())
+		let (play_widget, loop_start_widget, slider_widget, pan_widget, channel_widgets, speed_widget, pitch_widget) = playback.get_widgets_mut();
+		let (list_widget, budget_widget) = file_list.get_widgets_mut();
+		layout::lay_widgets(&mut window, &mut menu, toolbar.get_widgets_mut(), play_widget, loop_start_widget, slider_widget, pan_widget, channel_widgets, speed_widget, pitch_widget, list_widget, budget_widget)
 	}
 
 	window.handle(move |_, event| match event {
@@ -285,6 +881,8 @@ fn main() {
 			s.send(Message::ReLay);
 			true
 		},
+		// Shortcuts come here from a focused text field before the menu bar
+		Event::Shortcut => shortcuts::dispatch(),
 		_ => { false }
 	});
 	window.set_callback(move |_| {
@@ -294,10 +892,24 @@ fn main() {
 		}
 	});
 
-	let mut settings = Settings::new_default();
-
 	// Show the first-time greeting if necessary
-	settings.first_time_greeting(&window, s);
+	let first_time = settings.first_time();
+	settings.first_time_greeting(&window);
+
+	// Say what changed if another version was run last, unless there was none
+	let migrations = settings.take_migrations();
+	let last_run_version = settings.last_run_version().map(str::to_owned);
+	if last_run_version.as_deref() != Some(env!("CARGO_PKG_VERSION")) {
+		// Versions that didn't record this are older than any that did
+		let previous = last_run_version.unwrap_or_else(|| "0".to_owned());
+		info!("Last run version was {}", previous);
+		if !first_time {
+			if let Some(notes) = whats_new::notes(&previous, &migrations) {
+				whats_new::show(&notes, &window)
+			}
+		}
+		settings.set_last_run_version(env!("CARGO_PKG_VERSION"))
+	}
 
 	// Create the settings if needed
 	if let Err(error) = Settings::create_settings() {
@@ -307,53 +919,174 @@ fn main() {
 		alert(&window, &format!("Error creating the settings directory:\n{}", error))
 	}
 
-	// And reset the cache
-	if let Err(error) = Settings::reset_cache() {
-		error!("{}", error);
-		fltk::dialog::message_title("Fatal Error");
-		alert(&window, &format!("Error creating the cache directory:\n{}", error));
-		std::process::exit(1)
+	// Reflect the current settings in the menu
+	shortcuts::apply(&mut menu, &settings);
+	if settings.keep_cache() {
+		if let Some(mut item) = menu.find_item("&Edit/&Keep cache between sessions\t") {
+			item.set()
+		}
 	}
-	
+
+	if settings.prefetch() {
+		if let Some(mut item) = menu.find_item("&Edit/Prefetch nei&ghbouring items\t") {
+			item.set()
+		}
+	}
+
+	// A count set by hand that isn't in the menu loops forever, as the menu shows
+	let (loop_path, preview_loops) = LOOP_MENU.iter().copied().find(|(_, count)| *count == settings.preview_loops()).unwrap_or(LOOP_MENU[0]);
+	if let Some(mut item) = menu.find_item(loop_path) {
+		item.set()
+	}
+	playback.set_loop_count(preview_loops);
+
+	if settings.show_toolbar() {
+		if let Some(mut item) = menu.find_item("&Edit/Show tool&bar\t") {
+			item.set()
+		}
+		toolbar.set_shown(true);
+		s.send(Message::ReLay)
+	}
+
+	if settings.show_hashes() {
+		if let Some(mut item) = menu.find_item("&Edit/Show content hash&es\t") {
+			item.set()
+		}
+		file_list.set_show_hashes(true)
+	}
+
+	if settings.check_updates() {
+		if let Some(mut item) = menu.find_item("&Help/Check for updates on s&tartup\t") {
+			item.set()
+		}
+		update::check_in_background(s, Message::UpdateChecked(false))
+	}
+
+	// And reset the cache, unless it should be kept
+	if let Err(error) = settings.prepare_cache() {
+		error!("{}", error);
+		fltk::dialog::message_title("Fatal Error");
+		alert(&window, &format!("Error creating the cache directory:\n{}", error));
+		std::process::exit(1)
+	}
+	List::queue_release(s);
+
+	
+	// The folder being watched for replacements, if any
+	let mut watcher: Option<watch::Watcher> = None;
+	// Counts the times watching was started, see [watch::Watcher::generation]
+	let mut watch_generation: usize = 0;
+	// The file the next replacement is from, instead of asking for one
+	let mut replacement_path: Option<std::path::PathBuf> = None;
+	// The files waiting to be added
+	let mut files_to_add: Option<Vec<std::path::PathBuf>> = None;
+
 	// Main event loop
 	while app.wait() {
 		// Handle events
 		if let Some(e) = r.recv() {
 			match e {
 				Message::ReLay => {
-					let (play_widget, slider_widget) = playback.get_widgets_mut();
-					layout::lay_widgets(&mut window, &mut menu, play_widget, slider_widget, file_list.get_widget_mut())
+					let (play_widget, loop_start_widget, slider_widget, pan_widget, channel_widgets, speed_widget, pitch_widget) = playback.get_widgets_mut();
+					let (list_widget, budget_widget) = file_list.get_widgets_mut();
+					layout::lay_widgets(&mut window, &mut menu, toolbar.get_widgets_mut(), play_widget, loop_start_widget, slider_widget, pan_widget, channel_widgets, speed_widget, pitch_widget, list_widget, budget_widget)
 				},
+				Message::SortBy(_) | Message::ToggleSortDescending | Message::GroupBy(_) => {
+					let sort = match e {
+						Message::SortBy(key) => Sort { key, ..file_list.sort() },
+						Message::GroupBy(group) => Sort { group, ..file_list.sort() },
+						_ => Sort { descending: !file_list.sort().descending, ..file_list.sort() }
+					};
+					if let Err(error) = file_list.set_sort(sort) {
+						error!("{}", error);
+						fltk::dialog::message_title("Error");
+						alert(&window, &format!("Could not remember the order in the sidecar file:\n{}", error))
+					}
+				},
+				Message::SortChanged => {
+					let sort = file_list.sort();
+					for (path, key) in SORT_MENU {
+						if let Some(mut item) = menu.find_item(path) {
+							if key == sort.key { item.set() } else { item.clear() }
+						}
+					}
+					if let Some(mut item) = menu.find_item(SORT_DESCENDING_MENU) {
+						if sort.descending { item.set() } else { item.clear() }
+					}
+					for (path, group) in GROUP_MENU {
+						if let Some(mut item) = menu.find_item(path) {
+							if group == sort.group { item.set() } else { item.clear() }
+						}
+					}
+				},
+				Message::ToggleGroup => {
+					file_list.toggle_selected_group();
+				},
+				Message::ExpandAllGroups => file_list.expand_all(true),
+				Message::CollapseAllGroups => file_list.expand_all(false),
 				Message::New => {
 					file_list.clear()
 				},
+				Message::NewFromTemplate => {
+					let mut file_dialog = NativeFileChooser::new(FileDialogType::BrowseFile);
+					file_dialog.set_filter(NUS3AUDIO_FILTER);
+					file_dialog.show();
+
+					if file_dialog.filename().exists() {
+						let archive = match Archive::template(&file_dialog.filename()) {
+							Ok(archive) => archive,
+							Err(error) => {
+								fltk::dialog::message_title("Error");
+								alert(&window, &error);
+								continue
+							}
+						};
+
+						playback.stop_sink();
+						file_list.load(archive)
+					}
+				},
 				Message::Open => {
 					let mut file_dialog = NativeFileChooser::new(FileDialogType::BrowseFile);
 					file_dialog.set_filter(NUS3AUDIO_FILTER);
+					if let Some(directory) = settings.last_directory(DialogKind::Open) {
+						let _ = file_dialog.set_directory(&directory);
+					}
 					// Get file selection
 					file_dialog.show();
 
 					if file_dialog.filename().exists() {
+						settings.set_last_directory(DialogKind::Open, &file_dialog.filename());
 						window.set_cursor(Cursor::Wait);
 
-						// Attempt to read chosen file
-						let raw = match std::fs::read(file_dialog.filename()) {
-							Ok(r) => r,
-							Err(e) => {
-								fltk::dialog::message_title("Error");
-								window.set_cursor(Cursor::Default);
-								alert(&window, &format!("Error reading file:\n{}", e));
+						// Attempt to read and decode chosen file
+						let mut opened = progress::run(&window, "Opening", |progress, cancel| {
+							Archive::open(&file_dialog.filename(), &settings, progress, cancel)
+						});
+						// A damaged file may still have audio in it worth saving
+						if matches!(&opened, Err(error) if error == PARSE_ERROR) {
+							window.set_cursor(Cursor::Default);
+							fltk::dialog::message_title("Damaged File");
+							if layout::choice2(&window, "This file could not be read as a nus3audio file, it may be damaged.\nTry to recover the audio in it?\nRecovered items may be cut short or run long, so check them before saving.", "Recover", "Cancel", "") == Some(0) {
+								window.set_cursor(Cursor::Wait);
+								opened = progress::run(&window, "Recovering", |progress, cancel| {
+									Archive::recover(&file_dialog.filename(), &settings, progress, cancel)
+								})
+							} else {
 								continue
 							}
-						};
-
-						// Try to load the nus3audio file
-						let nus3audio = match Nus3audioFile::try_from_bytes(&raw) {
-							Some(f) => f,
-							None => {
+						}
+						let (archive, errors) = match opened {
+							Ok(opened) => opened,
+							Err(error) if error == CANCELLED => {
+								window.set_cursor(Cursor::Default);
+								status::announce(&mut window, "Cancelled opening");
+								continue
+							},
+							Err(error) => {
 								fltk::dialog::message_title("Error");
 								window.set_cursor(Cursor::Default);
-								alert(&window, "Error parsing file");
+								alert(&window, &error);
 								continue
 							}
 						};
@@ -361,43 +1094,70 @@ fn main() {
 						// Stop current playback before loading the file into the list
 						playback.stop_sink();
 
-						file_list.clear();
-						file_list.name = file_dialog.filename().file_name().unwrap().to_string_lossy().to_string();
-						file_list.path = Some(file_dialog.filename());
+						file_list.load(archive);
+						window.set_cursor(Cursor::Default);
+						if file_list.archive.items.iter().any(|item| item.recovered) {
+							status::announce(&mut window, &format!("Recovered {} items from {}", file_list.archive.items.len(), file_list.archive.name))
+						} else {
+							status::announce(&mut window, &format!("Opened {} with {} items", file_list.archive.name, file_list.archive.items.len()))
+						}
 
-						// Add the files to the list
-						for (index, file) in nus3audio.files.into_iter().enumerate() {
-							let mut item = ListItem::new(file.name.clone());
-							let mut item_name = file.name;
+						if !errors.is_empty() {
+							let errors: Vec<String> = errors.into_iter().map(|(name, error)| format!("Could not decode {}:\n{}", name, error)).collect();
+							fltk::dialog::message_title("Error");
+							alert(&window, &errors.join("\n\n"));
+						}
+					}
+				},
+				#[cfg(feature = "smash-arc")]
+				Message::OpenFromArc => {
+					let mut file_dialog = NativeFileChooser::new(FileDialogType::BrowseFile);
+					file_dialog.set_filter("data.arc\tdata.arc");
+					file_dialog.show();
 
-							let extension = list::extension_of_encoded(&file.data);
+					if !file_dialog.filename().exists() {
+						continue
+					}
 
-							// Set the item extension
-							if let Ok(extension) = extension {
-								item.extension = extension
-							}
+					fltk::dialog::message_title("Open from data.arc");
+					let arc_path = match layout::input(&window, "Path of the nus3audio inside data.arc:", "sound/bank/fighter_voice/vc_mario.nus3audio") {
+						Some(arc_path) if !arc_path.trim().is_empty() => arc_path.trim().to_owned(),
+						_ => continue
+					};
 
-							if let Err(error) = item.from_encoded(&file_list.name, file.data, &settings) {
-								error!("{}", error);
-								fltk::dialog::message_title("Error");
-								window.set_cursor(Cursor::Default);
-								alert(&window, &format!("Could not decode {}:\n{}", item_name, error));
-							};
+					window.set_cursor(Cursor::Wait);
+					let opened = progress::run(&window, "Opening", |progress, cancel| {
+						Archive::open_from_arc(&file_dialog.filename(), &arc_path, &settings, progress, cancel)
+					});
+					let (archive, errors) = match opened {
+						Ok(opened) => opened,
+						Err(error) if error == CANCELLED => {
+							window.set_cursor(Cursor::Default);
+							status::announce(&mut window, "Cancelled opening");
+							continue
+						},
+						Err(error) => {
+							fltk::dialog::message_title("Error");
+							window.set_cursor(Cursor::Default);
+							alert(&window, &error);
+							continue
+						}
+					};
 
-							// Set the item extension in the name
-							item_name.push_str(&format!(".{}", item.extension));
+					playback.stop_sink();
 
-							file_list.add_item(item, &item_name);
-							file_list.update_label_of(index)
-						};
+					file_list.load(archive);
+					window.set_cursor(Cursor::Default);
 
-						file_list.redraw();
-						window.set_cursor(Cursor::Default)
+					if !errors.is_empty() {
+						let errors: Vec<String> = errors.into_iter().map(|(name, error)| format!("Could not decode {}:\n{}", name, error)).collect();
+						fltk::dialog::message_title("Error");
+						alert(&window, &errors.join("\n\n"));
 					}
 				},
 				Message::ExportSingle => {
 					if let Some((index, sound_name)) = file_list.selected() {
-						let list_item = file_list.items.get_mut(index).expect("Failed to find internal list item");
+						let list_item = file_list.archive.items.get_mut(index).expect("Failed to find internal list item");
 
 						let (filter, default) = match list_item.extension {
 							list::AudioExtension::Bin => ("*", "bin"),
@@ -409,6 +1169,9 @@ fn main() {
 
 						let mut save_dialog = NativeFileChooser::new(FileDialogType::BrowseSaveFile);
 						save_dialog.set_filter(filter);
+						if let Some(directory) = settings.last_directory(DialogKind::ExportSingle) {
+							let _ = save_dialog.set_directory(&directory);
+						}
 
 						// Set the default file name to save
 						if let Some(filename) = default.to_str() {
@@ -431,16 +1194,32 @@ fn main() {
 						};
 
 						if !save_dialog.filename().to_string_lossy().is_empty() {
+							settings.set_last_directory(DialogKind::ExportSingle, &save_dialog.filename());
+							// Only decoded formats can be trimmed, nus3audio formats keep their loop
+							let range = if list_item.loop_points().is_some() && matches!(extension, "wav" | "ogg" | "flac") {
+								match export::choose_range(&window) {
+									Some(range) => range,
+									None => continue
+								}
+							} else { ExportRange::Full };
+
 							window.set_cursor(Cursor::Wait);
 
 							let target_file = target_file.with_extension(extension);
 
-							let raw = if extension == "wav" {
-								debug!("Export as wav audio");
-								list_item.get_audio_wav(None)
-							} else {
-								debug!("Export as idsp or lopus encoded audio");
-								list_item.get_nus3_encoded_raw(&file_list.name, extension, &settings)
+							let raw = match extension {
+								"wav" => {
+									debug!("Export as wav audio");
+									list_item.get_audio_wav_range(range)
+								},
+								"ogg" | "flac" => {
+									debug!("Export as {} audio", extension);
+									list_item.get_audio_encoded(EncodingType::from_extension(extension), range)
+								},
+								_ => {
+									debug!("Export as idsp or lopus encoded audio");
+									list_item.get_nus3_encoded_raw(&file_list.archive.name, extension, &settings)
+								}
 							};
 
 							info!("Exporting item to path {:?}", target_file);
@@ -472,66 +1251,322 @@ fn main() {
 				Message::ExportAll => {
 					let mut save_dialog = NativeFileChooser::new(FileDialogType::BrowseSaveDir);
 					save_dialog.set_filter("*.wav");
+					if let Some(directory) = settings.last_directory(DialogKind::ExportAll) {
+						let _ = save_dialog.set_directory(&directory);
+					}
 					save_dialog.show();
 
 					if !save_dialog.filename().to_string_lossy().is_empty() {
+						settings.set_last_directory(DialogKind::ExportAll, &save_dialog.filename());
+						let range = if file_list.archive.items.iter().any(|item| item.loop_points().is_some()) {
+							match export::choose_range(&window) {
+								Some(range) => range,
+								None => continue
+							}
+						} else { ExportRange::Full };
+						let template = match export::choose_name_template(&window, &mut settings) {
+							Some(template) => template,
+							None => continue
+						};
+
 						window.set_cursor(Cursor::Wait);
 
 						let mut skipped = String::new();
 						let mut index: usize = 0;
+						let cancel = Cancel::new();
+						let total = file_list.archive.items.len();
+						let mut progress = progress::ProgressDialog::cancellable(&window, "Exporting", total, &cancel);
+						let mut outcome = Ok(());
 
-						while let Some(sound_name) = file_list.get_label_of(index) {
-							let list_item = file_list.items.get_mut(index).expect("Failed to find internal list item");
-							match list_item.get_audio_wav(None) {
+						while let Some(list_item) = file_list.archive.items.get_mut(index) {
+							// Labels may have a status or friendly name, so use the item's own name
+							let sound_name = format!("{}.{}", list_item.name, list_item.extension);
+							progress.set(index, &sound_name);
+							if let Err(error) = cancel.check() {
+								outcome = Err(error);
+								break
+							}
+							match list_item.get_audio_wav_range(range) {
 								Ok(raw) => {
-									let target_file = save_dialog.filename().join(&format!("{}.wav", sound_name));
+									let format = list_item.extension.to_string();
+									let fields = NameFields { index, id: list_item.id, name: &list_item.name, format: &format, ext: "wav" };
+									let target_file = match expand_name_template(&template, &fields) {
+										Ok(file_name) => save_dialog.filename().join(file_name),
+										Err(error) => {
+											outcome = Err(error);
+											break
+										}
+									};
 
 									if let Err(error) = fs::write(target_file, raw) {
 										error!("{}", error);
-										fltk::dialog::message_title("Error");
-										window.set_cursor(Cursor::Default);
-										alert(&window, &format!("Error writing file:\n{}", error));
+										outcome = Err(format!("Error writing file:\n{}", error));
 										break
 									}
 								},
 								Err(error) => skipped.push_str(&format!("{}: {}\n", sound_name, error))
 							}
 
-							file_list.update_label_of(index);
-							
 							index += 1
 						}
+						// Exporting may have decoded or encoded items
+						file_list.update_labels();
+						progress.close();
+						window.set_cursor(Cursor::Default);
 
+						match outcome {
+							Err(error) if error == CANCELLED => status::announce(&mut window, &format!("Cancelled exporting after {} items", index)),
+							Err(error) => {
+								fltk::dialog::message_title("Error");
+								alert(&window, &error)
+							},
+							Ok(()) => ()
+						}
 						if !skipped.is_empty() {
 							fltk::dialog::message_title("Warning");
 							alert(&window, &format!("The following items were skipped:\n{}", skipped))
 						}
-
-						window.set_cursor(Cursor::Default)
 					}
 				},
+				Message::ExportPreviewReel => {
+					window.set_cursor(Cursor::Wait);
+					export::preview_reel_dialog(&window, &file_list.archive);
+					window.set_cursor(Cursor::Default)
+				},
 				Message::Add => {
-					let item = ListItem::new(format!("new_sound_{}", file_list.items.len() + 1));
-					file_list.add_item(item, &format!("new_sound_{}.idsp", file_list.items.len() + 1));
-					file_list.update_label_of(file_list.items.len() - 1)
+					let mut item = ListItem::new(format!("new_sound_{}", file_list.archive.items.len() + 1));
+					item.extension = settings.default_format();
+					file_list.add_item(item, &format!("new_sound_{}.{}", file_list.archive.items.len() + 1, settings.default_format()));
+					file_list.update_label_of(file_list.archive.items.len() - 1)
+				},
+				Message::AddFolder => {
+					let mut dir_dialog = NativeFileChooser::new(FileDialogType::BrowseDir);
+					if let Some(directory) = settings.last_directory(DialogKind::Audio) {
+						let _ = dir_dialog.set_directory(&directory);
+					}
+					dir_dialog.show();
+
+					if !dir_dialog.filename().is_dir() {
+						continue
+					}
+					settings.set_last_directory(DialogKind::Audio, &dir_dialog.filename());
+
+					let paths = match simple_nus3audio_core::batch::audio_files_in(&dir_dialog.filename()) {
+						Ok(paths) => paths,
+						Err(error) => {
+							fltk::dialog::message_title("Error");
+							alert(&window, &error);
+							continue
+						}
+					};
+
+					files_to_add = Some(paths);
+					s.send(Message::AddFiles)
+				},
+				Message::AddFiles => {
+					let mut paths = match files_to_add.take() {
+						Some(files) => files,
+						None => continue
+					};
+					if let Some((long, warning)) = list::long_imports(&paths, &settings) {
+						fltk::dialog::message_title("Long Audio");
+						match layout::choice2(&window, &format!("{}\nAdd them anyway?", warning), "Add them", "Skip them", "Cancel") {
+							Some(0) => (),
+							Some(1) => paths = paths.into_iter().enumerate()
+								.filter(|(index, _)| !long.contains(index))
+								.map(|(_, path)| path)
+								.collect(),
+							_ => continue
+						}
+					}
+
+					let cancel = Cancel::new();
+					let mut progress = progress::ProgressDialog::cancellable(&window, "Adding files", paths.len(), &cancel);
+					let mut skipped = String::new();
+					let mut cancelled = false;
+					let mut added = 0;
+					for (number, path) in paths.iter().enumerate() {
+						let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+						progress.set(number, &file_name);
+						if cancel.is_cancelled() {
+							cancelled = true;
+							break
+						}
+						match file_list.add_from_file(path, &settings) {
+							Ok(()) => added += 1,
+							Err(error) => {
+								error!("{}", error);
+								skipped.push_str(&format!("{}: {}\n", file_name, error))
+							}
+						}
+					}
+					progress.close();
+
+					if cancelled {
+						status::announce(&mut window, &format!("Cancelled adding files after {} of {}", added, paths.len()))
+					} else {
+						status::announce(&mut window, &format!("Added {} of {} files", added, paths.len()))
+					}
+					if !skipped.is_empty() {
+						fltk::dialog::message_title("Warning");
+						alert(&window, &format!("The following files were skipped:\n{}", skipped))
+					}
+				},
+				Message::GenerateSilence => {
+					let has_selection = file_list.selected().is_some();
+					if let Some((target, wav)) = generate::silence_dialog(&window, has_selection) {
+						if let Err(error) = generate::apply(&mut file_list, &mut playback, target, wav, "silence") {
+							error!("{}", error);
+							fltk::dialog::message_title("Error");
+							alert(&window, &error)
+						}
+					}
+				},
+				Message::GenerateTone => {
+					let has_selection = file_list.selected().is_some();
+					if let Some((target, wav)) = generate::tone_dialog(&window, has_selection) {
+						if let Err(error) = generate::apply(&mut file_list, &mut playback, target, wav, "tone") {
+							error!("{}", error);
+							fltk::dialog::message_title("Error");
+							alert(&window, &error)
+						}
+					}
 				},
 				Message::Remove => {
-					if let Some((index, _)) = file_list.selected() {
-						file_list.remove(index)
+					if let Some((index, name)) = file_list.selected() {
+						if settings.confirm_remove() {
+							let item = &file_list.archive.items[index];
+							let size = match item.save_size() {
+								Some(size) if item.save_source() == SaveSource::Encode => format!("about {}", human_readable_size(size as u64)),
+								Some(size) => human_readable_size(size as u64),
+								None => "empty".to_owned()
+							};
+							let id = item.id.map(|id| format!(", tone ID {}", id)).unwrap_or_default();
+							fltk::dialog::message_title("Remove");
+							let question = format!("Remove {}?\n{} format, {}{}\n\nIt can be restored from Edit > Trash until another file is opened.", item.name, item.extension.to_string().to_uppercase(), size, id);
+							match layout::choice2(&window, &question, "Remove", "Remove, don't ask again", "Cancel") {
+								Some(0) => (),
+								Some(1) => settings.set_confirm_remove(false),
+								_ => continue
+							}
+						}
+						file_list.remove(index);
+						playback.on_remove(index);
+						status::announce(&mut window, &format!("Moved {} to the trash", name))
 					} else {
 						fltk::dialog::message_title("Alert");
 						alert(&window, "Nothing is selected.");
 					}
 				},
+				Message::RemoveAll => {
+					let count = file_list.archive.items.len();
+					if count == 0 {
+						continue
+					}
+					fltk::dialog::message_title("Remove all sounds");
+					let question = format!("This removes all {} items from {}, keeping the file open.\nThey can be restored from Edit > Trash until another file is opened.\n\nType {} to remove them:", count, file_list.archive.name, count);
+					match layout::input(&window, &question, "") {
+						Some(answer) if answer.trim() == count.to_string() => (),
+						Some(_) => {
+							fltk::dialog::message_title("Alert");
+							alert(&window, "Nothing was removed, the number didn't match.");
+							continue
+						},
+						None => continue
+					}
+					playback.stop_sink();
+					file_list.remove_all();
+					status::announce(&mut window, &format!("Moved {} items to the trash", count))
+				},
+				Message::ShowTrash => {
+					match trash::show(&file_list.archive, &window) {
+						Some(trash::Action::Restore(trash_index)) => match file_list.restore(trash_index) {
+							Ok(index) => {
+								playback.on_insert(index);
+								file_list.select(index);
+								let item = &file_list.archive.items[index];
+								status::announce(&mut window, &format!("Restored {}.{}", item.name, item.extension))
+							},
+							Err(error) => {
+								error!("{}", error);
+								fltk::dialog::message_title("Error");
+								alert(&window, &error)
+							}
+						},
+						Some(trash::Action::Empty) => {
+							let count = file_list.archive.trash.len();
+							file_list.archive.trash.clear();
+							status::announce(&mut window, &format!("Dropped {} items from the trash", count))
+						},
+						None => ()
+					}
+				},
+				Message::JumpTo => {
+					if file_list.archive.items.is_empty() {
+						continue
+					}
+					fltk::dialog::message_title("Jump to item");
+					let query = match layout::input(&window, "Index, # and a tone ID, or the start of a name:", "") {
+						Some(query) if !query.trim().is_empty() => query,
+						_ => continue
+					};
+					match file_list.find(&query) {
+						Some(index) => file_list.select(index),
+						None => {
+							fltk::dialog::message_title("Alert");
+							alert(&window, &format!("No item matches {:?}.", query.trim()))
+						}
+					}
+				},
+				Message::ToggleBookmark => {
+					if let Some((index, name)) = file_list.selected() {
+						match file_list.archive.toggle_bookmark(index) {
+							Ok(bookmarked) => status::announce(&mut window, &format!("{} {}", if bookmarked { "Bookmarked" } else { "Removed bookmark of" }, name)),
+							Err(error) => {
+								error!("{}", error);
+								fltk::dialog::message_title("Error");
+								alert(&window, &format!("Could not save the bookmark:\n{}", error))
+							}
+						}
+						file_list.update_label_of(index)
+					} else {
+						fltk::dialog::message_title("Alert");
+						alert(&window, "Nothing is selected.");
+					}
+				},
+				Message::NextBookmark | Message::PreviousBookmark => {
+					let selected = file_list.selected().map(|(index, _)| index);
+					if let Some(index) = file_list.archive.next_bookmark(selected, matches!(e, Message::PreviousBookmark)) {
+						file_list.select(index)
+					}
+				},
 				Message::Properties => {
 					let (index, _, _) = if let Some((index, _)) = file_list.selected() {
-						let list_item = file_list.items.get_mut(index).expect("Failed to find internal list item");
+						let archive = &mut file_list.archive;
+						let list_item = archive.items.get_mut(index).expect("Failed to find internal list item");
+						let old_name = list_item.name.clone();
+						let mut notes = archive.project.note(&old_name).to_owned();
+						let source = archive.project.sources.get(&old_name).map(|source| source.as_str());
 
-						if item_properties::configure(list_item, &window) {
+						if item_properties::configure(list_item, &mut notes, source, &window, &mut settings) {
 							// Item was modified
-							file_list.modified = true
+							archive.modified = true
 						}
-						(index, list_item.name.clone(), list_item.extension.clone())
+
+						let configured = (index, list_item.name.clone(), list_item.extension.clone());
+
+						// Notes and bookmarks aren't part of the nus3audio, so they're saved right away
+						let new_name = &configured.1;
+						if *new_name != old_name || notes != archive.project.note(&old_name) {
+							archive.project.rename(&old_name, new_name);
+							archive.project.set_note(new_name, &notes);
+							if let Err(error) = archive.save_project() {
+								error!("{}", error);
+								fltk::dialog::message_title("Error");
+								alert(&window, &format!("Could not save the notes:\n{}", error))
+							}
+						}
+						configured
 					} else {
 						fltk::dialog::message_title("Alert");
 						alert(&window, "Nothing is selected.");
@@ -545,35 +1580,152 @@ fn main() {
 					playback.on_update()
 				},
 				Message::Replace => {
-					if let Some((index, _)) = file_list.selected() {
+					let path = replacement_path.take();
+					if let Some((index, name)) = file_list.selected() {
 						window.set_cursor(Cursor::Wait);
-						if let Err(error) = file_list.replace(index, &settings) {
+						if let Err(error) = file_list.replace(index, path, &mut settings, &window, &mut playback) {
 							error!("{}", error);
 							fltk::dialog::message_title("Error");
 							window.set_cursor(Cursor::Default);
 							alert(&window, &error.to_string());
 							continue
 						}
-						// Update the label of the item
-						file_list.update_label_of(index);
 						window.set_cursor(Cursor::Default);
+						file_list.check_resampled_loops(&window, Some(index), "Keep them", "");
+						match file_list.archive.project.sources.get(&file_list.archive.items[index].name) {
+							Some(source) => status::announce(&mut window, &format!("Replaced {} from {}", name, source)),
+							None => status::announce(&mut window, &format!("Replaced {}", name))
+						}
 					} else {
 						fltk::dialog::message_title("Alert");
 						alert(&window, "Nothing is selected.");
 					}
 				},
-				Message::Save => {
-					if file_list.path.is_some() {
-						window.set_cursor(Cursor::Wait);
-						if let Err(error) = file_list.save_nus3audio(None, &settings) {
+				Message::ReplaceFromClipboard => {
+					if file_list.selected().is_some() {
+						file_list.paste()
+					} else {
+						fltk::dialog::message_title("Alert");
+						alert(&window, "Nothing is selected.");
+					}
+				},
+				Message::Pasted => {
+					let text = file_list.take_pasted().unwrap_or_default();
+					match paths_from_text(&text).into_iter().find(|path| path.is_file()) {
+						Some(path) => {
+							replacement_path = Some(path);
+							s.send(Message::Replace)
+						},
+						None => {
+							fltk::dialog::message_title("Alert");
+							alert(&window, "The clipboard doesn't hold the path of a file.");
+						}
+					}
+				},
+				Message::Dropped => {
+					let text = file_list.take_pasted().unwrap_or_default();
+					let mut paths: Vec<std::path::PathBuf> = paths_from_text(&text).into_iter().filter(|path| path.is_file()).collect();
+					match paths.len() {
+						0 => {
+							fltk::dialog::message_title("Alert");
+							alert(&window, "Only files can be dropped here.");
+						},
+						// One file replaces the selected item, like Replace does
+						1 if file_list.selected().is_some() => {
+							replacement_path = paths.pop();
+							s.send(Message::Replace)
+						},
+						// Anything else is added at the end, in the order it was dropped
+						count => {
+							let mut names: Vec<String> = paths.iter()
+								.take(MAX_LISTED_FILES)
+								.map(|path| path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default())
+								.collect();
+							if count > MAX_LISTED_FILES {
+								names.push(format!("...and {} more", count - MAX_LISTED_FILES))
+							}
+							let files = if count == 1 { "1 file".to_owned() } else { format!("{} files", count) };
+							fltk::dialog::message_title("Add files");
+							if layout::choice2(&window, &format!("Add {} as new items, in this order?\n\n{}", files, names.join("\n")), "Add", "Cancel", "") == Some(0) {
+								files_to_add = Some(paths);
+								s.send(Message::AddFiles)
+							}
+						}
+					}
+				},
+				Message::DragOut => {
+					let index = match file_list.selected() {
+						Some((index, _)) => index,
+						None => {
+							file_list.drag_out(None);
+							continue
+						}
+					};
+
+					// Binary data can't be written as WAV
+					window.set_cursor(Cursor::Wait);
+					let name = file_list.archive.name.clone();
+					let item = &mut file_list.archive.items[index];
+					let written = if settings.drag_out_encoded() || item.extension == list::AudioExtension::Bin {
+						item.write_cached_encoded(&name, &settings)
+					} else {
+						item.write_cached_wav(&name, &settings)
+					};
+					window.set_cursor(Cursor::Default);
+					match written {
+						Ok(file) => {
+							info!("Dragging {:?} out of the list", file);
+							file_list.drag_out(Some(&file))
+						},
+						Err(error) => {
+							file_list.drag_out(None);
 							error!("{}", error);
 							fltk::dialog::message_title("Error");
-							window.set_cursor(Cursor::Default);
-							alert(&window, &format!("Error saving file:\n{}", error));
+							alert(&window, &error)
+						}
+					}
+				},
+				Message::Save => {
+					if file_list.archive.arc_path.is_some() {
+						// Files from data.arc can't be written back, so they go in a mod folder
+						s.send(Message::ExportToModFolder)
+					} else if file_list.archive.path.is_some() {
+						if let Some(warning) = file_list.over_budget() {
+							fltk::dialog::message_title("Warning");
+							if layout::choice2(&window, &format!("{}\nSave anyway?", warning), "Save", "Go back", "") != Some(0) {
+								continue
+							}
+						}
+						if !file_list.check_resampled_loops(&window, None, "Save anyway", "Go back") {
 							continue
 						}
+						window.set_cursor(Cursor::Wait);
+						let saved = progress::run(&window, "Saving", |progress, cancel| {
+							file_list.save_nus3audio(None, &settings, progress, cancel)
+						});
+						let (warnings, report) = match saved {
+							Ok(saved) => saved,
+							Err(error) if error == CANCELLED => {
+								window.set_cursor(Cursor::Default);
+								status::announce(&mut window, "Cancelled saving, nothing was written");
+								continue
+							},
+							Err(error) => {
+								error!("{}", error);
+								fltk::dialog::message_title("Error");
+								window.set_cursor(Cursor::Default);
+								alert(&window, &format!("Error saving file:\n{}", error));
+								continue
+							}
+						};
 
-						window.set_cursor(Cursor::Default)
+						window.set_cursor(Cursor::Default);
+						status::announce(&mut window, &format!("Saved {}", file_list.archive.name));
+						if !warnings.is_empty() {
+							fltk::dialog::message_title("Warning");
+							alert(&window, &format!("Saved, but it may not work as expected in-game:\n{}", warnings.join("\n")))
+						}
+						save_preview::show_report(&report, &window);
 					} else {
 						// Nothing to save to.
 						s.send(Message::SaveAs)
@@ -585,35 +1737,533 @@ fn main() {
 					save_dialog.show();
 
 					if !save_dialog.filename().to_string_lossy().is_empty() {
+						if let Some(warning) = file_list.over_budget() {
+							fltk::dialog::message_title("Warning");
+							if layout::choice2(&window, &format!("{}\nSave anyway?", warning), "Save", "Go back", "") != Some(0) {
+								continue
+							}
+						}
+						if !file_list.check_resampled_loops(&window, None, "Save anyway", "Go back") {
+							continue
+						}
 						window.set_cursor(Cursor::Wait);
-						if let Err(error) = file_list.save_nus3audio(Some(save_dialog.filename()), &settings) {
+						let saved = progress::run(&window, "Saving", |progress, cancel| {
+							file_list.save_nus3audio(Some(save_dialog.filename()), &settings, progress, cancel)
+						});
+						let (warnings, report) = match saved {
+							Ok(saved) => saved,
+							Err(error) if error == CANCELLED => {
+								window.set_cursor(Cursor::Default);
+								status::announce(&mut window, "Cancelled saving, nothing was written");
+								continue
+							},
+							Err(error) => {
+								error!("{}", error);
+								fltk::dialog::message_title("Error");
+								window.set_cursor(Cursor::Default);
+								alert(&window, &format!("Error saving file:\n{}", error));
+								continue
+							}
+						};
+
+						window.set_cursor(Cursor::Default);
+						status::announce(&mut window, &format!("Saved {}", file_list.archive.name));
+						if !warnings.is_empty() {
+							fltk::dialog::message_title("Warning");
+							alert(&window, &format!("Saved, but it may not work as expected in-game:\n{}", warnings.join("\n")))
+						}
+						save_preview::show_report(&report, &window);
+					}
+				},
+				Message::ExportToModFolder => {
+					if file_list.archive.arc_path.is_none() {
+						fltk::dialog::message_title("Alert");
+						alert(&window, "Only files opened from data.arc know where they go in a mod folder.");
+						continue
+					}
+
+					let mut dir_dialog = NativeFileChooser::new(FileDialogType::BrowseDir);
+					dir_dialog.show();
+
+					if dir_dialog.filename().to_string_lossy().is_empty() {
+						continue
+					}
+
+					let target = match file_list.archive.mod_path(&dir_dialog.filename()) {
+						Ok(target) => target,
+						Err(error) => {
+							fltk::dialog::message_title("Error");
+							alert(&window, &error);
+							continue
+						}
+					};
+					if let Some(parent) = target.parent() {
+						if let Err(error) = fs::create_dir_all(parent) {
+							error!("{}", error);
+							fltk::dialog::message_title("Error");
+							alert(&window, &format!("Error creating directory {:?}:\n{}", parent, error));
+							continue
+						}
+					}
+
+					if let Some(warning) = file_list.over_budget() {
+						fltk::dialog::message_title("Warning");
+						if layout::choice2(&window, &format!("{}\nSave anyway?", warning), "Save", "Go back", "") != Some(0) {
+							continue
+						}
+					}
+					if !file_list.check_resampled_loops(&window, None, "Save anyway", "Go back") {
+						continue
+					}
+					window.set_cursor(Cursor::Wait);
+					let saved = progress::run(&window, "Saving", |progress, cancel| {
+						file_list.save_nus3audio(Some(target), &settings, progress, cancel)
+					});
+					let (warnings, report) = match saved {
+						Ok(saved) => saved,
+						Err(error) if error == CANCELLED => {
+							window.set_cursor(Cursor::Default);
+							status::announce(&mut window, "Cancelled saving, nothing was written");
+							continue
+						},
+						Err(error) => {
 							error!("{}", error);
 							fltk::dialog::message_title("Error");
 							window.set_cursor(Cursor::Default);
 							alert(&window, &format!("Error saving file:\n{}", error));
 							continue
 						}
+					};
 
-						window.set_cursor(Cursor::Default)
+					window.set_cursor(Cursor::Default);
+					status::announce(&mut window, &format!("Saved {}", file_list.archive.name));
+					if !warnings.is_empty() {
+						fltk::dialog::message_title("Warning");
+						alert(&window, &format!("Saved, but it may not work as expected in-game:\n{}", warnings.join("\n")))
 					}
+					save_preview::show_report(&report, &window);
 				},
 				Message::PlayPause => {
-					if let Err(error) = playback.on_press(&mut file_list) {
-						error!("{}", error);
-						fltk::dialog::message_title("Error");
-						alert(&window, &error);
+					match playback.on_press(&mut file_list) {
+						Ok(()) if playback.is_playing() => {
+							let name = file_list.selected().map(|(_, name)| name).unwrap_or_default();
+							match playback.take_notice() {
+								Some(notice) => status::announce(&mut window, &format!("{}, playing {}", notice, name)),
+								None => status::announce(&mut window, &format!("Playing {}", name))
+							}
+						},
+						Ok(()) => status::announce(&mut window, "Paused"),
+						Err(error) => {
+							error!("{}", error);
+							fltk::dialog::message_title("Error");
+							alert(&window, &error);
+						}
+					}
+				},
+				Message::PlayFromLoopStart => {
+					match playback.play_from_loop_start(&mut file_list) {
+						Ok(()) => {
+							let name = file_list.selected().map(|(_, name)| name).unwrap_or_default();
+							match playback.take_notice() {
+								Some(notice) => status::announce(&mut window, &format!("{}, playing {} from its loop start", notice, name)),
+								None => status::announce(&mut window, &format!("Playing {} from its loop start", name))
+							}
+						},
+						Err(error) => {
+							error!("{}", error);
+							fltk::dialog::message_title("Error");
+							alert(&window, &error);
+						}
+					}
+				},
+				Message::Stop => {
+					playback.stop_sink();
+					status::announce(&mut window, "Stopped")
+				},
+				Message::Selected => if settings.prefetch() {
+					prefetch::start(&mut file_list, s, Message::Prefetched)
+				},
+				Message::Prefetched => prefetch::finish(&mut file_list),
+				Message::ReleaseAudio => {
+					file_list.release_idle_audio(&settings);
+					List::queue_release(s)
+				},
+				Message::Update => {
+					playback.on_update();
+					if let Some(notice) = playback.take_notice() {
+						status::announce(&mut window, &notice)
 					}
 				},
-				Message::Stop => playback.stop_sink(),
-				Message::Update => playback.on_update(),
 				Message::Seek => playback.on_seek(),
+				Message::SeekBackward => playback.seek_by(-playback::SEEK_STEP),
+				Message::SeekForward => playback.seek_by(playback::SEEK_STEP),
+				Message::PlaybackPan => playback.on_pan_changed(),
+				Message::PlaybackChannels => playback.on_channels_changed(),
+				Message::PlaybackRate => playback.on_rate_changed(),
+				Message::ResetPlaybackRate => playback.reset_rate(),
+				Message::PreviewLoops(count) => {
+					info!("Preview loops: {}", count);
+					settings.set_preview_loops(count);
+					playback.set_loop_count(count)
+				},
 				Message::ConfigureVGAudioCliPath => settings.configure_vgaudio_cli_path(&window),
 				#[cfg(not(target_os = "windows"))]
 				Message::ConfigureRuntimePath => settings.configure_vgaudio_cli_prepath(&window),
 				Message::ConfigureVgmstreamPath => settings.configure_vgmstream_path(&window),
+				Message::Preferences => {
+					if preferences::configure(&mut settings, &window) {
+						info!("Preferences were changed")
+					}
+				},
+				Message::ConfigureShortcuts => {
+					if preferences::configure_shortcuts(&mut settings, &window) {
+						info!("Shortcuts were changed");
+						shortcuts::apply(&mut menu, &settings)
+					}
+				},
+				Message::ConfigureToolTimeout => settings.configure_tool_timeout(&window),
+				Message::ConfigureUiScale => {
+					if settings.configure_ui_scale(&window) {
+						info!("UI scale: {}", settings.ui_scale());
+						settings.apply_scale();
+						s.send(Message::ReLay)
+					}
+				},
+				Message::ConfigureFontSize => settings.configure_font_size(&window),
+				Message::ConfigureSizeBudget => {
+					fltk::dialog::message_title("Size budget");
+					let current = file_list.budget().map(|budget| budget.to_string()).unwrap_or_default();
+					if let Some(budget) = layout::input(&window, "The largest this file should be when saved, in bytes.\nLeave empty for no budget.", &current) {
+						match budget.trim() {
+							"" => file_list.set_budget(None),
+							budget => match budget.parse::<u64>() {
+								Ok(budget) => file_list.set_budget(Some(budget)),
+								Err(_) => {
+									fltk::dialog::message_title("Error");
+									alert(&window, &format!("{} is not a valid size.", budget))
+								}
+							}
+						}
+					}
+				},
+				Message::ToggleKeepCache => {
+					let keep_cache = !settings.keep_cache();
+					info!("Keep cache between sessions: {}", keep_cache);
+					settings.set_keep_cache(keep_cache)
+				},
+				Message::TogglePrefetch => {
+					let prefetch = !settings.prefetch();
+					info!("Prefetch neighbouring items: {}", prefetch);
+					settings.set_prefetch(prefetch);
+					if !prefetch {
+						for item in file_list.archive.items.iter_mut() {
+							item.drop_preview()
+						}
+					}
+				},
+				Message::ToggleToolbar => {
+					let show_toolbar = !toolbar.shown();
+					info!("Show toolbar: {}", show_toolbar);
+					toolbar.set_shown(show_toolbar);
+					settings.set_show_toolbar(show_toolbar);
+					s.send(Message::ReLay)
+				},
+				Message::ToggleHashes => {
+					let show_hashes = !settings.show_hashes();
+					info!("Show content hashes: {}", show_hashes);
+					settings.set_show_hashes(show_hashes);
+					file_list.set_show_hashes(show_hashes)
+				},
+				Message::ShowConsole => console.show(&window),
+				Message::ShowDiagnostics => {
+					// Running the tools can take a moment
+					window.set_cursor(Cursor::Wait);
+					app::flush();
+					let report = diagnostics::report(&settings, &playback.audio_status());
+					window.set_cursor(Cursor::Default);
+					diagnostics::show(&report, &window)
+				},
+				Message::SelfTest => setup::self_test(&settings, &mut playback, &window),
+				Message::BatchProcess => {
+					// Progress is written to the console
+					console.show(&window);
+					batch::run_dialog(&window, &settings)
+				},
+				Message::BatchConvert => {
+					console.show(&window);
+					batch::convert_dialog(&window, &settings)
+				},
+				Message::ToggleWatchFolder => {
+					if watcher.take().is_some() {
+						info!("Stopped watching for replacements");
+						continue
+					}
+
+					let mut dir_dialog = NativeFileChooser::new(FileDialogType::BrowseDir);
+					dir_dialog.show();
+
+					if dir_dialog.filename().is_dir() {
+						info!("Watching {:?} for replacements", dir_dialog.filename());
+						watch_generation += 1;
+						watcher = Some(watch::Watcher::new(dir_dialog.filename(), watch_generation));
+						watch::queue_poll(s, watch_generation)
+					} else if let Some(mut item) = menu.find_item("&Tools/&Watch folder for replacements...\t") {
+						item.clear()
+					}
+				},
+				Message::WatchPoll(generation) => {
+					let changed = match &mut watcher {
+						Some(watcher) if watcher.generation == generation => watcher.poll(),
+						// Stopped or restarted watching since the last check
+						_ => continue
+					};
+
+					for path in changed {
+						let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+						let index = match file_list.archive.items.iter().position(|item| item.name == stem || safe_file_name(&item.name) == stem) {
+							Some(index) => index,
+							None => continue
+						};
+
+						console::log(&format!("Re-importing {} from {:?}\n", stem, path));
+						if let Err(error) = file_list.replace_with(index, &mut playback, |archive| archive.replace(index, &path, &settings)) {
+							error!("{}", error);
+							console::log(&format!("Could not re-import {}:\n{}\n", stem, error))
+						}
+					}
+					console::pump();
+					watch::queue_poll(s, generation)
+				},
+				Message::RunScript => {
+					let mut file_dialog = NativeFileChooser::new(FileDialogType::BrowseFile);
+					file_dialog.set_filter("Rhai scripts\t*.rhai");
+					file_dialog.show();
+
+					if file_dialog.filename().exists() {
+						playback.stop_sink();
+						console.show(&window);
+						window.set_cursor(Cursor::Wait);
+
+						let result = script::run(&file_dialog.filename(), &mut file_list.archive, &settings);
+
+						// Reload the list, since the script could have changed anything
+						file_list.reload();
+						window.set_cursor(Cursor::Default);
+
+						if let Err(error) = result {
+							error!("{}", error);
+							fltk::dialog::message_title("Error");
+							alert(&window, &error)
+						}
+					}
+				},
+				Message::ExportManifest => {
+					let mut save_dialog = NativeFileChooser::new(FileDialogType::BrowseSaveFile);
+					save_dialog.set_filter("CSV files\t*.csv");
+					let _ = save_dialog.set_preset_file(&format!("{}.csv", file_list.archive.name.trim_end_matches(".nus3audio")));
+					save_dialog.show();
+
+					if save_dialog.filename().to_string_lossy().is_empty() {
+						continue
+					}
+					let target = save_dialog.filename().with_extension("csv");
+					if let Err(error) = fs::write(&target, manifest::write(&file_list.archive)) {
+						error!("{}", error);
+						fltk::dialog::message_title("Error");
+						alert(&window, &format!("Error writing file:\n{}", error))
+					} else {
+						status::announce(&mut window, &format!("Wrote the manifest of {} items", file_list.archive.items.len()))
+					}
+				},
+				Message::ImportManifest => {
+					let mut file_dialog = NativeFileChooser::new(FileDialogType::BrowseFile);
+					file_dialog.set_filter("CSV files\t*.csv");
+					file_dialog.show();
+
+					if !file_dialog.filename().exists() {
+						continue
+					}
+					let changes = fs::read_to_string(file_dialog.filename())
+						.map_err(|error| format!("Error reading file:\n{}", error))
+						.and_then(|text| manifest::apply(&mut file_list.archive, &text));
+					let changes = match changes {
+						Ok(changes) => changes,
+						Err(error) => {
+							error!("{}", error);
+							fltk::dialog::message_title("Error");
+							alert(&window, &error);
+							continue
+						}
+					};
+
+					if !changes.is_empty() {
+						// Items may have moved out from under what's playing
+						playback.stop_sink();
+						file_list.reload();
+						// Notes and bookmarks follow renamed items, and they're saved right away
+						if changes.renamed > 0 {
+							if let Err(error) = file_list.archive.save_project() {
+								error!("{}", error);
+								fltk::dialog::message_title("Error");
+								alert(&window, &format!("Could not save the notes:\n{}", error))
+							}
+						}
+					}
+					status::announce(&mut window, &format!("Renamed {}, re-IDed {} and moved {} items", changes.renamed, changes.reidentified, changes.moved))
+				},
+				Message::InspectStructure => {
+					let path = match &file_list.archive.path {
+						Some(path) if path.exists() => path.clone(),
+						_ => {
+							fltk::dialog::message_title("Alert");
+							alert(&window, "This file hasn't been saved yet, so there is nothing on disk to inspect.");
+							continue
+						}
+					};
+					if file_list.archive.modified {
+						fltk::dialog::message_title("Alert");
+						alert(&window, "This shows the file as it was last saved, without the changes made since.")
+					}
+
+					let result = std::fs::read(simple_nus3audio_core::util::long_path(&path))
+						.map_err(|error| format!("Error reading {:?}:\n{}", path, error))
+						.and_then(|bytes| simple_nus3audio_core::structure::inspect(&bytes));
+					match result {
+						Ok(structure) => structure::show(&file_list.archive.name, &structure, &window),
+						Err(error) => {
+							error!("{}", error);
+							fltk::dialog::message_title("Error");
+							alert(&window, &error)
+						}
+					}
+				},
+				Message::NullTest => {
+					let (index, sound_name) = match file_list.selected() {
+						Some(selected) => selected,
+						None => {
+							fltk::dialog::message_title("Alert");
+							alert(&window, "Nothing is selected.");
+							continue
+						}
+					};
+
+					// A replaced item can be compared with the audio it replaced
+					let against_original = if file_list.archive.items[index].has_original_audio() {
+						fltk::dialog::message_title(&format!("Null test of {}", sound_name));
+						match layout::choice2(&window, "Compare the replacement with the original audio, or with itself once encoded?", "Original", "Encoded", "Cancel") {
+							Some(0) => true,
+							Some(1) => false,
+							_ => continue
+						}
+					} else { false };
+
+					window.set_cursor(Cursor::Wait);
+					let name = file_list.archive.name.clone();
+					let result = if against_original {
+						file_list.archive.items[index].null_test_original()
+					} else {
+						file_list.archive.items[index].null_test(&name, &settings)
+					};
+					// It may have been encoded just now
+					file_list.update_label_of(index);
+					window.set_cursor(Cursor::Default);
+
+					match result {
+						Ok(test) => {
+							let compared = if against_original { "Original audio against the replacement" } else { "Audio against the encoded audio" };
+							fltk::dialog::message_title(&format!("Null test of {}", sound_name));
+							alert(&window, &format!("{}:\n{}", compared, test.summary()))
+						},
+						Err(error) => {
+							error!("{}", error);
+							fltk::dialog::message_title("Error");
+							alert(&window, &error)
+						}
+					}
+				},
 				Message::WelcomeGreeting => {
 					settings.set_first_time(true);
-					settings.first_time_greeting(&window, s)
+					settings.first_time_greeting(&window)
+				},
+				Message::CheckForUpdates => {
+					window.set_cursor(Cursor::Wait);
+					update::check_in_background(s, Message::UpdateChecked(true))
+				},
+				Message::ToggleCheckUpdates => {
+					let check_updates = !settings.check_updates();
+					info!("Check for updates on startup: {}", check_updates);
+					settings.set_check_updates(check_updates)
+				},
+				Message::UpdateChecked(manual) => {
+					window.set_cursor(Cursor::Default);
+					match update::take_result() {
+						Some(Ok(Some(release))) => {
+							fltk::dialog::message_title("Update Available");
+							let response = layout::choice2(&window, &format!("Version {} is available.\nThis is version {}.", release.version, env!("CARGO_PKG_VERSION")), "Open release page", "Dismiss", "");
+							if let Some(0) = response {
+								let _ = open::that(&release.url);
+							}
+						},
+						Some(Ok(None)) => if manual {
+							fltk::dialog::message_title("No Updates");
+							alert(&window, &format!("Version {} is the latest version.", env!("CARGO_PKG_VERSION")))
+						},
+						Some(Err(error)) => {
+							warn!("{}", error);
+							if manual {
+								fltk::dialog::message_title("Error");
+								alert(&window, &error)
+							}
+						},
+						None => {}
+					}
+				},
+				Message::PreviewSave => save_preview::show(&file_list.archive, &window),
+				Message::ShowInFileManager => {
+					match file_list.archive.path.as_ref().and_then(|path| path.parent()) {
+						Some(directory) => {
+							info!("Opening {:?} in the file manager", directory);
+							if let Err(error) = open::that(directory) {
+								error!("{}", error);
+								fltk::dialog::message_title("Error");
+								alert(&window, &format!("Could not open the file manager:\n{}", error))
+							}
+						},
+						None => {
+							fltk::dialog::message_title("Alert");
+							alert(&window, "This nus3audio file hasn't been saved anywhere yet.")
+						}
+					}
+				},
+				Message::ShowSoundInFileManager => {
+					let index = match file_list.selected() {
+						Some((index, _)) => index,
+						None => {
+							fltk::dialog::message_title("Alert");
+							alert(&window, "Nothing is selected.");
+							continue
+						}
+					};
+
+					window.set_cursor(Cursor::Wait);
+					let written = file_list.archive.items[index].write_cached_wav(&file_list.archive.name, &settings);
+					window.set_cursor(Cursor::Default);
+					match written {
+						Ok(wav_file) => {
+							let directory = wav_file.parent().unwrap_or(&wav_file);
+							info!("Opening {:?} in the file manager", directory);
+							if let Err(error) = open::that(directory) {
+								error!("{}", error);
+								fltk::dialog::message_title("Error");
+								alert(&window, &format!("Could not open the file manager:\n{}", error))
+							}
+						},
+						Err(error) => {
+							error!("{}", error);
+							fltk::dialog::message_title("Error");
+							alert(&window, &error)
+						}
+					}
 				},
 				Message::Manual => {
 					info!("Opening manual at {}", MANUAL_URL);
@@ -621,7 +2271,7 @@ fn main() {
 				},
 				Message::Quit(code) => {
 					// True if we should quit
-					let response = if file_list.modified {
+					let response = if file_list.archive.modified {
 						fltk::dialog::message_title("Warning");
 						let response = layout::choice2(&window, "You have currently unsaved changes.\nWould you still like to quit?", "Quit", "Go back", "");
 
@@ -638,7 +2288,12 @@ fn main() {
 					};
 
 					if response {
+						settings.set_main_window_geometry(window.x(), window.y(), window.width(), window.height());
 						settings.save();
+						if settings.keep_cache() {
+							fltk::app::quit();
+							std::process::exit(code)
+						}
 						if let Err(error) = Settings::reset_cache() {
 							error!("Failed to reset the cache directory");
 							error!("{}", error);
@@ -654,6 +2309,9 @@ fn main() {
 	}
 
 	settings.save();
+	if settings.keep_cache() {
+		return
+	}
 	if let Err(error) = Settings::reset_cache() {
 		error!("Failed to reset the cache directory");
 		error!("{}", error);
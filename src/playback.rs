@@ -1,8 +1,11 @@
 use fltk::{
-	prelude::{ WidgetExt, ValuatorExt },
-	button::Button,
+	prelude::{ ButtonExt, WidgetBase, WidgetExt, ValuatorExt },
+	button::{ Button, ToggleButton },
+	draw,
+	enums::{ Color, Event, FrameType, MouseButton },
+	menu::Choice,
 	// valuator::HorNiceSlider
-	valuator::HorFillSlider
+	valuator::{ Dial, HorFillSlider }
 };
 use kira::{
 	manager::{
@@ -16,43 +19,282 @@ use kira::{
 	sound::{
 		EndPosition,
 		PlaybackPosition,
+		PlaybackRate,
 		PlaybackState,
 		static_sound::{
 			StaticSoundData,
 			StaticSoundHandle,
 			StaticSoundSettings
 		},
+		streaming::{
+			StreamingSoundData,
+			StreamingSoundHandle,
+			StreamingSoundSettings
+		},
+		FromFileError
+	},
+	track::{
+		effect::panning_control::{
+			PanningControlBuilder,
+			PanningControlHandle
+		},
+		TrackBuilder,
+		TrackHandle
 	},
 	tween::{
 		Easing,
 		Tween
-	}
+	},
+	CommandError,
+	Volume
+};
+use simple_nus3audio_core::codec::{ EncodedFile, EncodingType };
+use crate::list::ListItem;
+use std::{
+	cell::RefCell,
+	io::Cursor,
+	rc::Rc,
+	time::Instant
 };
-use std::io::Cursor;
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
 
 /// Play button text. FLTK gives us the option to use a nice, fancy icon.
 const PLAY: &str = "@>";
 /// Pause button text.
 const PAUSE: &str = "@||";
-/// The time between UI updates to the slider while actively playing audio.
+/// Tooltip of the play button while nothing is playing.
+const PLAY_TOOLTIP: &str = "Play selected audio";
+/// Tooltip of the play button while audio is playing.
+const PAUSE_TOOLTIP: &str = "Pause playing audio";
+/// Play from loop start button text.
+const LOOP_START: &str = "@|>";
+/// The longest time between UI updates to the slider while actively playing audio.
 const UPDATE_FREQUENCY: f64 = 0.1;
+/// The shortest time between UI updates to the slider, for short audio where
+/// each update moves the slider by more than a pixel.
+const MIN_UPDATE_INTERVAL: f64 = 1.0 / 60.0;
+/// How many columns of the waveform drawn behind the slider are read.
+const WAVEFORM_COLUMNS: usize = 1024;
+/// How far seeking with the keyboard moves, in seconds.
+pub const SEEK_STEP: f64 = 5.0;
+
+/// Tooltip of the slider.
+const SLIDER_TOOLTIP: &str = "Position of the playing audio";
+/// Tooltip of the slider while the audio has replaced other audio that isn't saved yet.
+const SLIDER_ORIGINAL_TOOLTIP: &str = "Position of the playing audio.\nThe yellow outline is the audio it replaced, until the file is saved";
+
+/// The speeds audio can be previewed at, as factors of its normal speed.
+const SPEEDS: [f64; 8] = [0.5, 0.75, 0.9, 1.0, 1.1, 1.25, 1.5, 2.0];
+/// Index of the normal speed in [SPEEDS].
+const NORMAL_SPEED: usize = 3;
+/// How far the pitch can be shifted either way, in semitones.
+const MAX_SEMITONES: i32 = 12;
+
+/// Labels and tooltips of the channel toggles, left then right.
+const CHANNELS: [(&str, &str); 2] = [
+	("L", "Hear the left channel of stereo audio. Turn the right one off to hear the left alone"),
+	("R", "Hear the right channel of stereo audio. Turn the left one off to hear the right alone")
+];
+
+/// Audio at least this big, in bytes, is streamed instead of decoded in full before playing.
+const STREAMING_THRESHOLD: usize = 1024 * 1024;
+
+/// What's drawn behind the slider, turning it into a timeline of the playing audio.
+#[derive(Default)]
+struct Timeline {
+	/// A coarse waveform, see [ListItem::waveform](crate::list::ListItem::waveform).
+	peaks: Vec<f32>,
+	/// The waveform of the audio the item had before it was replaced, and its
+	/// length as a fraction of the length of the playing audio.
+	/// See [ListItem::original_waveform](crate::list::ListItem::original_waveform).
+	original: Option<(Vec<f32>, f64)>,
+	/// The loop region, as fractions of the length of the audio.
+	loop_region: Option<(f64, f64)>
+}
+
+impl Timeline {
+	/// Draw this timeline as the background of `slider`, before the slider itself.
+	fn draw(&self, slider: &HorFillSlider) {
+		// The slider has no box of its own, so the timeline stays behind its fill
+		draw::draw_box(FrameType::DownBox, slider.x(), slider.y(), slider.w(), slider.h(), slider.color());
+		// Stay inside the frame of the slider
+		let (x, y, w, h) = (slider.x() + 2, slider.y() + 2, slider.w() - 4, slider.h() - 4);
+		if w <= 0 || h <= 0 { return }
+
+		draw::push_clip(x, y, w, h);
+		if !self.peaks.is_empty() {
+			draw::set_draw_color(Color::Dark3);
+			let middle = y + h / 2;
+			for column in 0..w {
+				let peak = self.peaks[column as usize * self.peaks.len() / w as usize];
+				let half = (peak * h as f32 / 2.0) as i32;
+				draw::draw_line(x + column, middle - half, x + column, middle + half);
+			}
+		}
+		// The original is drawn as an outline over the replacement, so both stay visible
+		if let Some((peaks, length)) = self.original.as_ref().filter(|(peaks, length)| !peaks.is_empty() && *length > 0.0) {
+			draw::set_draw_color(Color::DarkYellow);
+			let middle = y + h / 2;
+			let width = ((w as f64 * length) as i32).max(1);
+			let mut last: Option<i32> = None;
+			for column in 0..width.min(w) {
+				let peak = peaks[column as usize * peaks.len() / width as usize];
+				let half = (peak * h as f32 / 2.0) as i32;
+				let previous = last.unwrap_or(half);
+				draw::draw_line(x + column - 1, middle - previous, x + column, middle - half);
+				draw::draw_line(x + column - 1, middle + previous, x + column, middle + half);
+				last = Some(half);
+			}
+			// Mark where the original ends, if it's shorter
+			if width < w {
+				draw::draw_line(x + width, y, x + width, y + h - 1);
+			}
+		}
+		if let Some((start, end)) = self.loop_region {
+			let start = x + (start * w as f64) as i32;
+			let end = x + (end * w as f64) as i32;
+			draw::set_draw_color(Color::Red);
+			draw::draw_line(start, y, start, y + h - 1);
+			draw::draw_line(end, y, end, y + h - 1);
+			draw::draw_line(start, y, end, y);
+		}
+		draw::pop_clip();
+	}
+}
+
+/// A handle to either kind of playing sound.
+enum Handle {
+	/// Audio decoded in full before playing.
+	Static(StaticSoundHandle),
+	/// Audio decoded while it plays.
+	Streaming(StreamingSoundHandle<FromFileError>)
+}
+
+impl Handle {
+	fn position(&self) -> f64 {
+		match self {
+			Handle::Static(handle) => handle.position(),
+			Handle::Streaming(handle) => handle.position()
+		}
+	}
+
+	fn state(&self) -> PlaybackState {
+		match self {
+			Handle::Static(handle) => handle.state(),
+			Handle::Streaming(handle) => handle.state()
+		}
+	}
+
+	fn seek_to(&mut self, position: f64) -> Result<(), CommandError> {
+		match self {
+			Handle::Static(handle) => handle.seek_to(position),
+			Handle::Streaming(handle) => handle.seek_to(position)
+		}
+	}
+
+	fn pause(&mut self, tween: Tween) -> Result<(), CommandError> {
+		match self {
+			Handle::Static(handle) => handle.pause(tween),
+			Handle::Streaming(handle) => handle.pause(tween)
+		}
+	}
+
+	fn resume(&mut self, tween: Tween) -> Result<(), CommandError> {
+		match self {
+			Handle::Static(handle) => handle.resume(tween),
+			Handle::Streaming(handle) => handle.resume(tween)
+		}
+	}
+
+	fn stop(&mut self, tween: Tween) -> Result<(), CommandError> {
+		match self {
+			Handle::Static(handle) => handle.stop(tween),
+			Handle::Streaming(handle) => handle.stop(tween)
+		}
+	}
+
+	fn set_panning(&mut self, panning: f64, tween: Tween) -> Result<(), CommandError> {
+		match self {
+			Handle::Static(handle) => handle.set_panning(panning, tween),
+			Handle::Streaming(handle) => handle.set_panning(panning, tween)
+		}
+	}
+
+	fn set_volume(&mut self, amplitude: f64, tween: Tween) -> Result<(), CommandError> {
+		match self {
+			Handle::Static(handle) => handle.set_volume(Volume::Amplitude(amplitude), tween),
+			Handle::Streaming(handle) => handle.set_volume(Volume::Amplitude(amplitude), tween)
+		}
+	}
+
+	fn clear_loop_region(&mut self) -> Result<(), CommandError> {
+		match self {
+			Handle::Static(handle) => handle.set_loop_region(None::<kira::sound::Region>),
+			Handle::Streaming(handle) => handle.set_loop_region(None::<kira::sound::Region>)
+		}
+	}
+
+	fn set_playback_rate(&mut self, rate: f64, tween: Tween) -> Result<(), CommandError> {
+		match self {
+			Handle::Static(handle) => handle.set_playback_rate(PlaybackRate::Factor(rate), tween),
+			Handle::Streaming(handle) => handle.set_playback_rate(PlaybackRate::Factor(rate), tween)
+		}
+	}
+}
 
 /// Struct that keeps the UI play button and controls kira.
 pub struct Playback {
 	/// The play widget.
 	play_widget: Button,
+	/// The button playing from the loop start.
+	loop_start_widget: Button,
 	/// The slider widget.
 	slider_widget: HorFillSlider,
+	/// Where to place the preview between the left and right speakers, from 0 to 1.
+	pan_widget: Dial,
+	/// The track everything is played on, with the panning control that places
+	/// it, if the track could be made.
+	pan_track: Option<(TrackHandle, PanningControlHandle)>,
+	/// Whether or not to hear the left and right channels of stereo audio.
+	channel_widgets: [ToggleButton; 2],
+	/// Whether or not the playing audio is stereo, so the channel toggles apply to it.
+	stereo: bool,
+	/// The speed to preview at, one of [SPEEDS].
+	speed_widget: Choice,
+	/// The pitch shift to preview with, in semitones from -[MAX_SEMITONES].
+	pitch_widget: Choice,
 	/// Whether or not we should be playing.
 	playing: bool,
 	/// Audio manager, or the error it gave.
 	audio_manager: Result<AudioManager, CpalError>,
 	/// Playback handle.
-	playing_handle: Option<StaticSoundHandle>,
+	playing_handle: Option<Handle>,
 	/// The loop points of the playing audio in samples.
 	loop_points_samples: Option<(i64, i64)>,
+	/// How many times the loop plays before the audio after it, or 0 to loop forever.
+	loop_count: u32,
+	/// How many more times the playing audio goes back to its loop start before
+	/// the loop is let go of, so it plays on to the end.
+	loops_left: u32,
+	/// The length of the loop of the playing audio in seconds, to count how
+	/// many times it went around.
+	loop_length: f64,
+	/// The position of the playing audio at the last update.
+	last_position: f64,
+	/// When the position was last read, to work out how far the audio played since.
+	last_update: Instant,
 	/// The index of the currently playing audio in the list it came from.
 	current_playing_index: Option<usize>,
+	/// Whether or not the audio stream failed, like when the output device was
+	/// unplugged. The audio manager is created again on the next play.
+	stream_failed: bool,
+	/// A one-line status about the audio device to show the user, if any.
+	notice: Option<String>,
+	/// The waveform and loop region drawn behind the slider.
+	timeline: Rc<RefCell<Timeline>>,
+	/// The time between UI updates to the slider while playing, in seconds.
+	update_interval: f64,
 	/// App sender.
 	sender: fltk::app::Sender<crate::Message>
 }
@@ -79,15 +321,35 @@ impl Playback {
 		AudioManager::<CpalBackend>::new(manager_settings)
 	}
 
+	/// Make the track sounds are played on, so they can be panned as a whole.
+	fn create_pan_track(manager: &mut AudioManager, panning: f64) -> Option<(TrackHandle, PanningControlHandle)> {
+		let mut builder = TrackBuilder::new();
+		let control = builder.add_effect(PanningControlBuilder(panning.into()));
+		match manager.add_sub_track(builder) {
+			Ok(track) => Some((track, control)),
+			Err(error) => {
+				warn!("Could not make the track to pan audio on: {}", error);
+				None
+			}
+		}
+	}
+
 	/// Create a new instance of Self.
 	pub fn new(sender: fltk::app::Sender<crate::Message>) -> Self {
 		let mut play_widget = Button::default().with_label(PLAY);
-		play_widget.set_tooltip("Play selected audio");
+		play_widget.set_tooltip(PLAY_TOOLTIP);
+		play_widget.visible_focus(true);
 		play_widget.set_callback(move |c| c.emit(sender, crate::Message::PlayPause));
 
+		let mut loop_start_widget = Button::default().with_label(LOOP_START);
+		loop_start_widget.set_tooltip("Play selected audio from its loop start");
+		loop_start_widget.visible_focus(true);
+		loop_start_widget.set_callback(move |c| c.emit(sender, crate::Message::PlayFromLoopStart));
+
 		// let mut slider_widget = HorNiceSlider::default();
 		let mut slider_widget = HorFillSlider::default();
-		slider_widget.set_tooltip("Position of the playing audio");
+		slider_widget.set_tooltip(SLIDER_TOOLTIP);
+		slider_widget.visible_focus(true);
 		slider_widget.set_callback(move |c| c.emit(sender, crate::Message::Seek));
 		slider_widget.deactivate();
 		slider_widget.set_selection_color(fltk::enums::Color::DarkBlue);
@@ -96,48 +358,170 @@ impl Playback {
 		slider_widget.set_step(1.0, 1);
 		slider_widget.set_value(0.0);
 
-		let audio_manager = Self::create_audio_manager();
+		let mut pan_widget = Dial::default();
+		pan_widget.set_tooltip("Place the preview between the left and right speakers.\nRight click to center it");
+		pan_widget.set_bounds(0.0, 1.0);
+		pan_widget.set_value(0.5);
+		pan_widget.set_callback(move |c| c.emit(sender, crate::Message::PlaybackPan));
+		pan_widget.handle(move |dial, event| match event {
+			Event::Push if fltk::app::event_mouse_button() == MouseButton::Right => {
+				dial.set_value(0.5);
+				dial.do_callback();
+				true
+			},
+			_ => false
+		});
+
+		let channel_widgets = CHANNELS.map(|(label, tooltip)| {
+			let mut toggle = ToggleButton::default().with_label(label);
+			toggle.set_tooltip(tooltip);
+			toggle.toggle(true);
+			toggle.set_callback(move |c| c.emit(sender, crate::Message::PlaybackChannels));
+			toggle
+		});
+
+		let mut speed_widget = Choice::default();
+		speed_widget.set_tooltip("Speed to preview at. The pitch changes with it, and the saved audio doesn't");
+		for speed in SPEEDS {
+			speed_widget.add_choice(&format!("{}×", speed))
+		}
+		speed_widget.set_value(NORMAL_SPEED as i32);
+		speed_widget.set_callback(move |c| c.emit(sender, crate::Message::PlaybackRate));
+
+		let mut pitch_widget = Choice::default();
+		pitch_widget.set_tooltip("Semitones to shift the pitch of the preview by. The speed changes with it, and the saved audio doesn't");
+		for semitones in -MAX_SEMITONES..=MAX_SEMITONES {
+			pitch_widget.add_choice(&if semitones == 0 { "0 st".to_owned() } else { format!("{:+} st", semitones) })
+		}
+		pitch_widget.set_value(MAX_SEMITONES);
+		pitch_widget.set_callback(move |c| c.emit(sender, crate::Message::PlaybackRate));
+
+		let timeline = Rc::new(RefCell::new(Timeline::default()));
+		// The timeline draws the background of the slider, then the slider draws its fill over it
+		slider_widget.set_frame(FrameType::NoBox);
+		slider_widget.super_draw_first(false);
+		slider_widget.draw({
+			let timeline = timeline.clone();
+			move |slider| timeline.borrow().draw(slider)
+		});
+
+		let mut audio_manager = Self::create_audio_manager();
+		let pan_track = audio_manager.as_mut().ok().and_then(|manager| Self::create_pan_track(manager, 0.5));
 
 		Self {
 			play_widget,
+			loop_start_widget,
 			slider_widget,
+			pan_widget,
+			pan_track,
+			channel_widgets,
+			stereo: false,
+			speed_widget,
+			pitch_widget,
 			playing: false,
 			audio_manager,
 			playing_handle: None,
 			loop_points_samples: None,
+			loop_count: 0,
+			loops_left: 0,
+			loop_length: 0.0,
+			last_position: 0.0,
+			last_update: Instant::now(),
 			current_playing_index: None,
+			stream_failed: false,
+			notice: None,
+			timeline,
+			update_interval: UPDATE_FREQUENCY,
 			sender
 		}
 	}
 
 	/// Try to get the stream handle.
 	/// 
-	/// If the stream handle isn't set already, tries to create it again.
-	/// Otherwise, this does nothing.
+	/// If the stream handle isn't set already, or its stream failed, tries to
+	/// create it again. Otherwise, this does nothing.
 	pub fn get_manager(&mut self) {
-		if self.audio_manager.is_ok() { return }
-		
-		self.audio_manager = Self::create_audio_manager()
+		if self.audio_manager.is_ok() && !self.check_stream() { return }
+
+		if self.stream_failed {
+			self.stop_sink();
+		}
+		self.audio_manager = Self::create_audio_manager();
+		let panning = self.pan_widget.value();
+		self.pan_track = self.audio_manager.as_mut().ok().and_then(|manager| Self::create_pan_track(manager, panning));
+		if self.audio_manager.is_ok() && self.stream_failed {
+			info!("Reconnected to the audio device");
+			self.stream_failed = false;
+			self.notice = Some("Reconnected to the audio device".to_owned())
+		}
+	}
+
+	/// Check the audio stream for errors, like the output device being unplugged.
+	/// Returns whether or not the stream has failed.
+	fn check_stream(&mut self) -> bool {
+		if let Ok(manager) = &mut self.audio_manager {
+			if let Some(error) = manager.backend_mut().pop_error() {
+				warn!("Audio stream failed: {}", error);
+				self.stream_failed = true
+			}
+		}
+		self.stream_failed
+	}
+
+	/// Describe whether or not audio can be played, for diagnostics.
+	pub fn audio_status(&mut self) -> String {
+		if self.check_stream() {
+			return "The audio stream failed, it is restarted on the next play".to_owned()
+		}
+		match &self.audio_manager {
+			Ok(_) => "Working".to_owned(),
+			Err(error) => format!("No audio device: {}", error)
+		}
+	}
+
+	/// Take the status about the audio device to show the user, if any.
+	pub fn take_notice(&mut self) -> Option<String> {
+		self.notice.take()
 	}
 
 	/// Updates the value of the slider widget to match the sink position.
 	pub fn on_update(&mut self) {
+		if self.playing && self.check_stream() {
+			self.stop_sink();
+			self.notice = Some("Lost the audio device, play again to reconnect".to_owned());
+			return
+		}
 		if self.playing {
+			let rate = self.playback_rate();
 			if let Some(handle) = &mut self.playing_handle {
-				self.slider_widget.set_value(handle.position());
+				let position = handle.position();
+				let now = Instant::now();
+				if self.loops_left > 0 && self.loop_length > 0.0 {
+					// Whatever the audio played since the last update that it
+					// isn't further along for went around the loop
+					let played = now.duration_since(self.last_update).as_secs_f64() * rate;
+					let times = ((self.last_position + played - position) / self.loop_length).round().max(0.0) as u32;
+					self.loops_left = self.loops_left.saturating_sub(times);
+					if self.loops_left == 0 {
+						let _ = handle.clear_loop_region();
+					}
+				}
+				self.last_position = position;
+				self.last_update = now;
+				self.slider_widget.set_value(position);
 				// No need to run more updates if it's paused
 				if handle.state() != PlaybackState::Playing {
 					self.slider_widget.deactivate();
 					self.playing = false;
-					self.play_widget.set_label(PLAY)
+					Self::set_play_label(&mut self.play_widget, false)
 				} else {
 					self.slider_widget.activate();
-					Self::queue_update(self.sender)
+					Self::queue_update(self.sender, self.update_interval)
 				}
 				self.slider_widget.redraw()
 			} else {
 				self.playing = false;
-				self.play_widget.set_label(PLAY)
+				Self::set_play_label(&mut self.play_widget, false)
 			}
 		}
 		// Do nothing if we aren't playing anything
@@ -150,15 +534,93 @@ impl Playback {
 	}
 
 	pub fn seek(&mut self, to: f64) {
+		self.last_position = to;
+		self.last_update = Instant::now();
 		if let Some(handle) = &mut self.playing_handle {
 			let _ = handle.seek_to(to);
 			let _ = handle.resume(Self::no_tween());
 		}
 	}
 
-	/// Queue the slider update.
-	fn queue_update(sender: fltk::app::Sender<crate::Message>) {
-		fltk::app::add_timeout3(UPDATE_FREQUENCY, move |_| sender.send(crate::Message::Update));
+	/// Seek `by` seconds from the current position, backward if negative.
+	pub fn seek_by(&mut self, by: f64) {
+		if let Some(handle) = &self.playing_handle {
+			let to = (handle.position() + by).clamp(0.0, self.slider_widget.maximum());
+			self.slider_widget.set_value(to);
+			self.seek(to)
+		}
+	}
+
+	/// Follow the pan dial being turned, applying it to the track everything plays on.
+	pub fn on_pan_changed(&mut self) {
+		let panning = self.pan_widget.value();
+		if let Some((_, control)) = &mut self.pan_track {
+			let _ = control.set_panning(panning, Self::no_tween());
+		}
+	}
+
+	/// The panning and amplitude that play the channels of stereo audio that are
+	/// toggled on, see [kira's panning](kira::sound::static_sound::StaticSoundSettings::panning).
+	/// 
+	/// Panning all the way to one side silences the other channel and makes the
+	/// kept one louder by 3 dB, which the amplitude takes back.
+	fn channel_mix(&self) -> (f64, f64) {
+		if !self.stereo {
+			return (0.5, 1.0)
+		}
+		match (self.channel_widgets[0].is_toggled(), self.channel_widgets[1].is_toggled()) {
+			(true, true) => (0.5, 1.0),
+			(true, false) => (0.0, std::f64::consts::FRAC_1_SQRT_2),
+			(false, true) => (1.0, std::f64::consts::FRAC_1_SQRT_2),
+			(false, false) => (0.5, 0.0)
+		}
+	}
+
+	/// Follow a channel being toggled, applying it to what's playing.
+	pub fn on_channels_changed(&mut self) {
+		let (panning, amplitude) = self.channel_mix();
+		if let Some(handle) = &mut self.playing_handle {
+			let _ = handle.set_panning(panning, Self::no_tween());
+			let _ = handle.set_volume(amplitude, Self::no_tween());
+		}
+	}
+
+	/// Set how many times the loop of a preview plays before the audio after it,
+	/// or 0 to loop forever. Applies from the next play.
+	pub fn set_loop_count(&mut self, count: u32) {
+		self.loop_count = count
+	}
+
+	/// The factor of the normal rate to play at, from the speed and pitch widgets.
+	/// 
+	/// kira resamples to change the rate, so both change the speed and the pitch.
+	fn playback_rate(&self) -> f64 {
+		let speed = SPEEDS.get(self.speed_widget.value().max(0) as usize).copied().unwrap_or(1.0);
+		let semitones = (self.pitch_widget.value() - MAX_SEMITONES) as f64;
+		speed * 2f64.powf(semitones / 12.0)
+	}
+
+	/// Follow the speed or pitch being changed, applying it to what's playing.
+	pub fn on_rate_changed(&mut self) {
+		let rate = self.playback_rate();
+		if let Some(handle) = &mut self.playing_handle {
+			// The time played so far was at the old rate
+			self.last_position = handle.position();
+			self.last_update = Instant::now();
+			let _ = handle.set_playback_rate(rate, Self::no_tween());
+		}
+	}
+
+	/// Go back to the normal speed and pitch.
+	pub fn reset_rate(&mut self) {
+		self.speed_widget.set_value(NORMAL_SPEED as i32);
+		self.pitch_widget.set_value(MAX_SEMITONES);
+		self.on_rate_changed()
+	}
+
+	/// Queue the slider update in `interval` seconds.
+	fn queue_update(sender: fltk::app::Sender<crate::Message>, interval: f64) {
+		fltk::app::add_timeout3(interval, move |_| sender.send(crate::Message::Update));
 	}
 
 	/// Try to play the currently selected sound.
@@ -170,7 +632,7 @@ impl Playback {
 		let selected = file_list.selected().map(|(index, _)| index);
 
 		match &mut self.audio_manager {
-			Ok(manager) => {
+			Ok(_) => {
 				// Stream is fine
 				match &mut self.playing_handle {
 					// Matches if:
@@ -181,16 +643,18 @@ impl Playback {
 						// Already have a playback handle
 						if handle.state() == PlaybackState::Paused {
 							self.slider_widget.activate();
-							self.play_widget.set_label(PAUSE);
+							Self::set_play_label(&mut self.play_widget, true);
 							self.playing = true;
+							// The time it was paused for wasn't played
+							self.last_update = Instant::now();
 							if let Err(error) = handle.resume(Tween::default()) {
 								return Err(error.to_string())
 							}
-							Self::queue_update(self.sender);
+							Self::queue_update(self.sender, self.update_interval);
 							Ok(())
 						} else {
 							self.slider_widget.deactivate();
-							self.play_widget.set_label(PLAY);
+							Self::set_play_label(&mut self.play_widget, false);
 							if let Err(error) = handle.pause(Tween::default()) {
 								return Err(error.to_string())
 							}
@@ -199,57 +663,9 @@ impl Playback {
 					},
 					_ => {
 						// Playing new audio
-
-						// If there really is a handle, stop the audio now
-						if let Some(handle) = &mut self.playing_handle {
-							let _ = handle.stop(Self::no_tween());
-						}
-
-						// Check if anything is selected
 						if let Some(index) = selected {
-							let list_item = file_list.items.get_mut(index).expect("Failed to find internal list item");
-
-							// Refuse to attempt playing anything that isn't audio
-							if list_item.extension == crate::list::AudioExtension::Bin {
-								return Err("File is not audio or could not be read as audio.".to_owned())
-							}
-
-							self.loop_points_samples = list_item.loop_points_samples();
-
-							// Create the sound settings
-							let mut settings = StaticSoundSettings::default();
-							if let Some((begin, end)) = self.loop_points_samples {
-								settings.loop_region = Some(kira::sound::Region {
-									start: PlaybackPosition::Samples(begin),
-									end: EndPosition::Custom(PlaybackPosition::Samples(end))
-								})
-							}
-
-							// Create the sound data
-							let sound_data = StaticSoundData::from_cursor(Cursor::new(list_item.get_audio_wav(list_item.loop_end())?), settings);
-
-							match sound_data {
-								Ok(s) => {
-									let duration = s.duration();
-									self.slider_widget.set_bounds(0.0, duration.as_secs_f64());
-									self.slider_widget.set_step((duration.as_secs_f64() / 20.0).min(0.2), 2);
-
-									self.play_widget.set_label(PAUSE);
-									self.playing = true;
-									self.sender.send(crate::Message::Update);
-									match manager.play(s) {
-										Ok(handle) => {
-											self.playing_handle = Some(handle);
-											self.current_playing_index = selected
-										},
-										Err(error) => return Err(error.to_string())
-									};
-									Ok(())
-								},
-								Err(error) => {
-									Err(format!("Could not play audio:\n{}", error))
-								}
-							}
+							let list_item = file_list.archive.items.get_mut(index).expect("Failed to find internal list item");
+							self.play_item(list_item, Some(index), false)
 						} else {
 							Err("Nothing is selected.".to_owned())
 						}
@@ -260,21 +676,221 @@ impl Playback {
 		}
 	}
 
+	/// Play the selected item from its loop start, starting over if it's already playing.
+	pub fn play_from_loop_start(&mut self, file_list: &mut crate::list::List) -> Result<(), String> {
+		self.get_manager();
+
+		let index = match file_list.selected() {
+			Some((index, _)) => index,
+			None => return Err("Nothing is selected.".to_owned())
+		};
+		let list_item = file_list.archive.items.get_mut(index).expect("Failed to find internal list item");
+		if list_item.loop_points_samples().is_none() {
+			return Err(format!("{} has no loop.", list_item.name))
+		}
+		self.play_item(list_item, Some(index), true)
+	}
+
+	/// Play `list_item` from the start, or from its loop start if `from_loop_start`,
+	/// stopping whatever is playing. `index` is where it is in the list, if it's in the list.
+	fn play_item(&mut self, list_item: &mut ListItem, index: Option<usize>, from_loop_start: bool) -> Result<(), String> {
+		let manager = match &mut self.audio_manager {
+			Ok(manager) => manager,
+			Err(error) => return Err(error.to_string())
+		};
+
+		// If there really is a handle, stop the audio now
+		if let Some(handle) = &mut self.playing_handle {
+			let _ = handle.stop(Self::no_tween());
+		}
+
+		// Refuse to attempt playing anything that isn't audio
+		if list_item.extension == crate::list::AudioExtension::Bin {
+			return Err("File is not audio or could not be read as audio.".to_owned())
+		}
+
+		self.loop_points_samples = list_item.loop_points_samples();
+		// A loop played a set number of times is let go of after the last
+		// time around, so the audio plays on past the loop end
+		self.loops_left = if self.loop_points_samples.is_some() { self.loop_count.saturating_sub(1) } else { 0 };
+		let sample_rate = list_item.sample_rate().max(1) as f64;
+		self.loop_length = self.loop_points_samples.map(|(begin, end)| (end - begin) as f64 / sample_rate).unwrap_or(0.0);
+		self.last_position = match self.loop_points_samples {
+			Some((begin, _)) if from_loop_start => begin as f64 / sample_rate,
+			_ => 0.0
+		};
+		self.last_update = Instant::now();
+		let looped = self.loop_points_samples.is_some() && (self.loop_count == 0 || self.loops_left > 0);
+		let cut_end = if self.loop_count == 0 { list_item.loop_end() } else { None };
+
+		let start_position = match self.loop_points_samples {
+			Some((begin, _)) if from_loop_start => PlaybackPosition::Samples(begin),
+			_ => PlaybackPosition::Samples(0)
+		};
+
+		let loop_region = self.loop_points_samples.filter(|_| looped).map(|(begin, end)| kira::sound::Region {
+			start: PlaybackPosition::Samples(begin),
+			end: EndPosition::Custom(PlaybackPosition::Samples(end))
+		});
+
+		let rate = self.playback_rate();
+		self.stereo = list_item.channels() == 2;
+		let (panning, amplitude) = self.channel_mix();
+		let mut streaming_settings = StreamingSoundSettings::default();
+		streaming_settings.loop_region = loop_region;
+		streaming_settings.start_position = start_position;
+		streaming_settings.playback_rate = PlaybackRate::Factor(rate).into();
+		streaming_settings.panning = panning.into();
+		streaming_settings.volume = Volume::Amplitude(amplitude).into();
+		if let Some((track, _)) = &self.pan_track {
+			streaming_settings.output_destination = track.into();
+		}
+		let streamed = match (list_item.spilled_audio(), list_item.audio_file.as_ref()) {
+			// A preview made ahead of time is ready to play as it is
+			_ if list_item.has_preview(cut_end) => None,
+			// Audio too large to keep in memory is streamed from disk
+			(Some(path), _) if list_item.channels() <= 2 => Some(StreamingSoundData::from_file(path, streaming_settings)),
+			// Long audio kira can decode by itself is streamed, so it starts
+			// right away instead of being decoded in full first. Its bytes are
+			// shared with the item, not copied
+			(_, Some(file)) if Self::can_stream(file, list_item.channels()) => Some(StreamingSoundData::from_cursor(Cursor::new(file.bytes.clone()), streaming_settings)),
+			_ => None
+		};
+
+		let played = match streamed {
+			Some(Ok(s)) => {
+				let duration = s.duration();
+				manager.play(s).map(|handle| (Handle::Streaming(handle), duration)).map_err(|error| error.to_string())
+			},
+			Some(Err(error)) => Err(format!("Could not play audio:\n{}", error)),
+			None => {
+				let mut settings = StaticSoundSettings::default();
+				settings.loop_region = loop_region;
+				settings.start_position = start_position;
+				settings.playback_rate = PlaybackRate::Factor(rate).into();
+				settings.panning = panning.into();
+				settings.volume = Volume::Amplitude(amplitude).into();
+				if let Some((track, _)) = &self.pan_track {
+					settings.output_destination = track.into();
+				}
+				match StaticSoundData::from_cursor(Cursor::new(list_item.preview_wav(cut_end)?), settings) {
+					Ok(s) => {
+						let duration = s.duration();
+						manager.play(s).map(|handle| (Handle::Static(handle), duration)).map_err(|error| error.to_string())
+					},
+					Err(error) => Err(format!("Could not play audio:\n{}", error))
+				}
+			}
+		};
+		let (handle, duration) = played?;
+		let mut seconds = duration.as_secs_f64();
+		// Streamed audio can't be cut off at the loop end, but it loops
+		// forever when it would be, so it never plays past it either
+		if let (Handle::Streaming(_), Some(end)) = (&handle, cut_end) {
+			seconds = seconds.min(end as f64 / sample_rate)
+		}
+
+		self.slider_widget.set_bounds(0.0, seconds);
+		self.slider_widget.set_step((seconds / 20.0).min(0.2), 2);
+		// Update about once per pixel the slider moves, within limits
+		self.update_interval = (seconds / rate / self.slider_widget.w().max(1) as f64).clamp(MIN_UPDATE_INTERVAL, UPDATE_FREQUENCY);
+
+		let length = seconds * list_item.sample_rate() as f64;
+		*self.timeline.borrow_mut() = Timeline {
+			peaks: list_item.waveform(WAVEFORM_COLUMNS, cut_end).unwrap_or_default(),
+			original: list_item.original_waveform(WAVEFORM_COLUMNS)
+				.filter(|_| seconds > 0.0)
+				.map(|(peaks, original_seconds)| (peaks, original_seconds / seconds)),
+			loop_region: self.loop_points_samples
+				.filter(|_| length > 0.0)
+				.map(|(begin, end)| ((begin as f64 / length).min(1.0), (end as f64 / length).min(1.0)))
+		};
+		self.slider_widget.set_tooltip(if self.timeline.borrow().original.is_some() { SLIDER_ORIGINAL_TOOLTIP } else { SLIDER_TOOLTIP });
+		self.slider_widget.redraw();
+
+		Self::set_play_label(&mut self.play_widget, true);
+		self.playing = true;
+		self.sender.send(crate::Message::Update);
+		self.playing_handle = Some(handle);
+		self.current_playing_index = index;
+		Ok(())
+	}
+
+	/// Play `list_item`, which isn't in the list, to hear it before it's used.
+	/// Stop it with [Playback::stop_sink].
+	pub fn audition(&mut self, list_item: &mut ListItem) -> Result<(), String> {
+		self.get_manager();
+		self.play_item(list_item, None, false)
+	}
+
+	/// Whether or not `file` with `channels` should be streamed instead of
+	/// decoded in full before playing.
+	fn can_stream(file: &EncodedFile, channels: u16) -> bool {
+		channels <= 2
+			&& file.bytes.len() > STREAMING_THRESHOLD
+			&& matches!(file.encoding, EncodingType::WAV | EncodingType::Ogg | EncodingType::FLAC | EncodingType::MP3)
+	}
+
+	/// Show the play button as a pause button if `playing`, or as a play button.
+	fn set_play_label(play_widget: &mut Button, playing: bool) {
+		let (label, tooltip) = if playing { (PAUSE, PAUSE_TOOLTIP) } else { (PLAY, PLAY_TOOLTIP) };
+		play_widget.set_label(label);
+		play_widget.set_tooltip(tooltip)
+	}
+
+	/// Whether or not audio is playing, as shown by the play button.
+	pub fn is_playing(&self) -> bool {
+		self.play_widget.label() == PAUSE
+	}
+
 	/// Stop the current sink.
 	pub fn stop_sink(&mut self) {
 		if let Some(handle) = &mut self.playing_handle {
 			let _ = handle.stop(Self::no_tween());
 		}
-		self.play_widget.set_label(PLAY);
+		Self::set_play_label(&mut self.play_widget, false);
 		self.slider_widget.set_value(0.0);
 		self.playing = false;
 		self.loop_points_samples = None;
-		self.playing_handle = None
+		self.loops_left = 0;
+		*self.timeline.borrow_mut() = Timeline::default();
+		self.slider_widget.set_tooltip(SLIDER_TOOLTIP);
+		self.slider_widget.redraw();
+		self.playing_handle = None;
+		self.current_playing_index = None
+	}
+
+	/// Follow the item at `index` being removed from the list.
+	/// 
+	/// Stops playback if it was the playing item, so its audio doesn't keep
+	/// playing while another item is in its place.
+	pub fn on_remove(&mut self, index: usize) {
+		match self.current_playing_index {
+			Some(playing) if playing == index => self.stop_sink(),
+			Some(playing) if playing > index => self.current_playing_index = Some(playing - 1),
+			_ => ()
+		}
+	}
+
+	/// Follow an item being put in the list at `index`, moving the items after it down.
+	pub fn on_insert(&mut self, index: usize) {
+		if let Some(playing) = self.current_playing_index.filter(|&playing| playing >= index) {
+			self.current_playing_index = Some(playing + 1)
+		}
+	}
+
+	/// Follow the audio of the item at `index` being replaced.
+	/// 
+	/// Stops playback if it was the playing item, since the old audio is stale.
+	pub fn on_replace(&mut self, index: usize) {
+		if self.current_playing_index == Some(index) {
+			self.stop_sink()
+		}
 	}
 
-	/// Returns the [&mut Browser] widget of this List.
-	pub fn get_widgets_mut(&mut self) -> (&mut Button, &mut HorFillSlider) {
-		(&mut self.play_widget, &mut self.slider_widget)
+	/// Returns the play and play from loop start buttons, the slider, the pan dial, the channel toggles and the speed and pitch choices.
+	pub fn get_widgets_mut(&mut self) -> (&mut Button, &mut Button, &mut HorFillSlider, &mut Dial, &mut [ToggleButton], &mut Choice, &mut Choice) {
+		(&mut self.play_widget, &mut self.loop_start_widget, &mut self.slider_widget, &mut self.pan_widget, &mut self.channel_widgets, &mut self.speed_widget, &mut self.pitch_widget)
 	}
 
 	pub fn no_tween() -> Tween {
@@ -0,0 +1,270 @@
+use fltk::{
+	prelude::*,
+	app,
+	button::Button,
+	enums::Event,
+	group::Scroll,
+	input::Input,
+	menu::Choice,
+	window::Window
+};
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use crate::{
+	layout,
+	settings::Settings,
+	shortcuts::{ self, BINDINGS }
+};
+use simple_nus3audio_core::{
+	AudioExtension,
+	Decoder
+};
+
+#[derive(Clone)]
+enum PrefMessage {
+	ReLay,
+	Save
+}
+
+/// Create a [Choice] with `label`, holding the names of `decoders`, with `selected` chosen.
+fn decoder_choice(label: &str, tooltip: &str, selected: Decoder) -> Choice {
+	let mut choice = Choice::default().with_label(label);
+	choice.set_tooltip(tooltip);
+	for decoder in Decoder::ALL {
+		choice.add_choice(&decoder.to_string())
+	}
+	choice.set_value(Decoder::ALL.iter().position(|d| *d == selected).unwrap_or(0) as i32);
+	choice
+}
+
+/// The formats new items can default to, in the order they are shown.
+const DEFAULT_FORMATS: [AudioExtension; 2] = [AudioExtension::Idsp, AudioExtension::Lopus];
+
+/// Create a [Choice] holding the formats new items can default to, with `selected` chosen.
+fn format_choice(selected: AudioExtension) -> Choice {
+	let mut choice = Choice::default().with_label("Default format");
+	choice.set_tooltip("Format of new sounds, and of audio replacing binary data");
+	choice.add_choice("IDSP");
+	choice.add_choice("LOPUS");
+	choice.set_value(DEFAULT_FORMATS.iter().position(|format| *format == selected).unwrap_or(0) as i32);
+	choice
+}
+
+/// Create a [Choice] of what items dragged out of the list are written as, encoded if `encoded`.
+fn drag_out_choice(encoded: bool) -> Choice {
+	let mut choice = Choice::default().with_label("Drag out as");
+	choice.set_tooltip("What an item dragged from the list to a file manager is written as");
+	choice.add_choice("WAV");
+	choice.add_choice("Saved format");
+	choice.set_value(encoded as i32);
+	choice
+}
+
+/// Create a [Choice] of whether or not to ask before removing an item, asking if `confirm`.
+fn confirm_remove_choice(confirm: bool) -> Choice {
+	let mut choice = Choice::default().with_label("Before removing");
+	choice.set_tooltip("Whether or not removing an item asks first. Removed items can be restored from the trash either way");
+	choice.add_choice("Ask");
+	choice.add_choice("Don't ask");
+	choice.set_value(!confirm as i32);
+	choice
+}
+
+/// The lengths, in minutes, imported audio can be before asking, in the order
+/// they are shown. 0 never asks.
+const LONG_IMPORT_MINUTES: [u32; 5] = [5, 10, 20, 30, 0];
+
+/// Return the index in [LONG_IMPORT_MINUTES] shown for `minutes`. Lengths set
+/// in the settings file by hand show as the closest longer one.
+fn long_import_index(minutes: u32) -> usize {
+	LONG_IMPORT_MINUTES.iter().position(|choice| minutes != 0 && *choice >= minutes).unwrap_or(LONG_IMPORT_MINUTES.len() - 1)
+}
+
+/// Create a [Choice] of how long imported audio can be before asking, with `minutes` chosen.
+fn long_import_choice(minutes: u32) -> Choice {
+	let mut choice = Choice::default().with_label("Warn past");
+	choice.set_tooltip("Audio this long is usually the wrong file, and takes a while and a lot of memory to decode, so ask before using it");
+	for minutes in LONG_IMPORT_MINUTES {
+		if minutes == 0 {
+			choice.add_choice("Never ask")
+		} else {
+			choice.add_choice(&format!("{} minutes", minutes))
+		}
+	}
+	choice.set_value(long_import_index(minutes) as i32);
+	choice
+}
+
+/// Return the [Decoder] selected in `choice`.
+fn selected_decoder(choice: &Choice) -> Decoder {
+	Decoder::ALL.get(choice.value().max(0) as usize).copied().unwrap_or(Decoder::ALL[0])
+}
+
+/// Open the preferences window. Returns a boolean representing whether or not the settings were modified.
+pub fn configure(settings: &mut Settings, parent: &Window) -> bool {
+	let (s, r) = app::channel();
+
+	let mut window = Window::new(parent.x(), parent.y(), 350, 250, Some("Preferences"));
+	window.make_resizable(true);
+	window.size_range(300, 250, 0, 0);
+
+	let mut choices = vec![
+		decoder_choice("IDSP decoder", "Tool tried first when decoding IDSP audio", settings.decoder_for(&AudioExtension::Idsp)),
+		decoder_choice("LOPUS decoder", "Tool tried first when decoding LOPUS audio", settings.decoder_for(&AudioExtension::Lopus)),
+		format_choice(settings.default_format()),
+		drag_out_choice(settings.drag_out_encoded()),
+		confirm_remove_choice(settings.confirm_remove()),
+		long_import_choice(settings.long_import_minutes())
+	];
+
+	// Create the button to apply changes
+	let mut save_button = Button::default()
+		.with_label("Ok");
+	save_button.set_tooltip("Apply changes and close this window");
+	save_button.emit(s.clone(), PrefMessage::Save);
+
+	window.handle(move |_, event| match event {
+		Event::Resize => {
+			s.send(PrefMessage::ReLay);
+			true
+		},
+		_ => { false }
+	});
+
+	window.end();
+	layout::lay_pref_widgets(&mut window, &mut choices, &mut save_button);
+	window.show();
+
+	let mut apply = false;
+
+	// Mini event loop
+	while window.shown() {
+		app::wait();
+		if let Some(e) = r.recv() {
+			match e {
+				PrefMessage::ReLay => layout::lay_pref_widgets(&mut window, &mut choices, &mut save_button),
+				PrefMessage::Save => {
+					apply = true;
+					window.hide()
+				}
+			}
+		}
+	}
+
+	if apply {
+		let mut modified = false;
+
+		let idsp_decoder = selected_decoder(&choices[0]);
+		if idsp_decoder != settings.decoder_for(&AudioExtension::Idsp) {
+			settings.set_decoder_for(&AudioExtension::Idsp, idsp_decoder);
+			modified = true
+		}
+		let lopus_decoder = selected_decoder(&choices[1]);
+		if lopus_decoder != settings.decoder_for(&AudioExtension::Lopus) {
+			settings.set_decoder_for(&AudioExtension::Lopus, lopus_decoder);
+			modified = true
+		}
+		let default_format = DEFAULT_FORMATS.get(choices[2].value().max(0) as usize).cloned().unwrap_or(AudioExtension::Idsp);
+		if default_format != settings.default_format() {
+			settings.set_default_format(&default_format);
+			modified = true
+		}
+		let drag_out_encoded = choices[3].value() == 1;
+		if drag_out_encoded != settings.drag_out_encoded() {
+			settings.set_drag_out_encoded(drag_out_encoded);
+			modified = true
+		}
+		let confirm_remove = choices[4].value() == 0;
+		if confirm_remove != settings.confirm_remove() {
+			settings.set_confirm_remove(confirm_remove);
+			modified = true
+		}
+		// Only a different pick changes it, so lengths set by hand are kept otherwise
+		let long_import = choices[5].value().max(0) as usize;
+		if long_import != long_import_index(settings.long_import_minutes()) {
+			settings.set_long_import_minutes(LONG_IMPORT_MINUTES.get(long_import).copied().unwrap_or(0));
+			modified = true
+		}
+
+		trace!("Preferences changed? {}", modified);
+		modified
+	} else { false }
+}
+
+
+/// Open the keyboard shortcut editor. Returns a boolean representing whether or not the shortcuts were modified.
+pub fn configure_shortcuts(settings: &mut Settings, parent: &Window) -> bool {
+	let (s, r) = app::channel();
+
+	let mut window = Window::new(parent.x(), parent.y(), 400, 400, Some("Keyboard Shortcuts"));
+	window.make_resizable(true);
+	window.size_range(300, 150, 0, 0);
+
+	let mut scroll = Scroll::default();
+	let mut inputs: Vec<Input> = BINDINGS.iter()
+		.map(|binding| {
+			let mut input = Input::default().with_label(binding.label);
+			input.set_tooltip("A shortcut like Ctrl+Shift+S, Space or F5. Leave empty for no shortcut.");
+			input.set_value(&settings.shortcut(binding));
+			input
+		})
+		.collect();
+	scroll.end();
+
+	// Create the button to apply changes
+	let mut save_button = Button::default()
+		.with_label("Ok");
+	save_button.set_tooltip("Apply changes and close this window");
+	save_button.emit(s.clone(), PrefMessage::Save);
+
+	window.handle(move |_, event| match event {
+		Event::Resize => {
+			s.send(PrefMessage::ReLay);
+			true
+		},
+		_ => { false }
+	});
+
+	window.end();
+	layout::lay_shortcut_widgets(&mut window, &mut scroll, &mut inputs, &mut save_button);
+	window.show();
+
+	let mut apply = false;
+
+	// Mini event loop
+	while window.shown() {
+		app::wait();
+		if let Some(e) = r.recv() {
+			match e {
+				PrefMessage::ReLay => layout::lay_shortcut_widgets(&mut window, &mut scroll, &mut inputs, &mut save_button),
+				PrefMessage::Save => {
+					// Refuse to close with shortcuts that can't be used
+					let invalid: Vec<String> = BINDINGS.iter().zip(inputs.iter())
+						.filter_map(|(binding, input)| shortcuts::parse(&input.value()).err().map(|error| format!("{}: {}", binding.label, error)))
+						.collect();
+					if !invalid.is_empty() {
+						fltk::dialog::message_title("Error");
+						layout::alert(&window, &invalid.join("\n"));
+						continue
+					}
+					apply = true;
+					window.hide()
+				}
+			}
+		}
+	}
+
+	if apply {
+		let mut modified = false;
+		for (binding, input) in BINDINGS.iter().zip(inputs.iter()) {
+			let value = input.value().trim().to_owned();
+			if value != settings.shortcut(binding) {
+				settings.set_shortcut(binding.name, &value);
+				modified = true
+			}
+		}
+
+		trace!("Shortcuts changed? {}", modified);
+		modified
+	} else { false }
+}
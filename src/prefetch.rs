@@ -0,0 +1,111 @@
+//! Making previews of the items next to the selected one ahead of time, on
+//! another thread, so arrowing through a list and pressing play starts right away.
+
+use std::{
+	num::NonZeroUsize,
+	sync::{
+		atomic::{ AtomicUsize, Ordering },
+		Mutex
+	},
+	thread
+};
+use lazy_static::lazy_static;
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use simple_nus3audio_core::codec::EncodingType;
+use crate::list::List;
+
+/// How many items on each side of the selected one get a preview.
+const NEIGHBOURS: usize = 1;
+
+/// A finished preview: the index of the item, the stamp of the audio it was
+/// made from, the loop end it stops at, and the preview.
+type Prefetched = (usize, usize, Option<usize>, Vec<u8>);
+
+lazy_static! {
+	/// Previews made since the last [finish], waiting to be given to their items.
+	static ref RESULTS: Mutex<Vec<Prefetched>> = Mutex::new(Vec::new());
+}
+
+/// Counts calls to [start], so an older thread stops once the selection moves on.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Return the index of the selected item in `list`, followed by those of the items around it.
+fn around_selected(list: &mut List) -> Vec<usize> {
+	let count = list.archive.items.len();
+	let selected = match list.selected() {
+		Some((index, _)) => index,
+		None => return Vec::new()
+	};
+	std::iter::once(Some(selected))
+		.chain((1..=NEIGHBOURS).flat_map(|distance| [selected.checked_add(distance), selected.checked_sub(distance)]))
+		.flatten()
+		.filter(|&index| index < count)
+		.collect()
+}
+
+/// Start making previews of the selected item in `list` and those around it, on
+/// another thread. `sender` is sent `message` as each one is done, and they can
+/// then be given to their items with [finish].
+pub fn start(list: &mut List, sender: fltk::app::Sender<crate::Message>, message: crate::Message) {
+	let generation = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+
+	let around = around_selected(list);
+	for &index in &around {
+		// Bring back released audio now, since it's about to be used
+		if let Err(error) = list.archive.restore_audio(index) {
+			debug!("{}", error)
+		}
+	}
+
+	let jobs: Vec<_> = around.into_iter()
+		.filter_map(|index| {
+			let item = &list.archive.items[index];
+			let end = item.loop_end();
+			if item.has_preview(end) { return None }
+			item.audio_file.as_ref()
+				.filter(|file| file.encoding != EncodingType::Bin)
+				.map(|file| (index, item.audio_stamp(), end, file.clone()))
+		})
+		.collect();
+	if jobs.is_empty() { return }
+
+	thread::spawn(move || {
+		for (index, stamp, end, file) in jobs {
+			if GENERATION.load(Ordering::Relaxed) != generation {
+				trace!("Selection moved on, stopping prefetch");
+				return
+			}
+			match file.to_preview_wav(end.and_then(NonZeroUsize::new)) {
+				Ok(wav) => if let Ok(mut results) = RESULTS.lock() {
+					results.push((index, stamp, end, wav))
+				},
+				Err(error) => debug!("Could not prefetch item {}: {}", index, error)
+			}
+			sender.send(message)
+		}
+	});
+}
+
+/// Give finished previews to their items in `list`, and drop the previews of
+/// items that aren't around the selected one anymore.
+pub fn finish(list: &mut List) {
+	let results: Vec<Prefetched> = match RESULTS.lock() {
+		Ok(mut results) => results.drain(..).collect(),
+		Err(_) => return
+	};
+
+	for (index, stamp, end, wav) in results {
+		// The stamp is checked, so a preview can't end up on the wrong item
+		if let Some(item) = list.archive.items.get_mut(index) {
+			item.set_preview(stamp, end, wav)
+		}
+	}
+
+	let keep = around_selected(list);
+	for (index, item) in list.archive.items.iter_mut().enumerate() {
+		if !keep.contains(&index) {
+			item.drop_preview()
+		}
+	}
+}
@@ -0,0 +1,109 @@
+//! A small window showing the progress of a long operation.
+//! 
+//! The operation runs on the main thread, so the window is redrawn
+//! by [ProgressDialog::set] rather than by the event loop, each step and
+//! every so often during long ones. That is also when a click on its
+//! Cancel button is noticed.
+
+use fltk::{
+	prelude::*,
+	app,
+	button::Button,
+	enums::Color,
+	misc::Progress,
+	window::Window
+};
+use simple_nus3audio_core::Cancel;
+use crate::layout::MARGIN;
+
+/// Width of the progress window.
+const WIDTH: i32 = 400;
+/// Height of the progress window.
+const HEIGHT: i32 = 60;
+/// Height of the progress bar, when there is a Cancel button under it.
+const BAR_HEIGHT: i32 = 40;
+/// Size of the Cancel button.
+const BUTTON_SIZE: (i32, i32) = (90, 25);
+
+/// A progress window counting up to a number of steps.
+pub struct ProgressDialog {
+	window: Window,
+	bar: Progress
+}
+
+impl ProgressDialog {
+	/// Show a progress window titled `title` near `parent`, for `total` steps.
+	pub fn new(parent: &Window, title: &str, total: usize) -> Self {
+		Self::build(parent, title, total, None)
+	}
+
+	/// Show a progress window like [ProgressDialog::new], with a Cancel button
+	/// that sets `cancel`. Closing the window sets it too.
+	pub fn cancellable(parent: &Window, title: &str, total: usize, cancel: &Cancel) -> Self {
+		Self::build(parent, title, total, Some(cancel))
+	}
+
+	/// Lay out and show the window, with a Cancel button if there is a `cancel`.
+	fn build(parent: &Window, title: &str, total: usize, cancel: Option<&Cancel>) -> Self {
+		let height = if cancel.is_some() { MARGIN * 3 + BAR_HEIGHT + BUTTON_SIZE.1 } else { HEIGHT };
+		let mut window = Window::new(crate::layout::get_x(parent), crate::layout::get_y(parent), WIDTH, height, None)
+			.with_label(title);
+		let bar_height = if cancel.is_some() { BAR_HEIGHT } else { HEIGHT - MARGIN * 2 };
+		let mut bar = Progress::new(MARGIN, MARGIN, WIDTH - MARGIN * 2, bar_height, "");
+		bar.set_selection_color(Color::DarkBlue);
+		bar.set_minimum(0.0);
+		bar.set_maximum(total.max(1) as f64);
+		bar.set_value(0.0);
+
+		if let Some(cancel) = cancel {
+			let mut button = Button::new(WIDTH - MARGIN - BUTTON_SIZE.0, MARGIN * 2 + BAR_HEIGHT, BUTTON_SIZE.0, BUTTON_SIZE.1, "Cancel");
+			button.set_tooltip("Stop after the current step");
+			let button_cancel = cancel.clone();
+			button.set_callback(move |button| {
+				button_cancel.cancel();
+				button.set_label("Cancelling...");
+				button.deactivate()
+			});
+			// Closing the window cancels instead of hiding it mid-operation
+			let mut close_button = button.clone();
+			window.set_callback(move |_| close_button.do_callback())
+		}
+
+		window.end();
+		window.make_modal(true);
+		window.show();
+		app::flush();
+
+		Self { window, bar }
+	}
+
+	/// Show that step `number` (counting from 0) is starting, described by `label`.
+	pub fn set(&mut self, number: usize, label: &str) {
+		self.bar.set_value(number as f64);
+		self.bar.set_label(label);
+		self.window.redraw();
+		app::check();
+		app::flush()
+	}
+
+	/// Close the progress window.
+	pub fn close(mut self) {
+		self.window.hide()
+	}
+}
+
+/// Run `operation` with a progress callback and a [Cancel] for it. A cancellable
+/// progress window titled `title` is shown near `parent` the first time the
+/// operation reports progress, and closed once it's done.
+pub fn run<T>(parent: &Window, title: &str, operation: impl FnOnce(&mut dyn FnMut(usize, usize, &str), &Cancel) -> Result<T, String>) -> Result<T, String> {
+	let cancel = Cancel::new();
+	let mut dialog: Option<ProgressDialog> = None;
+	let result = operation(&mut |number, total, name| {
+		dialog.get_or_insert_with(|| ProgressDialog::cancellable(parent, title, total, &cancel))
+			.set(number, &format!("{} ({} of {})", name, number + 1, total))
+	}, &cancel);
+	if let Some(dialog) = dialog {
+		dialog.close()
+	}
+	result
+}
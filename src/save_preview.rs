@@ -0,0 +1,102 @@
+//! A preview of what saving would write, without touching the disk, and a
+//! report of what it did afterwards.
+
+use fltk::{
+	prelude::*,
+	app,
+	browser::Browser,
+	window::Window
+};
+use simple_nus3audio_core::{
+	util::human_readable_size,
+	Archive,
+	SaveReport,
+	SaveSource
+};
+use crate::layout::browser_text;
+
+/// Column widths of the preview table.
+const COLUMN_WIDTHS: &[i32] = &[60, 220, 70, 120, 90, 140];
+
+/// Show a table of what saving `archive` would write for each item, near `parent`.
+pub fn show(archive: &Archive, parent: &Window) {
+	let mut window = Window::new(crate::layout::get_x(parent), crate::layout::get_y(parent), 740, 400, None)
+		.with_label(&format!("Save preview of {}", archive.name));
+	window.make_resizable(true);
+	window.make_modal(true);
+
+	let mut browser = Browser::new(0, 0, 740, 400, "");
+	browser.set_column_widths(COLUMN_WIDTHS);
+	browser.set_column_char('\t');
+	browser.set_tooltip("What each item will be written as. Estimated sizes are marked with a ~\nItems with the same hash are written the same, items still to be encoded have none yet");
+	browser.add("@bID\t@bName\t@bFormat\t@bSource\t@bSize\t@bHash");
+
+	let mut total: u64 = 0;
+	let mut encodes = 0;
+	for item in &archive.items {
+		let source = item.save_source();
+		let size = item.save_size().unwrap_or(0);
+		total += size as u64;
+		if source == SaveSource::Encode { encodes += 1 }
+
+		let id = item.id.map(|id| id.to_string()).unwrap_or_else(|| "new".to_owned());
+		let estimate = if source == SaveSource::Encode { "~" } else { "" };
+		let hash = item.content_hash().map(|hash| format!("@f{:016x}", hash)).unwrap_or_default();
+		browser.add(&format!("{}\t{}\t{}\t{}\t{}{}\t{}", id, browser_text(&item.name), item.extension, source, estimate, human_readable_size(size as u64), hash));
+	}
+	browser.add(&format!("\t@b{} items, {} to encode\t\t\t@b{}", archive.items.len(), encodes, human_readable_size(total)));
+
+	window.resizable(&browser);
+	window.end();
+	window.show();
+
+	while window.shown() {
+		app::wait();
+	}
+}
+
+/// Show what saving did with each item, near `parent`.
+/// 
+/// Nothing is shown if nothing was encoded while saving, since then every item
+/// is written the same way it was the last time.
+pub fn show_report(report: &SaveReport, parent: &Window) {
+	if report.fresh_encodes == 0 {
+		return
+	}
+
+	let mut window = Window::new(crate::layout::get_x(parent), crate::layout::get_y(parent), 400, 300, None)
+		.with_label("Save report");
+	window.make_resizable(true);
+	window.make_modal(true);
+
+	let mut browser = Browser::new(0, 0, 400, 300, "");
+	browser.set_tooltip("What was done with each item while saving");
+
+	if !report.resampled.is_empty() {
+		browser.add(&format!("@b@C1Resampled ({}), loop points may be off", report.resampled.len()));
+		for (name, from, to) in &report.resampled {
+			browser.add(&browser_text(&format!("{}: {} Hz to {} Hz", name, from, to)));
+		}
+		browser.add("");
+	}
+	for (heading, names) in [
+		("Encoded", &report.encoded),
+		("Untouched", &report.untouched),
+		("Empty", &report.empty)
+	] {
+		if names.is_empty() { continue }
+		browser.add(&format!("@b{} ({})", heading, names.len()));
+		for name in names {
+			browser.add(&browser_text(name));
+		}
+		browser.add("");
+	}
+
+	window.resizable(&browser);
+	window.end();
+	window.show();
+
+	while window.shown() {
+		app::wait();
+	}
+}
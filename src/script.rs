@@ -0,0 +1,148 @@
+//! Running rhai scripts over the working nus3audio, for batch operations
+//! that don't deserve a button of their own.
+//! 
+//! Scripts see the archive through these functions:
+//! 
+//! - `item_count()` returns the number of items.
+//! - `item_name(index)` and `item_format(index)` describe an item.
+//! - `rename(index, name)` renames an item, unless another item has that name.
+//! - `replace(index, path)` replaces an item's audio with a file.
+//! - `set_loop(index, start, end)` and `clear_loop(index)` change an item's loop points.
+//! - `save(path)` saves the archive.
+//! 
+//! Anything printed is written to the tool console. A script that runs too many
+//! operations is stopped, so one that never ends can't hang the window.
+
+use std::{
+	cell::{ Cell, RefCell },
+	convert::TryFrom,
+	path::{ Path, PathBuf },
+	rc::Rc
+};
+use rhai::{ Engine, EvalAltResult };
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use simple_nus3audio_core::{ Archive, Cancel };
+use crate::settings::Settings;
+
+/// How many operations a script may run before it's stopped.
+const MAX_OPERATIONS: u64 = 100_000_000;
+
+/// Convert an error from the archive into one rhai can report.
+fn script_error(error: String) -> Box<EvalAltResult> {
+	error.into()
+}
+
+/// Return `index` as an index into `archive`, if there is an item there.
+fn item_index(archive: &Archive, index: i64) -> Result<usize, Box<EvalAltResult>> {
+	match usize::try_from(index) {
+		Ok(index) if index < archive.items.len() => Ok(index),
+		_ => Err(script_error(format!("There is no item at index {}", index)))
+	}
+}
+
+/// Run the rhai script at `path` over `archive`.
+/// 
+/// Changes made before an error are kept.
+pub fn run(path: &Path, archive: &mut Archive, settings: &Settings) -> Result<(), String> {
+	let script = std::fs::read_to_string(path).map_err(|error| format!("Could not read script {:?}:\n{}", path, error))?;
+
+	let shared = Rc::new(RefCell::new(std::mem::take(archive)));
+	let settings = Rc::new(settings.clone());
+	let renamed = Rc::new(Cell::new(false));
+	let mut engine = Engine::new();
+
+	// A script that never ends would hang the window, so stop it eventually
+	engine.set_max_operations(MAX_OPERATIONS);
+
+	engine.on_print(|text| crate::console::log(&format!("{}\n", text)));
+	engine.on_debug(|text, _, _| crate::console::log(&format!("{}\n", text)));
+
+	let state = shared.clone();
+	engine.register_fn("item_count", move || state.borrow().items.len() as i64);
+
+	let state = shared.clone();
+	engine.register_fn("item_name", move |index: i64| -> Result<String, Box<EvalAltResult>> {
+		let archive = state.borrow();
+		Ok(archive.items[item_index(&archive, index)?].name.clone())
+	});
+
+	let state = shared.clone();
+	engine.register_fn("item_format", move |index: i64| -> Result<String, Box<EvalAltResult>> {
+		let archive = state.borrow();
+		Ok(archive.items[item_index(&archive, index)?].extension.to_string())
+	});
+
+	let (state, renamed) = (shared.clone(), renamed.clone());
+	engine.register_fn("rename", move |index: i64, name: &str| -> Result<(), Box<EvalAltResult>> {
+		let mut archive = state.borrow_mut();
+		let index = item_index(&archive, index)?;
+		if archive.items[index].name == name {
+			return Ok(())
+		}
+		if archive.items.iter().any(|item| item.name == name) {
+			return Err(script_error(format!("More than one item would be named {}.", name)))
+		}
+		// Keep the item's notes, bookmark and source with it
+		let old_name = std::mem::replace(&mut archive.items[index].name, name.to_owned());
+		archive.project.rename(&old_name, name);
+		archive.modified = true;
+		renamed.set(true);
+		Ok(())
+	});
+
+	let (state, config) = (shared.clone(), settings.clone());
+	engine.register_fn("replace", move |index: i64, path: &str| -> Result<(), Box<EvalAltResult>> {
+		let mut archive = state.borrow_mut();
+		let index = item_index(&archive, index)?;
+		archive.replace(index, Path::new(path), config.as_ref()).map_err(script_error)
+	});
+
+	let state = shared.clone();
+	engine.register_fn("set_loop", move |index: i64, start: i64, end: i64| -> Result<(), Box<EvalAltResult>> {
+		let mut archive = state.borrow_mut();
+		let index = item_index(&archive, index)?;
+		if start < 0 || end < 0 {
+			return Err(script_error("Loop points can't be negative".to_owned()))
+		}
+		archive.set_loop_points(index, Some((start as usize, end as usize))).map_err(script_error)
+	});
+
+	let state = shared.clone();
+	engine.register_fn("clear_loop", move |index: i64| -> Result<(), Box<EvalAltResult>> {
+		let mut archive = state.borrow_mut();
+		let index = item_index(&archive, index)?;
+		archive.set_loop_points(index, None).map_err(script_error)
+	});
+
+	let (state, config) = (shared.clone(), settings);
+	engine.register_fn("save", move |path: &str| -> Result<(), Box<EvalAltResult>> {
+		let warnings = state.borrow_mut().save(Some(PathBuf::from(path)), config.as_ref(), &mut |_, _, _| (), &Cancel::new()).map_err(script_error)?;
+		for warning in warnings {
+			crate::console::log(&format!("{}\n", warning))
+		}
+		Ok(())
+	});
+
+	info!("Running script {:?}", path);
+	let result = engine.run(&script).map_err(|error| format!("Script {:?} failed:\n{}", path, error));
+
+	// The engine holds the other references, so drop it to get the archive back
+	drop(engine);
+	*archive = match Rc::try_unwrap(shared) {
+		Ok(shared) => shared.into_inner(),
+		Err(_) => unreachable!("the script engine was dropped, so nothing else holds the archive")
+	};
+
+	// Notes and bookmarks follow renamed items, and they're saved right away
+	if renamed.get() {
+		if let Err(error) = archive.save_project() {
+			let error = format!("Could not save the notes:\n{}", error);
+			return match result {
+				Ok(()) => Err(error),
+				Err(script_error) => Err(format!("{}\n\n{}", script_error, error))
+			}
+		}
+	}
+	result
+}
@@ -3,8 +3,10 @@ use which::which;
 use lazy_static::lazy_static;
 use directories::BaseDirs;
 use std::{
+	collections::HashMap,
 	fs,
-	path::PathBuf
+	path::PathBuf,
+	sync::Mutex
 };
 use crate::layout::{
 	choice2,
@@ -12,17 +14,44 @@ use crate::layout::{
 };
 use fltk::window::Window;
 use fltk::dialog::message_title;
+use simple_nus3audio_core::{
+	AudioExtension,
+	Config,
+	Decoder
+};
 
 lazy_static! {
+	/// The settings file given with `--config`, see [set_config_path].
+	static ref CONFIG_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
 	pub static ref CONFIGDIR: PathBuf = {
-		let base = BaseDirs::new().expect("Failed to get home directory");
-		base.config_dir().join(crate::NAME)
+		// Everything else in the config directory goes next to a given settings file
+		let given = CONFIG_OVERRIDE.lock().ok()
+			.and_then(|path| path.as_ref().map(|path| path.parent().map(PathBuf::from).unwrap_or_default()));
+		match given {
+			Some(dir) if dir.as_os_str().is_empty() => PathBuf::from("."),
+			Some(dir) => dir,
+			None => {
+				let base = BaseDirs::new().expect("Failed to get home directory");
+				base.config_dir().join(crate::NAME)
+			}
+		}
 	};
 	pub static ref CACHEDIR: PathBuf = {
 		let base = BaseDirs::new().expect("Failed to get home directory");
 		base.cache_dir().join(crate::NAME)
 	};
-	pub static ref CONFIG: PathBuf = CONFIGDIR.join("settings.toml");
+	pub static ref CONFIG: PathBuf = CONFIG_OVERRIDE.lock().ok()
+		.and_then(|path| path.clone())
+		.unwrap_or_else(|| CONFIGDIR.join("settings.toml"));
+	/// Settings given by environment variables, by key. These are used over the
+	/// saved settings, and are never saved themselves.
+	static ref ENV_OVERRIDES: HashMap<&'static str, String> = ENV_VARIABLES.iter()
+		.filter_map(|(key, variable)| {
+			std::env::var(variable).ok()
+				.filter(|value| !value.is_empty())
+				.map(|value| (*key, value))
+		})
+		.collect();
 }
 
 #[cfg(target_os = "windows")]
@@ -41,11 +70,41 @@ lazy_static! {
 	};
 }
 
-const VGAUDIO_CLI_PATH: &str = "vgaudio_cli_path";
-const VGAUDIO_CLI_PREPATH: &str = "vgaudio_cli_prepath";
-const VGMSTREAM_PATH: &str = "vgmstream_path";
+pub const VGAUDIO_CLI_PATH: &str = "vgaudio_cli_path";
+pub const VGAUDIO_CLI_PREPATH: &str = "vgaudio_cli_prepath";
+pub const VGMSTREAM_PATH: &str = "vgmstream_path";
 const FIRST_TIME: &str = "first_time";
+/// Old setting, superseded by the per-format decoder settings. Only read to migrate it.
 const PREFER_VGMSTREAM_DECODE: &str = "prefer_vgmstream_for_decode";
+const IDSP_DECODER: &str = "idsp_decoder";
+const LOPUS_DECODER: &str = "lopus_decoder";
+const KEEP_CACHE: &str = "keep_cache";
+const TOOL_TIMEOUT: &str = "tool_timeout_seconds";
+const CHECK_UPDATES: &str = "check_for_updates";
+const SHORTCUTS: &str = "shortcuts";
+const SHOW_TOOLBAR: &str = "show_toolbar";
+const SHOW_HASHES: &str = "show_content_hashes";
+const PREFETCH: &str = "prefetch_neighbours";
+const UI_SCALE: &str = "ui_scale";
+const FONT_SIZE: &str = "font_size";
+const MAIN_WINDOW_GEOMETRY: &str = "main_window_geometry";
+const PROPERTIES_WINDOW_SIZE: &str = "properties_window_size";
+const LAST_DIRECTORIES: &str = "last_directories";
+const DEFAULT_FORMAT: &str = "default_format";
+const EXPORT_NAME_TEMPLATE: &str = "export_name_template";
+const DRAG_OUT_ENCODED: &str = "drag_out_encoded";
+const CONFIRM_REMOVE: &str = "confirm_remove";
+const LONG_IMPORT_MINUTES: &str = "long_import_warning_minutes";
+const PREVIEW_LOOPS: &str = "preview_loop_count";
+const LAST_RUN_VERSION: &str = "last_run_version";
+
+/// The settings that can be given by an environment variable instead, and the
+/// variable each is read from. An empty variable is the same as an unset one.
+const ENV_VARIABLES: [(&str, &str); 3] = [
+	(VGAUDIO_CLI_PATH, "SIMPLE_NUS3_VGAUDIO_CLI"),
+	(VGMSTREAM_PATH, "SIMPLE_NUS3_VGMSTREAM"),
+	(VGAUDIO_CLI_PREPATH, "SIMPLE_NUS3_RUNTIME")
+];
 
 #[cfg(target_os = "windows")]
 const VGAUDIO_CLI_PATH_DEFAULT: &str = ".\\VGAudioCli\\VGAudioCli.exe";
@@ -58,16 +117,64 @@ const VGMSTREAM_PATH_DEFAULT: &str = ".\\vgmstream\\test.exe";
 const VGMSTREAM_PATH_DEFAULT: &str = "./vgmstream/vgmstream-cli";
 
 const FIRST_TIME_DEFAULT: bool = false;
-const PREFER_VGMSTREAM_DECODE_DEFAULT: bool = true;
+const DECODER_DEFAULT: Decoder = Decoder::Vgmstream;
+const KEEP_CACHE_DEFAULT: bool = false;
+const TOOL_TIMEOUT_DEFAULT: i64 = 120;
+const CHECK_UPDATES_DEFAULT: bool = false;
+const SHOW_TOOLBAR_DEFAULT: bool = false;
+const SHOW_HASHES_DEFAULT: bool = false;
+const PREFETCH_DEFAULT: bool = true;
+const DEFAULT_FORMAT_DEFAULT: AudioExtension = AudioExtension::Idsp;
+/// Names exports like `bgm_title.idsp.wav`, as they were before templates.
+const EXPORT_NAME_TEMPLATE_DEFAULT: &str = "{name}.{format}.{ext}";
+const DRAG_OUT_ENCODED_DEFAULT: bool = false;
+const CONFIRM_REMOVE_DEFAULT: bool = true;
+const LONG_IMPORT_MINUTES_DEFAULT: i64 = 10;
+/// Loop previews forever, as they always did.
+const PREVIEW_LOOPS_DEFAULT: i64 = 0;
+const UI_SCALE_DEFAULT: f64 = 1.0;
+/// FLTK's own default font size
+const FONT_SIZE_DEFAULT: i64 = 14;
 
 const CONFIGURE_VGAUDIO_CLI_MESSAGE: &str = "Please set the path to the VGAudioCli executable.\nThis is required for encoding audio, i.e. saving any nus3audio file.";
+const CONFIGURE_TOOL_TIMEOUT_MESSAGE: &str = "Please set the number of seconds VGAudioCli and vgmstream may run before they are stopped.\nSet this to 0 to wait forever.";
+const CONFIGURE_UI_SCALE_MESSAGE: &str = "Please set how much larger everything should be drawn, from 0.5 to 4.\nFor example, 2 doubles the size of everything on a 4K display.";
+const CONFIGURE_FONT_SIZE_MESSAGE: &str = "Please set the size of text, from 8 to 48.\nThis is used from the next time the program is started.";
 const CONFIGURE_VGMSTREAM_MESSAGE: &str = "Please set the path to the vgmstream executable.\nThis is required for reading loop metadata from audio, and can decode audio.";
 #[cfg(not(target_os = "windows"))]
 const CONFIGURE_RUNTIME_MESSAGE: &str = "Please set the path to the executable used to run .NET applications.
 This executable will be given the path to the VGAudioCli executable, immediately followed by arguments passed to it.
 It is recommended to use mono or dotnet over wine.";
 
-pub struct Settings (pub toml::map::Map<String, toml::Value>, bool);
+#[derive(Clone)]
+/// File dialogs that remember the last directory they were used in.
+#[derive(Clone, Copy, Debug)]
+pub enum DialogKind {
+	/// Opening nus3audio files.
+	Open,
+	/// Choosing audio to replace or add items with.
+	Audio,
+	/// Exporting a single item.
+	ExportSingle,
+	/// Exporting every item.
+	ExportAll
+}
+
+impl DialogKind {
+	/// The name of this dialog in the settings.
+	fn key(self) -> &'static str {
+		match self {
+			Self::Open => "open",
+			Self::Audio => "audio",
+			Self::ExportSingle => "export_single",
+			Self::ExportAll => "export_all"
+		}
+	}
+}
+
+/// The settings, whether or not they changed since they were read, and what
+/// was changed to bring older settings up to date, see [Settings::take_migrations].
+pub struct Settings (pub toml::map::Map<String, toml::Value>, bool, Vec<String>);
 
 impl Default for Settings {
 	fn default() -> Self {
@@ -96,11 +203,50 @@ impl Settings {
 		if !map.contains_key(FIRST_TIME) {
 			map.insert(FIRST_TIME.to_owned(), toml::Value::Boolean(FIRST_TIME_DEFAULT));
 		}
-		if !map.contains_key(PREFER_VGMSTREAM_DECODE) {
-			map.insert(PREFER_VGMSTREAM_DECODE.to_owned(), toml::Value::Boolean(PREFER_VGMSTREAM_DECODE_DEFAULT));
+		// Migrate the old decoder preference to the per-format ones
+		let mut migrations = Vec::new();
+		let decoder_default = match map.remove(PREFER_VGMSTREAM_DECODE) {
+			Some(old) => {
+				let decoder = if let toml::Value::Boolean(false) = old { Decoder::VGAudioCli } else { DECODER_DEFAULT };
+				migrations.push(format!("The decoder preference is now set for IDSP and LOPUS separately. Both use {} as before.", decoder));
+				decoder
+			},
+			None => DECODER_DEFAULT
+		};
+		if !map.contains_key(IDSP_DECODER) {
+			map.insert(IDSP_DECODER.to_owned(), toml::Value::String(decoder_default.to_string()));
+		}
+		if !map.contains_key(LOPUS_DECODER) {
+			map.insert(LOPUS_DECODER.to_owned(), toml::Value::String(decoder_default.to_string()));
+		}
+		if !map.contains_key(KEEP_CACHE) {
+			map.insert(KEEP_CACHE.to_owned(), toml::Value::Boolean(KEEP_CACHE_DEFAULT));
+		}
+		if !map.contains_key(CHECK_UPDATES) {
+			map.insert(CHECK_UPDATES.to_owned(), toml::Value::Boolean(CHECK_UPDATES_DEFAULT));
+		}
+		if !map.contains_key(TOOL_TIMEOUT) {
+			map.insert(TOOL_TIMEOUT.to_owned(), toml::Value::Integer(TOOL_TIMEOUT_DEFAULT));
+		}
+		if !map.contains_key(SHOW_TOOLBAR) {
+			map.insert(SHOW_TOOLBAR.to_owned(), toml::Value::Boolean(SHOW_TOOLBAR_DEFAULT));
+		}
+		if !map.contains_key(SHOW_HASHES) {
+			map.insert(SHOW_HASHES.to_owned(), toml::Value::Boolean(SHOW_HASHES_DEFAULT));
+		}
+		if !map.contains_key(DEFAULT_FORMAT) {
+			map.insert(DEFAULT_FORMAT.to_owned(), toml::Value::String(DEFAULT_FORMAT_DEFAULT.to_string()));
+		}
+		if !map.contains_key(UI_SCALE) {
+			map.insert(UI_SCALE.to_owned(), toml::Value::Float(UI_SCALE_DEFAULT));
+		}
+		if !map.contains_key(FONT_SIZE) {
+			map.insert(FONT_SIZE.to_owned(), toml::Value::Integer(FONT_SIZE_DEFAULT));
 		}
 
-		Self (map, false)
+		// Write migrated settings back, so the old ones aren't read again
+		let dirty = !migrations.is_empty();
+		Self (map, dirty, migrations)
 	}
 
 	/// Return a deserialized settings file, or the default.
@@ -110,14 +256,27 @@ impl Settings {
 				Ok(map) => return Self::from_default(map),
 				Err(error) => println!("couldn't read settings, skipping: {}", error)
 			},
-			Err(error) => println!("couldn't read settings, skipping: {}", error)
+			Err(error) => {
+				println!("couldn't read settings, skipping: {}", error);
+				if error.kind() == std::io::ErrorKind::NotFound {
+					// This is the first time the program has been run
+					let mut settings = Self::new();
+					settings.set_first_time(true);
+					return settings
+				}
+			}
 		}
 
 		Self::new()
 	}
 
 	/// Return the path to VGAudioCli's executable.
+	/// 
+	/// The `SIMPLE_NUS3_VGAUDIO_CLI` environment variable is used over the setting.
 	pub fn vgaudio_cli_path(&self) -> &str {
+		if let Some((_, value)) = env_override(VGAUDIO_CLI_PATH) {
+			return value
+		}
 		let value = self.0.get::<str>(VGAUDIO_CLI_PATH);
 		if let Some(toml::Value::String(value)) = value {
 			value
@@ -127,7 +286,12 @@ impl Settings {
 	}
 
 	/// Return the path to vgmstream's executable.
+	/// 
+	/// The `SIMPLE_NUS3_VGMSTREAM` environment variable is used over the setting.
 	pub fn vgmstream_path(&self) -> &str {
+		if let Some((_, value)) = env_override(VGMSTREAM_PATH) {
+			return value
+		}
 		let value = self.0.get::<str>(VGMSTREAM_PATH);
 		if let Some(toml::Value::String(value)) = value {
 			value
@@ -141,7 +305,11 @@ impl Settings {
 	/// 
 	/// Though the .NET runtime is not configurable in Windows,
 	/// this setting is still used there (although it defaults to an empty string).
+	/// The `SIMPLE_NUS3_RUNTIME` environment variable is used over the setting.
 	pub fn vgaudio_cli_prepath(&self) -> &str {
+		if let Some((_, value)) = env_override(VGAUDIO_CLI_PREPATH) {
+			return value
+		}
 		let value = self.0.get::<str>(VGAUDIO_CLI_PREPATH);
 		if let Some(toml::Value::String(value)) = value {
 			value
@@ -155,7 +323,11 @@ impl Settings {
 	/// 
 	/// Though the .NET runtime is not configurable in Windows,
 	/// this setting is still used there (although it defaults to an empty string).
+	/// The `SIMPLE_NUS3_RUNTIME` environment variable is used over the setting.
 	pub fn vgaudio_cli_prepath(&self) -> &str {
+		if let Some((_, value)) = env_override(VGAUDIO_CLI_PREPATH) {
+			return value
+		}
 		let value = self.0.get::<str>(VGAUDIO_CLI_PREPATH);
 		if let Some(toml::Value::String(value)) = value {
 			value
@@ -164,6 +336,22 @@ impl Settings {
 		}
 	}
 
+	/// Return the version of this program that was last run with these settings,
+	/// if it was recorded.
+	pub fn last_run_version(&self) -> Option<&str> {
+		if let Some(toml::Value::String(value)) = self.0.get::<str>(LAST_RUN_VERSION) {
+			Some(value)
+		} else {
+			None
+		}
+	}
+
+	/// Take the notes on what was changed to bring older settings up to date,
+	/// like a setting that was split in two.
+	pub fn take_migrations(&mut self) -> Vec<String> {
+		std::mem::take(&mut self.2)
+	}
+
 	/// Return the first time boolean. Whether or not the first-time message should be displayed.
 	pub fn first_time(&self) -> bool {
 		let value = self.0.get::<str>(FIRST_TIME);
@@ -174,17 +362,365 @@ impl Settings {
 		}
 	}
 
-	/// Return the prefer vgmstream for decode boolean.
-	/// Whether or not vgmstream should be preferred over VGAudioCli when vgmstream's path is not empty.
-	pub fn prefer_vgmstream_decode(&self) -> bool {
-		let value = self.0.get::<str>(PREFER_VGMSTREAM_DECODE);
+	/// Return the decoder that should be tried first for audio in the `extension` format.
+	/// 
+	/// The other decoder is still used if this one's path is empty.
+	pub fn decoder_for(&self, extension: &AudioExtension) -> Decoder {
+		let key = match extension {
+			AudioExtension::Idsp => IDSP_DECODER,
+			AudioExtension::Lopus => LOPUS_DECODER,
+			AudioExtension::Bin => return DECODER_DEFAULT
+		};
+		let value = self.0.get::<str>(key);
+		if let Some(toml::Value::String(value)) = value {
+			value.parse().unwrap_or(DECODER_DEFAULT)
+		} else {
+			DECODER_DEFAULT
+		}
+	}
+
+	/// Set the decoder that should be tried first for audio in the `extension` format.
+	pub fn set_decoder_for(&mut self, extension: &AudioExtension, decoder: Decoder) {
+		let key = match extension {
+			AudioExtension::Idsp => IDSP_DECODER,
+			AudioExtension::Lopus => LOPUS_DECODER,
+			AudioExtension::Bin => return
+		};
+		self.0.insert(key.to_owned(), toml::Value::String(decoder.to_string()));
+		self.1 = true
+	}
+
+	/// Return the default format.
+	/// The format new items and audio replacing binary data are encoded as.
+	pub fn default_format(&self) -> AudioExtension {
+		match self.0.get::<str>(DEFAULT_FORMAT).and_then(|value| value.as_str()).and_then(|value| value.parse().ok()) {
+			// Binary data isn't a format audio can be encoded as
+			Some(AudioExtension::Bin) | None => DEFAULT_FORMAT_DEFAULT,
+			Some(format) => format
+		}
+	}
+
+	/// Set the default format.
+	pub fn set_default_format(&mut self, format: &AudioExtension) {
+		self.0.insert(DEFAULT_FORMAT.to_owned(), toml::Value::String(format.to_string()));
+		self.1 = true
+	}
+
+	/// Return the export name template.
+	/// The file name each item gets when every item is exported, see [simple_nus3audio_core::util::expand_name_template].
+	pub fn export_name_template(&self) -> &str {
+		match self.0.get::<str>(EXPORT_NAME_TEMPLATE) {
+			Some(toml::Value::String(template)) if !template.trim().is_empty() => template,
+			_ => EXPORT_NAME_TEMPLATE_DEFAULT
+		}
+	}
+
+	/// Set the export name template.
+	pub fn set_export_name_template(&mut self, template: &str) {
+		self.0.insert(EXPORT_NAME_TEMPLATE.to_owned(), toml::Value::String(template.to_owned()));
+		self.1 = true
+	}
+
+	/// Return the drag out encoded boolean.
+	/// Whether items dragged out of the list are written as they are saved,
+	/// rather than as WAV.
+	pub fn drag_out_encoded(&self) -> bool {
+		match self.0.get::<str>(DRAG_OUT_ENCODED) {
+			Some(toml::Value::Boolean(value)) => *value,
+			_ => DRAG_OUT_ENCODED_DEFAULT
+		}
+	}
+
+	/// Set the drag out encoded boolean.
+	pub fn set_drag_out_encoded(&mut self, value: bool) {
+		self.0.insert(DRAG_OUT_ENCODED.to_owned(), toml::Value::Boolean(value));
+		self.1 = true
+	}
+
+	/// Return the confirm remove boolean.
+	/// Whether or not to ask before an item is removed.
+	pub fn confirm_remove(&self) -> bool {
+		match self.0.get::<str>(CONFIRM_REMOVE) {
+			Some(toml::Value::Boolean(value)) => *value,
+			_ => CONFIRM_REMOVE_DEFAULT
+		}
+	}
+
+	/// Set the confirm remove boolean.
+	pub fn set_confirm_remove(&mut self, value: bool) {
+		self.0.insert(CONFIRM_REMOVE.to_owned(), toml::Value::Boolean(value));
+		self.1 = true
+	}
+
+	/// Return how long, in minutes, audio being imported may be before asking
+	/// whether it's the right file, or 0 to never ask.
+	pub fn long_import_minutes(&self) -> u32 {
+		match self.0.get::<str>(LONG_IMPORT_MINUTES) {
+			Some(toml::Value::Integer(value)) => (*value).clamp(0, u32::MAX as i64) as u32,
+			_ => LONG_IMPORT_MINUTES_DEFAULT as u32
+		}
+	}
+
+	/// Set how long audio being imported may be before asking, see [Settings::long_import_minutes].
+	pub fn set_long_import_minutes(&mut self, value: u32) {
+		self.0.insert(LONG_IMPORT_MINUTES.to_owned(), toml::Value::Integer(value as i64));
+		self.1 = true
+	}
+
+	/// Return how many times the loop of a preview plays before the rest of the
+	/// audio after it, or 0 to loop forever.
+	pub fn preview_loops(&self) -> u32 {
+		match self.0.get::<str>(PREVIEW_LOOPS) {
+			Some(toml::Value::Integer(value)) => (*value).clamp(0, u32::MAX as i64) as u32,
+			_ => PREVIEW_LOOPS_DEFAULT as u32
+		}
+	}
+
+	/// Set how many times the loop of a preview plays, see [Settings::preview_loops].
+	pub fn set_preview_loops(&mut self, value: u32) {
+		self.0.insert(PREVIEW_LOOPS.to_owned(), toml::Value::Integer(value as i64));
+		self.1 = true
+	}
+
+	/// Return the keep cache boolean.
+	/// Whether or not the cache directory should be kept between sessions.
+	pub fn keep_cache(&self) -> bool {
+		let value = self.0.get::<str>(KEEP_CACHE);
+		if let Some(toml::Value::Boolean(value)) = value {
+			*value
+		} else {
+			KEEP_CACHE_DEFAULT
+		}
+	}
+
+	/// Return the time external tools may run before they are killed.
+	/// A duration of zero means there is no timeout.
+	pub fn tool_timeout(&self) -> std::time::Duration {
+		let value = self.0.get::<str>(TOOL_TIMEOUT);
+		let seconds = if let Some(toml::Value::Integer(value)) = value {
+			*value
+		} else {
+			TOOL_TIMEOUT_DEFAULT
+		};
+		std::time::Duration::from_secs(seconds.max(0) as u64)
+	}
+
+	/// Return the check for updates boolean.
+	/// Whether or not GitHub should be checked for a new release on startup.
+	pub fn check_updates(&self) -> bool {
+		let value = self.0.get::<str>(CHECK_UPDATES);
+		if let Some(toml::Value::Boolean(value)) = value {
+			*value
+		} else {
+			CHECK_UPDATES_DEFAULT
+		}
+	}
+
+	/// Set the check for updates boolean.
+	pub fn set_check_updates(&mut self, check_updates: bool) {
+		self.0.insert(CHECK_UPDATES.to_owned(), toml::Value::Boolean(check_updates));
+		self.1 = true
+	}
+
+	/// Return the show toolbar boolean.
+	/// Whether or not the toolbar is shown under the menu bar.
+	pub fn show_toolbar(&self) -> bool {
+		let value = self.0.get::<str>(SHOW_TOOLBAR);
 		if let Some(toml::Value::Boolean(value)) = value {
 			*value
 		} else {
-			PREFER_VGMSTREAM_DECODE_DEFAULT
+			SHOW_TOOLBAR_DEFAULT
 		}
 	}
 
+	/// Set the show toolbar boolean.
+	pub fn set_show_toolbar(&mut self, show_toolbar: bool) {
+		self.0.insert(SHOW_TOOLBAR.to_owned(), toml::Value::Boolean(show_toolbar));
+		self.1 = true
+	}
+
+	/// Return the show content hashes boolean.
+	/// Whether or not the list shows the content hash of each item after its name.
+	pub fn show_hashes(&self) -> bool {
+		let value = self.0.get::<str>(SHOW_HASHES);
+		if let Some(toml::Value::Boolean(value)) = value {
+			*value
+		} else {
+			SHOW_HASHES_DEFAULT
+		}
+	}
+
+	/// Set the show content hashes boolean.
+	pub fn set_show_hashes(&mut self, show_hashes: bool) {
+		self.0.insert(SHOW_HASHES.to_owned(), toml::Value::Boolean(show_hashes));
+		self.1 = true
+	}
+
+	/// Return the prefetch boolean.
+	/// Whether or not previews of the items around the selected one are made ahead of time.
+	pub fn prefetch(&self) -> bool {
+		let value = self.0.get::<str>(PREFETCH);
+		if let Some(toml::Value::Boolean(value)) = value {
+			*value
+		} else {
+			PREFETCH_DEFAULT
+		}
+	}
+
+	/// Set the prefetch boolean.
+	pub fn set_prefetch(&mut self, prefetch: bool) {
+		self.0.insert(PREFETCH.to_owned(), toml::Value::Boolean(prefetch));
+		self.1 = true
+	}
+
+	/// Return the UI scale.
+	/// How much larger everything is drawn, on every screen.
+	pub fn ui_scale(&self) -> f64 {
+		match self.0.get::<str>(UI_SCALE) {
+			Some(toml::Value::Float(value)) => *value,
+			Some(toml::Value::Integer(value)) => *value as f64,
+			_ => UI_SCALE_DEFAULT
+		}
+	}
+
+	/// Return the font size.
+	pub fn font_size(&self) -> i32 {
+		let value = self.0.get::<str>(FONT_SIZE);
+		if let Some(toml::Value::Integer(value)) = value {
+			*value as i32
+		} else {
+			FONT_SIZE_DEFAULT as i32
+		}
+	}
+
+	/// Apply the UI scale and font size.
+	/// 
+	/// The font size only affects widgets created afterward,
+	/// so this should be called before the main window is made.
+	pub fn apply_scale(&self) {
+		for screen in 0..fltk::app::screen_count() {
+			fltk::app::set_screen_scale(screen, self.ui_scale() as f32)
+		}
+		fltk::app::set_font_size(self.font_size())
+	}
+
+	/// Return the array of integers `key`, if it is one.
+	fn integers(&self, key: &str) -> Option<Vec<i32>> {
+		match self.0.get::<str>(key) {
+			Some(toml::Value::Array(values)) => values.iter()
+				.map(|value| value.as_integer().map(|value| value as i32))
+				.collect(),
+			_ => None
+		}
+	}
+
+	/// Set the array of integers `key`.
+	fn set_integers(&mut self, key: &str, values: &[i32]) {
+		let values = values.iter().map(|value| toml::Value::Integer(*value as i64)).collect();
+		self.0.insert(key.to_owned(), toml::Value::Array(values));
+		self.1 = true
+	}
+
+	/// Return the position and size of the main window when it was last closed,
+	/// as x, y, width and height.
+	pub fn main_window_geometry(&self) -> Option<(i32, i32, i32, i32)> {
+		match self.integers(MAIN_WINDOW_GEOMETRY)?[..] {
+			[x, y, width, height] => Some((x, y, width, height)),
+			_ => None
+		}
+	}
+
+	/// Set the position and size of the main window.
+	pub fn set_main_window_geometry(&mut self, x: i32, y: i32, width: i32, height: i32) {
+		self.set_integers(MAIN_WINDOW_GEOMETRY, &[x, y, width, height])
+	}
+
+	/// Return the size of the properties window when it was last closed,
+	/// as width and height.
+	pub fn properties_window_size(&self) -> Option<(i32, i32)> {
+		match self.integers(PROPERTIES_WINDOW_SIZE)?[..] {
+			[width, height] => Some((width, height)),
+			_ => None
+		}
+	}
+
+	/// Set the size of the properties window.
+	pub fn set_properties_window_size(&mut self, width: i32, height: i32) {
+		self.set_integers(PROPERTIES_WINDOW_SIZE, &[width, height])
+	}
+
+	/// Return the shortcut of the menu action `binding`, as text.
+	pub fn shortcut(&self, binding: &crate::shortcuts::Binding) -> String {
+		match self.0.get::<str>(SHORTCUTS).and_then(|shortcuts| shortcuts.get(binding.name)) {
+			Some(toml::Value::String(value)) => value.to_owned(),
+			_ => binding.default.to_owned()
+		}
+	}
+
+	/// Set the shortcut of the menu action named `name`, as text.
+	pub fn set_shortcut(&mut self, name: &str, shortcut: &str) {
+		let shortcuts = self.0.entry(SHORTCUTS.to_owned()).or_insert_with(|| toml::Value::Table(toml::map::Map::new()));
+		if !shortcuts.is_table() {
+			*shortcuts = toml::Value::Table(toml::map::Map::new())
+		}
+		if let toml::Value::Table(shortcuts) = shortcuts {
+			shortcuts.insert(name.to_owned(), toml::Value::String(shortcut.to_owned()));
+		}
+		self.1 = true
+	}
+
+	/// Return the directory the `kind` dialog was last used in, if it still exists.
+	pub fn last_directory(&self, kind: DialogKind) -> Option<PathBuf> {
+		match self.0.get::<str>(LAST_DIRECTORIES).and_then(|directories| directories.get(kind.key())) {
+			Some(toml::Value::String(value)) => Some(PathBuf::from(value)).filter(|path| path.is_dir()),
+			_ => None
+		}
+	}
+
+	/// Remember that the `kind` dialog was used to choose `path`.
+	/// 
+	/// If `path` isn't an existing directory, its parent is remembered.
+	pub fn set_last_directory(&mut self, kind: DialogKind, path: &std::path::Path) {
+		let directory = if path.is_dir() { Some(path) } else { path.parent() };
+		let directory = match directory {
+			Some(directory) => directory.to_string_lossy().to_string(),
+			None => return
+		};
+
+		let directories = self.0.entry(LAST_DIRECTORIES.to_owned()).or_insert_with(|| toml::Value::Table(toml::map::Map::new()));
+		if !directories.is_table() {
+			*directories = toml::Value::Table(toml::map::Map::new())
+		}
+		if let toml::Value::Table(directories) = directories {
+			directories.insert(kind.key().to_owned(), toml::Value::String(directory));
+		}
+		self.1 = true
+	}
+
+	/// Set the path to VGAudioCli's executable.
+	pub fn set_vgaudio_cli_path(&mut self, path: &str) {
+		self.0.insert(VGAUDIO_CLI_PATH.to_owned(), toml::Value::String(path.to_owned()));
+		self.1 = true
+	}
+
+	/// Set the path to vgmstream's executable.
+	pub fn set_vgmstream_path(&mut self, path: &str) {
+		self.0.insert(VGMSTREAM_PATH.to_owned(), toml::Value::String(path.to_owned()));
+		self.1 = true
+	}
+
+	/// Set the keep cache boolean.
+	pub fn set_keep_cache(&mut self, keep_cache: bool) {
+		self.0.insert(KEEP_CACHE.to_owned(), toml::Value::Boolean(keep_cache));
+		self.1 = true
+	}
+
+	/// Set the version of this program that was last run with these settings.
+	pub fn set_last_run_version(&mut self, version: &str) {
+		self.0.insert(LAST_RUN_VERSION.to_owned(), toml::Value::String(version.to_owned()));
+		self.1 = true
+	}
+
 	/// Set the first time boolean. Whether or not the first-time message should be displayed.
 	pub fn set_first_time(&mut self, first_time: bool) {
 		self.0.insert(FIRST_TIME.to_owned(), toml::Value::Boolean(first_time));
@@ -208,24 +744,41 @@ impl Settings {
 		}
 	}
 
-	/// Shows the first-time greeting if it hasn't already been shown.
-	pub fn first_time_greeting(&mut self, window: &Window, sender: fltk::app::Sender<crate::Message>) {
+	/// Shows the first-time setup wizard if it hasn't already been shown.
+	pub fn first_time_greeting(&mut self, window: &Window) {
 		if self.first_time() {
-			message_title("Welcome");
-			let response = choice2(window, "To get started, please download a release of
-https://ci.appveyor.com/project/Thealexbarney/VGAudio/build/artifacts
-Then, visit \"File → Configure VGAudioCli\" to set this location.", "Dismiss", "Show me", "");
-
-			if let Some(1) = response {
-				let _ = open::that("https://ci.appveyor.com/project/Thealexbarney/VGAudio/build/artifacts");
-				sender.send(crate::Message::ConfigureVGAudioCliPath)
-			}
+			crate::setup::wizard(self, window);
 
 			self.set_first_time(false);
 			self.1 = true
 		}
 	}
 
+	/// Download and install VGAudioCli, set its path, and check that it runs.
+	pub fn install_vgaudio_cli(&mut self, window: &Window) {
+		match crate::setup::install_vgaudio_cli(window) {
+			Ok(executable) => {
+				self.set_vgaudio_cli_path(&executable.to_string_lossy());
+
+				match crate::setup::test_vgaudio_cli(self) {
+					Ok(line) => {
+						message_title("VGAudioCli Installed");
+						crate::alert(window, &format!("VGAudioCli was installed to\n{}\nand runs correctly:\n{}", executable.to_string_lossy(), line))
+					},
+					Err(error) => {
+						message_title("Warning");
+						crate::alert(window, &format!("VGAudioCli was installed to\n{}\nbut could not be run:\n{}", executable.to_string_lossy(), error))
+					}
+				}
+			},
+			Err(error) if error == simple_nus3audio_core::cancel::CANCELLED => (),
+			Err(error) => {
+				message_title("Error");
+				crate::alert(window, &error)
+			}
+		}
+	}
+
 	/// Open an input dialog that allows changing the VGAudioCli path.
 	pub fn configure_vgaudio_cli_path(&mut self, window: &Window) {
 		self.configure_value(VGAUDIO_CLI_PATH, "VGAudioCli Path", CONFIGURE_VGAUDIO_CLI_MESSAGE, window)
@@ -245,6 +798,67 @@ Then, visit \"File → Configure VGAudioCli\" to set this location.", "Dismiss",
 		self.configure_value(VGMSTREAM_PATH, "vgmstream Path", CONFIGURE_VGMSTREAM_MESSAGE, window)
 	}
 
+	/// Open an input dialog that allows changing the external tool timeout.
+	pub fn configure_tool_timeout(&mut self, window: &Window) {
+		message_title("Tool Timeout");
+		let default = self.tool_timeout().as_secs().to_string();
+
+		if let Some(new_value) = input(window, CONFIGURE_TOOL_TIMEOUT_MESSAGE, &default) {
+			match new_value.trim().parse::<u32>() {
+				Ok(seconds) => {
+					self.0.insert(TOOL_TIMEOUT.to_owned(), toml::Value::Integer(seconds as i64));
+					self.1 = true
+				},
+				Err(_) => {
+					message_title("Error");
+					crate::alert(window, "The timeout must be a whole, positive number of seconds.")
+				}
+			}
+		}
+	}
+
+	/// Open an input dialog that allows changing the UI scale.
+	/// 
+	/// Returns true if the scale was changed.
+	pub fn configure_ui_scale(&mut self, window: &Window) -> bool {
+		message_title("UI Scale");
+		let default = self.ui_scale().to_string();
+
+		if let Some(new_value) = input(window, CONFIGURE_UI_SCALE_MESSAGE, &default) {
+			match new_value.trim().parse::<f64>() {
+				Ok(scale) if (0.5..=4.0).contains(&scale) => {
+					self.0.insert(UI_SCALE.to_owned(), toml::Value::Float(scale));
+					self.1 = true;
+					return true
+				},
+				_ => {
+					message_title("Error");
+					crate::alert(window, "The scale must be a number from 0.5 to 4.")
+				}
+			}
+		}
+		false
+	}
+
+	/// Open an input dialog that allows changing the font size.
+	pub fn configure_font_size(&mut self, window: &Window) {
+		message_title("Font Size");
+		let default = self.font_size().to_string();
+
+		if let Some(new_value) = input(window, CONFIGURE_FONT_SIZE_MESSAGE, &default) {
+			match new_value.trim().parse::<i64>() {
+				Ok(size) if (8..=48).contains(&size) => {
+					self.0.insert(FONT_SIZE.to_owned(), toml::Value::Integer(size));
+					self.1 = true
+				},
+				_ => {
+					message_title("Error");
+					crate::alert(window, "The font size must be a whole number from 8 to 48.")
+				}
+			}
+		}
+	}
+
 	/// Configure the value `key` with a dialog window.
 	pub fn configure_value(&mut self, key: &str, title: &str, message: &str, window: &Window) {
 		message_title(title);
@@ -263,7 +877,13 @@ Then, visit \"File → Configure VGAudioCli\" to set this location.", "Dismiss",
 			}
 		}
 
-		if let Some(new_value) = input(window, message, default) {
+		// The saved setting can still be changed, but say why it won't be used yet
+		let message = match env_override(key) {
+			Some((variable, value)) => format!("{}\n\nThe environment variable {} is set to {}, which is used instead until it is unset.", message, variable, value),
+			None => message.to_owned()
+		};
+
+		if let Some(new_value) = input(window, &message, default) {
 			self.0.insert(key.to_owned(), toml::Value::String(new_value));
 			self.1 = true
 		}
@@ -303,4 +923,75 @@ Then, visit \"File → Configure VGAudioCli\" to set this location.", "Dismiss",
 		fs::create_dir(CACHEDIR.as_path())?;
 		Ok(())
 	}
+
+	/// Function that will make sure the cache dir exists without emptying it.
+	pub fn create_cache() -> Result<(), std::io::Error> {
+		if CACHEDIR.is_file() {
+			fs::remove_file(CACHEDIR.as_path())?
+		}
+
+		fs::create_dir_all(CACHEDIR.as_path())
+	}
+
+	/// Reset the cache dir, unless these settings say it should be kept.
+	/// A kept cache is pruned instead, see [simple_nus3audio_core::item::prune_cache].
+	pub fn prepare_cache(&self) -> Result<(), std::io::Error> {
+		if self.keep_cache() {
+			Self::create_cache()?;
+			simple_nus3audio_core::item::prune_cache(CACHEDIR.as_path())
+		} else {
+			Self::reset_cache()
+		}
+	}
+}
+
+/// Read and write the settings at `path` instead of the usual place, with the
+/// rest of the config directory next to it.
+/// 
+/// Only has an effect before the settings are first used.
+pub fn set_config_path(path: PathBuf) {
+	if let Ok(mut config) = CONFIG_OVERRIDE.lock() {
+		*config = Some(path)
+	}
+}
+
+/// Return the environment variable setting `key` and its value, if it's set.
+pub fn env_override(key: &str) -> Option<(&'static str, &'static str)> {
+	let value = ENV_OVERRIDES.get(key)?;
+	let (_, variable) = ENV_VARIABLES.iter().find(|(variable_key, _)| *variable_key == key)?;
+	Some((*variable, value))
+}
+
+impl Config for Settings {
+	fn vgaudio_cli_path(&self) -> &str {
+		Settings::vgaudio_cli_path(self)
+	}
+
+	fn vgaudio_cli_prepath(&self) -> &str {
+		Settings::vgaudio_cli_prepath(self)
+	}
+
+	fn vgmstream_path(&self) -> &str {
+		Settings::vgmstream_path(self)
+	}
+
+	fn tool_timeout(&self) -> std::time::Duration {
+		Settings::tool_timeout(self)
+	}
+
+	fn decoder_for(&self, extension: &AudioExtension) -> Decoder {
+		Settings::decoder_for(self, extension)
+	}
+
+	fn keep_cache(&self) -> bool {
+		Settings::keep_cache(self)
+	}
+
+	fn cache_dir(&self) -> &std::path::Path {
+		CACHEDIR.as_path()
+	}
+
+	fn default_format(&self) -> AudioExtension {
+		Settings::default_format(self)
+	}
 }
@@ -0,0 +1,415 @@
+//! Getting the external tools set up.
+
+use std::{
+	fs,
+	io::{ Cursor, Read },
+	path::{ Path, PathBuf },
+	process::Command,
+	sync::{
+		atomic::{ AtomicU64, Ordering },
+		Arc
+	},
+	thread,
+	time::Duration
+};
+use fltk::{
+	app,
+	dialog::{ message_title, FileDialogType, NativeFileChooser },
+	window::Window
+};
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use simple_nus3audio_core::{
+	util::human_readable_size,
+	Cancel
+};
+use crate::{
+	layout::{ alert, choice2 },
+	playback::Playback,
+	progress::ProgressDialog,
+	settings::{
+		CACHEDIR,
+		CONFIGDIR,
+		Settings
+	}
+};
+
+/// Where vgmstream releases can be found.
+pub const VGMSTREAM_RELEASES_URL: &str = "https://github.com/vgmstream/vgmstream/releases/latest";
+/// The GitHub API endpoint for the latest VGAudio release, which has VGAudioCli in it.
+const VGAUDIO_LATEST_RELEASE_URL: &str = "https://api.github.com/repos/Thealexbarney/VGAudio/releases/latest";
+/// Name of the VGAudioCli release archive among the files of a VGAudio release.
+const VGAUDIO_CLI_ASSET: &str = "VGAudioCli.zip";
+/// Name of the VGAudioCli executable inside the release archive.
+const VGAUDIO_CLI_EXECUTABLE: &str = "VGAudioCli.exe";
+/// The largest download we'll accept, to avoid filling memory if something goes very wrong.
+const MAX_DOWNLOAD_SIZE: u64 = 64_000_000;
+/// How long to wait for GitHub before giving up.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Return the directory VGAudioCli is installed to by [install_vgaudio_cli].
+pub fn vgaudio_cli_install_dir() -> PathBuf {
+	CONFIGDIR.join("VGAudioCli")
+}
+
+/// Return where the VGAudioCli release archive of the latest VGAudio release
+/// can be downloaded from, as GitHub lists it.
+pub fn vgaudio_cli_download_url(agent: &ureq::Agent) -> Result<String, String> {
+	debug!("Looking up the latest VGAudio release at {}", VGAUDIO_LATEST_RELEASE_URL);
+
+	let text = agent.get(VGAUDIO_LATEST_RELEASE_URL).set("User-Agent", crate::NAME).call()
+		.map_err(|error| error.to_string())
+		.and_then(|response| response.into_string().map_err(|error| error.to_string()))
+		.map_err(|error| format!("Error looking up the latest VGAudio release\n{}", error))?;
+	let release = json::parse(&text).map_err(|error| format!("Error reading the latest VGAudio release\n{}", error))?;
+
+	release["assets"].members()
+		.find(|asset| asset["name"].as_str() == Some(VGAUDIO_CLI_ASSET))
+		.and_then(|asset| asset["browser_download_url"].as_str())
+		.map(|url| url.to_owned())
+		.ok_or_else(|| format!("The latest VGAudio release has no {}", VGAUDIO_CLI_ASSET))
+}
+
+/// Download the VGAudioCli release archive of the latest VGAudio release and return its bytes.
+/// 
+/// `progress` is called with how many bytes were downloaded so far, and how many
+/// there are if the server said. Once `cancel` is set, the download stops.
+pub fn download_vgaudio_cli(progress: &dyn Fn(u64, Option<u64>), cancel: &Cancel) -> Result<Vec<u8>, String> {
+	let agent = ureq::AgentBuilder::new().timeout_connect(DOWNLOAD_TIMEOUT).timeout_read(DOWNLOAD_TIMEOUT).build();
+	let url = vgaudio_cli_download_url(&agent)?;
+	info!("Downloading VGAudioCli from {}", url);
+
+	let response = match agent.get(&url).set("User-Agent", crate::NAME).call() {
+		Ok(response) => response,
+		Err(error) => return Err(format!("Error downloading VGAudioCli\n{}", error))
+	};
+	let too_large = || format!("The VGAudioCli download is larger than {}, which is more than it should be. It was stopped.", human_readable_size(MAX_DOWNLOAD_SIZE));
+	let length = response.header("Content-Length").and_then(|length| length.parse::<u64>().ok());
+	if length.map(|length| length > MAX_DOWNLOAD_SIZE).unwrap_or(false) {
+		return Err(too_large())
+	}
+
+	let mut reader = response.into_reader();
+	let mut bytes = Vec::with_capacity(length.unwrap_or(0) as usize);
+	let mut buffer = [0; 64 * 1024];
+	loop {
+		cancel.check()?;
+		let read = reader.read(&mut buffer).map_err(|error| format!("Error downloading VGAudioCli\n{}", error))?;
+		if read == 0 { break }
+		bytes.extend_from_slice(&buffer[..read]);
+		if bytes.len() as u64 > MAX_DOWNLOAD_SIZE {
+			return Err(too_large())
+		}
+		progress(bytes.len() as u64, length)
+	}
+
+	debug!("Downloaded VGAudioCli (download is {})", human_readable_size(bytes.len() as u64));
+	Ok(bytes)
+}
+
+/// Extract the VGAudioCli release archive in `archive` to `target_dir`,
+/// and return the path to the executable.
+pub fn extract_vgaudio_cli(archive: &[u8], target_dir: &Path) -> Result<PathBuf, String> {
+	let mut zip = match zip::ZipArchive::new(Cursor::new(archive)) {
+		Ok(zip) => zip,
+		Err(error) => return Err(format!("Error reading the VGAudioCli archive\n{}", error))
+	};
+
+	if let Err(error) = fs::create_dir_all(target_dir) {
+		return Err(format!("Error creating directory {:?}\n{}", target_dir, error))
+	}
+
+	if let Err(error) = zip.extract(target_dir) {
+		return Err(format!("Error extracting the VGAudioCli archive\n{}", error))
+	}
+
+	// The executable might not be at the root of the archive
+	let executable = (0..zip.len())
+		.filter_map(|index| zip.by_index(index).ok().and_then(|file| file.enclosed_name().map(|name| name.to_owned())))
+		.find(|name| name.file_name().map(|name| name == VGAUDIO_CLI_EXECUTABLE).unwrap_or(false));
+
+	match executable {
+		Some(executable) => Ok(target_dir.join(executable)),
+		None => Err(format!("The VGAudioCli archive doesn't contain {}", VGAUDIO_CLI_EXECUTABLE))
+	}
+}
+
+/// Download and extract VGAudioCli next to the config directory, and return the path to the executable.
+/// 
+/// This happens on another thread, while a cancellable progress window is shown near `window`.
+pub fn install_vgaudio_cli(window: &Window) -> Result<PathBuf, String> {
+	let cancel = Cancel::new();
+	let downloaded = Arc::new(AtomicU64::new(0));
+	// 0 until the size of the download is known
+	let total = Arc::new(AtomicU64::new(0));
+
+	let worker = {
+		let (cancel, downloaded, total) = (cancel.clone(), downloaded.clone(), total.clone());
+		thread::spawn(move || {
+			let archive = download_vgaudio_cli(&|bytes: u64, length: Option<u64>| {
+				downloaded.store(bytes, Ordering::Relaxed);
+				total.store(length.unwrap_or(0), Ordering::Relaxed)
+			}, &cancel)?;
+			extract_vgaudio_cli(&archive, &vgaudio_cli_install_dir())
+		})
+	};
+
+	let mut dialog = ProgressDialog::cancellable(window, "Downloading VGAudioCli", 100, &cancel);
+	while !worker.is_finished() {
+		let _ = app::wait_for(0.05);
+		let (bytes, length) = (downloaded.load(Ordering::Relaxed), total.load(Ordering::Relaxed));
+		match length {
+			0 => dialog.set(0, &human_readable_size(bytes)),
+			length => dialog.set((bytes * 100 / length) as usize, &format!("{} of {}", human_readable_size(bytes), human_readable_size(length)))
+		}
+	}
+	dialog.close();
+
+	let executable = worker.join().unwrap_or_else(|_| Err("Installing VGAudioCli stopped unexpectedly".to_owned()))?;
+	info!("Installed VGAudioCli to {:?}", executable);
+	Ok(executable)
+}
+
+/// Check that VGAudioCli can be run with the current settings.
+/// 
+/// Returns the first line VGAudioCli printed if it could be run.
+pub fn test_vgaudio_cli(settings: &Settings) -> Result<String, String> {
+	let vgaudio_cli_path = settings.vgaudio_cli_path();
+	if vgaudio_cli_path.is_empty() {
+		return Err("VGAudioCli path is empty".to_owned())
+	}
+
+	let mut command = match settings.vgaudio_cli_prepath() {
+		prepath if !prepath.is_empty() => {
+			let mut command = Command::new(prepath);
+			command.arg(vgaudio_cli_path);
+			command
+		},
+		_ => Command::new(vgaudio_cli_path)
+	};
+	command.arg("--help");
+
+	let output = simple_nus3audio_core::tool::output(&mut command, "VGAudioCli", settings.tool_timeout(), true)?;
+	let stdout = String::from_utf8_lossy(&output.stdout);
+
+	// VGAudioCli prints its usage when asked for help, which is all we need to see
+	match stdout.lines().find(|line| !line.trim().is_empty()) {
+		Some(line) => Ok(line.trim().to_owned()),
+		None => Err("VGAudioCli ran, but didn't print anything".to_owned())
+	}
+}
+
+/// Check that vgmstream can be run with the current settings.
+/// 
+/// Returns the first line vgmstream printed if it could be run.
+pub fn test_vgmstream(settings: &Settings) -> Result<String, String> {
+	let vgmstream_path = settings.vgmstream_path();
+	if vgmstream_path.is_empty() {
+		return Err("vgmstream path is empty".to_owned())
+	}
+
+	// vgmstream prints its usage to stderr and exits with an error when given nothing to do
+	let mut command = Command::new(vgmstream_path);
+	let output = simple_nus3audio_core::tool::output(&mut command, "vgmstream", settings.tool_timeout(), true)?;
+	let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+
+	match text.lines().find(|line| !line.trim().is_empty()) {
+		Some(line) => Ok(line.trim().to_owned()),
+		None => Err("vgmstream ran, but didn't print anything".to_owned())
+	}
+}
+
+/// Encode a short tone with VGAudioCli to each format, and decode it again
+/// with whichever decoder is configured for that format.
+/// 
+/// Returns a line describing the result of each step.
+pub fn test_conversions(settings: &Settings) -> Vec<(String, Result<(), String>)> {
+	run_conversions(settings).0
+}
+
+/// Run the steps of [test_conversions], also returning the decoded items.
+fn run_conversions(settings: &Settings) -> (Vec<(String, Result<(), String>)>, Vec<crate::list::ListItem>) {
+	let mut results = Vec::new();
+	let mut decoded_items = Vec::new();
+	let wav = simple_nus3audio_core::codec::sine_wav(440.0, 0.5, 48_000);
+
+	for extension in [crate::list::AudioExtension::Idsp, crate::list::AudioExtension::Lopus] {
+		let mut item = crate::list::ListItem::new(format!("setup_test_{}", extension));
+		let generated = wav.clone().and_then(|wav| item.set_audio_from_bytes(wav, simple_nus3audio_core::codec::EncodingType::WAV));
+		let generate_passed = generated.is_ok();
+		results.push((format!("Generate a tone for {}", extension), generated));
+		if !generate_passed { continue }
+
+		let encoded = item.get_nus3_encoded_raw("setup_test", &extension.to_string(), settings);
+		let encode_passed = encoded.is_ok();
+		results.push((format!("Encode {} with VGAudioCli", extension), encoded.as_ref().map(|_| ()).map_err(|e| e.clone())));
+
+		if encode_passed {
+			let mut decoded_item = crate::list::ListItem::new(format!("setup_test_{}_decoded", extension));
+			let decoded = decoded_item.from_encoded("setup_test", encoded.unwrap(), settings)
+				.and_then(|_| if decoded_item.extension == crate::list::AudioExtension::Bin {
+					Err("Audio could not be decoded".to_owned())
+				} else {
+					Ok(())
+				});
+			if decoded.is_ok() {
+				decoded_items.push(decoded_item)
+			}
+			results.push((format!("Decode {} with {}", extension, settings.decoder_for(&extension)), decoded))
+		}
+	}
+
+	let _ = fs::remove_dir_all(CACHEDIR.join("setup_test"));
+
+	(results, decoded_items)
+}
+
+/// Run a short tone through every stage of handling audio and report which
+/// passed: generating it, encoding it with VGAudioCli, decoding it back and
+/// playing it. This shows whether a problem is in one of the tools, the audio
+/// output, or this program itself.
+pub fn self_test(settings: &Settings, playback: &mut Playback, window: &Window) {
+	fltk::app::set_cursor(fltk::enums::Cursor::Wait);
+	fltk::app::flush();
+	let (mut results, mut decoded_items) = run_conversions(settings);
+	fltk::app::set_cursor(fltk::enums::Cursor::Default);
+
+	// Only the listener can tell whether the tone came out of the speakers
+	let played = match decoded_items.first_mut() {
+		Some(item) => loop {
+			if let Err(error) = playback.audition(item) {
+				break Err(error)
+			}
+			message_title("Self Test");
+			let answer = choice2(window, "A short tone is playing.\nDid you hear it?", "Yes", "No", "Play again");
+			playback.stop_sink();
+			match answer {
+				Some(0) => break Ok(()),
+				Some(2) => continue,
+				_ => break Err("The tone played, but wasn't heard".to_owned())
+			}
+		},
+		None => Err("Nothing was decoded to play".to_owned())
+	};
+	results.push(("Play the decoded tone".to_owned(), played));
+
+	let mut summary = String::new();
+	for (step, result) in &results {
+		match result {
+			Ok(()) => summary.push_str(&format!("OK: {}\n", step)),
+			Err(error) => summary.push_str(&format!("FAILED: {}\n    {}\n", step, error.lines().next().unwrap_or_default()))
+		}
+	}
+
+	// Point at the first stage that failed, since the later ones depend on it
+	summary.push('\n');
+	let failed = |prefix: &str| results.iter().any(|(step, result)| step.starts_with(prefix) && result.is_err());
+	summary.push_str(if failed("Generate") {
+		"The problem is in this program itself. Please report it, with Help → Diagnostics."
+	} else if failed("Encode") {
+		"The problem is with VGAudioCli. Check its path and the runtime it's run with."
+	} else if failed("Decode") {
+		"The problem is with the decoder. Check its path, or try the other decoder in Preferences."
+	} else if failed("Play") {
+		"The problem is with the audio output. Check the output device and its volume."
+	} else {
+		"Everything works."
+	});
+
+	info!("Self test:\n{}", summary);
+	message_title("Self Test");
+	alert(window, &summary)
+}
+
+/// Ask the user for the path to an executable. Returns None if they cancel.
+fn locate_executable(title: &str) -> Option<String> {
+	let mut open_dialog = NativeFileChooser::new(FileDialogType::BrowseFile);
+	open_dialog.set_title(title);
+	open_dialog.show();
+
+	let filename = open_dialog.filename();
+	if filename.to_string_lossy().is_empty() {
+		None
+	} else {
+		Some(filename.to_string_lossy().to_string())
+	}
+}
+
+/// Walk the user through setting up the external tools, testing them,
+/// and finally summarize which features will work.
+pub fn wizard(settings: &mut Settings, window: &Window) {
+	message_title("Welcome");
+	if choice2(window, "Welcome! This will help set up the tools needed to open and save nus3audio files:
+- VGAudioCli, to encode (and optionally decode) audio
+- vgmstream, to decode audio and read loop points
+You can run this again from \"Help → Setup wizard\".", "Continue", "Skip setup", "") != Some(0) {
+		return
+	}
+
+	// The runtime needs to be right before VGAudioCli can be tested
+	#[cfg(not(target_os = "windows"))]
+	{
+		settings.configure_vgaudio_cli_prepath(window)
+	}
+
+	// VGAudioCli
+	loop {
+		match test_vgaudio_cli(settings) {
+			Ok(_) => break,
+			Err(error) => {
+				message_title("VGAudioCli");
+				match choice2(window, &format!("VGAudioCli could not be run:\n{}\n\nIt can be downloaded automatically, or you can locate it yourself.", error), "Download", "Locate...", "Skip") {
+					Some(0) => settings.install_vgaudio_cli(window),
+					Some(1) => match locate_executable("Locate VGAudioCli.exe") {
+						Some(path) => settings.set_vgaudio_cli_path(&path),
+						None => continue
+					},
+					_ => break
+				}
+			}
+		}
+	}
+
+	// vgmstream
+	loop {
+		match test_vgmstream(settings) {
+			Ok(_) => break,
+			Err(error) => {
+				message_title("vgmstream");
+				match choice2(window, &format!("vgmstream could not be run:\n{}\n\nIt isn't required, but without it loop points can't be read.", error), "Locate...", "Open downloads", "Skip") {
+					Some(0) => if let Some(path) = locate_executable("Locate vgmstream") {
+						settings.set_vgmstream_path(&path)
+					},
+					Some(1) => { let _ = open::that(VGMSTREAM_RELEASES_URL); },
+					_ => break
+				}
+			}
+		}
+	}
+
+	// Test conversions
+	fltk::app::set_cursor(fltk::enums::Cursor::Wait);
+	fltk::app::flush();
+	let vgaudio_cli = test_vgaudio_cli(settings);
+	let vgmstream = test_vgmstream(settings);
+	let conversions = test_conversions(settings);
+	fltk::app::set_cursor(fltk::enums::Cursor::Default);
+
+	let mut summary = String::new();
+	let mark = |result: bool| if result { "OK" } else { "FAILED" };
+	summary.push_str(&format!("{}: VGAudioCli runs\n", mark(vgaudio_cli.is_ok())));
+	summary.push_str(&format!("{}: vgmstream runs\n", mark(vgmstream.is_ok())));
+	for (step, result) in &conversions {
+		summary.push_str(&format!("{}: {}\n", mark(result.is_ok()), step))
+	}
+
+	summary.push('\n');
+	let can_encode = conversions.iter().any(|(step, result)| step.starts_with("Encode") && result.is_ok());
+	let can_decode = conversions.iter().any(|(step, result)| step.starts_with("Decode") && result.is_ok());
+	summary.push_str(if can_encode { "Saving nus3audio files will work.\n" } else { "Saving nus3audio files will NOT work.\n" });
+	summary.push_str(if can_decode { "Playing and exporting existing sounds will work.\n" } else { "Playing and exporting existing sounds will NOT work.\n" });
+	summary.push_str(if vgmstream.is_ok() { "Loop points will be read from existing sounds." } else { "Loop points will NOT be read from existing sounds." });
+
+	message_title("Setup Summary");
+	alert(window, &summary)
+}
@@ -0,0 +1,235 @@
+//! Keyboard shortcuts of the menu bar, which can be rebound in the settings.
+//! 
+//! Shortcuts are written as text like `Ctrl+Shift+S`, `Space` or `F5`.
+//! An empty shortcut means the action has none.
+//! 
+//! Shortcuts reach the main window before the menu bar, which holds back the
+//! ones meant for the list while a text field has the keyboard focus, see [dispatch].
+
+use std::cell::RefCell;
+use fltk::{
+	prelude::*,
+	app,
+	enums::{ EventState, Key, Shortcut },
+	input::Input,
+	menu::MenuBar
+};
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+use crate::settings::Settings;
+
+/// A menu action whose shortcut can be rebound.
+pub struct Binding {
+	/// The name of this action in the settings.
+	pub name: &'static str,
+	/// The path of this action in the menu bar.
+	pub path: &'static str,
+	/// What this action is called in the shortcut editor.
+	pub label: &'static str,
+	/// The shortcut this action has by default.
+	pub default: &'static str
+}
+
+/// Every menu action whose shortcut can be rebound, in menu order.
+pub const BINDINGS: &[Binding] = &[
+	Binding { name: "new", path: "&File/&New\t", label: "New", default: "Ctrl+N" },
+	Binding { name: "new_from_template", path: "&File/New from &template...\t", label: "New from template", default: "Ctrl+Shift+N" },
+	Binding { name: "open", path: "&File/&Open nus3audio\t", label: "Open", default: "Ctrl+O" },
+	#[cfg(feature = "smash-arc")]
+	Binding { name: "open_from_arc", path: "&File/Open from &data.arc...\t", label: "Open from data.arc", default: "Ctrl+Shift+O" },
+	Binding { name: "save", path: "&File/&Save nus3audio\t", label: "Save", default: "Ctrl+S" },
+	Binding { name: "save_as", path: "&File/Save nus3audio &as...\t", label: "Save as", default: "Ctrl+Shift+S" },
+	Binding { name: "export_single", path: "&File/&Export single sound...\t", label: "Export single sound", default: "Ctrl+E" },
+	Binding { name: "export_all", path: "&File/E&xport all...\t", label: "Export all", default: "Ctrl+Shift+E" },
+	Binding { name: "export_preview_reel", path: "&File/Export preview &reel...\t", label: "Export preview reel", default: "" },
+	Binding { name: "export_to_mod_folder", path: "&File/Export to &mod folder...\t", label: "Export to mod folder", default: "Ctrl+M" },
+	Binding { name: "preview_save", path: "&File/Pre&view save...\t", label: "Preview save", default: "" },
+	Binding { name: "show_in_file_manager", path: "&File/Show in file mana&ger\t", label: "Show in file manager", default: "" },
+	Binding { name: "show_sound_in_file_manager", path: "&File/Show sound in file manager\t", label: "Show sound in file manager", default: "" },
+	Binding { name: "quit", path: "&File/&Quit\t", label: "Quit", default: "Ctrl+Q" },
+	Binding { name: "add", path: "&Edit/&Add sound\t", label: "Add sound", default: "Ctrl+=" },
+	Binding { name: "add_folder", path: "&Edit/Add &folder...\t", label: "Add folder", default: "Ctrl+Shift+=" },
+	Binding { name: "generate_silence", path: "&Edit/Generate s&ilence...\t", label: "Generate silence", default: "" },
+	Binding { name: "generate_tone", path: "&Edit/Generate to&ne...\t", label: "Generate tone", default: "" },
+	Binding { name: "remove", path: "&Edit/Re&move selected sound\t", label: "Remove sound", default: "Ctrl+-" },
+	Binding { name: "remove_all", path: "&Edit/Remove all sounds...\t", label: "Remove all sounds", default: "" },
+	Binding { name: "show_trash", path: "&Edit/Trash...\t", label: "Trash", default: "" },
+	Binding { name: "properties", path: "&Edit/Sound &properties...\t", label: "Sound properties", default: "Ctrl+P" },
+	Binding { name: "jump_to", path: "&Edit/&Jump to item...\t", label: "Jump to item", default: "Ctrl+G" },
+	Binding { name: "toggle_bookmark", path: "&Edit/Toggle boo&kmark\t", label: "Toggle bookmark", default: "Ctrl+B" },
+	Binding { name: "next_bookmark", path: "&Edit/Next bookmark\t", label: "Next bookmark", default: "F2" },
+	Binding { name: "previous_bookmark", path: "&Edit/Previous bookmark\t", label: "Previous bookmark", default: "Shift+F2" },
+	Binding { name: "replace", path: "&Edit/&Replace single sound...\t", label: "Replace sound", default: "Ctrl+R" },
+	Binding { name: "replace_from_clipboard", path: "&Edit/Replace from clipboard pat&h\t", label: "Replace from clipboard path", default: "Ctrl+Shift+V" },
+	Binding { name: "preferences", path: "&Edit/Pre&ferences...\t", label: "Preferences", default: "" },
+	Binding { name: "play", path: "&Playback/&Play\t", label: "Play", default: "Space" },
+	Binding { name: "play_from_loop_start", path: "&Playback/Play from loop s&tart\t", label: "Play from loop start", default: "" },
+	Binding { name: "stop", path: "&Playback/&Stop\t", label: "Stop", default: "" },
+	Binding { name: "seek_backward", path: "&Playback/Seek &backward\t", label: "Seek backward", default: "" },
+	Binding { name: "seek_forward", path: "&Playback/Seek &forward\t", label: "Seek forward", default: "" },
+	Binding { name: "reset_playback_rate", path: "&Playback/&Reset speed and pitch\t", label: "Reset speed and pitch", default: "" },
+	Binding { name: "run_script", path: "&Tools/&Run script...\t", label: "Run script", default: "" },
+	Binding { name: "export_manifest", path: "&Tools/Export &manifest...\t", label: "Export manifest", default: "" },
+	Binding { name: "import_manifest", path: "&Tools/&Import manifest...\t", label: "Import manifest", default: "" },
+	Binding { name: "null_test", path: "&Tools/&Null test selected item...\t", label: "Null test", default: "" },
+	Binding { name: "inspect_structure", path: "&Tools/Inspect file &structure...\t", label: "Inspect file structure", default: "" },
+	Binding { name: "batch_process", path: "&Tools/&Batch process folder...\t", label: "Batch process folder", default: "" },
+	Binding { name: "batch_convert", path: "&Tools/Batch &convert folder...\t", label: "Batch convert folder", default: "" },
+	Binding { name: "show_console", path: "&Help/Tool &console...\t", label: "Tool console", default: "" },
+	Binding { name: "show_diagnostics", path: "&Help/&Diagnostics...\t", label: "Diagnostics", default: "" },
+	Binding { name: "self_test", path: "&Help/Run self t&est...\t", label: "Run self test", default: "" }
+];
+
+/// Actions that change the list, whose shortcuts are held back while typing
+/// even with Ctrl, so keys meant for the text don't remove sounds.
+const LIST_EDITS: &[&str] = &["remove", "remove_all"];
+
+thread_local! {
+	/// The shortcuts held back while a text field has the keyboard focus.
+	/// Only set on the main thread, by [apply].
+	static HELD_WHILE_TYPING: RefCell<Vec<Shortcut>> = RefCell::new(Vec::new());
+}
+
+/// Named keys, as they are written in shortcuts.
+const NAMED_KEYS: &[(&str, Key)] = &[
+	("Enter", Key::Enter),
+	("Tab", Key::Tab),
+	("Delete", Key::Delete),
+	("BackSpace", Key::BackSpace),
+	("Insert", Key::Insert),
+	("Home", Key::Home),
+	("End", Key::End),
+	("PageUp", Key::PageUp),
+	("PageDown", Key::PageDown),
+	("Left", Key::Left),
+	("Right", Key::Right),
+	("Up", Key::Up),
+	("Down", Key::Down),
+	("F1", Key::F1),
+	("F2", Key::F2),
+	("F3", Key::F3),
+	("F4", Key::F4),
+	("F5", Key::F5),
+	("F6", Key::F6),
+	("F7", Key::F7),
+	("F8", Key::F8),
+	("F9", Key::F9),
+	("F10", Key::F10),
+	("F11", Key::F11),
+	("F12", Key::F12)
+];
+
+/// Parse a shortcut like `Ctrl+Shift+S`.
+/// 
+/// An empty string is no shortcut. Returns an error if `text` isn't a shortcut.
+pub fn parse(text: &str) -> Result<Shortcut, String> {
+	let text = text.trim();
+	if text.is_empty() {
+		return Ok(Shortcut::None)
+	}
+
+	// The key itself may be a plus, like Ctrl++
+	let (modifiers, key) = match text.strip_suffix("++") {
+		Some(modifiers) => (modifiers, "+"),
+		None => match text.rsplit_once('+') {
+			Some((modifiers, key)) => (modifiers, key),
+			None => ("", text)
+		}
+	};
+
+	let mut shortcut = Shortcut::None;
+	for modifier in modifiers.split('+').map(|modifier| modifier.trim()).filter(|modifier| !modifier.is_empty()) {
+		shortcut |= match &modifier.to_lowercase()[..] {
+			"ctrl" | "control" => Shortcut::Ctrl,
+			"shift" => Shortcut::Shift,
+			"alt" => Shortcut::Alt,
+			"meta" | "cmd" | "command" => Shortcut::Meta,
+			_ => return Err(format!("{} is not a modifier key", modifier))
+		}
+	}
+
+	let key = key.trim();
+	let key = match NAMED_KEYS.iter().find(|(name, _)| name.eq_ignore_ascii_case(key)) {
+		Some((_, key)) => *key,
+		None if key.eq_ignore_ascii_case("space") => Key::from_char(' '),
+		None => {
+			let mut chars = key.chars();
+			match (chars.next(), chars.next()) {
+				(Some(c), None) => Key::from_char(c.to_ascii_lowercase()),
+				_ => return Err(format!("{} is not a key", key))
+			}
+		}
+	};
+
+	Ok(shortcut | Shortcut::from_key(key))
+}
+
+/// Whether or not `shortcut` of `binding` is held back while typing.
+/// 
+/// Keys without Ctrl, Alt or Meta, like Space, are part of the text, and
+/// [list edits](LIST_EDITS) would change the list by mistake.
+fn held_while_typing(binding: &Binding, shortcut: Shortcut) -> bool {
+	shortcut != Shortcut::None && (
+		!shortcut.intersects(Shortcut::Ctrl | Shortcut::Alt | Shortcut::Meta)
+		|| LIST_EDITS.contains(&binding.name)
+	)
+}
+
+/// Whether or not a text field the user can type in has the keyboard focus.
+fn typing() -> bool {
+	app::focus()
+		.and_then(|widget| Input::from_dyn_widget_ptr(widget.as_widget_ptr()))
+		.map(|input| !input.readonly())
+		.unwrap_or(false)
+}
+
+/// The shortcut of the key event being handled.
+fn event_shortcut() -> Shortcut {
+	let state = app::event_state();
+	let mut shortcut = Shortcut::from_key(app::event_key());
+	for (held, modifier) in [(EventState::Ctrl, Shortcut::Ctrl), (EventState::Shift, Shortcut::Shift), (EventState::Alt, Shortcut::Alt), (EventState::Meta, Shortcut::Meta)] {
+		if state.contains(held) {
+			shortcut |= modifier
+		}
+	}
+	shortcut
+}
+
+/// Handle a shortcut event reaching the main window, before it goes on to the menu bar.
+/// 
+/// Returns true to hold the shortcut back, when a text field has the keyboard
+/// focus and the shortcut is one that shouldn't fire while typing.
+pub fn dispatch() -> bool {
+	if !typing() { return false }
+	let pressed = event_shortcut();
+	let held = HELD_WHILE_TYPING.with(|held| held.borrow().contains(&pressed));
+	if held {
+		trace!("Holding back shortcut {:?} while typing", pressed)
+	}
+	held
+}
+
+/// Set the shortcuts of `menu` to the ones in `settings`.
+/// 
+/// Shortcuts that can't be read are left at their default.
+pub fn apply(menu: &mut MenuBar, settings: &Settings) {
+	let mut held_while_typing = Vec::new();
+	for binding in BINDINGS {
+		let index = menu.find_index(binding.path);
+		if index < 0 {
+			warn!("No menu item {:?} to give a shortcut", binding.path);
+			continue
+		}
+
+		let text = settings.shortcut(binding);
+		let shortcut = parse(&text).unwrap_or_else(|error| {
+			warn!("Shortcut {:?} for {} can't be used: {}", text, binding.name, error);
+			parse(binding.default).unwrap_or(Shortcut::None)
+		});
+		if held_while_typing(binding, shortcut) {
+			held_while_typing.push(shortcut)
+		}
+		menu.set_shortcut(index, shortcut)
+	}
+	HELD_WHILE_TYPING.with(|held| *held.borrow_mut() = held_while_typing)
+}
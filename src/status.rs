@@ -0,0 +1,18 @@
+//! Status messages, like playback starting or an item being replaced.
+//! 
+//! FLTK doesn't expose widgets to assistive technology, but screen readers
+//! do announce the title of the focused window when it changes. So status
+//! messages are put in the title of the main window, and logged.
+
+use fltk::{
+	prelude::*,
+	window::Window
+};
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+
+/// Announce `text` in the title of `window`.
+pub fn announce(window: &mut Window, text: &str) {
+	info!("{}", text);
+	window.set_label(&format!("{} - {}", crate::NAME, text))
+}
@@ -0,0 +1,63 @@
+//! A window showing the sections of a nus3audio file and the items they
+//! describe, as written on disk.
+
+use fltk::{
+	prelude::*,
+	app,
+	browser::Browser,
+	window::Window
+};
+use simple_nus3audio_core::{
+	structure::Structure,
+	util::human_readable_size
+};
+use crate::layout::browser_text;
+
+/// Column widths of the structure table.
+const COLUMN_WIDTHS: &[i32] = &[80, 70, 80, 80, 140];
+
+/// Format `value` as a hex offset, or a dash if it couldn't be read.
+fn hex(value: Option<u32>) -> String {
+	value.map(|value| format!("{:#x}", value)).unwrap_or_else(|| "-".to_owned())
+}
+
+/// Show the layout of the nus3audio file `name`, read as `structure`, near `parent`.
+pub fn show(name: &str, structure: &Structure, parent: &Window) {
+	let mut window = Window::new(crate::layout::get_x(parent), crate::layout::get_y(parent), 680, 400, None)
+		.with_label(&format!("Structure of {}", name));
+	window.make_resizable(true);
+	window.make_modal(true);
+
+	let mut browser = Browser::new(0, 0, 680, 400, "");
+	browser.set_column_widths(COLUMN_WIDTHS);
+	browser.set_column_char('\t');
+	browser.set_tooltip("The sections of the file as written on disk, then the items they describe.\nOffsets are from the start of the file");
+
+	browser.add(&format!("@b{}, {} items", human_readable_size(structure.file_size as u64), structure.count.map(|count| count.to_string()).unwrap_or_else(|| "?".to_owned())));
+	for problem in &structure.problems {
+		browser.add(&format!("@C1{}", browser_text(problem)))
+	}
+
+	browser.add("");
+	browser.add("@bSection\t@bOffset\t@bData at\t@bSize");
+	browser.add(&format!("NUS3\t0x0\t0x8\t{:#x}", structure.declared_size));
+	for section in &structure.sections {
+		browser.add(&format!("{}\t{:#x}\t{:#x}\t{:#x}", browser_text(&section.magic), section.offset, section.data_offset(), section.size))
+	}
+
+	browser.add("");
+	browser.add("@bItem\t@bTone ID\t@bName at\t@bAudio at\t@bAudio size\t@bName");
+	for (index, entry) in structure.entries.iter().enumerate() {
+		let id = entry.id.map(|id| id.to_string()).unwrap_or_else(|| "-".to_owned());
+		let size = entry.data_size.map(|size| format!("{:#x} ({})", size, human_readable_size(size as u64))).unwrap_or_else(|| "-".to_owned());
+		browser.add(&format!("{}\t{}\t{}\t{}\t{}\t{}", index, id, hex(entry.name_offset), hex(entry.data_offset), size, browser_text(entry.name.as_deref().unwrap_or("-"))));
+	}
+
+	window.resizable(&browser);
+	window.end();
+	window.show();
+
+	while window.shown() {
+		app::wait();
+	}
+}
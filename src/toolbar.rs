@@ -0,0 +1,60 @@
+//! An optional row of buttons for the most common actions, for those who
+//! prefer clicking over shortcuts.
+
+use fltk::{
+	prelude::*,
+	button::Button
+};
+use crate::Message;
+
+/// Symbol, tooltip and message of each toolbar button, in order.
+const BUTTONS: &[(&str, &str, Message)] = &[
+	("@fileopen", "Open nus3audio", Message::Open),
+	("@filesave", "Save nus3audio", Message::Save),
+	("@+", "Add sound", Message::Add),
+	("@line", "Remove selected sound", Message::Remove),
+	("@refresh", "Replace selected sound", Message::Replace),
+	("@>", "Play or pause selected sound", Message::PlayPause),
+	("@square", "Stop playback", Message::Stop),
+	("@->", "Export selected sound", Message::ExportSingle)
+];
+
+/// The toolbar of the main window.
+pub struct Toolbar {
+	buttons: Vec<Button>
+}
+
+impl Toolbar {
+	/// Create the (hidden) toolbar, whose buttons send their messages with `sender`.
+	pub fn new(sender: fltk::app::Sender<Message>) -> Self {
+		let buttons = BUTTONS.iter()
+			.map(|(symbol, tooltip, message)| {
+				let mut button = Button::default().with_label(symbol);
+				button.set_tooltip(tooltip);
+				button.visible_focus(true);
+				button.emit(sender, *message);
+				button.hide();
+				button
+			})
+			.collect();
+
+		Self { buttons }
+	}
+
+	/// Whether or not the toolbar is shown.
+	pub fn shown(&self) -> bool {
+		self.buttons.first().map(|button| button.visible()).unwrap_or(false)
+	}
+
+	/// Show or hide the toolbar. The window needs to be laid out again afterward.
+	pub fn set_shown(&mut self, shown: bool) {
+		for button in self.buttons.iter_mut() {
+			if shown { button.show() } else { button.hide() }
+		}
+	}
+
+	/// Returns the buttons of this toolbar.
+	pub fn get_widgets_mut(&mut self) -> &mut [Button] {
+		&mut self.buttons
+	}
+}
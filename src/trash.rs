@@ -0,0 +1,84 @@
+//! A window showing the items removed this session, to put them back.
+
+use fltk::{
+	prelude::*,
+	app,
+	browser::HoldBrowser,
+	button::Button,
+	window::Window
+};
+use simple_nus3audio_core::Archive;
+use crate::layout::{ browser_text, MARGIN, RADIO_HEIGHT };
+
+/// Width of the trash window.
+const WIDTH: i32 = 400;
+/// Height of the trash window.
+const HEIGHT: i32 = 300;
+/// Width of each button.
+const BUTTON_WIDTH: i32 = 110;
+/// Column widths of the trash list.
+const COLUMN_WIDTHS: &[i32] = &[60, 240];
+
+/// What to do with the trash, as picked in [show].
+#[derive(Clone, Copy)]
+pub enum Action {
+	/// Put the item at this index of the trash back.
+	Restore(usize),
+	/// Drop everything in the trash for good.
+	Empty
+}
+
+/// Show the trash of `archive` near `parent`, and return what to do with it.
+/// 
+/// Returns None if the window was closed.
+pub fn show(archive: &Archive, parent: &Window) -> Option<Action> {
+	let (s, r) = app::channel::<Action>();
+
+	let mut window = Window::new(crate::layout::get_x(parent), crate::layout::get_y(parent), WIDTH, HEIGHT, None)
+		.with_label(&format!("Trash ({} items)", archive.trash.len()));
+	window.make_modal(true);
+
+	let list_height = HEIGHT - RADIO_HEIGHT - MARGIN * 3;
+	let mut browser = HoldBrowser::new(MARGIN, MARGIN, WIDTH - MARGIN * 2, list_height, "");
+	browser.set_column_widths(COLUMN_WIDTHS);
+	browser.set_column_char('\t');
+	browser.set_tooltip("Items removed this session, newest first, with where they were in the list.\nThey are gone for good once another file is opened");
+	// Newest first, so what was just removed is at the top
+	for trashed in archive.trash.iter().rev() {
+		browser.add(&format!("{}\t{}", trashed.index, browser_text(&format!("{}.{}", trashed.item.name, trashed.item.extension))));
+	}
+	if !archive.trash.is_empty() {
+		browser.select(1)
+	}
+
+	let y = HEIGHT - RADIO_HEIGHT - MARGIN;
+	let mut restore_button = Button::new(MARGIN, y, BUTTON_WIDTH, RADIO_HEIGHT, "Restore");
+	restore_button.set_tooltip("Put the selected item back where it was");
+	let mut empty_button = Button::new(WIDTH - MARGIN - BUTTON_WIDTH, y, BUTTON_WIDTH, RADIO_HEIGHT, "Empty trash");
+	empty_button.set_tooltip("Drop every item in the trash for good");
+	if archive.trash.is_empty() {
+		restore_button.deactivate();
+		empty_button.deactivate()
+	}
+
+	let length = archive.trash.len();
+	restore_button.set_callback({
+		let browser = browser.clone();
+		move |_| if browser.value() > 0 {
+			s.send(Action::Restore(length - browser.value() as usize))
+		}
+	});
+	empty_button.emit(s, Action::Empty);
+
+	window.end();
+	window.show();
+
+	while window.shown() {
+		app::wait();
+		if let Some(action) = r.recv() {
+			window.hide();
+			return Some(action)
+		}
+	}
+	None
+}
@@ -0,0 +1,88 @@
+//! Checking GitHub for newer releases.
+
+use std::{
+	sync::Mutex,
+	thread,
+	time::Duration
+};
+use lazy_static::lazy_static;
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+
+/// The GitHub API endpoint for the latest release.
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/junetried/simple-nus3audio-gui/releases/latest";
+/// How long to wait for GitHub before giving up.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A release found on GitHub.
+#[derive(Clone)]
+pub struct Release {
+	/// The version of the release, like "0.5.3".
+	pub version: String,
+	/// The page of the release.
+	pub url: String
+}
+
+lazy_static! {
+	/// The result of the last check, waiting to be picked up by [take_result].
+	static ref RESULT: Mutex<Option<Result<Option<Release>, String>>> = Mutex::new(None);
+}
+
+/// Parse a version like "v1.2.3" into its numbers.
+pub fn parse_version(version: &str) -> Vec<u64> {
+	version.trim_start_matches('v')
+		.split(|c: char| c == '.' || c == '-')
+		.map_while(|part| part.parse().ok())
+		.collect()
+}
+
+/// Query GitHub for the latest release. Returns it if it is newer than this version.
+pub fn check() -> Result<Option<Release>, String> {
+	debug!("Checking for updates at {}", LATEST_RELEASE_URL);
+
+	let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+	let response = match agent.get(LATEST_RELEASE_URL).set("User-Agent", crate::NAME).call() {
+		Ok(response) => response,
+		Err(error) => return Err(format!("Error checking for updates\n{}", error))
+	};
+	let text = match response.into_string() {
+		Ok(text) => text,
+		Err(error) => return Err(format!("Error checking for updates\n{}", error))
+	};
+	let release = match json::parse(&text) {
+		Ok(release) => release,
+		Err(error) => return Err(format!("Error reading update information\n{}", error))
+	};
+
+	let (tag, url) = match (release["tag_name"].as_str(), release["html_url"].as_str()) {
+		(Some(tag), Some(url)) => (tag, url),
+		_ => return Err("Update information is missing the release version".to_owned())
+	};
+
+	let latest = parse_version(tag);
+	let current = parse_version(env!("CARGO_PKG_VERSION"));
+	debug!("Latest release is {}, this is {}", tag, env!("CARGO_PKG_VERSION"));
+
+	if latest > current {
+		Ok(Some(Release { version: tag.trim_start_matches('v').to_owned(), url: url.to_owned() }))
+	} else {
+		Ok(None)
+	}
+}
+
+/// Check for updates on another thread. `sender` is sent `message` when done,
+/// and the result can then be retrieved with [take_result].
+pub fn check_in_background(sender: fltk::app::Sender<crate::Message>, message: crate::Message) {
+	thread::spawn(move || {
+		let result = check();
+		if let Ok(mut slot) = RESULT.lock() {
+			slot.replace(result);
+		}
+		sender.send(message)
+	});
+}
+
+/// Take the result of the last background check, if there is one.
+pub fn take_result() -> Option<Result<Option<Release>, String>> {
+	RESULT.lock().ok().and_then(|mut slot| slot.take())
+}
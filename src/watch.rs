@@ -0,0 +1,90 @@
+//! Watching a folder for exported audio, so items can be re-imported
+//! automatically while iterating in a DAW.
+
+use std::{
+	collections::HashMap,
+	fs,
+	path::{ Path, PathBuf },
+	time::SystemTime
+};
+#[allow(unused_imports)]
+use log::{ trace, debug, info, warn, error };
+
+/// How often the folder is checked, in seconds.
+pub const POLL_FREQUENCY: f64 = 1.0;
+
+/// What a file looked like the last time it was checked.
+type Stamp = (SystemTime, u64);
+
+/// A folder being watched for `itemname.wav` files, named as exports are,
+/// see [safe_file_name](simple_nus3audio_core::util::safe_file_name).
+pub struct Watcher {
+	/// The folder being watched.
+	pub dir: PathBuf,
+	/// Which time watching was started, so checks queued for an earlier
+	/// watcher can be told apart and dropped.
+	pub generation: usize,
+	/// The last seen stamp of each file.
+	seen: HashMap<PathBuf, Stamp>,
+	/// Files that changed, waiting to stop changing before they are reported.
+	pending: HashMap<PathBuf, Stamp>
+}
+
+impl Watcher {
+	/// Start watching `dir`. Files already there are not reported until they change.
+	pub fn new(dir: PathBuf, generation: usize) -> Self {
+		let seen = Self::scan(&dir);
+		Self { dir, generation, seen, pending: HashMap::new() }
+	}
+
+	/// Return the stamps of the WAV files in `dir`.
+	fn scan(dir: &Path) -> HashMap<PathBuf, Stamp> {
+		let entries = match fs::read_dir(dir) {
+			Ok(entries) => entries,
+			Err(error) => {
+				warn!("Could not read watched folder {:?}: {}", dir, error);
+				return HashMap::new()
+			}
+		};
+
+		entries.filter_map(|entry| entry.ok())
+			.map(|entry| entry.path())
+			.filter(|path| path.extension().and_then(|e| e.to_str()).map(|e| e.eq_ignore_ascii_case("wav")).unwrap_or(false))
+			.filter_map(|path| {
+				let metadata = fs::metadata(&path).ok()?;
+				Some((path, (metadata.modified().ok()?, metadata.len())))
+			})
+			.collect()
+	}
+
+	/// Check the folder, and return files that appeared or changed.
+	/// 
+	/// A file is only returned once it looks the same for two checks in a row,
+	/// so files still being written aren't imported half-finished.
+	pub fn poll(&mut self) -> Vec<PathBuf> {
+		let current = Self::scan(&self.dir);
+		let mut ready = Vec::new();
+
+		for (path, stamp) in &current {
+			if self.seen.get(path) == Some(stamp) {
+				continue
+			}
+			if self.pending.get(path) == Some(stamp) {
+				self.pending.remove(path);
+				self.seen.insert(path.clone(), *stamp);
+				ready.push(path.clone())
+			} else {
+				self.pending.insert(path.clone(), *stamp);
+			}
+		}
+
+		self.seen.retain(|path, _| current.contains_key(path));
+		ready.sort();
+		ready
+	}
+}
+
+/// Queue the next check of the folder watched by the watcher of `generation`.
+pub fn queue_poll(sender: fltk::app::Sender<crate::Message>, generation: usize) {
+	fltk::app::add_timeout3(POLL_FREQUENCY, move |_| sender.send(crate::Message::WatchPoll(generation)));
+}
@@ -0,0 +1,94 @@
+//! A short list of what changed, shown the first time a new version is run.
+
+use fltk::{
+	prelude::*,
+	app,
+	button::Button,
+	text::{ TextBuffer, TextDisplay, WrapMode },
+	window::Window
+};
+use crate::{
+	layout::{ MARGIN, RADIO_HEIGHT },
+	update::parse_version
+};
+
+/// Width of the window.
+const WIDTH: i32 = 500;
+/// Height of the window.
+const HEIGHT: i32 = 350;
+/// Width of the close button.
+const BUTTON_WIDTH: i32 = 110;
+
+/// The highlights of each version, oldest first.
+const CHANGES: &[(&str, &[&str])] = &[
+	("0.6.0", &[
+		"Removed items go to a trash (Edit → Trash...) they can be restored from.",
+		"Audio files can be dropped on the list to add them, or dragged out of it to export them.",
+		"Replacements are played first, to confirm them before replacing.",
+		"Previews can be sped up, slowed down or pitched with the choices next to the seek bar.",
+		"Either channel of stereo audio can be heard alone with the L and R toggles, and the preview panned with the dial.",
+		"Loops can be previewed a set number of times before the rest of the audio (Playback → Loop preview).",
+		"The button next to Play plays the selected item from its loop start.",
+		"Space and the Remove shortcut no longer fire while typing in a text field.",
+		"The list can be sorted by name, tone ID, format or size, and each file remembers its order.",
+		"Large files can be grouped by name prefix or tone ID range (Edit → Group list by).",
+		"Item bookmarks and notes are kept in a sidecar file next to the nus3audio.",
+		"Manifests rename, reorder and re-ID items in bulk from a spreadsheet.",
+		"Tools has batch processing, batch conversion, scripting and a null test.",
+		"Long operations show their progress and can be cancelled.",
+		"Help → Diagnostics collects what's needed for a bug report."
+	])
+];
+
+/// Return what changed since `previous` was run, with the `migrations` made
+/// to the settings, or None if there is nothing to say.
+pub fn notes(previous: &str, migrations: &[String]) -> Option<String> {
+	let previous_version = parse_version(previous);
+	let current_version = parse_version(env!("CARGO_PKG_VERSION"));
+
+	let mut text = String::new();
+	for (version, changes) in CHANGES {
+		let parsed = parse_version(version);
+		if parsed > previous_version && parsed <= current_version {
+			text.push_str(&format!("New in {}:\n", version));
+			for change in changes.iter() {
+				text.push_str(&format!("• {}\n", change))
+			}
+			text.push('\n')
+		}
+	}
+	if !migrations.is_empty() {
+		text.push_str("Your settings were updated:\n");
+		for migration in migrations {
+			text.push_str(&format!("• {}\n", migration))
+		}
+	}
+
+	if text.is_empty() { None } else { Some(text) }
+}
+
+/// Show `notes` near `parent`.
+pub fn show(notes: &str, parent: &Window) {
+	let mut window = Window::new(crate::layout::get_x(parent), crate::layout::get_y(parent), WIDTH, HEIGHT, None)
+		.with_label(&format!("What's new in {}", env!("CARGO_PKG_VERSION")));
+	window.make_modal(true);
+
+	let mut buffer = TextBuffer::default();
+	buffer.set_text(notes);
+	let mut display = TextDisplay::new(MARGIN, MARGIN, WIDTH - MARGIN * 2, HEIGHT - RADIO_HEIGHT - MARGIN * 3, "");
+	display.set_buffer(buffer);
+	display.wrap_mode(WrapMode::AtBounds, 0);
+
+	let mut close_button = Button::new(WIDTH - MARGIN - BUTTON_WIDTH, HEIGHT - RADIO_HEIGHT - MARGIN, BUTTON_WIDTH, RADIO_HEIGHT, "Close");
+	close_button.set_callback({
+		let mut window = window.clone();
+		move |_| window.hide()
+	});
+
+	window.end();
+	window.show();
+
+	while window.shown() {
+		app::wait();
+	}
+}